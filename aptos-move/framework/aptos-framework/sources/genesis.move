@@ -31,6 +31,13 @@ module aptos_framework::genesis {
         full_node_network_addresses: vector<u8>,
     }
 
+    struct LockedStakeConfiguration has copy, drop {
+        owner_address: address,
+        operator_address: address,
+        voter_address: address,
+        stake_amount: u64,
+    }
+
     /// Genesis step 1: Initialize aptos framework account and core modules on chain.
     fun initialize(
         gas_schedule: vector<u8>,
@@ -124,6 +131,31 @@ module aptos_framework::genesis {
         aptos_coin::configure_accounts_for_test(aptos_framework, &core_resources, mint_cap);
     }
 
+    /// Creates stake pools outside of the validator set, e.g. for employee accounts, with their stake locked up for
+    /// the recurring lockup duration. Must run before `create_initialize_validators`, which destroys the aptos
+    /// framework account's ability to mint coins.
+    fun create_locked_stake_pools(aptos_framework: &signer, pools: vector<LockedStakeConfiguration>) {
+        let i = 0;
+        let num_pools = vector::length(&pools);
+        while (i < num_pools) {
+            let pool = vector::borrow(&pools, i);
+            let owner = &account::create_account(pool.owner_address);
+            if (pool.operator_address != pool.owner_address && !account::exists_at(pool.operator_address)) {
+                account::create_account(pool.operator_address);
+            };
+            if (pool.voter_address != pool.owner_address && !account::exists_at(pool.voter_address)) {
+                account::create_account(pool.voter_address);
+            };
+
+            coin::register<AptosCoin>(owner);
+            aptos_coin::mint(aptos_framework, pool.owner_address, pool.stake_amount);
+            stake::initialize_stake_owner(owner, pool.stake_amount, pool.operator_address, pool.voter_address);
+            stake::increase_lockup(owner);
+
+            i = i + 1;
+        };
+    }
+
     /// Sets up the initial validator set for the network.
     /// The validator "owner" accounts, and their authentication
     /// Addresses (and keys) are encoded in the `owners`
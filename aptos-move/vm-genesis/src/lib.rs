@@ -20,7 +20,7 @@ use aptos_types::{
     account_config::{self, events::NewEpochEvent, CORE_CODE_ADDRESS},
     chain_id::ChainId,
     contract_event::ContractEvent,
-    on_chain_config::{ConsensusConfigV1, OnChainConsensusConfig, APTOS_MAX_KNOWN_VERSION},
+    on_chain_config::{OnChainConsensusConfig, APTOS_MAX_KNOWN_VERSION},
     transaction::{authenticator::AuthenticationKey, ChangeSet, Transaction, WriteSetPayload},
 };
 use aptos_vm::{
@@ -67,6 +67,10 @@ pub struct GenesisConfiguration {
     pub rewards_apy_percentage: u64,
     pub voting_duration_secs: u64,
     pub voting_power_increase_limit: u64,
+    /// Gas parameters of the gas schedule set at genesis
+    pub gas_parameters: AptosGasParameters,
+    /// Stake pools created outside of the validator set
+    pub locked_stake_pools: Vec<LockedStakePool>,
 }
 
 pub static GENESIS_KEYPAIR: Lazy<(Ed25519PrivateKey, Ed25519PublicKey)> = Lazy::new(|| {
@@ -80,11 +84,10 @@ pub fn encode_genesis_transaction(
     aptos_root_key: Ed25519PublicKey,
     validators: &[Validator],
     framework: &ReleaseBundle,
+    consensus_config: OnChainConsensusConfig,
     chain_id: ChainId,
     genesis_config: GenesisConfiguration,
 ) -> Transaction {
-    let consensus_config = OnChainConsensusConfig::V1(ConsensusConfigV1::default());
-
     Transaction::GenesisTransaction(WriteSetPayload::Direct(encode_genesis_change_set(
         &aptos_root_key,
         validators,
@@ -127,6 +130,9 @@ pub fn encode_genesis_change_set(
         initialize_aptos_coin(&mut session);
     }
     initialize_on_chain_governance(&mut session, genesis_config);
+    if !genesis_config.locked_stake_pools.is_empty() {
+        create_locked_stake_pools(&mut session, &genesis_config.locked_stake_pools);
+    }
     create_and_initialize_validators(&mut session, validators);
     if genesis_config.is_test {
         allow_core_resources_to_set_version(&mut session);
@@ -245,9 +251,9 @@ fn initialize(
     chain_id: ChainId,
     genesis_config: &GenesisConfiguration,
 ) {
-    let genesis_gas_params = AptosGasParameters::initial();
-    let gas_schedule_blob = bcs::to_bytes(&genesis_gas_params.to_on_chain_gas_schedule())
-        .expect("Failure serializing genesis gas schedule");
+    let gas_schedule_blob =
+        bcs::to_bytes(&genesis_config.gas_parameters.to_on_chain_gas_schedule())
+            .expect("Failure serializing genesis gas schedule");
 
     let consensus_config_bytes =
         bcs::to_bytes(&consensus_config).expect("Failure serializing genesis consensus config");
@@ -361,6 +367,22 @@ fn create_and_initialize_validators(
     );
 }
 
+fn create_locked_stake_pools(
+    session: &mut SessionExt<impl MoveResolver>,
+    pools: &[LockedStakePool],
+) {
+    let pools_bytes = bcs::to_bytes(pools).expect("Locked stake pools can be serialized");
+    let mut serialized_values = serialize_values(&vec![MoveValue::Signer(CORE_CODE_ADDRESS)]);
+    serialized_values.push(pools_bytes);
+    exec_function(
+        session,
+        GENESIS_MODULE_NAME,
+        "create_locked_stake_pools",
+        vec![],
+        serialized_values,
+    );
+}
+
 fn allow_core_resources_to_set_version(session: &mut SessionExt<impl MoveResolver>) {
     exec_function(
         session,
@@ -506,6 +528,17 @@ pub struct Validator {
     pub full_node_network_addresses: Vec<u8>,
 }
 
+/// A stake pool created at genesis without joining the validator set, e.g. for an employee
+/// account. Its stake is locked up for the recurring lockup duration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedStakePool {
+    pub owner_address: AccountAddress,
+    pub operator_address: AccountAddress,
+    pub voter_address: AccountAddress,
+    /// Amount to stake, also the amount minted to the owner account.
+    pub stake_amount: u64,
+}
+
 pub struct TestValidator {
     pub key: Ed25519PrivateKey,
     pub consensus_key: bls12381::PrivateKey,
@@ -582,6 +615,8 @@ pub fn generate_test_genesis(
             rewards_apy_percentage: 10,
             voting_duration_secs: 3600,
             voting_power_increase_limit: 50,
+            gas_parameters: AptosGasParameters::initial(),
+            locked_stake_pools: vec![],
         },
     );
     (genesis, test_validators)
@@ -616,6 +651,8 @@ pub fn generate_mainnet_genesis(
             rewards_apy_percentage: 10,
             voting_duration_secs: 7 * 24 * 3600, // 7 days
             voting_power_increase_limit: 30,
+            gas_parameters: AptosGasParameters::initial(),
+            locked_stake_pools: vec![],
         },
     );
     (genesis, test_validators)
@@ -5,7 +5,7 @@ use crate::keys::PublicIdentity;
 use crate::{
     config::ValidatorConfiguration,
     keys::{generate_key_objects, PrivateIdentity},
-    GenesisInfo,
+    GenesisInfo, LockedStakePool,
 };
 use anyhow::ensure;
 use aptos_config::config::RocksDbStorageConfig;
@@ -23,9 +23,13 @@ use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
     PrivateKey,
 };
+use aptos_gas::{AptosGasParameters, InitialGasSchedule};
 use aptos_keygen::KeyGen;
 use aptos_logger::prelude::*;
-use aptos_types::{chain_id::ChainId, transaction::Transaction, waypoint::Waypoint};
+use aptos_types::{
+    chain_id::ChainId, on_chain_config::OnChainConsensusConfig, transaction::Transaction,
+    waypoint::Waypoint,
+};
 use framework::ReleaseBundle;
 use rand::Rng;
 use serde::{de::DeserializeOwned, Serialize};
@@ -77,6 +81,27 @@ impl ValidatorNodeConfig {
         })
     }
 
+    /// Creates a validator joining the network started from `genesis`, e.g. to be added to a
+    /// running swarm. Its keys are generated and its config saved, but it only becomes part of
+    /// the validator set once it joined it on chain.
+    pub fn join_network(
+        name: String,
+        base_dir: &Path,
+        template: NodeConfig,
+        stake_amount: u64,
+        waypoint: &Waypoint,
+        genesis: &Transaction,
+    ) -> anyhow::Result<ValidatorNodeConfig> {
+        let mut validator = Self::new(name, base_dir, template, stake_amount)?;
+        validator.init_keys(None)?;
+        validator.init_networks();
+        validator.config.randomize_ports();
+        validator.insert_waypoint(waypoint);
+        validator.insert_genesis(genesis);
+        validator.save_config()?;
+        Ok(validator)
+    }
+
     /// Initializes keys and identities for a validator config
     /// TODO: Put this all in storage rather than files?
     fn init_keys(&mut self, seed: Option<[u8; 32]>) -> anyhow::Result<()> {
@@ -156,6 +181,48 @@ impl ValidatorNodeConfig {
         self.config.base.waypoint = waypoint_config;
     }
 
+    /// Sets up the public and VFN networks, and safety rules in a thread backed by rocksdb
+    fn init_networks(&mut self) {
+        // By default, we don't start with VFNs, so ensure that the REST port is open
+        let vfn_identity_path = self.dir.join(VFN_IDENTITY);
+
+        let config = &mut self.config;
+        let fullnode_network_listen_address =
+            if let Some(template_fullnode_config) = config.full_node_networks.first() {
+                template_fullnode_config.listen_address.clone()
+            } else {
+                aptos_config::utils::get_available_port_in_multiaddr(true)
+            };
+
+        let fullnode_network = NetworkConfig {
+            listen_address: fullnode_network_listen_address,
+            network_id: NetworkId::Public,
+            max_outbound_connections: 0,
+            discovery_method: DiscoveryMethod::Onchain,
+            identity: Identity::from_file(vfn_identity_path.clone()),
+            ..Default::default()
+        };
+
+        // VFN has the same credentials as the public full node identity
+        let vfn_network = NetworkConfig {
+            listen_address: aptos_config::utils::get_available_port_in_multiaddr(true),
+            network_id: NetworkId::Vfn,
+            max_outbound_connections: 0,
+            identity: Identity::from_file(vfn_identity_path),
+            ..Default::default()
+        };
+
+        config.full_node_networks = vec![fullnode_network, vfn_network];
+
+        // Ensure safety rules runs in a thread
+        config.consensus.safety_rules.service = SafetyRulesService::Thread;
+
+        // Use a rocksdb storage backend for safety rules
+        let mut storage = RocksDbStorageConfig::default();
+        storage.set_data_dir(self.dir.clone());
+        config.consensus.safety_rules.backend = SecureBackend::RocksDbStorage(storage);
+    }
+
     fn save_config(&mut self) -> anyhow::Result<()> {
         Ok(self.config.save(self.dir.join(CONFIG_FILE))?)
     }
@@ -391,6 +458,9 @@ pub struct GenesisConfiguration {
     pub rewards_apy_percentage: u64,
     pub voting_duration_secs: u64,
     pub voting_power_increase_limit: u64,
+    pub consensus_config: OnChainConsensusConfig,
+    pub gas_parameters: AptosGasParameters,
+    pub locked_stake_pools: Vec<LockedStakePool>,
 }
 
 pub type InitConfigFn = Arc<dyn Fn(usize, &mut NodeConfig, &mut u64) + Send + Sync>;
@@ -403,8 +473,10 @@ pub struct Builder {
     framework: ReleaseBundle,
     num_validators: NonZeroUsize,
     randomize_first_validator_ports: bool,
+    randomize_ports: bool,
     init_config: Option<InitConfigFn>,
     init_genesis_config: Option<InitGenesisConfigFn>,
+    chain_id: ChainId,
 }
 
 impl Builder {
@@ -417,8 +489,10 @@ impl Builder {
             framework,
             num_validators: NonZeroUsize::new(1).unwrap(),
             randomize_first_validator_ports: true,
+            randomize_ports: true,
             init_config: None,
             init_genesis_config: None,
+            chain_id: ChainId::test(),
         })
     }
 
@@ -427,6 +501,13 @@ impl Builder {
         self
     }
 
+    /// If false, validators keep the ports and listen addresses set by the init config, e.g.
+    /// when they run on other hosts.
+    pub fn with_randomize_ports(mut self, value: bool) -> Self {
+        self.randomize_ports = value;
+        self
+    }
+
     pub fn with_num_validators(mut self, num_validators: NonZeroUsize) -> Self {
         self.num_validators = num_validators;
         self
@@ -445,6 +526,11 @@ impl Builder {
         self
     }
 
+    pub fn with_chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
     /// Build all of the validators and save their configs
     pub fn build<R>(
         mut self,
@@ -512,48 +598,10 @@ impl Builder {
         )?;
 
         validator.init_keys(Some(rng.gen()))?;
+        validator.init_networks();
 
-        // By default, we don't start with VFNs, so ensure that the REST port is open
-        let vfn_identity_path = validator.dir.join(VFN_IDENTITY);
-
-        let config = &mut validator.config;
-        let fullnode_network_listen_address =
-            if let Some(template_fullnode_config) = config.full_node_networks.first() {
-                template_fullnode_config.listen_address.clone()
-            } else {
-                aptos_config::utils::get_available_port_in_multiaddr(true)
-            };
-
-        let fullnode_network = NetworkConfig {
-            listen_address: fullnode_network_listen_address,
-            network_id: NetworkId::Public,
-            max_outbound_connections: 0,
-            discovery_method: DiscoveryMethod::Onchain,
-            identity: Identity::from_file(vfn_identity_path.clone()),
-            ..Default::default()
-        };
-
-        // VFN has the same credentials as the public full node identity
-        let vfn_network = NetworkConfig {
-            listen_address: aptos_config::utils::get_available_port_in_multiaddr(true),
-            network_id: NetworkId::Vfn,
-            max_outbound_connections: 0,
-            identity: Identity::from_file(vfn_identity_path),
-            ..Default::default()
-        };
-
-        config.full_node_networks = vec![fullnode_network, vfn_network];
-
-        // Ensure safety rules runs in a thread
-        config.consensus.safety_rules.service = SafetyRulesService::Thread;
-
-        // Use a rocksdb storage backend for safety rules
-        let mut storage = RocksDbStorageConfig::default();
-        storage.set_data_dir(validator.dir.clone());
-        config.consensus.safety_rules.backend = SecureBackend::RocksDbStorage(storage);
-
-        if index > 0 || self.randomize_first_validator_ports {
-            config.randomize_ports();
+        if self.randomize_ports && (index > 0 || self.randomize_first_validator_ports) {
+            validator.config.randomize_ports();
         }
 
         Ok(validator)
@@ -583,6 +631,9 @@ impl Builder {
             rewards_apy_percentage: 10,
             voting_duration_secs: ONE_DAY / 24,
             voting_power_increase_limit: 50,
+            consensus_config: OnChainConsensusConfig::default(),
+            gas_parameters: AptosGasParameters::initial(),
+            locked_stake_pools: Vec::new(),
         };
         if let Some(init_genesis_config) = &self.init_genesis_config {
             (init_genesis_config)(&mut genesis_config);
@@ -590,7 +641,7 @@ impl Builder {
 
         // Build genesis & waypoint
         let mut genesis_info = GenesisInfo::new(
-            ChainId::test(),
+            self.chain_id,
             root_key,
             configs,
             self.framework.clone(),
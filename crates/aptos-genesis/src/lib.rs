@@ -16,14 +16,19 @@ use aptos_config::config::{
     TARGET_SNAPSHOT_SIZE,
 };
 use aptos_crypto::ed25519::Ed25519PublicKey;
+use aptos_gas::AptosGasParameters;
 use aptos_temppath::TempPath;
-use aptos_types::{chain_id::ChainId, transaction::Transaction, waypoint::Waypoint};
+use aptos_types::{
+    chain_id::ChainId, on_chain_config::OnChainConsensusConfig, transaction::Transaction,
+    waypoint::Waypoint,
+};
 use aptos_vm::AptosVM;
 use aptosdb::AptosDB;
 use framework::ReleaseBundle;
 use std::convert::TryInto;
 use storage_interface::DbReaderWriter;
-use vm_genesis::Validator;
+
+pub use vm_genesis::{LockedStakePool, Validator};
 
 /// Holder object for all pieces needed to generate a genesis transaction
 #[derive(Clone)]
@@ -60,6 +65,12 @@ pub struct GenesisInfo {
     pub voting_duration_secs: u64,
     /// Percent of current epoch's total voting power that can be added in this epoch.
     pub voting_power_increase_limit: u64,
+    /// On-chain consensus config set at genesis
+    pub consensus_config: OnChainConsensusConfig,
+    /// Gas parameters of the gas schedule set at genesis
+    pub gas_parameters: AptosGasParameters,
+    /// Stake pools created outside of the validator set
+    pub locked_stake_pools: Vec<LockedStakePool>,
 }
 
 impl GenesisInfo {
@@ -93,6 +104,9 @@ impl GenesisInfo {
             rewards_apy_percentage: genesis_config.rewards_apy_percentage,
             voting_duration_secs: genesis_config.voting_duration_secs,
             voting_power_increase_limit: genesis_config.voting_power_increase_limit,
+            consensus_config: genesis_config.consensus_config.clone(),
+            gas_parameters: genesis_config.gas_parameters.clone(),
+            locked_stake_pools: genesis_config.locked_stake_pools.clone(),
         })
     }
 
@@ -110,6 +124,7 @@ impl GenesisInfo {
             self.root_key.clone(),
             &self.validators,
             &self.framework,
+            self.consensus_config.clone(),
             self.chain_id,
             vm_genesis::GenesisConfiguration {
                 allow_new_validators: self.allow_new_validators,
@@ -123,6 +138,8 @@ impl GenesisInfo {
                 rewards_apy_percentage: self.rewards_apy_percentage,
                 voting_duration_secs: self.voting_duration_secs,
                 voting_power_increase_limit: self.voting_power_increase_limit,
+                gas_parameters: self.gas_parameters.clone(),
+                locked_stake_pools: self.locked_stake_pools.clone(),
             },
         )
     }
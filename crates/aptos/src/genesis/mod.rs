@@ -17,13 +17,14 @@ use crate::{
     CliCommand, CliResult,
 };
 use aptos_crypto::{bls12381, ed25519::Ed25519PublicKey, x25519, ValidCryptoMaterialStringExt};
+use aptos_gas::{AptosGasParameters, InitialGasSchedule};
 use aptos_genesis::builder::GenesisConfiguration;
 use aptos_genesis::config::{StringOperatorConfiguration, StringOwnerConfiguration};
 use aptos_genesis::{
     config::{Layout, ValidatorConfiguration},
     GenesisInfo,
 };
-use aptos_types::account_address::AccountAddress;
+use aptos_types::{account_address::AccountAddress, on_chain_config::OnChainConsensusConfig};
 use async_trait::async_trait;
 use clap::Parser;
 use std::path::Path;
@@ -163,6 +164,9 @@ pub fn fetch_genesis_info(git_options: GitOptions) -> CliTypedResult<GenesisInfo
             rewards_apy_percentage: layout.rewards_apy_percentage,
             voting_duration_secs: layout.voting_duration_secs,
             voting_power_increase_limit: layout.voting_power_increase_limit,
+            consensus_config: OnChainConsensusConfig::default(),
+            gas_parameters: AptosGasParameters::initial(),
+            locked_stake_pools: Vec::new(),
         },
     )?)
 }
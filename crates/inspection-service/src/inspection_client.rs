@@ -1,9 +1,19 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
+use crate::inspection_service::DISABLED_ENDPOINT_MESSAGE;
+use anyhow::{bail, Result};
 use reqwest::Url;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+/// Number of connections of a node on one of its networks, in one direction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionCount {
+    pub network_id: String,
+    /// `inbound` or `outbound`
+    pub direction: String,
+    pub count: i64,
+}
 
 pub struct InspectionClient {
     client: reqwest::Client,
@@ -59,6 +69,55 @@ impl InspectionClient {
         }
     }
 
+    /// Returns the debug formatted node configuration, secrets excluded.
+    pub async fn get_configuration(&self) -> Result<String> {
+        self.get_enabled_endpoint("configuration").await
+    }
+
+    /// Returns the system and build information of the node.
+    pub async fn get_system_information(&self) -> Result<BTreeMap<String, String>> {
+        let information = self.get_enabled_endpoint("system_information").await?;
+        Ok(serde_json::from_str(&information)?)
+    }
+
+    /// Returns the number of connections of the node, per network and direction.
+    pub async fn get_connection_counts(&self) -> Result<Vec<ConnectionCount>> {
+        let mut counts: Vec<_> = self
+            .get_node_metric_with_name("aptos_connections")
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(metric, count)| {
+                let labels = parse_labels(&metric);
+                let label = |name: &str| labels.get(name).cloned().unwrap_or_default();
+                ConnectionCount {
+                    network_id: label("network_id"),
+                    direction: label("direction"),
+                    count,
+                }
+            })
+            .collect();
+        counts.sort_by(|a, b| (&a.network_id, &a.direction).cmp(&(&b.network_id, &b.direction)));
+        Ok(counts)
+    }
+
+    async fn get_enabled_endpoint(&self, path: &str) -> Result<String> {
+        let mut url = self.url.clone();
+        url.set_path(path);
+        let body = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        if body == DISABLED_ENDPOINT_MESSAGE {
+            bail!("The {} endpoint is disabled on this node", path);
+        }
+        Ok(body)
+    }
+
     pub async fn get_node_metrics(&self) -> Result<HashMap<String, i64>> {
         let mut url = self.url.clone();
         url.set_path("forge_metrics");
@@ -79,3 +138,31 @@ impl InspectionClient {
             .collect()
     }
 }
+
+/// Parses the labels of a metric as keyed by `get_node_metrics`, i.e. `name{key=value,...}`.
+fn parse_labels(metric: &str) -> HashMap<&str, &str> {
+    metric
+        .split_once('{')
+        .and_then(|(_, labels)| labels.strip_suffix('}'))
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|label| label.split_once('='))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labels() {
+        let labels = parse_labels(
+            "aptos_connections{direction=inbound,network_id=Validator,role_type=validator}",
+        );
+        assert_eq!(labels.len(), 3);
+        assert_eq!(labels["direction"], "inbound");
+        assert_eq!(labels["network_id"], "Validator");
+        assert!(parse_labels("aptos_connections{}").is_empty());
+        assert!(parse_labels("aptos_connections").is_empty());
+    }
+}
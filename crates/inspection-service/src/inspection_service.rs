@@ -21,7 +21,7 @@ use std::{
 use tokio::runtime;
 
 // The message displayed when the endpoint is disabled.
-const DISABLED_ENDPOINT_MESSAGE: &str =
+pub(crate) const DISABLED_ENDPOINT_MESSAGE: &str =
     "This endpoint is disabled! Enable it in the InspectionServiceConfig.";
 
 pub fn encode_metrics(encoder: impl Encoder) -> Vec<u8> {
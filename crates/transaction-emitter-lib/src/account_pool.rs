@@ -0,0 +1,183 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Account pool files, holding accounts created ahead of the runs using them, possibly on a
+//! different machine.
+
+use crate::query_sequence_numbers;
+use anyhow::{ensure, Context, Result};
+use aptos_crypto::{ed25519::Ed25519PrivateKey, ValidCryptoMaterialStringExt};
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::types::{account_address::AccountAddress, chain_id::ChainId, LocalAccount};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+/// Sequence numbers and balances of pooled accounts are queried this many accounts at a time
+const QUERY_BATCH: usize = 100;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccountPool {
+    /// Chain the accounts were created on
+    pub chain_id: ChainId,
+    pub accounts: Vec<PooledAccount>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PooledAccount {
+    pub address: AccountAddress,
+    /// Hex encoded private key
+    pub private_key: String,
+}
+
+impl AccountPool {
+    pub fn new(chain_id: ChainId, accounts: &[LocalAccount]) -> Result<Self> {
+        let accounts = accounts
+            .iter()
+            .map(|account| {
+                Ok(PooledAccount {
+                    address: account.address(),
+                    private_key: account.private_key().to_encoded_string()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { chain_id, accounts })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read account pool {}", path.display()))?;
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse account pool {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_yaml::to_string(self)?)
+            .with_context(|| format!("Failed to write account pool {}", path.display()))
+    }
+
+    pub fn check_chain_id(&self, chain_id: ChainId) -> Result<()> {
+        ensure!(
+            self.chain_id == chain_id,
+            "Account pool was created on chain {}, not {}",
+            self.chain_id,
+            chain_id
+        );
+        Ok(())
+    }
+
+    /// Balances of the pooled accounts, in pool order.
+    pub async fn balances(&self, client: &RestClient) -> Result<Vec<u64>> {
+        let mut balances = Vec::with_capacity(self.accounts.len());
+        for batch in self.accounts.chunks(QUERY_BATCH) {
+            let batch_balances = try_join_all(batch.iter().map(|account| async move {
+                client
+                    .get_account_balance(account.address)
+                    .await
+                    .with_context(|| format!("Failed to get the balance of {}", account.address))
+                    .map(|balance| balance.into_inner().get())
+            }))
+            .await?;
+            balances.extend(batch_balances);
+        }
+        Ok(balances)
+    }
+
+    pub fn addresses(&self) -> Vec<AccountAddress> {
+        self.accounts
+            .iter()
+            .map(|account| account.address)
+            .collect()
+    }
+
+    /// Turns the pool into accounts of `chain_id`, with their sequence numbers as seen by
+    /// `client`. All accounts must exist on chain.
+    pub async fn into_local_accounts(
+        self,
+        client: &RestClient,
+        chain_id: ChainId,
+    ) -> Result<Vec<LocalAccount>> {
+        self.check_chain_id(chain_id)?;
+        let mut local_accounts = Vec::with_capacity(self.accounts.len());
+        for batch in self.accounts.chunks(QUERY_BATCH) {
+            let sequence_numbers =
+                query_sequence_numbers(client, batch.iter().map(|account| &account.address))
+                    .await
+                    .context("Failed to query the sequence numbers of the account pool")?;
+            for (account, sequence_number) in batch.iter().zip(sequence_numbers) {
+                let private_key = Ed25519PrivateKey::from_encoded_string(&account.private_key)
+                    .with_context(|| format!("Invalid private key for {}", account.address))?;
+                local_accounts.push(LocalAccount::new(
+                    account.address,
+                    private_key,
+                    sequence_number,
+                ));
+            }
+        }
+        Ok(local_accounts)
+    }
+}
+
+/// What topping up an account pool did.
+#[derive(Debug, Default)]
+pub struct FundingSummary {
+    pub checked_accounts: usize,
+    pub topped_up_accounts: usize,
+    /// Coins sent to the topped up accounts
+    pub distributed: u64,
+}
+
+impl fmt::Display for FundingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "topped up {} of {} accounts, distributing {} coins",
+            self.topped_up_accounts, self.checked_accounts, self.distributed
+        )
+    }
+}
+
+/// What sweeping an account pool did.
+#[derive(Debug, Default)]
+pub struct CleanupSummary {
+    pub checked_accounts: usize,
+    pub swept_accounts: usize,
+    /// Coins sent back to the funding account
+    pub swept: u64,
+}
+
+impl fmt::Display for CleanupSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "swept {} coins from {} of {} accounts",
+            self.swept, self.swept_accounts, self.checked_accounts
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_account_pool_roundtrip() {
+        let mut rng = StdRng::from_seed([0; 32]);
+        let accounts: Vec<_> = (0..3).map(|_| LocalAccount::generate(&mut rng)).collect();
+        let pool = AccountPool::new(ChainId::test(), &accounts).unwrap();
+
+        let loaded: AccountPool =
+            serde_yaml::from_str(&serde_yaml::to_string(&pool).unwrap()).unwrap();
+
+        assert_eq!(loaded.chain_id, ChainId::test());
+        assert_eq!(
+            loaded.addresses(),
+            accounts.iter().map(|a| a.address()).collect::<Vec<_>>()
+        );
+        for (pooled, account) in loaded.accounts.iter().zip(&accounts) {
+            let private_key = Ed25519PrivateKey::from_encoded_string(&pooled.private_key).unwrap();
+            assert_eq!(private_key.to_bytes(), account.private_key().to_bytes());
+        }
+    }
+}
@@ -1,7 +1,11 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{emitter::query_sequence_numbers, instance::Instance, ClusterArgs};
+use crate::{
+    emitter::query_sequence_numbers, instance::Instance, DeadInstancePolicy, TargetSelectionPolicy,
+};
+#[cfg(feature = "cli")]
+use crate::{ClusterArgs, Target};
 use anyhow::{anyhow, bail, format_err, Result};
 use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
@@ -23,6 +27,94 @@ pub struct Cluster {
     instances: Vec<Instance>,
     mint_key_pair: KeyPair<Ed25519PrivateKey, Ed25519PublicKey>,
     pub chain_id: ChainId,
+    target_selection: TargetSelectionPolicy,
+    dead_instance_policy: DeadInstancePolicy,
+    /// Instances left out of the cluster, with the reason why
+    excluded: Vec<String>,
+    /// Instances used for read-side calls, if different from the submission instances
+    query_instances: Vec<Instance>,
+}
+
+/// Fetches a hex encoded mint key from a cloud secret manager, through the cloud's CLI so that
+/// its usual credential chain applies. Supported secrets are
+/// `aws-sm://<secret-id>[?region=<region>]` and `gcp-sm://<project>/<secret>[/<version>]`.
+#[cfg(feature = "cloud-secrets")]
+pub async fn fetch_mint_key_secret(secret: &str) -> Result<Ed25519PrivateKey> {
+    use aptos_crypto::ValidCryptoMaterialStringExt;
+    use tokio::process::Command;
+
+    let mut command = if let Some(secret_id) = secret.strip_prefix("aws-sm://") {
+        let (secret_id, region) = match secret_id.split_once("?region=") {
+            Some((secret_id, region)) => (secret_id, Some(region)),
+            None => (secret_id, None),
+        };
+        let mut command = Command::new("aws");
+        command.args(&[
+            "secretsmanager",
+            "get-secret-value",
+            "--secret-id",
+            secret_id,
+            "--query",
+            "SecretString",
+            "--output",
+            "text",
+        ]);
+        if let Some(region) = region {
+            command.args(&["--region", region]);
+        }
+        command
+    } else if let Some(secret_path) = secret.strip_prefix("gcp-sm://") {
+        let parts: Vec<_> = secret_path.split('/').collect();
+        let (project, name, version) = match parts.as_slice() {
+            [project, name] => (*project, *name, "latest"),
+            [project, name, version] => (*project, *name, *version),
+            _ => bail!(
+                "Expected gcp-sm://<project>/<secret>[/<version>], got {}",
+                secret
+            ),
+        };
+        let mut command = Command::new("gcloud");
+        command.args(&[
+            "secrets",
+            "versions",
+            "access",
+            version,
+            "--secret",
+            name,
+            "--project",
+            project,
+        ]);
+        command
+    } else {
+        bail!(
+            "Unsupported mint key secret {}, expected aws-sm:// or gcp-sm://",
+            secret
+        );
+    };
+
+    let output = command
+        .output()
+        .await
+        .map_err(|e| format_err!("Failed to run secret manager CLI for {}: {}", secret, e))?;
+    if !output.status.success() {
+        bail!(
+            "Failed to fetch mint key secret {}: {}",
+            secret,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let encoded = String::from_utf8(output.stdout)
+        .map_err(|_| format_err!("Mint key secret {} is not valid UTF-8", secret))?;
+    Ed25519PrivateKey::from_encoded_string(encoded.trim())
+        .map_err(|e| format_err!("Mint key secret {} is not a valid key: {}", secret, e))
+}
+
+#[cfg(not(feature = "cloud-secrets"))]
+pub async fn fetch_mint_key_secret(secret: &str) -> Result<Ed25519PrivateKey> {
+    bail!(
+        "Cannot fetch mint key secret {}, the emitter was built without the cloud-secrets feature",
+        secret
+    )
 }
 
 fn clone(key: &Ed25519PrivateKey) -> Ed25519PrivateKey {
@@ -38,24 +130,36 @@ impl Cluster {
         mint_key: Ed25519PrivateKey,
         chain_id: ChainId,
         reuse_accounts: bool,
+    ) -> Result<Self> {
+        let instances = peers
+            .iter()
+            .map(|url| Instance::new(peer_name(url), url.clone(), None))
+            .collect();
+        Self::from_instances(instances, mint_key, chain_id, reuse_accounts).await
+    }
+
+    /// Builds the cluster out of the reachable `peers` that are on the expected chain.
+    pub async fn from_instances(
+        peers: Vec<Instance>,
+        mint_key: Ed25519PrivateKey,
+        chain_id: ChainId,
+        reuse_accounts: bool,
     ) -> Result<Self> {
         let num_peers = peers.len();
 
         let mut instance_states = Vec::new();
         let mut errors = Vec::new();
-        for url in &peers {
-            let instance = Instance::new(
-                format!(
-                    "{}:{}",
-                    url.host().unwrap(),
-                    url.port_or_known_default().unwrap()
-                ), /* short_hash */
-                url.clone(),
-                None,
-            );
-            match instance.rest_client().get_ledger_information().await {
-                Ok(v) => instance_states.push((instance, v.into_inner())),
-                Err(err) => errors.push(err),
+        let mut excluded = Vec::new();
+        for instance in peers {
+            match instance.rest_client().get_index().await {
+                Ok(v) => {
+                    let index = v.into_inner();
+                    instance_states.push((instance.with_role(index.node_role), index))
+                }
+                Err(err) => {
+                    excluded.push(format!("{} (not alive: {})", instance.peer_name(), err));
+                    errors.push(err);
+                }
             }
         }
 
@@ -69,9 +173,9 @@ impl Cluster {
         let mut instances = Vec::new();
         let max_version = instance_states
             .iter()
-            .map(|(_, s)| s.version)
+            .map(|(_, s)| *s.ledger_version.inner())
             .max()
-            .unwrap();
+            .unwrap_or_default();
 
         for (instance, state) in instance_states.into_iter() {
             if state.chain_id != chain_id.id() {
@@ -80,13 +184,23 @@ impl Cluster {
                     instance.peer_name(),
                     state.chain_id
                 );
-            } else if state.version + 100000 < max_version {
+                excluded.push(format!(
+                    "{} (wrong chain {})",
+                    instance.peer_name(),
+                    state.chain_id
+                ));
+            } else if *state.ledger_version.inner() + 100000 < max_version {
                 warn!(
                     "Client {} too stale, {}, while chain at {}",
                     instance.peer_name(),
-                    state.version,
+                    state.ledger_version,
                     max_version
                 );
+                excluded.push(format!(
+                    "{} (too stale at {})",
+                    instance.peer_name(),
+                    state.ledger_version
+                ));
             } else {
                 instances.push(instance);
             }
@@ -115,32 +229,153 @@ impl Cluster {
             instances,
             mint_key_pair,
             chain_id,
+            target_selection: TargetSelectionPolicy::Any,
+            dead_instance_policy: DeadInstancePolicy::Skip,
+            excluded,
+            query_instances: Vec::new(),
         })
     }
 
+    pub fn with_target_selection(mut self, target_selection: TargetSelectionPolicy) -> Self {
+        self.target_selection = target_selection;
+        self
+    }
+
+    /// Fails if any instance was left out of the cluster under `DeadInstancePolicy::Fail`.
+    /// The policy also applies to instances dying while transactions are emitted.
+    pub fn with_dead_instance_policy(mut self, policy: DeadInstancePolicy) -> Result<Self> {
+        if policy == DeadInstancePolicy::Fail && !self.excluded.is_empty() {
+            bail!(
+                "Some instances are not usable: {}",
+                self.excluded.join(", ")
+            );
+        }
+        self.dead_instance_policy = policy;
+        Ok(self)
+    }
+
+    pub fn with_query_instances(mut self, query_instances: Vec<Instance>) -> Self {
+        self.query_instances = query_instances;
+        self
+    }
+
+    /// Instances to use for read-side calls such as sequence number polling. Defaults to the
+    /// submission instances, each caller building its own clients (and connection pools).
+    pub fn query_instances(&self) -> Box<dyn Iterator<Item = &Instance> + '_> {
+        if self.query_instances.is_empty() {
+            Box::new(self.submission_instances())
+        } else {
+            Box::new(self.query_instances.iter())
+        }
+    }
+
+    pub fn dead_instance_policy(&self) -> DeadInstancePolicy {
+        self.dead_instance_policy
+    }
+
+    /// Instances left out of the cluster when it was built, with the reason why.
+    pub fn excluded_instances(&self) -> &[String] {
+        &self.excluded
+    }
+
+    #[cfg(feature = "cli")]
     pub async fn try_from_cluster_args(args: &ClusterArgs) -> Result<Self> {
-        let mut urls = Vec::new();
-        for url in &args.targets {
-            if !url.has_host() {
-                bail!("No host found in URL: {}", url);
+        let mut instances = Vec::new();
+        for target in &args.targets {
+            if !target.url.has_host() {
+                bail!("No host found in URL: {}", target.url);
             }
-            let mut url = url.clone();
+            let mut url = target.url.clone();
             if url.port_or_known_default().is_none() {
                 url.set_port(Some(8080))
                     .map_err(|_| format_err!("Failed to set port unexpectedly"))?;
             }
-            urls.push(url);
+            let instance = if target.detect_scheme {
+                Self::detect_target_scheme(args, target, url).await?
+            } else {
+                Self::target_instance(args, target, url)?
+            };
+            instances.push(instance);
         }
 
-        let mint_key = args.mint_args.get_mint_key()?;
+        let mint_key = match &args.mint_args.mint_key_secret {
+            Some(secret) => fetch_mint_key_secret(secret).await?,
+            None => args.mint_args.get_mint_key()?,
+        };
 
-        let cluster = Cluster::from_host_port(urls, mint_key, args.chain_id, args.reuse_accounts)
-            .await
-            .map_err(|e| format_err!("failed to create a cluster from host and port: {}", e))?;
+        let cluster =
+            Cluster::from_instances(instances, mint_key, args.chain_id, args.reuse_accounts)
+                .await
+                .map_err(|e| format_err!("failed to create a cluster from host and port: {}", e))?
+                .with_target_selection(args.target_selection)
+                .with_dead_instance_policy(args.dead_instances)?;
+
+        let mut query_instances = Vec::new();
+        for url in &args.query_targets {
+            let target = Target::from(url.clone());
+            query_instances.push(Self::target_instance(args, &target, url.clone())?);
+        }
+        let cluster = cluster.with_query_instances(query_instances);
 
         Ok(cluster)
     }
 
+    #[cfg(feature = "cli")]
+    fn target_instance(args: &ClusterArgs, target: &Target, url: Url) -> Result<Instance> {
+        let mut instance =
+            Instance::new(peer_name(&url), url.clone(), None).with_weight(target.weight);
+        for header in args.target_headers.iter().filter(|h| h.matches(&url)) {
+            instance = instance.with_header(&header.name, &header.value)?;
+        }
+        let proxy = args
+            .target_proxies
+            .iter()
+            .find(|p| p.matches(&url))
+            .map(|p| &p.proxy)
+            .or_else(|| args.proxy.as_ref());
+        if let Some(proxy) = proxy {
+            instance = instance.with_proxy(proxy.clone())?;
+        }
+        Ok(instance)
+    }
+
+    /// Picks the first of the configured schemes the target answers on. If it answers on none,
+    /// the first scheme is kept and the target is reported as unreachable with the others.
+    #[cfg(feature = "cli")]
+    async fn detect_target_scheme(
+        args: &ClusterArgs,
+        target: &Target,
+        url: Url,
+    ) -> Result<Instance> {
+        let mut fallback = None;
+        for scheme in &args.bare_target_schemes {
+            let mut url = url.clone();
+            url.set_scheme(scheme.as_str())
+                .map_err(|_| format_err!("Failed to set scheme {} for {}", scheme.as_str(), url))?;
+            let instance = Self::target_instance(args, target, url)?;
+            match instance.rest_client().get_index().await {
+                Ok(_) => {
+                    info!(
+                        "Using {} for target {}",
+                        scheme.as_str(),
+                        instance.peer_name()
+                    );
+                    return Ok(instance);
+                }
+                Err(e) => {
+                    info!(
+                        "Target {} does not answer on {}: {}",
+                        instance.peer_name(),
+                        scheme.as_str(),
+                        e
+                    );
+                    fallback.get_or_insert(instance);
+                }
+            }
+        }
+        fallback.ok_or_else(|| format_err!("No scheme configured for bare target {}", url))
+    }
+
     fn account_key(&self) -> AccountKey {
         AccountKey::from_private_key(clone(&self.mint_key_pair.private_key))
     }
@@ -185,9 +420,50 @@ impl Cluster {
             .clone()
     }
 
+    /// Picks a random instance for administrative calls, e.g. loading the root account.
+    /// Validators are preferred when the cluster prefers to submit load to fullnodes.
+    pub fn random_admin_instance(&self) -> Instance {
+        match self.target_selection {
+            TargetSelectionPolicy::Any => self.random_instance(),
+            TargetSelectionPolicy::PreferFullNodes => {
+                let validators: Vec<_> =
+                    self.instances.iter().filter(|i| i.is_validator()).collect();
+                let mut rnd = rand::thread_rng();
+                validators
+                    .choose(&mut rnd)
+                    .map(|i| (*i).clone())
+                    .unwrap_or_else(|| self.random_instance())
+            }
+        }
+    }
+
     pub fn all_instances(&self) -> impl Iterator<Item = &Instance> {
         self.instances.iter()
     }
+
+    /// Returns the instances transactions should be submitted to, according to the cluster's
+    /// target selection policy.
+    pub fn submission_instances(&self) -> impl Iterator<Item = &Instance> {
+        let fullnodes_only = self.target_selection == TargetSelectionPolicy::PreferFullNodes
+            && self.instances.iter().any(|i| i.is_full_node());
+        if self.target_selection == TargetSelectionPolicy::PreferFullNodes && !fullnodes_only {
+            warn!("No fullnode targets found, submitting to all targets");
+        }
+        self.instances
+            .iter()
+            .filter(move |i| !fullnodes_only || i.is_full_node())
+    }
+}
+
+/// Builds a short, human readable name for the peer behind `url`. The path prefix is included so
+/// that several targets served by the same gateway can be told apart.
+fn peer_name(url: &Url) -> String {
+    format!(
+        "{}:{}{}",
+        url.host().unwrap(),
+        url.port_or_known_default().unwrap(),
+        url.path().trim_end_matches('/')
+    )
 }
 
 pub fn dummy_key_pair() -> KeyPair<Ed25519PrivateKey, Ed25519PublicKey> {
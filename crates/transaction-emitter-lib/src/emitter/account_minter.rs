@@ -4,11 +4,11 @@
 use crate::emitter::wait_for_single_account_sequence;
 use crate::{
     emitter::{GAS_AMOUNT, MAX_TXNS, RETRY_POLICY, SEND_AMOUNT},
-    query_sequence_numbers, EmitJobRequest, EmitModeParams,
+    mint_key::load_mint_key,
+    query_sequence_numbers, EmitJobRequest, EmitModeParams, MintKeyFormat,
 };
 use anyhow::{anyhow, format_err, Context, Result};
-use aptos::common::types::EncodingType;
-use aptos_crypto::ed25519::{Ed25519PrivateKey, Ed25519PublicKey};
+use aptos_crypto::ed25519::Ed25519PublicKey;
 use aptos_infallible::Mutex;
 use aptos_logger::sample::Sampling;
 use aptos_logger::{debug, info, sample, sample::SampleRate, warn};
@@ -34,6 +34,43 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use std::{collections::HashMap, path::Path};
 
+/// Coins the root account hands out to create accounts, see `AccountMinter::create_accounts`.
+#[derive(Clone, Copy, Debug)]
+pub struct AccountFunding {
+    pub num_seed_accounts: usize,
+    pub coins_per_seed_account: u64,
+    pub coins_per_account: u64,
+    /// Balance the root account needs for all of it
+    pub coins_for_root: u64,
+}
+
+impl AccountFunding {
+    pub fn new(num_clients: usize, num_accounts: usize) -> Self {
+        let num_seed_accounts = if num_accounts / num_clients > MAX_CHILD_VASP_NUM {
+            num_accounts / MAX_CHILD_VASP_NUM + 1
+        } else {
+            (num_accounts / 50).max(1)
+        };
+        let coins_per_account = (MAX_TXNS / num_accounts as u64)
+            .checked_mul(SEND_AMOUNT + GAS_AMOUNT)
+            .unwrap(); // extra coins for secure to pay none zero gas price
+        let coins_per_seed_account = (num_accounts as u64)
+            .checked_mul(coins_per_account + 1_000_000)
+            .unwrap();
+        let coins_for_root = coins_per_seed_account
+            .checked_mul(num_seed_accounts as u64)
+            .unwrap()
+            .checked_add(1_000_000)
+            .unwrap();
+        Self {
+            num_seed_accounts,
+            coins_per_seed_account,
+            coins_per_account,
+            coins_for_root,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct AccountMinter<'t> {
     txn_factory: TransactionFactory,
@@ -70,25 +107,14 @@ impl<'t> AccountMinter<'t> {
         total_requested_accounts: usize,
     ) -> Result<Vec<LocalAccount>> {
         let mut accounts = vec![];
-        let expected_num_seed_accounts =
-            if total_requested_accounts / req.rest_clients.len() > MAX_CHILD_VASP_NUM {
-                total_requested_accounts / MAX_CHILD_VASP_NUM + 1
-            } else {
-                (total_requested_accounts / 50).max(1)
-            };
         let num_accounts = total_requested_accounts - accounts.len(); // Only minting extra accounts
-        let coins_per_account = (MAX_TXNS / total_requested_accounts as u64)
-            .checked_mul(SEND_AMOUNT + GAS_AMOUNT)
-            .unwrap(); // extra coins for secure to pay none zero gas price
+        let AccountFunding {
+            num_seed_accounts: expected_num_seed_accounts,
+            coins_per_seed_account,
+            coins_per_account,
+            coins_for_root,
+        } = AccountFunding::new(req.rest_clients.len(), num_accounts);
         let txn_factory = self.txn_factory.clone();
-        let coins_per_seed_account = (num_accounts as u64)
-            .checked_mul(coins_per_account + 1_000_000)
-            .unwrap();
-        let coins_for_root = coins_per_seed_account
-            .checked_mul(expected_num_seed_accounts as u64)
-            .unwrap()
-            .checked_add(1_000_000)
-            .unwrap();
         if req.mint_to_root {
             self.mint_to_root(&req.rest_clients, coins_for_root).await?;
         } else {
@@ -273,9 +299,7 @@ impl<'t> AccountMinter<'t> {
         index: usize,
     ) -> Result<LocalAccount> {
         let file = "vasp".to_owned() + index.to_string().as_str() + ".key";
-        let mint_key: Ed25519PrivateKey = EncodingType::BCS
-            .load_key("vasp private key", Path::new(&file))
-            .unwrap();
+        let mint_key = load_mint_key(Path::new(&file), MintKeyFormat::Bcs, None, "").unwrap();
         let account_key = AccountKey::from_private_key(mint_key);
         let address = account_key.authentication_key().derived_address();
         let sequence_number = query_sequence_numbers(client, [address].iter())
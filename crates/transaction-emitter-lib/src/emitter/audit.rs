@@ -0,0 +1,249 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Audit of the transactions of a job: every submitted transaction is tracked by hash to a
+//! terminal state, and the outcome is checked against the job's stats.
+
+use crate::emitter::stats::TxnStats;
+use anyhow::{bail, Result};
+use aptos_infallible::Mutex;
+use aptos_rest_client::{aptos_api_types::Transaction, error::RestError, Client as RestClient};
+use aptos_sdk::{crypto::HashValue, types::transaction::SignedTransaction};
+use futures::future::join_all;
+use std::{
+    collections::HashSet,
+    fmt,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::time;
+
+/// Transactions are looked up this many at a time
+const AUDIT_BATCH: usize = 100;
+
+/// A transaction as submitted by a job.
+#[derive(Clone, Debug)]
+pub struct SubmittedTxn {
+    pub hash: HashValue,
+    pub expiration_timestamp_secs: u64,
+    /// Endpoint the transaction was submitted to
+    pub endpoint: String,
+    /// Whether the endpoint accepted the transaction
+    pub accepted: bool,
+}
+
+/// Every transaction submitted by a job, if it audits its transactions.
+#[derive(Debug, Default)]
+pub struct SubmissionLog {
+    txns: Mutex<Vec<SubmittedTxn>>,
+}
+
+impl SubmissionLog {
+    /// Records a batch submitted to `endpoint`, the transactions at `rejected` indices having
+    /// been rejected by it.
+    pub(crate) fn record(
+        &self,
+        endpoint: &str,
+        txns: &[SignedTransaction],
+        rejected: &HashSet<usize>,
+    ) {
+        let mut log = self.txns.lock();
+        log.extend(txns.iter().enumerate().map(|(index, txn)| SubmittedTxn {
+            hash: txn.clone().committed_hash(),
+            expiration_timestamp_secs: txn.expiration_timestamp_secs(),
+            endpoint: endpoint.to_string(),
+            accepted: !rejected.contains(&index),
+        }));
+    }
+
+    pub fn take(&self) -> Vec<SubmittedTxn> {
+        std::mem::take(&mut *self.txns.lock())
+    }
+}
+
+/// Terminal state of a submitted transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxnOutcome {
+    Committed,
+    /// Not committed, and past its expiration
+    Expired,
+    /// Rejected by the endpoint it was submitted to
+    Rejected,
+    /// Accepted by the endpoint, but unknown to it before its expiration
+    NeverSeen,
+    /// Still pending past its expiration, or couldn't be looked up
+    Unresolved,
+}
+
+#[derive(Debug, Default)]
+pub struct TxnAudit {
+    pub outcomes: Vec<(HashValue, TxnOutcome)>,
+}
+
+impl TxnAudit {
+    pub fn count(&self, outcome: TxnOutcome) -> u64 {
+        self.outcomes.iter().filter(|(_, o)| *o == outcome).count() as u64
+    }
+
+    /// Transactions without a terminal state.
+    pub fn missing(&self) -> Vec<HashValue> {
+        self.outcomes
+            .iter()
+            .filter(|(_, o)| matches!(o, TxnOutcome::NeverSeen | TxnOutcome::Unresolved))
+            .map(|(hash, _)| *hash)
+            .collect()
+    }
+
+    /// Fails if some transactions have no terminal state, or if the counts of `stats` don't
+    /// match the audited ones, listing the transactions without a terminal state.
+    pub fn check(&self, stats: &TxnStats) -> Result<()> {
+        let mut discrepancies = vec![];
+        let mut compare = |what: &str, counted: u64, audited: u64| {
+            if counted != audited {
+                discrepancies.push(format!("{} {} counted, {} audited", counted, what, audited));
+            }
+        };
+        compare("submitted", stats.submitted, self.outcomes.len() as u64);
+        compare(
+            "committed",
+            stats.committed,
+            self.count(TxnOutcome::Committed),
+        );
+        compare(
+            "failed submissions",
+            stats.failed_submission,
+            self.count(TxnOutcome::Rejected),
+        );
+        compare(
+            "expired",
+            stats.expired,
+            self.count(TxnOutcome::Expired)
+                + self.count(TxnOutcome::NeverSeen)
+                + self.count(TxnOutcome::Unresolved),
+        );
+        let missing = self.missing();
+        if discrepancies.is_empty() && missing.is_empty() {
+            return Ok(());
+        }
+        bail!(
+            "Transaction audit failed: {}, {} transactions without a terminal state: {:?}",
+            if discrepancies.is_empty() {
+                "counts match".to_string()
+            } else {
+                discrepancies.join(", ")
+            },
+            missing.len(),
+            missing
+        );
+    }
+}
+
+impl fmt::Display for TxnAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "audited {} transactions: committed: {}, expired: {}, rejected: {}, never seen: {}, unresolved: {}",
+            self.outcomes.len(),
+            self.count(TxnOutcome::Committed),
+            self.count(TxnOutcome::Expired),
+            self.count(TxnOutcome::Rejected),
+            self.count(TxnOutcome::NeverSeen),
+            self.count(TxnOutcome::Unresolved),
+        )
+    }
+}
+
+/// Looks every transaction up on the endpoint it was submitted to (or the first of `clients`
+/// if it's gone), waiting for pending ones up to `grace` past their expiration.
+pub async fn audit_transactions(
+    clients: &[RestClient],
+    txns: Vec<SubmittedTxn>,
+    grace: Duration,
+) -> TxnAudit {
+    let mut audit = TxnAudit::default();
+    if clients.is_empty() {
+        audit.outcomes = txns
+            .into_iter()
+            .map(|txn| (txn.hash, TxnOutcome::Unresolved))
+            .collect();
+        return audit;
+    }
+    for batch in txns.chunks(AUDIT_BATCH) {
+        let outcomes = join_all(batch.iter().map(|txn| {
+            let client = clients
+                .iter()
+                .find(|client| client.path_prefix_string() == txn.endpoint)
+                .unwrap_or(&clients[0]);
+            audit_transaction(client, txn, grace)
+        }))
+        .await;
+        audit
+            .outcomes
+            .extend(batch.iter().map(|txn| txn.hash).zip(outcomes));
+    }
+    audit
+}
+
+async fn audit_transaction(client: &RestClient, txn: &SubmittedTxn, grace: Duration) -> TxnOutcome {
+    if !txn.accepted {
+        return TxnOutcome::Rejected;
+    }
+    let expiration = UNIX_EPOCH + Duration::from_secs(txn.expiration_timestamp_secs);
+    let deadline = Instant::now()
+        + expiration
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+        + grace;
+    let mut seen = false;
+    loop {
+        let expired = SystemTime::now() >= expiration;
+        match client.get_transaction_by_hash(txn.hash).await {
+            Ok(response) => match response.into_inner() {
+                Transaction::PendingTransaction(_) => seen = true,
+                _ => return TxnOutcome::Committed,
+            },
+            Err(RestError::Api(e)) if e.status_code.as_u16() == 404 => {
+                return if expired || seen {
+                    TxnOutcome::Expired
+                } else {
+                    TxnOutcome::NeverSeen
+                };
+            }
+            Err(_) => {}
+        }
+        if Instant::now() >= deadline {
+            return TxnOutcome::Unresolved;
+        }
+        time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_check() {
+        let hashes: Vec<_> = (0..4).map(|_| HashValue::random()).collect();
+        let mut audit = TxnAudit {
+            outcomes: vec![
+                (hashes[0], TxnOutcome::Committed),
+                (hashes[1], TxnOutcome::Committed),
+                (hashes[2], TxnOutcome::Expired),
+                (hashes[3], TxnOutcome::Rejected),
+            ],
+        };
+        let stats = TxnStats {
+            submitted: 4,
+            committed: 2,
+            expired: 1,
+            failed_submission: 1,
+            ..TxnStats::default()
+        };
+        audit.check(&stats).unwrap();
+
+        audit.outcomes[1].1 = TxnOutcome::NeverSeen;
+        let error = audit.check(&stats).unwrap_err().to_string();
+        assert!(error.contains("2 committed counted, 1 audited"));
+        assert!(error.contains(&format!("{:?}", hashes[1])));
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_infallible::RwLock;
+use aptos_rest_client::Client as RestClient;
+use rand::Rng;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// Weight of the newest sample in the moving average, higher values react faster to changes.
+const LATENCY_SMOOTHING: f64 = 0.2;
+/// Latency recorded for a failed submission, so that endpoints failing fast are avoided rather
+/// than preferred
+const FAILED_SUBMISSION_PENALTY: Duration = Duration::from_secs(10);
+
+/// Exponentially weighted moving average of the submission latency of every endpoint.
+#[derive(Debug, Default)]
+pub struct EndpointLatencies {
+    latencies_millis: RwLock<HashMap<String, f64>>,
+}
+
+impl EndpointLatencies {
+    pub fn record(&self, endpoint: String, latency: Duration) {
+        let sample = latency.as_secs_f64() * 1000.0;
+        let mut latencies = self.latencies_millis.write();
+        let average = latencies.entry(endpoint).or_insert(sample);
+        *average += LATENCY_SMOOTHING * (sample - *average);
+    }
+
+    pub fn record_failure(&self, endpoint: String) {
+        self.record(endpoint, FAILED_SUBMISSION_PENALTY);
+    }
+
+    pub fn get(&self, endpoint: &str) -> Option<f64> {
+        self.latencies_millis.read().get(endpoint).cloned()
+    }
+
+    /// Endpoints with their average submission latency in millis, fastest first.
+    pub fn ranking(&self) -> Vec<(String, u64)> {
+        let mut ranking: Vec<_> = self
+            .latencies_millis
+            .read()
+            .iter()
+            .map(|(endpoint, latency)| (endpoint.clone(), latency.round() as u64))
+            .collect();
+        ranking.sort_by(|(a_endpoint, a), (b_endpoint, b)| {
+            a.cmp(b).then_with(|| a_endpoint.cmp(b_endpoint))
+        });
+        ranking
+    }
+}
+
+/// Picks the endpoint a batch of transactions is submitted to, favoring the endpoints that
+/// currently accept submissions the fastest.
+#[derive(Debug)]
+pub struct EndpointSelector {
+    clients: RwLock<Vec<RestClient>>,
+    latencies: Arc<EndpointLatencies>,
+    /// 0 spreads submissions evenly, every increment doubles how strongly an endpoint twice
+    /// as fast as another one is preferred.
+    aggressiveness: f64,
+}
+
+impl EndpointSelector {
+    pub fn new(
+        clients: Vec<RestClient>,
+        latencies: Arc<EndpointLatencies>,
+        aggressiveness: f64,
+    ) -> Self {
+        Self {
+            clients: RwLock::new(clients),
+            latencies,
+            aggressiveness,
+        }
+    }
+
+    pub fn set_clients(&self, clients: Vec<RestClient>) {
+        *self.clients.write() = clients;
+    }
+
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> Option<RestClient> {
+        let clients = self.clients.read();
+        let latencies: Vec<_> = clients
+            .iter()
+            .map(|client| self.latencies.get(&client.path_prefix_string()))
+            .collect();
+        let weights = latency_weights(&latencies, self.aggressiveness);
+        let mut picked = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (client, weight) in clients.iter().zip(weights) {
+            if picked < weight {
+                return Some(client.clone());
+            }
+            picked -= weight;
+        }
+        clients.last().cloned()
+    }
+}
+
+/// Relative weight of every endpoint, the fastest one has weight 1. Endpoints without any
+/// measurement yet are treated as the fastest, so that they get measured.
+fn latency_weights(latencies_millis: &[Option<f64>], aggressiveness: f64) -> Vec<f64> {
+    let fastest = latencies_millis
+        .iter()
+        .flatten()
+        .fold(f64::INFINITY, |fastest, latency| {
+            fastest.min(latency.max(1.0))
+        });
+    latencies_millis
+        .iter()
+        .map(|latency| match latency {
+            Some(latency) => (fastest / latency.max(1.0)).powf(aggressiveness),
+            None => 1.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_weights() {
+        let latencies = [Some(100.0), Some(200.0), None, Some(400.0)];
+        assert_eq!(latency_weights(&latencies, 0.0), vec![1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(latency_weights(&latencies, 1.0), vec![1.0, 0.5, 1.0, 0.25]);
+        assert_eq!(
+            latency_weights(&latencies, 2.0),
+            vec![1.0, 0.25, 1.0, 0.0625]
+        );
+    }
+
+    #[test]
+    fn test_ranking() {
+        let latencies = EndpointLatencies::default();
+        latencies.record("b".to_string(), Duration::from_millis(300));
+        latencies.record("a".to_string(), Duration::from_millis(100));
+        latencies.record("b".to_string(), Duration::from_millis(200));
+        assert_eq!(
+            latencies.ranking(),
+            vec![("a".to_string(), 100), ("b".to_string(), 280)]
+        );
+
+        latencies.record_failure("a".to_string());
+        assert_eq!(
+            latencies.ranking(),
+            vec![("b".to_string(), 280), ("a".to_string(), 2080)]
+        );
+    }
+}
@@ -2,12 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod account_minter;
+pub mod audit;
+pub mod endpoint_latency;
 pub mod stats;
 pub mod submission_worker;
 
 use ::aptos_logger::*;
 use again::RetryPolicy;
-use anyhow::{anyhow, format_err, Result};
+use anyhow::{anyhow, bail, ensure, format_err, Result};
 use aptos_infallible::RwLock;
 use aptos_rest_client::Client as RestClient;
 use aptos_sdk::{
@@ -23,6 +25,7 @@ use rand_core::SeedableRng;
 use std::{
     cmp::{max, min},
     collections::{HashMap, HashSet},
+    fmt, mem,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -32,13 +35,18 @@ use std::{
 use tokio::{runtime::Handle, task::JoinHandle, time};
 
 use crate::{
-    args::TransactionType,
-    emitter::{account_minter::AccountMinter, submission_worker::SubmissionWorker},
+    emitter::{
+        account_minter::{AccountFunding, AccountMinter},
+        audit::audit_transactions,
+        endpoint_latency::EndpointSelector,
+        submission_worker::SubmissionWorker,
+    },
     transaction_generator::{
         account_generator::AccountGeneratorCreator, nft_mint::NFTMintGeneratorCreator,
         p2p_transaction_generator::P2PTransactionGeneratorCreator,
         transaction_mix_generator::TxnMixGeneratorCreator, TransactionGeneratorCreator,
     },
+    DeadInstancePolicy, TransactionType,
 };
 use aptos_sdk::transaction_builder::aptos_stdlib;
 use rand::rngs::StdRng;
@@ -78,8 +86,19 @@ pub struct EmitModeParams {
 
 #[derive(Clone, Debug)]
 pub enum EmitJobMode {
-    MaxLoad { mempool_backlog: usize },
-    ConstTps { tps: usize },
+    MaxLoad {
+        mempool_backlog: usize,
+    },
+    ConstTps {
+        tps: usize,
+    },
+    /// Submits `txns_per_burst` transactions spread over `burst_interval`, then nothing for
+    /// `idle`, over and over.
+    Burst {
+        txns_per_burst: usize,
+        burst_interval: Duration,
+        idle: Duration,
+    },
 }
 
 impl EmitJobMode {
@@ -116,6 +135,16 @@ pub struct EmitJobRequest {
     max_account_working_set: usize,
 
     txn_expiration_time_secs: u64,
+
+    latency_bias: f64,
+
+    liveness_policy: Option<DeadInstancePolicy>,
+
+    query_clients: Vec<RestClient>,
+
+    audit: bool,
+
+    min_success_ratio: Option<f64>,
 }
 
 impl Default for EmitJobRequest {
@@ -133,6 +162,11 @@ impl Default for EmitJobRequest {
             add_created_accounts_to_pool: true,
             max_account_working_set: 1_000_000,
             txn_expiration_time_secs: 60,
+            latency_bias: 0.0,
+            liveness_policy: None,
+            query_clients: Vec::new(),
+            audit: false,
+            min_success_ratio: None,
         }
     }
 }
@@ -192,6 +226,54 @@ impl EmitJobRequest {
         self
     }
 
+    /// Submit transactions to endpoints picked by their measured submission latency, instead of
+    /// each worker submitting to its own endpoint. 0 disables the bias, higher values send more
+    /// submissions to faster endpoints: an endpoint twice as fast as another one receives
+    /// 2^latency_bias times as many.
+    pub fn latency_bias(mut self, latency_bias: f64) -> Self {
+        self.latency_bias = latency_bias;
+        self
+    }
+
+    /// Clients used by the workers to poll account sequence numbers, so that polling doesn't
+    /// compete with submissions for connections. Workers are spread over them round robin.
+    /// If empty, workers poll through their submission client.
+    pub fn query_clients(mut self, query_clients: Vec<RestClient>) -> Self {
+        self.query_clients = query_clients;
+        self
+    }
+
+    /// Probe endpoints for liveness while the job runs (with every periodic stat), and skip
+    /// or fail on dead ones according to `policy`.
+    pub fn check_liveness(mut self, policy: DeadInstancePolicy) -> Self {
+        self.liveness_policy = Some(policy);
+        self
+    }
+
+    /// Track every submitted transaction to a terminal state when the job stops, failing if
+    /// some have none or if the stats don't add up, see `TxnEmitter::stop_job_with_checks`.
+    pub fn audit_transactions(mut self) -> Self {
+        self.audit = true;
+        self
+    }
+
+    /// Fail the job when it stops if less than this share of the submitted transactions was
+    /// committed, see `TxnEmitter::stop_job_with_checks`.
+    pub fn min_success_ratio(mut self, min_success_ratio: f64) -> Self {
+        self.min_success_ratio = Some(min_success_ratio);
+        self
+    }
+
+    /// Accounts used by a job started from this request.
+    pub fn num_accounts(&self, mode_params: &EmitModeParams) -> usize {
+        self.rest_clients.len() * mode_params.workers_per_endpoint * mode_params.accounts_per_worker
+    }
+
+    /// Coins the root account hands out to create `num_accounts` accounts for this request.
+    pub fn account_funding(&self, num_accounts: usize) -> AccountFunding {
+        AccountFunding::new(self.rest_clients.len(), num_accounts)
+    }
+
     pub fn calculate_mode_params(&self) -> EmitModeParams {
         let clients_count = self.rest_clients.len();
 
@@ -297,6 +379,53 @@ impl EmitJobRequest {
                     check_account_sequence_only_once_fraction: 1.0 - 0.02,
                 }
             }
+            EmitJobMode::Burst {
+                txns_per_burst,
+                burst_interval,
+                idle,
+            } => {
+                // Every worker submits a single batch per burst, the workers being spread over
+                // the burst interval, and all of them waiting out the idle period together.
+                // Workers wait for their transactions before the next burst, so a burst can be
+                // delayed if the previous one takes longer than the whole cycle to commit.
+                let transactions_per_account = min(100, txns_per_burst);
+                assert!(
+                    transactions_per_account > 0,
+                    "Transactions per burst ({}) needs to be larger than 0",
+                    txns_per_burst,
+                );
+                let num_workers_per_endpoint = max(
+                    (txns_per_burst + clients_count * transactions_per_account - 1)
+                        / (clients_count * transactions_per_account),
+                    1,
+                );
+                let cycle_millis = (burst_interval + idle).as_millis() as u64;
+                assert!(cycle_millis > 0, "Burst interval and idle can't both be 0");
+
+                info!(
+                    " Transaction emitter bursting {} txns over {:?} every {:?}",
+                    clients_count * num_workers_per_endpoint * transactions_per_account,
+                    burst_interval,
+                    burst_interval + idle
+                );
+                info!(
+                    " Will use {} clients and {} workers per client",
+                    clients_count, num_workers_per_endpoint
+                );
+
+                EmitModeParams {
+                    wait_millis: cycle_millis,
+                    txn_expiration_time_secs: self.txn_expiration_time_secs,
+                    transactions_per_account,
+                    max_submit_batch_size: 100,
+                    start_offset_multiplier_millis: burst_interval.as_millis() as f64
+                        / (num_workers_per_endpoint * clients_count) as f64,
+                    start_jitter_millis: 0,
+                    accounts_per_worker: 1,
+                    workers_per_endpoint: num_workers_per_endpoint,
+                    check_account_sequence_only_once_fraction: 0.0,
+                }
+            }
         }
     }
 }
@@ -304,18 +433,261 @@ impl EmitJobRequest {
 #[derive(Debug)]
 struct Worker {
     join_handle: JoinHandle<Vec<LocalAccount>>,
+    stop: Arc<AtomicBool>,
+    client: RestClient,
+    index: usize,
+    check_account_sequence_only_once: bool,
 }
 
-#[derive(Debug)]
 pub struct EmitJob {
     workers: Vec<Worker>,
-    stop: Arc<AtomicBool>,
+    clients: Vec<RestClient>,
+    endpoint_selector: Option<Arc<EndpointSelector>>,
+    liveness_policy: Option<DeadInstancePolicy>,
+    query_clients: Vec<RestClient>,
+    min_success_ratio: Option<f64>,
     stats: Arc<StatsAccumulator>,
+    mode_params: EmitModeParams,
+    txn_generator_creator: Box<dyn TransactionGeneratorCreator>,
+    rng: StdRng,
+}
+
+impl fmt::Debug for EmitJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmitJob")
+            .field("workers", &self.workers)
+            .field("endpoints", &self.endpoints())
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl EmitJob {
+    /// The stats of the job, updated as it runs.
+    pub fn stats(&self) -> Arc<StatsAccumulator> {
+        self.stats.clone()
+    }
+
+    /// Returns the endpoints the job is currently submitting to.
+    pub fn endpoints(&self) -> Vec<String> {
+        self.clients
+            .iter()
+            .map(|c| c.path_prefix_string())
+            .collect()
+    }
+
+    /// Adds a new endpoint to the running job, moving workers over from the most loaded
+    /// endpoints so that load stays evenly spread. No new accounts are created.
+    pub async fn add_endpoint(&mut self, client: RestClient) -> Result<()> {
+        let endpoint = client.path_prefix_string();
+        if self.endpoints().contains(&endpoint) {
+            bail!("Endpoint {} is already part of the job", endpoint);
+        }
+
+        let mut workers_by_endpoint = self.workers_by_endpoint();
+        let num_to_move = self.workers.len() / (self.clients.len() + 1);
+        let mut assignments = Vec::new();
+        for _ in 0..num_to_move {
+            let busiest = workers_by_endpoint
+                .values_mut()
+                .max_by_key(|indices| indices.len())
+                .expect("Job must have at least one endpoint");
+            if let Some(index) = busiest.pop() {
+                assignments.push((index, client.clone()));
+            }
+        }
+        info!(
+            "Adding endpoint {}, moving {} out of {} workers to it",
+            endpoint,
+            assignments.len(),
+            self.workers.len()
+        );
+
+        self.clients.push(client);
+        self.update_endpoint_selector();
+        self.reassign_workers(assignments).await;
+        Ok(())
+    }
+
+    /// Removes an endpoint from the running job, moving its workers (and their accounts) over
+    /// to the least loaded of the remaining endpoints.
+    pub async fn drain_endpoint(&mut self, endpoint: &str) -> Result<()> {
+        if !self.endpoints().iter().any(|e| e == endpoint) {
+            bail!("Endpoint {} is not part of the job", endpoint);
+        }
+        if self.clients.len() == 1 {
+            bail!(
+                "Cannot drain {}, it is the last endpoint of the job",
+                endpoint
+            );
+        }
+
+        let mut workers_by_endpoint = self.workers_by_endpoint();
+        let drained = workers_by_endpoint.remove(endpoint).unwrap_or_default();
+        self.clients.retain(|c| c.path_prefix_string() != endpoint);
+
+        let mut assignments = Vec::new();
+        for index in drained {
+            let (target, indices) = workers_by_endpoint
+                .iter_mut()
+                .min_by_key(|(_, indices)| indices.len())
+                .expect("Job must have at least one remaining endpoint");
+            indices.push(index);
+            let client = self
+                .clients
+                .iter()
+                .find(|c| &c.path_prefix_string() == target)
+                .cloned()
+                .expect("Every endpoint must have a client");
+            assignments.push((index, client));
+        }
+        info!(
+            "Draining endpoint {}, moving {} workers to other endpoints",
+            endpoint,
+            assignments.len()
+        );
+
+        self.update_endpoint_selector();
+        self.reassign_workers(assignments).await;
+        Ok(())
+    }
+
+    /// Probes every endpoint, if the job checks liveness. Dead endpoints are drained (their
+    /// workers move to live endpoints) and listed in the stats, or fail the job, depending
+    /// on the policy.
+    pub async fn check_liveness(&mut self) -> Result<()> {
+        let policy = match self.liveness_policy {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+        let mut dead = Vec::new();
+        let mut probed = HashSet::new();
+        for client in &self.clients {
+            let endpoint = client.path_prefix_string();
+            if !probed.insert(endpoint.clone()) {
+                continue;
+            }
+            if let Err(e) = client.get_index().await {
+                dead.push((endpoint, e));
+            }
+        }
+
+        for (endpoint, e) in dead {
+            if policy == DeadInstancePolicy::Fail {
+                bail!("Endpoint {} is not alive: {}", endpoint, e);
+            }
+            if probed.len() == 1 {
+                warn!(
+                    "Endpoint {} is not alive, keeping it as it is the last one: {}",
+                    endpoint, e
+                );
+                continue;
+            }
+            warn!("Endpoint {} is not alive, dropping it: {}", endpoint, e);
+            self.drain_endpoint(&endpoint).await?;
+            probed.remove(&endpoint);
+            self.stats
+                .excluded_endpoints
+                .write()
+                .push(format!("{} (not alive: {})", endpoint, e));
+        }
+        Ok(())
+    }
+
+    fn update_endpoint_selector(&self) {
+        if let Some(selector) = &self.endpoint_selector {
+            selector.set_clients(self.clients.clone());
+        }
+    }
+
+    /// Worker indices grouped by the endpoint they submit to, including endpoints without workers.
+    fn workers_by_endpoint(&self) -> HashMap<String, Vec<usize>> {
+        let mut workers_by_endpoint: HashMap<_, _> = self
+            .endpoints()
+            .into_iter()
+            .map(|endpoint| (endpoint, Vec::new()))
+            .collect();
+        for worker in &self.workers {
+            workers_by_endpoint
+                .entry(worker.client.path_prefix_string())
+                .or_default()
+                .push(worker.index);
+        }
+        workers_by_endpoint
+    }
+
+    /// Stops the given workers and restarts them, with the same accounts, against their new
+    /// endpoints.
+    async fn reassign_workers(&mut self, assignments: Vec<(usize, RestClient)>) {
+        let mut assignments: HashMap<_, _> = assignments.into_iter().collect();
+        let (moved, kept): (Vec<_>, Vec<_>) = mem::take(&mut self.workers)
+            .into_iter()
+            .partition(|worker| assignments.contains_key(&worker.index));
+        self.workers = kept;
+
+        for worker in &moved {
+            worker.stop.store(true, Ordering::Relaxed);
+        }
+        for worker in moved {
+            let accounts = worker
+                .join_handle
+                .await
+                .expect("TxnEmitter worker thread failed");
+            let client = assignments
+                .remove(&worker.index)
+                .expect("Moved worker must have an assignment");
+            let worker = self.spawn_worker(
+                accounts,
+                client,
+                worker.index,
+                worker.check_account_sequence_only_once,
+            );
+            self.workers.push(worker);
+        }
+    }
+
+    fn spawn_worker(
+        &mut self,
+        accounts: Vec<LocalAccount>,
+        client: RestClient,
+        index: usize,
+        check_account_sequence_only_once: bool,
+    ) -> Worker {
+        let stop = Arc::new(AtomicBool::new(false));
+        let query_client = if self.query_clients.is_empty() {
+            client.clone()
+        } else {
+            self.query_clients[index % self.query_clients.len()].clone()
+        };
+        let worker = SubmissionWorker::new(
+            accounts,
+            client.clone(),
+            query_client,
+            self.endpoint_selector.clone(),
+            stop.clone(),
+            self.mode_params.clone(),
+            self.stats.clone(),
+            self.txn_generator_creator.create_transaction_generator(),
+            index,
+            check_account_sequence_only_once,
+            StdRng::from_rng(&mut self.rng).unwrap(),
+        );
+        let join_handle = Handle::current().spawn(worker.run().boxed());
+        Worker {
+            join_handle,
+            stop,
+            client,
+            index,
+            check_account_sequence_only_once,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct TxnEmitter {
     accounts: Vec<LocalAccount>,
+    /// Accounts given with `add_accounts`, for the next jobs to use before creating new ones
+    supplied_accounts: Vec<LocalAccount>,
     txn_factory: TransactionFactory,
     rng: StdRng,
 }
@@ -324,17 +696,26 @@ impl TxnEmitter {
     pub fn new(transaction_factory: TransactionFactory, rng: StdRng) -> Self {
         Self {
             accounts: vec![],
+            supplied_accounts: vec![],
             txn_factory: transaction_factory,
             rng,
         }
     }
 
+    /// Adds existing accounts, e.g. from an account pool, for the next jobs to use before
+    /// creating new ones. The accounts need enough coins to pay for their transactions. Only
+    /// these are shared between jobs, the accounts a job creates are never used by another one.
+    pub fn add_accounts(&mut self, mut accounts: Vec<LocalAccount>) {
+        self.supplied_accounts.append(&mut accounts);
+    }
+
     pub fn take_account(&mut self) -> LocalAccount {
         self.accounts.remove(0)
     }
 
     pub fn clear(&mut self) {
         self.accounts.clear();
+        self.supplied_accounts.clear();
     }
 
     pub fn rng(&mut self) -> &mut StdRng {
@@ -350,27 +731,42 @@ impl TxnEmitter {
         root_account: &mut LocalAccount,
         req: EmitJobRequest,
     ) -> Result<EmitJob> {
+        if let Some(min_success_ratio) = req.min_success_ratio {
+            ensure!(
+                (0.0..=1.0).contains(&min_success_ratio),
+                "The minimum success ratio must be between 0 and 1, not {}",
+                min_success_ratio
+            );
+        }
         let mode_params = req.calculate_mode_params();
         let workers_per_endpoint = mode_params.workers_per_endpoint;
         let num_workers = req.rest_clients.len() * workers_per_endpoint;
-        let num_accounts = num_workers * mode_params.accounts_per_worker;
+        let num_accounts = req.num_accounts(&mode_params);
         info!(
             "Will use {} workers per endpoint for a total of {} endpoint clients and {} accounts",
             workers_per_endpoint, num_workers, num_accounts
         );
-        let mut account_minter =
-            AccountMinter::new(root_account, self.txn_factory.clone(), self.rng.clone());
-        let mut new_accounts = account_minter
-            .create_accounts(&req, &mode_params, num_accounts)
-            .await?;
-        self.accounts.append(&mut new_accounts);
-        let all_accounts = self.accounts.split_off(self.accounts.len() - num_accounts);
+        let num_supplied_accounts = num_accounts.min(self.supplied_accounts.len());
+        let mut all_accounts: Vec<_> = self
+            .supplied_accounts
+            .drain(..num_supplied_accounts)
+            .collect();
+        let num_new_accounts = num_accounts - num_supplied_accounts;
+        if num_new_accounts > 0 {
+            let mut account_minter =
+                AccountMinter::new(root_account, self.txn_factory.clone(), self.rng.clone());
+            let mut new_accounts = account_minter
+                .create_accounts(&req, &mode_params, num_new_accounts)
+                .await?;
+            all_accounts.append(&mut new_accounts);
+        }
         let all_addresses: Vec<_> = all_accounts.iter().map(|d| d.address()).collect();
         let all_addresses = Arc::new(RwLock::new(all_addresses));
         let mut all_accounts = all_accounts.into_iter();
-        let stop = Arc::new(AtomicBool::new(false));
-        let stats = Arc::new(StatsAccumulator::default());
-        let tokio_handle = Handle::current();
+        let stats = Arc::new(StatsAccumulator {
+            submission_log: req.audit.then(Default::default),
+            ..StatsAccumulator::default()
+        });
         let txn_factory = self
             .txn_factory
             .clone()
@@ -431,40 +827,55 @@ impl TxnEmitter {
             total_workers
         );
 
-        let mut workers = vec![];
+        let endpoint_selector = if req.latency_bias > 0.0 {
+            info!(
+                "Biasing submissions towards faster endpoints with aggressiveness {}",
+                req.latency_bias
+            );
+            Some(Arc::new(EndpointSelector::new(
+                req.rest_clients.clone(),
+                stats.endpoint_latencies.clone(),
+                req.latency_bias,
+            )))
+        } else {
+            None
+        };
+
+        let mut job = EmitJob {
+            workers: vec![],
+            clients: req.rest_clients.clone(),
+            endpoint_selector,
+            liveness_policy: req.liveness_policy,
+            query_clients: req.query_clients.clone(),
+            min_success_ratio: req.min_success_ratio,
+            stats,
+            mode_params: mode_params.clone(),
+            txn_generator_creator,
+            rng: self.from_rng(),
+        };
         for _ in 0..workers_per_endpoint {
             for client in &req.rest_clients {
                 let accounts = (&mut all_accounts)
                     .take(mode_params.accounts_per_worker)
                     .collect();
-                let stop = stop.clone();
-                let stats = Arc::clone(&stats);
-
-                let worker = SubmissionWorker::new(
+                let index = job.workers.len();
+                let worker = job.spawn_worker(
                     accounts,
                     client.clone(),
-                    stop,
-                    mode_params.clone(),
-                    stats,
-                    txn_generator_creator.create_transaction_generator(),
-                    workers.len(),
-                    check_account_sequence_only_once_for.contains(&workers.len()),
-                    self.from_rng(),
+                    index,
+                    check_account_sequence_only_once_for.contains(&index),
                 );
-                let join_handle = tokio_handle.spawn(worker.run().boxed());
-                workers.push(Worker { join_handle });
+                job.workers.push(worker);
             }
         }
         info!("Tx emitter workers started");
-        Ok(EmitJob {
-            workers,
-            stop,
-            stats,
-        })
+        Ok(job)
     }
 
     pub async fn stop_job(&mut self, job: EmitJob) -> TxnStats {
-        job.stop.store(true, Ordering::Relaxed);
+        for worker in &job.workers {
+            worker.stop.store(true, Ordering::Relaxed);
+        }
         for worker in job.workers {
             let mut accounts = worker
                 .join_handle
@@ -475,11 +886,44 @@ impl TxnEmitter {
         job.stats.accumulate()
     }
 
+    /// Stops the job, then runs the checks its request asked for. If it audits its
+    /// transactions, every one of them is looked up, failing if some have no terminal state or
+    /// if the stats don't match the audit. If it has a minimum success ratio, fails if less
+    /// than that share of the submitted transactions was committed.
+    pub async fn stop_job_with_checks(&mut self, job: EmitJob) -> Result<TxnStats> {
+        let submission_log = job.stats.submission_log.clone();
+        let clients = job.clients.clone();
+        let min_success_ratio = job.min_success_ratio;
+        let grace = Duration::from_secs(30);
+        let stats = self.stop_job(job).await;
+        if let Some(submission_log) = submission_log {
+            let audit = audit_transactions(&clients, submission_log.take(), grace).await;
+            info!("Transaction audit: {}", audit);
+            audit.check(&stats)?;
+        }
+        if let Some(min_success_ratio) = min_success_ratio {
+            let success_ratio = stats.success_ratio().unwrap_or(0.0);
+            ensure!(
+                success_ratio >= min_success_ratio,
+                "Success ratio {:.3} is below the minimum of {} ({})",
+                success_ratio,
+                min_success_ratio,
+                stats
+            );
+        }
+        Ok(stats)
+    }
+
     pub fn peek_job_stats(&self, job: &EmitJob) -> TxnStats {
         job.stats.accumulate()
     }
 
-    pub async fn periodic_stat(&mut self, job: &EmitJob, duration: Duration, interval_secs: u64) {
+    pub async fn periodic_stat(
+        &mut self,
+        job: &mut EmitJob,
+        duration: Duration,
+        interval_secs: u64,
+    ) -> Result<()> {
         let deadline = Instant::now() + duration;
         let mut prev_stats: Option<TxnStats> = None;
         let window = Duration::from_secs(max(interval_secs, 1));
@@ -489,7 +933,13 @@ impl TxnEmitter {
             let delta = &stats - &prev_stats.unwrap_or_default();
             prev_stats = Some(stats);
             info!("{}", delta.rate(window));
+            info!(
+                "Endpoints ranked by submission latency (ms): {:?}",
+                delta.endpoint_latencies
+            );
+            job.check_liveness().await?;
         }
+        Ok(())
     }
 
     pub async fn emit_txn_for(
@@ -502,7 +952,7 @@ impl TxnEmitter {
         info!("Starting emitting txns for {} secs", duration.as_secs());
         time::sleep(duration).await;
         info!("Ran for {} secs, stopping job...", duration.as_secs());
-        let stats = self.stop_job(job).await;
+        let stats = self.stop_job_with_checks(job).await?;
         info!("Stopped job");
         Ok(stats)
     }
@@ -515,12 +965,13 @@ impl TxnEmitter {
         interval_secs: u64,
     ) -> Result<TxnStats> {
         info!("Starting emitting txns for {} secs", duration.as_secs());
-        let job = self.start_job(root_account, emit_job_request).await?;
-        self.periodic_stat(&job, duration, interval_secs).await;
+        let mut job = self.start_job(root_account, emit_job_request).await?;
+        let result = self.periodic_stat(&mut job, duration, interval_secs).await;
         info!("Ran for {} secs, stopping job...", duration.as_secs());
-        let stats = self.stop_job(job).await;
+        let stats = self.stop_job_with_checks(job).await;
         info!("Stopped job");
-        Ok(stats)
+        result?;
+        stats
     }
 
     pub async fn submit_single_transaction(
@@ -1,6 +1,8 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::emitter::{audit::SubmissionLog, endpoint_latency::EndpointLatencies};
+use aptos_infallible::RwLock;
 use std::{
     fmt,
     ops::Sub,
@@ -20,6 +22,10 @@ pub struct TxnStats {
     pub latency: u64,
     pub latency_samples: u64,
     pub latency_buckets: AtomicHistogramSnapshot,
+    /// Endpoints with their average submission latency in millis, fastest first.
+    pub endpoint_latencies: Vec<(String, u64)>,
+    /// Endpoints that were dropped from the job, with the reason why
+    pub excluded_endpoints: Vec<String>,
 }
 
 #[derive(Debug, Default)]
@@ -43,6 +49,14 @@ impl fmt::Display for TxnStatsRate {
 }
 
 impl TxnStats {
+    /// Share of the submitted transactions that were committed, None if none was submitted.
+    pub fn success_ratio(&self) -> Option<f64> {
+        if self.submitted == 0 {
+            return None;
+        }
+        Some(self.committed as f64 / self.submitted as f64)
+    }
+
     pub fn rate(&self, window: Duration) -> TxnStatsRate {
         let mut window_secs = window.as_secs();
         if window_secs < 1 {
@@ -69,7 +83,11 @@ impl fmt::Display for TxnStats {
             f,
             "submitted: {}, committed: {}, expired: {}, failed submission: {}",
             self.submitted, self.committed, self.expired, self.failed_submission,
-        )
+        )?;
+        if !self.excluded_endpoints.is_empty() {
+            write!(f, ", excluded endpoints: {:?}", self.excluded_endpoints)?;
+        }
+        Ok(())
     }
 }
 
@@ -85,6 +103,8 @@ impl Sub for &TxnStats {
             latency: self.latency - other.latency,
             latency_samples: self.latency_samples - other.latency_samples,
             latency_buckets: &self.latency_buckets - &other.latency_buckets,
+            endpoint_latencies: self.endpoint_latencies.clone(),
+            excluded_endpoints: self.excluded_endpoints.clone(),
         }
     }
 }
@@ -98,6 +118,10 @@ pub struct StatsAccumulator {
     pub latency: AtomicU64,
     pub latency_samples: AtomicU64,
     pub latencies: Arc<AtomicHistogramAccumulator>,
+    pub endpoint_latencies: Arc<EndpointLatencies>,
+    pub excluded_endpoints: RwLock<Vec<String>>,
+    /// Every submitted transaction, if the job audits its transactions
+    pub submission_log: Option<Arc<SubmissionLog>>,
 }
 
 impl StatsAccumulator {
@@ -110,6 +134,8 @@ impl StatsAccumulator {
             latency: self.latency.load(Ordering::Relaxed),
             latency_samples: self.latency_samples.load(Ordering::Relaxed),
             latency_buckets: self.latencies.snapshot(),
+            endpoint_latencies: self.endpoint_latencies.ranking(),
+            excluded_endpoints: self.excluded_endpoints.read().clone(),
         }
     }
 }
@@ -283,6 +309,8 @@ mod test {
             latency: 0,
             latency_samples: 0,
             latency_buckets: histogram.snapshot(),
+            endpoint_latencies: vec![],
+            excluded_endpoints: vec![],
         };
         let res = stat.latency_buckets.percentile(9, 10);
         assert_eq!(res, 900);
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    emitter::{stats::StatsAccumulator, wait_for_accounts_sequence},
+    emitter::{
+        endpoint_latency::EndpointSelector, stats::StatsAccumulator, wait_for_accounts_sequence,
+    },
     transaction_generator::TransactionGenerator,
     EmitModeParams,
 };
@@ -20,12 +22,15 @@ use futures::future::try_join_all;
 use rand::seq::IteratorRandom;
 use rand::Rng;
 use std::sync::atomic::AtomicU64;
-use std::{sync::Arc, time::Instant};
+use std::{collections::HashSet, sync::Arc, time::Instant};
 use tokio::time::sleep;
 
 pub struct SubmissionWorker {
     pub(crate) accounts: Vec<LocalAccount>,
     client: RestClient,
+    /// Used to poll account sequence numbers
+    query_client: RestClient,
+    endpoint_selector: Option<Arc<EndpointSelector>>,
     stop: Arc<AtomicBool>,
     params: EmitModeParams,
     stats: Arc<StatsAccumulator>,
@@ -39,6 +44,8 @@ impl SubmissionWorker {
     pub fn new(
         accounts: Vec<LocalAccount>,
         client: RestClient,
+        query_client: RestClient,
+        endpoint_selector: Option<Arc<EndpointSelector>>,
         stop: Arc<AtomicBool>,
         params: EmitModeParams,
         stats: Arc<StatsAccumulator>,
@@ -50,6 +57,8 @@ impl SubmissionWorker {
         Self {
             accounts,
             client,
+            query_client,
+            endpoint_selector,
             stop,
             params,
             stats,
@@ -95,11 +104,12 @@ impl SubmissionWorker {
             let requests = self.gen_requests();
             let num_requests = requests.len();
             let txn_offset_time = Arc::new(AtomicU64::new(0));
+            let submission_client = self.submission_client();
 
             if let Err(e) = try_join_all(requests.chunks(self.params.max_submit_batch_size).map(
                 |reqs| {
                     submit_transactions(
-                        &self.client,
+                        &submission_client,
                         reqs,
                         loop_start_time.clone(),
                         txn_offset_time.clone(),
@@ -157,6 +167,16 @@ impl SubmissionWorker {
         }
     }
 
+    /// Transactions are submitted to the worker's own endpoint, unless latency based endpoint
+    /// selection is enabled. Sequence numbers are always checked through the query client.
+    fn submission_client(&mut self) -> RestClient {
+        match &self.endpoint_selector {
+            Some(selector) => selector.pick(&mut self.rng),
+            None => None,
+        }
+        .unwrap_or_else(|| self.client.clone())
+    }
+
     fn start_sleep_time(&mut self) -> Duration {
         let random_jitter_millis = if self.params.start_jitter_millis > 0 {
             self.rng.gen_range(0, self.params.start_jitter_millis)
@@ -191,7 +211,7 @@ impl SubmissionWorker {
         );
         let (num_expired, sum_of_completion_timestamps_millis) = wait_for_accounts_sequence(
             start_time,
-            &self.client,
+            &self.query_client,
             &mut self.accounts,
             self.params.transactions_per_account,
             wait_for_accounts_sequence_timeout,
@@ -271,8 +291,21 @@ pub async fn submit_transactions(
         .submitted
         .fetch_add(txns.len() as u64, Ordering::Relaxed);
 
-    match client.submit_batch_bcs(txns).await {
+    let submit_start = Instant::now();
+    let result = client.submit_batch_bcs(txns).await;
+
+    match result {
         Err(e) => {
+            stats
+                .endpoint_latencies
+                .record_failure(client.path_prefix_string());
+            if let Some(log) = &stats.submission_log {
+                log.record(
+                    &client.path_prefix_string(),
+                    txns,
+                    &(0..txns.len()).collect(),
+                );
+            }
             stats
                 .failed_submission
                 .fetch_add(txns.len() as u64, Ordering::Relaxed);
@@ -286,7 +319,14 @@ pub async fn submit_transactions(
             );
         }
         Ok(v) => {
+            stats
+                .endpoint_latencies
+                .record(client.path_prefix_string(), submit_start.elapsed());
             let failures = v.into_inner().transaction_failures;
+            if let Some(log) = &stats.submission_log {
+                let rejected: HashSet<_> = failures.iter().map(|f| f.transaction_index).collect();
+                log.record(&client.path_prefix_string(), txns, &rejected);
+            }
             stats
                 .failed_submission
                 .fetch_add(failures.len() as u64, Ordering::Relaxed);
@@ -0,0 +1,196 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers for executing admin (governance) payloads with the root account and waiting for
+//! the reconfiguration they trigger, e.g. version bumps, feature flags, consensus config or
+//! gas schedule updates.
+
+use anyhow::{bail, format_err};
+use aptos_logger::info;
+use aptos_rest_client::{Client as RestClient, State, Transaction};
+use aptos_sdk::{
+    move_types::account_address::AccountAddress,
+    transaction_builder::{aptos_stdlib, TransactionFactory},
+    types::{transaction::TransactionPayload, LocalAccount},
+};
+use std::fmt;
+
+/// Admin payloads can be expensive (e.g. publishing a new gas schedule).
+const ADMIN_MAX_GAS_AMOUNT: u64 = 100_000;
+
+/// Ledger state right before an admin payload was executed, and right after the epoch change
+/// it triggered.
+#[derive(Clone, Debug)]
+pub struct EpochChange {
+    pub old_state: State,
+    pub new_state: State,
+}
+
+/// Failure to execute an admin payload, with enough context to decide whether to retry.
+#[derive(Debug)]
+pub struct GovernanceError {
+    /// Payload that failed, none if we failed before building it.
+    pub payload: Option<TransactionPayload>,
+    pub account: AccountAddress,
+    /// Sequence number the payload was signed with (or would have been).
+    pub sequence_number: u64,
+    /// Sequence number of the account on chain, if it could be fetched.
+    pub on_chain_sequence_number: Option<u64>,
+    /// Most recent transactions of the account, oldest first.
+    pub recent_transactions: Vec<String>,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for GovernanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Couldn't execute {:?}, for account {} (seq={}, on chain seq={:?}), error {:#}, last account transactions: {:?}",
+            self.payload,
+            self.account,
+            self.sequence_number,
+            self.on_chain_sequence_number,
+            self.error,
+            self.recent_transactions
+        )
+    }
+}
+
+impl std::error::Error for GovernanceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl GovernanceError {
+    async fn new(
+        client: &RestClient,
+        payload: Option<TransactionPayload>,
+        account: &LocalAccount,
+        sequence_number: u64,
+        error: anyhow::Error,
+    ) -> Self {
+        let on_chain_sequence_number = client
+            .get_account(account.address())
+            .await
+            .ok()
+            .map(|account| account.into_inner().sequence_number);
+        let recent_transactions = client
+            .get_account_transactions(account.address(), None, None)
+            .await
+            .map(|result| {
+                result
+                    .into_inner()
+                    .iter()
+                    .map(|t| {
+                        if let Transaction::UserTransaction(ut) = t {
+                            format!(
+                                "user seq={}, payload={:?}",
+                                ut.request.sequence_number, ut.request.payload
+                            )
+                        } else {
+                            t.type_str().to_string()
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            payload,
+            account: account.address(),
+            sequence_number,
+            on_chain_sequence_number,
+            recent_transactions,
+            error,
+        }
+    }
+}
+
+/// Executes `payload` with the root account and waits for the resulting epoch change.
+pub async fn execute_and_reconfigure(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    root_account: &mut LocalAccount,
+    payload: TransactionPayload,
+) -> Result<EpochChange, GovernanceError> {
+    let sequence_number = root_account.sequence_number();
+    match try_execute_and_reconfigure(client, transaction_factory, root_account, &payload).await {
+        Ok(change) => Ok(change),
+        Err(e) => {
+            Err(GovernanceError::new(client, Some(payload), root_account, sequence_number, e).await)
+        }
+    }
+}
+
+async fn try_execute_and_reconfigure(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    root_account: &mut LocalAccount,
+    payload: &TransactionPayload,
+) -> anyhow::Result<EpochChange> {
+    let old_state = client.get_ledger_information().await?.into_inner();
+    let txn = root_account.sign_with_transaction_builder(
+        transaction_factory
+            .clone()
+            .with_max_gas_amount(ADMIN_MAX_GAS_AMOUNT)
+            .payload(payload.clone()),
+    );
+    let transaction = client.submit_and_wait(&txn).await?;
+    let version = transaction
+        .inner()
+        .version()
+        .ok_or_else(|| format_err!("Committed transaction has no version"))?;
+    // Next transaction after reconfig should be a new epoch.
+    let new_state = client.wait_for_version(version + 1).await?;
+    if old_state.epoch == new_state.epoch {
+        bail!(
+            "Payload did not trigger an epoch change, still at epoch {}",
+            new_state.epoch
+        );
+    }
+
+    Ok(EpochChange {
+        old_state,
+        new_state,
+    })
+}
+
+/// Bumps the on-chain major version, the cheapest way to force a reconfiguration.
+pub async fn bump_version(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    root_account: &mut LocalAccount,
+) -> Result<EpochChange, GovernanceError> {
+    let current_version = match client.get_aptos_version().await {
+        Ok(version) => *version.into_inner().major.inner(),
+        Err(e) => {
+            let sequence_number = root_account.sequence_number();
+            return Err(GovernanceError::new(
+                client,
+                None,
+                root_account,
+                sequence_number,
+                format_err!("Failed to query the current aptos version: {}", e),
+            )
+            .await);
+        }
+    };
+    let change = execute_and_reconfigure(
+        client,
+        transaction_factory,
+        root_account,
+        aptos_stdlib::version_set_version(current_version + 1),
+    )
+    .await?;
+
+    info!(
+        "Changed aptos version from {} (epoch={}, ledger_v={}), to {}, (epoch={}, ledger_v={})",
+        current_version,
+        change.old_state.epoch,
+        change.old_state.version,
+        current_version + 1,
+        change.new_state.epoch,
+        change.new_state.version
+    );
+    Ok(change)
+}
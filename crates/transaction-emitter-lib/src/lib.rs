@@ -3,22 +3,42 @@
 
 #![forbid(unsafe_code)]
 
+pub mod account_pool;
+#[cfg(feature = "cli")]
 mod args;
 mod cluster;
 pub mod emitter;
+pub mod governance;
 mod instance;
+pub mod mint_key;
 mod transaction_generator;
+mod types;
+#[cfg(feature = "cli")]
 mod wrappers;
 
 // These are the top level things you should need to run the emitter.
-pub use args::{ClusterArgs, EmitArgs, MintArgs, TransactionType};
-pub use wrappers::emit_transactions;
-
-// We export these if you want finer grained control.
-pub use cluster::Cluster;
+pub use cluster::{fetch_mint_key_secret, Cluster};
 pub use emitter::{
+    audit::{TxnAudit, TxnOutcome},
     query_sequence_numbers,
     stats::{TxnStats, TxnStatsRate},
     EmitJob, EmitJobMode, EmitJobRequest, EmitModeParams, TxnEmitter,
 };
-pub use wrappers::emit_transactions_with_cluster;
+pub use instance::Instance;
+pub use mint_key::MintKeyFormat;
+pub use types::{
+    DeadInstancePolicy, Target, TargetHeader, TargetProxy, TargetScheme, TargetSelectionPolicy,
+    TransactionType,
+};
+
+// Command line arguments and the wrappers running the emitter from them, behind the cli
+// feature so that embedding the emitter doesn't pull clap.
+#[cfg(feature = "cli")]
+pub use args::{
+    CleanupArgs, ClusterArgs, CreateAccountsArgs, EmitArgs, FundAccountsArgs, MintArgs,
+};
+#[cfg(feature = "cli")]
+pub use wrappers::{
+    cleanup_account_pool, create_account_pool, emit_transactions, emit_transactions_with_cluster,
+    fund_account_pool, plan_emit_transactions, EmitPlan,
+};
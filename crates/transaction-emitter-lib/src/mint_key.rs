@@ -0,0 +1,307 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Loading of the mint (root) key from the key files the other Aptos tools write: genesis and
+//! `aptos key generate` key files, and Aptos CLI configs, as well as from wallet mnemonics and
+//! password encrypted keyfiles. The cryptography is the one of `ring`, which aptos-crypto
+//! builds on, and of `tiny-bip39` for mnemonics.
+
+use anyhow::{ensure, format_err, Context, Result};
+use aptos_crypto::{ed25519::Ed25519PrivateKey, ValidCryptoMaterialStringExt};
+use aptos_sdk::bcs;
+use bip39::{Language, Mnemonic, Seed};
+use rand::Rng;
+use ring::{aead, hmac, pbkdf2};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, convert::TryFrom, num::NonZeroU32, path::Path};
+
+/// Derivation path of the first account of a wallet, as used by the Aptos wallets
+const APTOS_DERIVATION_PATH: [u32; 5] = [44, 637, 0, 0, 0];
+const SLIP10_ED25519_SEED: &[u8] = b"ed25519 seed";
+const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+const KEYFILE_KDF: &str = "pbkdf2-sha256";
+const KEYFILE_ITERATIONS: u32 = 100_000;
+const KEYFILE_SALT_LENGTH: usize = 16;
+
+/// Encodings of key files, as in the `--encoding` of the Aptos CLI, and CLI configs.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ArgEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum MintKeyFormat {
+    /// BCS encoded private key, as generated by genesis
+    Bcs,
+    /// Hex encoded private key, with or without 0x prefix
+    Hex,
+    /// Base64 encoded private key
+    Base64,
+    /// BIP39 mnemonic phrase, derived at m/44'/637'/0'/0'/0' like the Aptos wallets do
+    Mnemonic,
+    /// Password encrypted keyfile, see `encrypt_mint_key`
+    Encrypted,
+    /// Aptos CLI config.yaml, the key of the selected profile is used
+    CliProfile,
+}
+
+impl Default for MintKeyFormat {
+    fn default() -> Self {
+        Self::Bcs
+    }
+}
+
+/// Loads the mint key from `path`. The `password` is the one of encrypted keyfiles, and the
+/// optional BIP39 passphrase of mnemonics. `profile` is only used for Aptos CLI configs.
+pub fn load_mint_key(
+    path: &Path,
+    format: MintKeyFormat,
+    password: Option<&str>,
+    profile: &str,
+) -> Result<Ed25519PrivateKey> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("Failed to read mint key from {}", path.display()))?;
+    decode_mint_key(&contents, format, password, profile)
+}
+
+/// Decodes the contents of a mint key file of `format`, see `load_mint_key`.
+pub fn decode_mint_key(
+    contents: &[u8],
+    format: MintKeyFormat,
+    password: Option<&str>,
+    profile: &str,
+) -> Result<Ed25519PrivateKey> {
+    let text = || std::str::from_utf8(contents).context("Mint key file is not UTF-8");
+    match format {
+        MintKeyFormat::Bcs => bcs::from_bytes(contents).context("Failed to decode BCS mint key"),
+        MintKeyFormat::Hex => Ed25519PrivateKey::from_encoded_string(text()?.trim())
+            .map_err(|e| format_err!("Failed to decode hex mint key: {}", e)),
+        MintKeyFormat::Base64 => {
+            let bytes =
+                base64::decode(text()?.trim()).context("Failed to decode base64 mint key")?;
+            Ok(Ed25519PrivateKey::try_from(bytes.as_slice())?)
+        }
+        MintKeyFormat::Mnemonic => key_from_mnemonic(text()?.trim(), password.unwrap_or("")),
+        MintKeyFormat::Encrypted => decrypt_mint_key(
+            text()?,
+            password.ok_or_else(|| format_err!("A password is required for encrypted keyfiles"))?,
+        ),
+        MintKeyFormat::CliProfile => key_from_cli_config(text()?, profile),
+    }
+}
+
+pub fn key_from_mnemonic(phrase: &str, passphrase: &str) -> Result<Ed25519PrivateKey> {
+    let mnemonic = Mnemonic::from_phrase(phrase, Language::English)
+        .map_err(|e| format_err!("Invalid mnemonic: {}", e))?;
+    let seed = Seed::new(&mnemonic, passphrase);
+    let key = derive_slip10_ed25519(seed.as_bytes(), &APTOS_DERIVATION_PATH);
+    Ok(Ed25519PrivateKey::try_from(&key[..])?)
+}
+
+/// SLIP-0010 ed25519 derivation of the key at `path`, every index is hardened.
+fn derive_slip10_ed25519(seed: &[u8], path: &[u32]) -> [u8; 32] {
+    let (mut key, mut chain_code) = split_hmac_sha512(SLIP10_ED25519_SEED, &[seed]);
+    for index in path {
+        let index = (index | HARDENED_OFFSET).to_be_bytes();
+        let (child_key, child_chain_code) = split_hmac_sha512(&chain_code, &[&[0], &key, &index]);
+        key = child_key;
+        chain_code = child_chain_code;
+    }
+    key
+}
+
+fn split_hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut context = hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA512, key));
+    for data in data {
+        context.update(data);
+    }
+    let output = context.sign();
+    let mut left = [0u8; 32];
+    let mut right = [0u8; 32];
+    left.copy_from_slice(&output.as_ref()[..32]);
+    right.copy_from_slice(&output.as_ref()[32..]);
+    (left, right)
+}
+
+/// Password encrypted keyfile: the key is encrypted with AES-256-GCM, with a key derived from
+/// the password with PBKDF2-HMAC-SHA256. Binary fields are hex encoded.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EncryptedKeyfile {
+    pub kdf: String,
+    pub iterations: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// Encrypts `key` with `password`, as the YAML of an `EncryptedKeyfile`.
+pub fn encrypt_mint_key<R: Rng>(
+    key: &Ed25519PrivateKey,
+    password: &str,
+    rng: &mut R,
+) -> Result<String> {
+    encrypt_with_iterations(key, password, KEYFILE_ITERATIONS, rng)
+}
+
+fn encrypt_with_iterations<R: Rng>(
+    key: &Ed25519PrivateKey,
+    password: &str,
+    iterations: u32,
+    rng: &mut R,
+) -> Result<String> {
+    let salt: [u8; KEYFILE_SALT_LENGTH] = rng.gen();
+    let nonce: [u8; aead::NONCE_LEN] = rng.gen();
+    let mut ciphertext = key.to_bytes().to_vec();
+    keyfile_key(password, &salt, iterations)?
+        .seal_in_place_append_tag(
+            aead::Nonce::assume_unique_for_key(nonce),
+            aead::Aad::empty(),
+            &mut ciphertext,
+        )
+        .map_err(|_| format_err!("Failed to encrypt mint key"))?;
+    let keyfile = EncryptedKeyfile {
+        kdf: KEYFILE_KDF.to_string(),
+        iterations,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    };
+    Ok(serde_yaml::to_string(&keyfile)?)
+}
+
+pub fn decrypt_mint_key(keyfile: &str, password: &str) -> Result<Ed25519PrivateKey> {
+    let keyfile: EncryptedKeyfile =
+        serde_yaml::from_str(keyfile).context("Failed to parse encrypted keyfile")?;
+    ensure!(
+        keyfile.kdf == KEYFILE_KDF,
+        "Unsupported keyfile kdf {}, expected {}",
+        keyfile.kdf,
+        KEYFILE_KDF
+    );
+    let nonce = aead::Nonce::try_assume_unique_for_key(&hex::decode(&keyfile.nonce)?)
+        .map_err(|_| format_err!("Keyfile nonce must be {} bytes", aead::NONCE_LEN))?;
+    let mut ciphertext = hex::decode(&keyfile.ciphertext)?;
+    let key = keyfile_key(password, &hex::decode(&keyfile.salt)?, keyfile.iterations)?
+        .open_in_place(nonce, aead::Aad::empty(), &mut ciphertext)
+        .map_err(|_| format_err!("Failed to decrypt keyfile, is the password correct?"))?;
+    Ok(Ed25519PrivateKey::try_from(&*key)?)
+}
+
+fn keyfile_key(password: &str, salt: &[u8], iterations: u32) -> Result<aead::LessSafeKey> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| format_err!("Keyfile iterations must not be 0"))?;
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        password.as_bytes(),
+        &mut key,
+    );
+    let key = aead::UnboundKey::new(&aead::AES_256_GCM, &key)
+        .map_err(|_| format_err!("Invalid keyfile key"))?;
+    Ok(aead::LessSafeKey::new(key))
+}
+
+/// The part of the Aptos CLI config.yaml holding the keys, parsed here rather than with the CLI
+/// types so that the emitter doesn't depend on the CLI.
+#[derive(Deserialize)]
+struct CliConfig {
+    #[serde(default)]
+    profiles: BTreeMap<String, CliProfile>,
+}
+
+#[derive(Deserialize)]
+struct CliProfile {
+    #[serde(default)]
+    private_key: Option<Ed25519PrivateKey>,
+}
+
+pub fn key_from_cli_config(config: &str, profile: &str) -> Result<Ed25519PrivateKey> {
+    let mut config: CliConfig =
+        serde_yaml::from_str(config).context("Failed to parse Aptos CLI config")?;
+    config
+        .profiles
+        .remove(profile)
+        .ok_or_else(|| format_err!("Profile {} not found in Aptos CLI config", profile))?
+        .private_key
+        .ok_or_else(|| format_err!("Profile {} has no private key", profile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_crypto::{Uniform, ValidCryptoMaterial};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_decode_mint_key() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let key = Ed25519PrivateKey::generate(&mut rng);
+        let encoded = [
+            (MintKeyFormat::Bcs, bcs::to_bytes(&key).unwrap()),
+            (
+                MintKeyFormat::Hex,
+                format!("{}\n", key.to_encoded_string().unwrap()).into_bytes(),
+            ),
+            (
+                MintKeyFormat::Base64,
+                base64::encode(key.to_bytes()).into_bytes(),
+            ),
+        ];
+        for (format, contents) in encoded {
+            assert_eq!(decode_mint_key(&contents, format, None, "").unwrap(), key);
+        }
+        assert!(decode_mint_key(b"not a key", MintKeyFormat::Hex, None, "").is_err());
+    }
+
+    #[test]
+    fn test_key_from_mnemonic() {
+        // SLIP-0010 test vector 1, master key
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        assert_eq!(
+            hex::encode(derive_slip10_ed25519(&seed, &[])),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                      abandon abandon about";
+        let key = decode_mint_key(phrase.as_bytes(), MintKeyFormat::Mnemonic, None, "").unwrap();
+        assert_eq!(
+            hex::encode(key.to_bytes()),
+            "cc92c0eaf80206d817f150e21917f797e49cf644a33ac514de3c316baa2f1bf5"
+        );
+        assert_ne!(key_from_mnemonic(phrase, "passphrase").unwrap(), key);
+        assert!(key_from_mnemonic("abandon abandon abandon", "").is_err());
+    }
+
+    #[test]
+    fn test_encrypted_keyfile() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let key = Ed25519PrivateKey::generate(&mut rng);
+        let keyfile = encrypt_with_iterations(&key, "password", 10, &mut rng).unwrap();
+        assert_eq!(
+            decode_mint_key(
+                keyfile.as_bytes(),
+                MintKeyFormat::Encrypted,
+                Some("password"),
+                ""
+            )
+            .unwrap(),
+            key
+        );
+        assert!(decrypt_mint_key(&keyfile, "wrong password").is_err());
+        assert!(decode_mint_key(keyfile.as_bytes(), MintKeyFormat::Encrypted, None, "").is_err());
+    }
+
+    #[test]
+    fn test_key_from_cli_config() {
+        let mut rng = StdRng::from_seed([0u8; 32]);
+        let key = Ed25519PrivateKey::generate(&mut rng);
+        let config = format!(
+            "---\nprofiles:\n  default:\n    private_key: \"{}\"\n  empty:\n    rest_url: \"http://localhost:8080\"\n",
+            key.to_encoded_string().unwrap()
+        );
+        assert_eq!(key_from_cli_config(&config, "default").unwrap(), key);
+        assert!(key_from_cli_config(&config, "empty").is_err());
+        assert!(key_from_cli_config(&config, "missing").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Plain types shared by the programmatic API and the command line arguments.
+
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ArgEnum))]
+pub enum DeadInstancePolicy {
+    /// Leave dead instances out and keep going with the others
+    Skip,
+    /// Fail as soon as an instance is found dead
+    Fail,
+}
+
+impl Default for DeadInstancePolicy {
+    fn default() -> Self {
+        Self::Skip
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ArgEnum))]
+pub enum TargetScheme {
+    Http,
+    Https,
+}
+
+impl TargetScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TargetScheme::Http => "http",
+            TargetScheme::Https => "https",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Target {
+    pub url: Url,
+    /// Share of the load sent to this target, relative to the other targets
+    pub weight: usize,
+    /// The target was given without a scheme, `url` uses http as a placeholder
+    #[serde(default)]
+    pub detect_scheme: bool,
+}
+
+impl From<Url> for Target {
+    fn from(url: Url) -> Self {
+        Self {
+            url,
+            weight: 1,
+            detect_scheme: false,
+        }
+    }
+}
+
+/// A header that is only sent to the targets matching `target`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TargetHeader {
+    /// Either a bare host or host:port
+    pub target: String,
+    pub name: String,
+    pub value: String,
+}
+
+impl TargetHeader {
+    pub fn matches(&self, url: &Url) -> bool {
+        target_matches(&self.target, url)
+    }
+}
+
+/// A proxy that is only used for the targets matching `target`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TargetProxy {
+    /// Either a bare host or host:port
+    pub target: String,
+    pub proxy: Url,
+}
+
+impl TargetProxy {
+    pub fn matches(&self, url: &Url) -> bool {
+        target_matches(&self.target, url)
+    }
+}
+
+/// Whether `url` points to `target`, given as a bare host or host:port. IPv6 hosts match with
+/// or without brackets, e.g. `::1`, `[::1]` or `[::1]:8080`.
+fn target_matches(target: &str, url: &Url) -> bool {
+    let host = match url.host() {
+        Some(host) => host,
+        None => return false,
+    };
+    if let Host::Ipv6(addr) = host {
+        if target == addr.to_string() {
+            return true;
+        }
+    }
+    let host = host.to_string();
+    target == host
+        || url
+            .port_or_known_default()
+            .map(|port| target == format!("{}:{}", host, port))
+            .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ArgEnum, clap::Parser))]
+pub enum TargetSelectionPolicy {
+    /// Use every target for both submissions and administrative calls
+    Any,
+    /// Submit to fullnodes and keep validators for administrative calls, falling
+    /// back to any target if there are none of the preferred kind
+    PreferFullNodes,
+}
+
+impl Default for TargetSelectionPolicy {
+    fn default() -> Self {
+        TargetSelectionPolicy::Any
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ArgEnum, clap::Parser))]
+pub enum TransactionType {
+    P2P,
+    AccountGeneration,
+    NftMint,
+}
+
+impl Default for TransactionType {
+    fn default() -> Self {
+        TransactionType::P2P
+    }
+}
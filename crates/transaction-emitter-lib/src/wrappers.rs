@@ -2,20 +2,39 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    args::{ClusterArgs, EmitArgs},
+    account_pool::{AccountPool, CleanupSummary, FundingSummary},
+    args::{CleanupArgs, ClusterArgs, CreateAccountsArgs, EmitArgs, FundAccountsArgs},
     cluster::Cluster,
-    emitter::{stats::TxnStats, EmitJobMode, EmitJobRequest, TxnEmitter},
-    instance::Instance,
+    emitter::{
+        account_minter::{execute_and_wait_transactions, AccountMinter},
+        stats::TxnStats,
+        EmitJobMode, EmitJobRequest, TxnEmitter,
+    },
+    governance,
 };
-use anyhow::{Context, Result};
-use aptos_sdk::transaction_builder::TransactionFactory;
+use anyhow::{ensure, Context, Result};
+use aptos_logger::info;
+use aptos_rest_client::FaucetClient;
+use aptos_sdk::{
+    move_types::account_address::AccountAddress,
+    transaction_builder::{aptos_stdlib, TransactionFactory},
+};
+use futures::future::try_join_all;
 use rand::{rngs::StdRng, Rng};
 use rand_core::{OsRng, SeedableRng};
 use std::{
     cmp::{max, min},
+    fmt, fs,
+    iter::repeat,
+    sync::atomic::AtomicUsize,
     time::Duration,
 };
 
+/// Top ups and sweeps of an account pool are submitted this many at a time
+const FUNDING_BATCH_SIZE: usize = 100;
+/// Max gas units of a sweep, left behind in the swept accounts to pay for it
+const SWEEP_MAX_GAS_AMOUNT: u64 = 1_000;
+
 pub async fn emit_transactions(
     cluster_args: &ClusterArgs,
     emit_args: &EmitArgs,
@@ -31,18 +50,53 @@ pub async fn emit_transactions_with_cluster(
     args: &EmitArgs,
     reuse_accounts: bool,
 ) -> Result<TxnStats> {
-    let emitter_mode = EmitJobMode::create(args.mempool_backlog, args.target_tps);
-
     let duration = Duration::from_secs(args.duration);
-    let client = cluster.random_instance().rest_client();
+    let client = cluster.random_admin_instance().rest_client();
     let mut root_account = cluster.load_aptos_root_account(&client).await?;
-    let mut emitter = TxnEmitter::new(
-        TransactionFactory::new(cluster.chain_id)
-            .with_gas_unit_price(1)
-            .with_transaction_expiration_time(args.txn_expiration_time_secs),
-        StdRng::from_seed(OsRng.gen()),
-    );
+    let txn_factory = TransactionFactory::new(cluster.chain_id)
+        .with_gas_unit_price(1)
+        .with_transaction_expiration_time(args.txn_expiration_time_secs);
+    if args.reconfig_before_run {
+        governance::bump_version(&client, &txn_factory, &mut root_account)
+            .await
+            .context("Failed to reconfigure before emitting")?;
+    }
+    let mut emitter = TxnEmitter::new(txn_factory, StdRng::from_seed(OsRng.gen()));
+    if let Some(account_pool_file) = &args.account_pool_file {
+        let accounts = AccountPool::load(account_pool_file)?
+            .into_local_accounts(&client, cluster.chain_id)
+            .await?;
+        info!(
+            "Loaded {} accounts from {}",
+            accounts.len(),
+            account_pool_file.display()
+        );
+        emitter.add_accounts(accounts);
+    }
 
+    let mut stats = emitter
+        .emit_txn_for_with_stats(
+            &mut root_account,
+            emit_job_request(cluster, args, reuse_accounts),
+            duration,
+            min(10, max(args.duration / 5, 1)),
+        )
+        .await?;
+    stats
+        .excluded_endpoints
+        .splice(0..0, cluster.excluded_instances().iter().cloned());
+    Ok(stats)
+}
+
+fn emit_job_request(cluster: &Cluster, args: &EmitArgs, reuse_accounts: bool) -> EmitJobRequest {
+    let emitter_mode = match args.burst_txns {
+        Some(txns_per_burst) => EmitJobMode::Burst {
+            txns_per_burst,
+            burst_interval: Duration::from_secs(args.burst_secs),
+            idle: Duration::from_secs(args.burst_idle_secs),
+        },
+        None => EmitJobMode::create(args.mempool_backlog, args.target_tps),
+    };
     let transaction_mix = if args.transaction_type_weights.is_empty() {
         args.transaction_type.iter().map(|t| (*t, 1)).collect()
     } else {
@@ -58,23 +112,288 @@ pub async fn emit_transactions_with_cluster(
             .collect()
     };
 
-    let mut emit_job_request =
-        EmitJobRequest::new(cluster.all_instances().map(Instance::rest_client).collect())
-            .mode(emitter_mode)
-            .invalid_transaction_ratio(args.invalid_tx)
-            .transaction_mix(transaction_mix)
-            .txn_expiration_time_secs(args.txn_expiration_time_secs)
-            .gas_price(1);
+    let mut emit_job_request = EmitJobRequest::new(
+        cluster
+            .submission_instances()
+            .flat_map(|instance| repeat(instance.rest_client()).take(instance.weight()))
+            .collect(),
+    )
+    .mode(emitter_mode)
+    .invalid_transaction_ratio(args.invalid_tx)
+    .transaction_mix(transaction_mix)
+    .txn_expiration_time_secs(args.txn_expiration_time_secs)
+    .latency_bias(args.latency_bias)
+    .check_liveness(cluster.dead_instance_policy())
+    .query_clients(
+        cluster
+            .query_instances()
+            .map(|instance| instance.rest_client())
+            .collect(),
+    )
+    .gas_price(1);
     if reuse_accounts {
         emit_job_request = emit_job_request.reuse_accounts();
     }
-    let stats = emitter
-        .emit_txn_for_with_stats(
-            &mut root_account,
-            emit_job_request,
-            duration,
-            min(10, max(args.duration / 5, 1)),
+    if args.audit {
+        emit_job_request = emit_job_request.audit_transactions();
+    }
+    if let Some(min_success_ratio) = args.min_success_ratio {
+        emit_job_request = emit_job_request.min_success_ratio(min_success_ratio);
+    }
+    emit_job_request
+}
+
+/// What an emit-tx run would do, as found by `--dry-run`.
+#[derive(Debug)]
+pub struct EmitPlan {
+    pub submission_endpoints: Vec<String>,
+    pub excluded_endpoints: Vec<String>,
+    pub root_address: AccountAddress,
+    pub root_balance: u64,
+    pub num_accounts: usize,
+    /// Accounts taken from the account pool file
+    pub pooled_accounts: usize,
+    /// Balance the root account needs to create the accounts missing from the pool
+    pub required_root_balance: u64,
+}
+
+impl EmitPlan {
+    pub fn is_funded(&self) -> bool {
+        self.root_balance >= self.required_root_balance
+    }
+}
+
+impl fmt::Display for EmitPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Submission endpoints: {:?}", self.submission_endpoints)?;
+        writeln!(f, "Excluded endpoints: {:?}", self.excluded_endpoints)?;
+        writeln!(
+            f,
+            "Accounts: {} ({} from the account pool, {} to create)",
+            self.num_accounts,
+            self.pooled_accounts,
+            self.num_accounts.saturating_sub(self.pooled_accounts)
+        )?;
+        write!(
+            f,
+            "Root account {}: balance {}, needed {}",
+            self.root_address, self.root_balance, self.required_root_balance
         )
-        .await?;
-    Ok(stats)
+    }
+}
+
+/// Checks everything an emit-tx run needs without submitting anything: builds the cluster,
+/// loads the root account and the account pool, and works out the coins the run would spend.
+pub async fn plan_emit_transactions(
+    cluster_args: &ClusterArgs,
+    emit_args: &EmitArgs,
+) -> Result<EmitPlan> {
+    let cluster = Cluster::try_from_cluster_args(cluster_args)
+        .await
+        .context("Failed to build cluster")?;
+    let client = cluster.random_admin_instance().rest_client();
+    let root_account = cluster.load_aptos_root_account(&client).await?;
+    let root_balance = client
+        .get_account_balance(root_account.address())
+        .await?
+        .into_inner()
+        .get();
+    let pooled_accounts = match &emit_args.account_pool_file {
+        Some(account_pool_file) => AccountPool::load(account_pool_file)?
+            .into_local_accounts(&client, cluster.chain_id)
+            .await?
+            .len(),
+        None => 0,
+    };
+
+    let req = emit_job_request(&cluster, emit_args, cluster_args.reuse_accounts);
+    let num_accounts = req.num_accounts(&req.calculate_mode_params());
+    let num_new_accounts = num_accounts.saturating_sub(pooled_accounts);
+    let required_root_balance = if num_new_accounts > 0 && !cluster_args.reuse_accounts {
+        req.account_funding(num_new_accounts).coins_for_root
+    } else {
+        0
+    };
+
+    Ok(EmitPlan {
+        submission_endpoints: cluster
+            .submission_instances()
+            .map(|instance| instance.peer_name().clone())
+            .collect(),
+        excluded_endpoints: cluster.excluded_instances().to_vec(),
+        root_address: root_account.address(),
+        root_balance,
+        num_accounts,
+        pooled_accounts,
+        required_root_balance,
+    })
+}
+
+/// Creates and funds the accounts of an account pool, without emitting any load, and writes
+/// the pool to its file.
+pub async fn create_account_pool(
+    cluster_args: &ClusterArgs,
+    args: &CreateAccountsArgs,
+) -> Result<AccountPool> {
+    ensure!(args.num_accounts > 0, "No accounts to create");
+    let cluster = Cluster::try_from_cluster_args(cluster_args)
+        .await
+        .context("Failed to build cluster")?;
+    let client = cluster.random_admin_instance().rest_client();
+    let mut root_account = cluster.load_aptos_root_account(&client).await?;
+    let txn_factory = TransactionFactory::new(cluster.chain_id).with_gas_unit_price(1);
+
+    let mut req = EmitJobRequest::new(
+        cluster
+            .submission_instances()
+            .map(|instance| instance.rest_client())
+            .collect(),
+    )
+    .gas_price(1);
+    if cluster_args.reuse_accounts {
+        req = req.reuse_accounts();
+    }
+    let mode_params = req.calculate_mode_params();
+    let accounts = AccountMinter::new(
+        &mut root_account,
+        txn_factory,
+        StdRng::from_seed(OsRng.gen()),
+    )
+    .create_accounts(&req, &mode_params, args.num_accounts)
+    .await
+    .context("Failed to create accounts")?;
+
+    let pool = AccountPool::new(cluster.chain_id, &accounts[..args.num_accounts])?;
+    pool.save(&args.account_pool_file)?;
+    Ok(pool)
+}
+
+/// Tops up the accounts of an account pool holding less than the minimum balance, from the
+/// root account or through a faucet.
+pub async fn fund_account_pool(
+    cluster_args: &ClusterArgs,
+    args: &FundAccountsArgs,
+) -> Result<FundingSummary> {
+    let target_balance = args.target_balance.unwrap_or(args.min_balance);
+    ensure!(
+        target_balance >= args.min_balance,
+        "Target balance {} is below the minimum balance {}",
+        target_balance,
+        args.min_balance
+    );
+    let cluster = Cluster::try_from_cluster_args(cluster_args)
+        .await
+        .context("Failed to build cluster")?;
+    let instance = cluster.random_admin_instance();
+    let client = instance.rest_client();
+    let pool = AccountPool::load(&args.account_pool_file)?;
+    pool.check_chain_id(cluster.chain_id)?;
+
+    let balances = pool.balances(&client).await?;
+    let top_ups: Vec<_> = pool
+        .addresses()
+        .into_iter()
+        .zip(balances)
+        .filter(|(_, balance)| *balance < args.min_balance)
+        .map(|(address, balance)| (address, target_balance - balance))
+        .collect();
+    let summary = FundingSummary {
+        checked_accounts: pool.accounts.len(),
+        topped_up_accounts: top_ups.len(),
+        distributed: top_ups.iter().map(|(_, amount)| amount).sum(),
+    };
+    if top_ups.is_empty() {
+        return Ok(summary);
+    }
+
+    if let Some(faucet_url) = &args.faucet_url {
+        let faucet = FaucetClient::new(faucet_url.clone(), instance.api_url());
+        for batch in top_ups.chunks(FUNDING_BATCH_SIZE) {
+            try_join_all(
+                batch
+                    .iter()
+                    .map(|(address, amount)| faucet.fund(*address, *amount)),
+            )
+            .await
+            .context("Failed to fund accounts through the faucet")?;
+        }
+    } else {
+        let mut root_account = cluster.load_aptos_root_account(&client).await?;
+        let root_balance = client
+            .get_account_balance(root_account.address())
+            .await?
+            .into_inner()
+            .get();
+        ensure!(
+            root_balance >= summary.distributed,
+            "Root ({}) doesn't have enough coins, balance {} < needed {}",
+            root_account.address(),
+            root_balance,
+            summary.distributed
+        );
+        let txn_factory = TransactionFactory::new(cluster.chain_id).with_gas_unit_price(1);
+        for batch in top_ups.chunks(FUNDING_BATCH_SIZE) {
+            let txns = batch
+                .iter()
+                .map(|(address, amount)| {
+                    root_account.sign_with_transaction_builder(
+                        txn_factory
+                            .payload(aptos_stdlib::aptos_account_transfer(*address, *amount)),
+                    )
+                })
+                .collect();
+            execute_and_wait_transactions(&client, &mut root_account, txns, &AtomicUsize::new(0))
+                .await
+                .context("Failed to fund accounts from the root account")?;
+        }
+    }
+    Ok(summary)
+}
+
+/// Sends the balances of the accounts of an account pool back to the root account, e.g. to
+/// tidy up a shared testnet after a benchmark campaign.
+pub async fn cleanup_account_pool(
+    cluster_args: &ClusterArgs,
+    args: &CleanupArgs,
+) -> Result<CleanupSummary> {
+    let cluster = Cluster::try_from_cluster_args(cluster_args)
+        .await
+        .context("Failed to build cluster")?;
+    let client = cluster.random_admin_instance().rest_client();
+    let root_address = cluster.load_aptos_root_account(&client).await?.address();
+    let pool = AccountPool::load(&args.account_pool_file)?;
+    let balances = pool.balances(&client).await?;
+    let accounts = pool.into_local_accounts(&client, cluster.chain_id).await?;
+    let checked_accounts = accounts.len();
+
+    let txn_factory = TransactionFactory::new(cluster.chain_id)
+        .with_gas_unit_price(1)
+        .with_max_gas_amount(SWEEP_MAX_GAS_AMOUNT);
+    let mut sweeps: Vec<_> = accounts
+        .into_iter()
+        .zip(balances)
+        .filter(|(_, balance)| *balance > SWEEP_MAX_GAS_AMOUNT)
+        .map(|(account, balance)| (account, balance - SWEEP_MAX_GAS_AMOUNT))
+        .collect();
+    let summary = CleanupSummary {
+        checked_accounts,
+        swept_accounts: sweeps.len(),
+        swept: sweeps.iter().map(|(_, amount)| amount).sum(),
+    };
+    for batch in sweeps.chunks_mut(FUNDING_BATCH_SIZE) {
+        try_join_all(batch.iter_mut().map(|(account, amount)| {
+            let txn = account.sign_with_transaction_builder(
+                txn_factory.payload(aptos_stdlib::aptos_account_transfer(root_address, *amount)),
+            );
+            let client = &client;
+            async move { client.submit_and_wait(&txn).await }
+        }))
+        .await
+        .context("Failed to sweep accounts")?;
+    }
+
+    if args.delete_key_file {
+        fs::remove_file(&args.account_pool_file)?;
+    }
+    Ok(summary)
 }
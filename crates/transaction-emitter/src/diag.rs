@@ -14,7 +14,7 @@ use std::{
 use transaction_emitter_lib::{query_sequence_numbers, Cluster, TxnEmitter};
 
 pub async fn diag(cluster: &Cluster) -> Result<()> {
-    let client = cluster.random_instance().rest_client();
+    let client = cluster.random_admin_instance().rest_client();
     let mut faucet_account = cluster.load_aptos_root_account(&client).await?;
     let emitter = TxnEmitter::new(
         TransactionFactory::new(cluster.chain_id).with_gas_unit_price(1),
@@ -4,11 +4,15 @@
 mod diag;
 
 use ::aptos_logger::{Level, Logger};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::{Parser, Subcommand};
 use diag::diag;
 use std::time::Duration;
-use transaction_emitter_lib::{emit_transactions, Cluster, ClusterArgs, EmitArgs};
+use transaction_emitter_lib::{
+    cleanup_account_pool, create_account_pool, emit_transactions, fund_account_pool,
+    plan_emit_transactions, CleanupArgs, Cluster, ClusterArgs, CreateAccountsArgs, EmitArgs,
+    FundAccountsArgs,
+};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -26,6 +30,16 @@ enum TxnEmitterCommand {
     /// This runs the transaction emitter in diag mode, where the focus is on
     /// FullNodes instead of ValidatorNodes. This performs a simple health check.
     Diag(Diag),
+
+    /// Creates and funds accounts without emitting load, and writes them to an account pool
+    /// file for later runs to use with --account-pool-file.
+    CreateAccounts(CreateAccounts),
+
+    /// Tops up the accounts of an account pool file holding less than a minimum balance.
+    FundAccounts(FundAccounts),
+
+    /// Sends the balances of the accounts of an account pool file back to the root account.
+    Cleanup(Cleanup),
 }
 
 #[derive(Parser, Debug)]
@@ -37,6 +51,33 @@ struct EmitTx {
     emit_args: EmitArgs,
 }
 
+#[derive(Parser, Debug)]
+struct CreateAccounts {
+    #[clap(flatten)]
+    cluster_args: ClusterArgs,
+
+    #[clap(flatten)]
+    create_accounts_args: CreateAccountsArgs,
+}
+
+#[derive(Parser, Debug)]
+struct FundAccounts {
+    #[clap(flatten)]
+    cluster_args: ClusterArgs,
+
+    #[clap(flatten)]
+    fund_accounts_args: FundAccountsArgs,
+}
+
+#[derive(Parser, Debug)]
+struct Cleanup {
+    #[clap(flatten)]
+    cluster_args: ClusterArgs,
+
+    #[clap(flatten)]
+    cleanup_args: CleanupArgs,
+}
+
 #[derive(Parser, Debug)]
 struct Diag {
     #[clap(flatten)]
@@ -51,6 +92,16 @@ pub async fn main() -> Result<()> {
 
     // TODO: Check if I need DisplayChain here in the error case.
     match args.command {
+        TxnEmitterCommand::EmitTx(args) if args.emit_args.dry_run => {
+            let plan = plan_emit_transactions(&args.cluster_args, &args.emit_args)
+                .await
+                .context("Dry run failed")?;
+            println!("{}", plan);
+            if !plan.is_funded() {
+                bail!("Root account doesn't have enough coins for the run");
+            }
+            Ok(())
+        }
         TxnEmitterCommand::EmitTx(args) => {
             let stats = emit_transactions(&args.cluster_args, &args.emit_args)
                 .await
@@ -69,5 +120,30 @@ pub async fn main() -> Result<()> {
             diag(&cluster).await.context("Diag failed")?;
             Ok(())
         }
+        TxnEmitterCommand::CreateAccounts(args) => {
+            let pool = create_account_pool(&args.cluster_args, &args.create_accounts_args)
+                .await
+                .context("Create accounts failed")?;
+            println!(
+                "Wrote {} accounts to {}",
+                pool.accounts.len(),
+                args.create_accounts_args.account_pool_file.display()
+            );
+            Ok(())
+        }
+        TxnEmitterCommand::FundAccounts(args) => {
+            let summary = fund_account_pool(&args.cluster_args, &args.fund_accounts_args)
+                .await
+                .context("Fund accounts failed")?;
+            println!("Funding done, {}", summary);
+            Ok(())
+        }
+        TxnEmitterCommand::Cleanup(args) => {
+            let summary = cleanup_account_pool(&args.cluster_args, &args.cleanup_args)
+                .await
+                .context("Cleanup failed")?;
+            println!("Cleanup done, {}", summary);
+            Ok(())
+        }
     }
 }
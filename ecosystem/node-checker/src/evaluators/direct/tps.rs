@@ -12,7 +12,8 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use thiserror::Error as ThisError;
 use transaction_emitter_lib::{
-    emit_transactions_with_cluster, Cluster, ClusterArgs, EmitArgs, MintArgs,
+    emit_transactions_with_cluster, Cluster, ClusterArgs, DeadInstancePolicy, EmitArgs, MintArgs,
+    TargetSelectionPolicy,
 };
 
 use super::types::DirectEvaluatorInput;
@@ -92,10 +93,17 @@ impl Evaluator for TpsEvaluator {
         let target_url = input.target_node_address.get_api_url();
 
         let cluster_args = ClusterArgs {
-            targets: vec![target_url; self.args.repeat_target_count],
+            targets: vec![target_url.into(); self.args.repeat_target_count],
+            query_targets: vec![],
+            target_headers: vec![],
+            proxy: None,
+            target_proxies: vec![],
             reuse_accounts: false,
             mint_args: self.args.mint_args.clone(),
             chain_id: input.baseline_node_information.chain_id,
+            target_selection: TargetSelectionPolicy::Any,
+            bare_target_schemes: vec![],
+            dead_instances: DeadInstancePolicy::Skip,
         };
         let cluster = Cluster::try_from_cluster_args(&cluster_args)
             .await
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::protocols::wire::handshake::v1::ProtocolId;
-use aptos_config::network_id::NetworkContext;
+use aptos_config::network_id::{NetworkContext, NetworkId};
 use aptos_metrics_core::{
     register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
     Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
@@ -11,6 +11,7 @@ use aptos_types::PeerId;
 use netcore::transport::ConnectionOrigin;
 use once_cell::sync::Lazy;
 use short_hex_str::AsShortHexStr;
+use std::time::Duration;
 
 // some type labels
 pub const REQUEST_LABEL: &str = "request";
@@ -240,6 +241,34 @@ pub fn outbound_rpc_request_latency(
     ])
 }
 
+pub static APTOS_NETWORK_HEALTH_CHECKER_PING_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_network_health_checker_ping_latency_seconds",
+        "Round-trip time of the successful health checker pings to a particular peer",
+        &["role_type", "network_id", "peer_id", "remote_peer_id"]
+    )
+    .unwrap()
+});
+
+/// Observes the round-trip time of a ping to `remote_peer_id`. Not on the public network, which
+/// has too many peers to label by.
+pub fn health_checker_ping_latency(
+    network_context: &NetworkContext,
+    remote_peer_id: &PeerId,
+    latency: Duration,
+) {
+    if network_context.network_id() != NetworkId::Public {
+        APTOS_NETWORK_HEALTH_CHECKER_PING_LATENCY
+            .with_label_values(&[
+                network_context.role().as_str(),
+                network_context.network_id().as_str(),
+                network_context.peer_id().short_str().as_str(),
+                remote_peer_id.short_str().as_str(),
+            ])
+            .observe(latency.as_secs_f64())
+    }
+}
+
 pub static APTOS_NETWORK_INBOUND_RPC_HANDLER_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
         "aptos_network_inbound_rpc_handler_latency_seconds",
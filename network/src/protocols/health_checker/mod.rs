@@ -48,7 +48,10 @@ use futures::{
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use short_hex_str::AsShortHexStr;
-use std::{collections::hash_map::Entry, time::Duration};
+use std::{
+    collections::hash_map::Entry,
+    time::{Duration, Instant},
+};
 
 pub mod builder;
 mod interface;
@@ -463,6 +466,7 @@ impl HealthChecker {
             round,
             nonce
         );
+        let ping_start = Instant::now();
         let res_pong_msg = network_tx
             .send_rpc(peer_id, HealthCheckerMsg::Ping(Ping(nonce)), ping_timeout)
             .await
@@ -470,6 +474,9 @@ impl HealthChecker {
                 HealthCheckerMsg::Pong(res) => Ok(res),
                 _ => Err(RpcError::InvalidRpcResponse),
             });
+        if res_pong_msg.is_ok() {
+            counters::health_checker_ping_latency(&network_context, &peer_id, ping_start.elapsed());
+        }
         (peer_id, round, nonce, res_pong_msg)
     }
 }
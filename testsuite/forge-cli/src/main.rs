@@ -9,7 +9,7 @@ use forge::success_criteria::SuccessCriteria;
 use forge::{ForgeConfig, Options, *};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::{env, num::NonZeroUsize, process, thread, time::Duration};
+use std::{env, num::NonZeroUsize, path::PathBuf, process, thread, time::Duration};
 use structopt::StructOpt;
 use testcases::continuous_progress_test::ContinuousProgressTest;
 use testcases::network_bandwidth_test::NetworkBandwidthTest;
@@ -40,6 +40,11 @@ struct Args {
         default_value = "land_blocking"
     )]
     suite: String,
+    #[structopt(
+        long,
+        help = "Runs the scenario defined in this YAML file instead of a test suite"
+    )]
+    scenario: Option<PathBuf>,
     #[structopt(long, multiple = true)]
     changelog: Option<Vec<String>>,
 
@@ -58,6 +63,7 @@ enum CliCommand {
 enum TestCommand {
     LocalSwarm(LocalSwarm),
     K8sSwarm(K8sSwarm),
+    SshSwarm(SshSwarm),
 }
 
 #[derive(StructOpt, Debug)]
@@ -68,7 +74,13 @@ enum OperatorCommand {
 }
 
 #[derive(StructOpt, Debug)]
-struct LocalSwarm {}
+struct LocalSwarm {
+    #[structopt(
+        long,
+        help = "If set, runs Prometheus and Grafana in Docker to monitor the swarm"
+    )]
+    with_observability: bool,
+}
 
 #[derive(StructOpt, Debug)]
 struct K8sSwarm {
@@ -108,6 +120,34 @@ struct K8sSwarm {
     keep: bool,
     #[structopt(long, help = "If set, enables HAProxy for each of the validators")]
     enable_haproxy: bool,
+    #[structopt(
+        long,
+        help = "If set, funds test accounts through this faucet instead of the root account"
+    )]
+    faucet_url: Option<Url>,
+}
+
+#[derive(StructOpt, Debug)]
+struct SshSwarm {
+    #[structopt(
+        long,
+        required = true,
+        min_values = 1,
+        help = "Hosts to run the validators on, as [user@]ip[:port], one validator per host"
+    )]
+    hosts: Vec<SshHost>,
+    #[structopt(long, help = "Private key to authenticate to the hosts with")]
+    identity_file: Option<PathBuf>,
+    #[structopt(long, help = "The aptos-node binary to run on the hosts")]
+    aptos_node_bin: PathBuf,
+    #[structopt(
+        long,
+        help = "Directory of the swarm, both locally and on the hosts",
+        default_value = "/tmp/forge-ssh"
+    )]
+    dir: PathBuf,
+    #[structopt(long, help = "If set, keeps the nodes running once the tests are done")]
+    keep: bool,
 }
 
 #[derive(StructOpt, Debug)]
@@ -183,7 +223,10 @@ fn main() -> Result<()> {
         // cmd input for test
         CliCommand::Test(ref test_cmd) => {
             // Identify the test suite to run
-            let mut test_suite = get_test_suite(suite_name, duration)?;
+            let mut test_suite = match &args.scenario {
+                Some(path) => Scenario::load(path)?.into_forge_config(),
+                None => get_test_suite(suite_name, duration)?,
+            };
 
             // Identify the number of validators and fullnodes to run
             // (if overriding what test has specified)
@@ -208,44 +251,78 @@ fn main() -> Result<()> {
 
             // Run the test suite
             match test_cmd {
-                TestCommand::LocalSwarm(..) => {
-                    // Loosen all criteria for local runs
-                    test_suite.get_success_criteria_mut().avg_tps = 400;
-                    test_suite.get_success_criteria_mut().max_latency_ms = 60000;
-                    let previous_emit_job = test_suite.get_emit_job().clone();
-                    let test_suite =
-                        test_suite.with_emit_job(previous_emit_job.mode(EmitJobMode::MaxLoad {
-                            mempool_backlog: 5000,
-                        }));
+                TestCommand::LocalSwarm(local) => {
+                    // Loosen all criteria for local runs, scenarios defining their own
+                    if args.scenario.is_none() {
+                        test_suite.get_success_criteria_mut().avg_tps = 400;
+                        test_suite.get_success_criteria_mut().max_latency_ms = 60000;
+                        let previous_emit_job = test_suite.get_emit_job().clone();
+                        test_suite = test_suite.with_emit_job(previous_emit_job.mode(
+                            EmitJobMode::MaxLoad {
+                                mempool_backlog: 5000,
+                            },
+                        ));
+                    }
 
+                    let mut factory = LocalFactory::from_workspace()?;
+                    if local.with_observability {
+                        factory = factory.with_observability();
+                    }
                     run_forge(
                         duration,
                         test_suite,
-                        LocalFactory::from_workspace()?,
+                        factory,
                         &args.options,
                         args.changelog.clone(),
                     )
                 }
-                TestCommand::K8sSwarm(k8s) => {
-                    if let Some(move_modules_dir) = &k8s.move_modules_dir {
-                        test_suite = test_suite.with_genesis_modules_path(move_modules_dir.clone());
+                TestCommand::SshSwarm(ssh) => {
+                    let hosts = ssh
+                        .hosts
+                        .iter()
+                        .map(|host| match &ssh.identity_file {
+                            Some(identity_file) => {
+                                host.clone().with_identity_file(identity_file.clone())
+                            }
+                            None => host.clone(),
+                        })
+                        .collect();
+                    let versions = vec![(
+                        Version::new(0, "current".to_string()),
+                        ssh.aptos_node_bin.clone(),
+                    )]
+                    .into_iter()
+                    .collect();
+                    let mut factory = SshFactory::new(hosts, versions, ssh.dir.clone());
+                    if ssh.keep {
+                        factory = factory.with_keep();
                     }
                     run_forge(
                         duration,
                         test_suite,
-                        K8sFactory::new(
-                            k8s.namespace.clone(),
-                            k8s.image_tag.clone(),
-                            k8s.upgrade_image_tag.clone(),
-                            k8s.port_forward,
-                            k8s.reuse,
-                            k8s.keep,
-                            k8s.enable_haproxy,
-                        )
-                        .unwrap(),
+                        factory,
                         &args.options,
-                        args.changelog,
-                    )?;
+                        args.changelog.clone(),
+                    )
+                }
+                TestCommand::K8sSwarm(k8s) => {
+                    if let Some(move_modules_dir) = &k8s.move_modules_dir {
+                        test_suite = test_suite.with_genesis_modules_path(move_modules_dir.clone());
+                    }
+                    let mut factory = K8sFactory::new(
+                        k8s.namespace.clone(),
+                        k8s.image_tag.clone(),
+                        k8s.upgrade_image_tag.clone(),
+                        k8s.port_forward,
+                        k8s.reuse,
+                        k8s.keep,
+                        k8s.enable_haproxy,
+                    )
+                    .unwrap();
+                    if let Some(faucet_url) = &k8s.faucet_url {
+                        factory = factory.with_faucet_url(faucet_url.clone());
+                    }
+                    run_forge(duration, test_suite, factory, &args.options, args.changelog)?;
                     Ok(())
                 }
             }
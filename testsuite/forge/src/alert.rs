@@ -0,0 +1,52 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Alerts sent to a webhook while a run is still going, e.g. when a long test starts breaching
+//! its success criteria, instead of finding out once it's over.
+
+use anyhow::{anyhow, bail, Result};
+use aptos_logger::warn;
+use once_cell::sync::OnceCell;
+use serde_json::json;
+use std::{thread, time::Duration};
+use url::Url;
+
+const ALERT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static ALERT_WEBHOOK: OnceCell<Url> = OnceCell::new();
+
+/// Sends the alerts of the run to `url`, as Slack incoming webhook payloads.
+pub fn init_alert_webhook(url: Url) -> Result<()> {
+    ALERT_WEBHOOK
+        .set(url)
+        .map_err(|_| anyhow!("Alert webhook already registered"))
+}
+
+/// Posts `text` to the alert webhook. Does nothing unless a webhook was registered, failures
+/// are only logged as they shouldn't fail the run.
+pub fn send_alert(text: &str) {
+    let url = match ALERT_WEBHOOK.get() {
+        Some(url) => url.clone(),
+        None => return,
+    };
+    let body = json!({ "text": text });
+    // The blocking client can't be used from within the runtimes alerts are sent from.
+    let result = thread::spawn(move || -> Result<()> {
+        let response = reqwest::blocking::Client::builder()
+            .timeout(ALERT_TIMEOUT)
+            .build()?
+            .post(url)
+            .json(&body)
+            .send()?;
+        if !response.status().is_success() {
+            bail!("Webhook responded with {}", response.status());
+        }
+        Ok(())
+    })
+    .join()
+    .map_err(|_| anyhow!("Sending the alert panicked"))
+    .and_then(|result| result);
+    if let Err(e) = result {
+        warn!("Failed to send alert: {:#}", e);
+    }
+}
@@ -6,8 +6,9 @@ use std::process::Command;
 use tempfile::TempDir;
 
 use crate::{
-    dump_string_to_file, Result, SwarmChaos, SwarmNetworkBandwidth, SwarmNetworkDelay,
-    SwarmNetworkLoss, SwarmNetworkPartition, KUBECTL_BIN,
+    dump_string_to_file, Result, SwarmChaos, SwarmClockSkew, SwarmCpuStress, SwarmDiskDelay,
+    SwarmMemoryStress, SwarmNetworkBandwidth, SwarmNetworkDelay, SwarmNetworkLoss,
+    SwarmNetworkPartition, UnsupportedChaos, KUBECTL_BIN,
 };
 
 macro_rules! DELAY_NETWORK_CHAOS_TEMPLATE {
@@ -32,6 +33,30 @@ macro_rules! NETWORK_LOSS_CHAOS_TEMPLATE {
     };
 }
 
+macro_rules! CPU_STRESS_CHAOS_TEMPLATE {
+    () => {
+        "chaos/cpu_stress.yaml"
+    };
+}
+
+macro_rules! MEMORY_STRESS_CHAOS_TEMPLATE {
+    () => {
+        "chaos/memory_stress.yaml"
+    };
+}
+
+macro_rules! DISK_DELAY_CHAOS_TEMPLATE {
+    () => {
+        "chaos/disk_delay.yaml"
+    };
+}
+
+macro_rules! CLOCK_SKEW_CHAOS_TEMPLATE {
+    () => {
+        "chaos/clock_skew.yaml"
+    };
+}
+
 /// Injects the SwarmChaos into the specified namespace
 pub fn inject_swarm_chaos(kube_namespace: &str, chaos: &SwarmChaos) -> Result<()> {
     let template = create_chaos_template(kube_namespace, chaos)?;
@@ -93,17 +118,67 @@ fn create_network_loss_template(
     )
 }
 
+fn create_cpu_stress_template(kube_namespace: &str, swarm_cpu_stress: &SwarmCpuStress) -> String {
+    format!(
+        include_str!(CPU_STRESS_CHAOS_TEMPLATE!()),
+        namespace = kube_namespace,
+        num_workers = swarm_cpu_stress.num_workers,
+        load_percentage = swarm_cpu_stress.load_percentage,
+    )
+}
+
+fn create_memory_stress_template(
+    kube_namespace: &str,
+    swarm_memory_stress: &SwarmMemoryStress,
+) -> String {
+    format!(
+        include_str!(MEMORY_STRESS_CHAOS_TEMPLATE!()),
+        namespace = kube_namespace,
+        num_workers = swarm_memory_stress.num_workers,
+        size_mb = swarm_memory_stress.size_mb,
+    )
+}
+
+fn create_disk_delay_template(kube_namespace: &str, swarm_disk_delay: &SwarmDiskDelay) -> String {
+    format!(
+        include_str!(DISK_DELAY_CHAOS_TEMPLATE!()),
+        namespace = kube_namespace,
+        latency_ms = swarm_disk_delay.latency_ms,
+        percentage = swarm_disk_delay.percentage,
+    )
+}
+
+fn create_clock_skew_template(kube_namespace: &str, swarm_clock_skew: &SwarmClockSkew) -> String {
+    let direction = if swarm_clock_skew.offset_ms < 0 {
+        "behind"
+    } else {
+        "ahead"
+    };
+    format!(
+        include_str!(CLOCK_SKEW_CHAOS_TEMPLATE!()),
+        namespace = kube_namespace,
+        direction = direction,
+        offset_abs_ms = swarm_clock_skew.offset_ms.unsigned_abs(),
+        offset_ms = swarm_clock_skew.offset_ms,
+    )
+}
+
 fn create_chaos_template(kube_namespace: &str, chaos: &SwarmChaos) -> Result<String> {
     let template = match chaos {
         SwarmChaos::Delay(c) => create_network_delay_template(kube_namespace, c),
         SwarmChaos::Partition(c) => create_network_partition_template(kube_namespace, c),
         SwarmChaos::Bandwidth(c) => create_network_bandwidth_template(kube_namespace, c),
         SwarmChaos::Loss(c) => create_network_loss_template(kube_namespace, c),
+        SwarmChaos::Cpu(c) => create_cpu_stress_template(kube_namespace, c),
+        SwarmChaos::Memory(c) => create_memory_stress_template(kube_namespace, c),
+        SwarmChaos::Disk(c) => create_disk_delay_template(kube_namespace, c),
+        SwarmChaos::Clock(c) => create_clock_skew_template(kube_namespace, c),
+        SwarmChaos::Pause(_) => return Err(UnsupportedChaos(chaos.kind()).into()),
     };
     Ok(template)
 }
 
-/// Creates and applies the NetworkChaos CRD
+/// Creates and applies the chaos CRD
 fn inject_chaos_template(kube_namespace: &str, chaos_template: String) -> Result<()> {
     let tmp_dir = TempDir::new().expect("Could not create temp dir");
     let latency_network_chaos_file_path = dump_string_to_file(
@@ -125,7 +200,7 @@ fn inject_chaos_template(kube_namespace: &str, chaos_template: String) -> Result
     Ok(())
 }
 
-/// Removes the NetworkChaos CRD
+/// Removes the chaos CRD
 fn remove_chaos_template(kube_namespace: &str, chaos_template: String) -> Result<()> {
     let tmp_dir = TempDir::new().expect("Could not create temp dir");
     let latency_network_chaos_file_path = dump_string_to_file(
@@ -5,6 +5,7 @@ use crate::{Factory, GenesisConfig, GenesisConfigFn, NodeConfigFn, Result, Swarm
 use anyhow::bail;
 use aptos_logger::info;
 use rand::rngs::StdRng;
+use reqwest::Url;
 use std::time::Duration;
 use std::{convert::TryInto, num::NonZeroUsize};
 
@@ -35,6 +36,7 @@ pub struct K8sFactory {
     reuse: bool,
     keep: bool,
     enable_haproxy: bool,
+    faucet_url: Option<Url>,
 }
 
 impl K8sFactory {
@@ -74,8 +76,16 @@ impl K8sFactory {
             reuse,
             keep,
             enable_haproxy,
+            faucet_url: None,
         })
     }
+
+    /// Funds test accounts through the faucet at `faucet_url`, e.g. when reusing a network the
+    /// root key of which isn't known.
+    pub fn with_faucet_url(mut self, faucet_url: Url) -> Self {
+        self.faucet_url = Some(faucet_url);
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -165,6 +175,10 @@ impl Factory for K8sFactory {
         )
         .await
         .unwrap();
+        let swarm = match &self.faucet_url {
+            Some(faucet_url) => swarm.with_faucet_url(faucet_url.clone()),
+            None => swarm,
+        };
         Ok(Box::new(swarm))
     }
 }
@@ -2,9 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    get_free_port, scale_stateful_set_replicas, FullNode, HealthCheckError, Node, NodeExt, Result,
-    Validator, Version, KUBECTL_BIN, LOCALHOST, NODE_METRIC_PORT, REST_API_HAPROXY_SERVICE_PORT,
-    REST_API_SERVICE_PORT,
+    get_free_port, record_event, scale_stateful_set_replicas, FullNode, HealthCheckError,
+    HealthCheckFailure, Node, NodeExt, Result, TimelineEventKind, Validator, Version, KUBECTL_BIN,
+    LOCALHOST, NODE_METRIC_PORT, REST_API_HAPROXY_SERVICE_PORT, REST_API_SERVICE_PORT,
 };
 use anyhow::{anyhow, format_err};
 use aptos_config::config::NodeConfig;
@@ -142,13 +142,20 @@ impl Node for K8sNode {
             self.rest_api_port = get_free_port();
             self.port_forward_rest_api()?;
         }
+        record_event(TimelineEventKind::NodeStarted {
+            node: self.name.clone(),
+        });
         self.wait_until_healthy(Instant::now() + Duration::from_secs(60))
             .await
     }
 
     async fn stop(&mut self) -> Result<()> {
         info!("going to stop node {}", self.stateful_set_name());
-        scale_stateful_set_replicas(self.stateful_set_name(), self.namespace(), 0).await
+        scale_stateful_set_replicas(self.stateful_set_name(), self.namespace(), 0).await?;
+        record_event(TimelineEventKind::NodeStopped {
+            node: self.name.clone(),
+        });
+        Ok(())
     }
 
     fn version(&self) -> Version {
@@ -245,7 +252,10 @@ impl Node for K8sNode {
             .await
             .map(|_| ())
             .map_err(|e| {
-                HealthCheckError::Failure(format_err!("K8s node health_check failed: {}", e))
+                HealthCheckError::Failure(HealthCheckFailure::Other(format_err!(
+                    "K8s node health_check failed: {}",
+                    e
+                )))
             })
     }
 
@@ -61,6 +61,24 @@ pub async fn query_with_metadata(
     }
 }
 
+pub async fn query_range_with_metadata(
+    prom_client: &PrometheusClient,
+    query: &str,
+    start: i64,
+    end: i64,
+    step: f64,
+    labels_map: BTreeMap<String, String>,
+) -> Result<PromqlResult> {
+    let new_query = construct_query_with_extra_labels(query, labels_map);
+    match prom_client
+        .query_range(&new_query, start, end, step, None)
+        .await
+    {
+        Ok(r) => Ok(r),
+        Err(e) => bail!(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use prometheus_http_query::Error as PrometheusError;
@@ -1,13 +1,17 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::interface::system_metrics::{query_prometheus_system_metrics, SystemMetricsThreshold};
+use crate::interface::system_metrics::{
+    query_prometheus_disk_metrics, query_prometheus_network_metrics,
+    query_prometheus_system_metrics, SystemMetricsThreshold,
+};
 use crate::{
     chaos, check_for_container_restart, create_k8s_client, get_free_port, get_stateful_set_image,
     node::K8sNode,
-    prometheus::{self, query_with_metadata},
-    query_sequence_numbers, set_stateful_set_image_tag, uninstall_testnet_resources, ChainInfo,
-    FullNode, Node, Result, Swarm, SwarmChaos, Validator, Version, HAPROXY_SERVICE_SUFFIX,
+    prometheus::{self, query_range_with_metadata, query_with_metadata},
+    query_sequence_numbers, record_event, set_stateful_set_image_tag, trace_span,
+    uninstall_testnet_resources, ChainInfo, FullNode, Node, Result, Swarm, SwarmChaos,
+    SwarmChaosKind, TimelineEventKind, Validator, Version, HAPROXY_SERVICE_SUFFIX,
     REST_API_HAPROXY_SERVICE_PORT, REST_API_SERVICE_PORT,
 };
 use ::aptos_logger::*;
@@ -26,6 +30,7 @@ use kube::{
 };
 use prometheus_http_query::{response::PromqlResult, Client as PrometheusClient};
 use regex::Regex;
+use reqwest::Url;
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
@@ -45,6 +50,7 @@ pub struct K8sSwarm {
     keep: bool,
     chaoses: HashSet<SwarmChaos>,
     prom_client: Option<PrometheusClient>,
+    faucet_url: Option<Url>,
 }
 
 impl K8sSwarm {
@@ -99,9 +105,16 @@ impl K8sSwarm {
             keep,
             chaoses: HashSet::new(),
             prom_client,
+            faucet_url: None,
         })
     }
 
+    /// Funds test accounts through the faucet at `faucet_url` instead of the root account.
+    pub fn with_faucet_url(mut self, faucet_url: Url) -> Self {
+        self.faucet_url = Some(faucet_url);
+        self
+    }
+
     fn get_rest_api_url(&self) -> String {
         self.validators
             .values()
@@ -162,6 +175,9 @@ impl Swarm for K8sSwarm {
             .get(version)
             .cloned()
             .ok_or_else(|| anyhow!("Invalid version: {:?}", version))?;
+        let _span = trace_span("upgrade validator")
+            .with_attribute("node", validator.name())
+            .with_attribute("version", &version);
         // stop the validator first so there is no race on the upgrade
         validator.stop().await?;
         // set the image tag of the StatefulSet spec while there are 0 replicas
@@ -201,29 +217,29 @@ impl Swarm for K8sSwarm {
         self.fullnodes.get_mut(&id).map(|v| v as &mut dyn FullNode)
     }
 
-    fn add_validator(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
-        todo!()
+    async fn add_validator(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
+        bail!("Adding validators is not supported by the K8s backend")
     }
 
-    fn remove_validator(&mut self, _id: PeerId) -> Result<()> {
-        todo!()
+    async fn remove_validator(&mut self, _id: PeerId) -> Result<()> {
+        bail!("Removing validators is not supported by the K8s backend")
     }
 
-    fn add_validator_full_node(
+    async fn add_validator_full_node(
         &mut self,
         _version: &Version,
         _template: NodeConfig,
         _id: PeerId,
     ) -> Result<PeerId> {
-        todo!()
+        bail!("Adding full nodes is not supported by the K8s backend")
     }
 
-    fn add_full_node(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
-        todo!()
+    async fn add_full_node(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
+        bail!("Adding full nodes is not supported by the K8s backend")
     }
 
-    fn remove_full_node(&mut self, _id: PeerId) -> Result<()> {
-        todo!()
+    async fn remove_full_node(&mut self, _id: PeerId) -> Result<()> {
+        bail!("Removing full nodes is not supported by the K8s backend")
     }
 
     fn versions<'a>(&'a self) -> Box<dyn Iterator<Item = Version> + 'a> {
@@ -232,7 +248,11 @@ impl Swarm for K8sSwarm {
 
     fn chain_info(&mut self) -> ChainInfo<'_> {
         let rest_api_url = self.get_rest_api_url();
-        ChainInfo::new(&mut self.root_account, rest_api_url, self.chain_id)
+        let chain_info = ChainInfo::new(&mut self.root_account, rest_api_url, self.chain_id);
+        match &self.faucet_url {
+            Some(faucet_url) => chain_info.with_faucet_url(faucet_url.clone()),
+            None => chain_info,
+        }
     }
 
     // returns a kubectl logs command to retrieve the logs manually
@@ -242,20 +262,37 @@ impl Swarm for K8sSwarm {
     }
 
     fn inject_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        let _span = trace_span("inject chaos").with_attribute("chaos", format!("{:?}", chaos));
         chaos::inject_swarm_chaos(&self.kube_namespace, &chaos)?;
+        record_event(TimelineEventKind::ChaosInjected {
+            chaos: format!("{:?}", chaos),
+        });
         self.chaoses.insert(chaos);
         Ok(())
     }
 
     fn remove_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        let _span = trace_span("remove chaos").with_attribute("chaos", format!("{:?}", chaos));
         if self.chaoses.remove(&chaos) {
             chaos::remove_swarm_chaos(&self.kube_namespace, &chaos)?;
+            record_event(TimelineEventKind::ChaosRemoved {
+                chaos: format!("{:?}", chaos),
+            });
         } else {
             bail!("Chaos {:?} not found", chaos);
         }
         Ok(())
     }
 
+    /// Chaos Mesh covers every kind of chaos but pausing nodes
+    fn supported_chaos(&self) -> Vec<SwarmChaosKind> {
+        SwarmChaosKind::ALL
+            .iter()
+            .copied()
+            .filter(|kind| *kind != SwarmChaosKind::Pause)
+            .collect()
+    }
+
     async fn ensure_no_validator_restart(&self) -> Result<()> {
         for validator in &self.validators {
             if let Err(e) = check_for_container_restart(
@@ -300,6 +337,21 @@ impl Swarm for K8sSwarm {
         bail!("No prom client");
     }
 
+    async fn query_range_metrics(
+        &self,
+        query: &str,
+        start: i64,
+        end: i64,
+        step: f64,
+    ) -> Result<PromqlResult> {
+        if let Some(c) = &self.prom_client {
+            let mut labels_map = BTreeMap::new();
+            labels_map.insert("namespace".to_string(), self.kube_namespace.clone());
+            return query_range_with_metadata(c, query, start, end, step, labels_map).await;
+        }
+        bail!("No prom client");
+    }
+
     async fn ensure_healthy_system_metrics(
         &mut self,
         start_time: i64,
@@ -307,7 +359,7 @@ impl Swarm for K8sSwarm {
         threshold: SystemMetricsThreshold,
     ) -> Result<()> {
         if let Some(c) = &self.prom_client {
-            let system_metrics = query_prometheus_system_metrics(
+            let mut system_metrics = query_prometheus_system_metrics(
                 c,
                 start_time,
                 end_time,
@@ -315,6 +367,28 @@ impl Swarm for K8sSwarm {
                 &self.kube_namespace,
             )
             .await?;
+            if threshold.checks_disk() {
+                let (read, write) = query_prometheus_disk_metrics(
+                    c,
+                    start_time,
+                    end_time,
+                    30.0,
+                    &self.kube_namespace,
+                )
+                .await?;
+                system_metrics = system_metrics.with_disk_metrics(read, write);
+            }
+            if threshold.checks_network() {
+                let (received, sent) = query_prometheus_network_metrics(
+                    c,
+                    start_time,
+                    end_time,
+                    30.0,
+                    &self.kube_namespace,
+                )
+                .await?;
+                system_metrics = system_metrics.with_network_metrics(received, sent);
+            }
             threshold.ensure_threshold(&system_metrics)?;
             Ok(())
         } else {
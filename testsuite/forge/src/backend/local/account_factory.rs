@@ -0,0 +1,155 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Funded accounts created ahead of time in the background, so tests needing many accounts
+//! don't wait for their creation one at a time.
+
+use crate::{LocalSwarm, NodeExt, Result, Swarm};
+use anyhow::{ensure, format_err};
+use aptos_logger::warn;
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::{transaction_builder::TransactionFactory, types::LocalAccount};
+use rand::{
+    rngs::{OsRng, StdRng},
+    Rng, SeedableRng,
+};
+use std::sync::atomic::AtomicUsize;
+use tokio::{sync::mpsc, task::JoinHandle};
+use transaction_emitter_lib::emitter::account_minter::{
+    create_and_fund_account_request, execute_and_wait_transactions,
+};
+
+/// Max gas a transaction of the factory can cost at its gas price
+const MAX_GAS_PER_TXN: u64 = 1_000;
+
+/// Hands out funded accounts created in batches by a background task, which keeps one batch
+/// ahead of the consumer. The accounts are funded by an account of their own, funded once by
+/// the root account, so the root account can keep being used while the factory runs.
+pub struct AccountFactory {
+    accounts: mpsc::Receiver<Result<LocalAccount>>,
+    remaining: usize,
+    task: JoinHandle<()>,
+}
+
+impl AccountFactory {
+    /// The next funded account, waiting for its batch to be created if needed.
+    pub async fn next_account(&mut self) -> Result<LocalAccount> {
+        ensure!(self.remaining > 0, "All accounts of the factory were used");
+        let account = self
+            .accounts
+            .recv()
+            .await
+            .ok_or_else(|| format_err!("Account factory stopped"))??;
+        self.remaining -= 1;
+        Ok(account)
+    }
+
+    /// Accounts the factory can still hand out.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl Drop for AccountFactory {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl LocalSwarm {
+    /// Starts creating up to `max_accounts` accounts holding `amount` coins each, `batch_size`
+    /// at a time, in the background. See `AccountFactory`.
+    pub async fn account_factory(
+        &mut self,
+        amount: u64,
+        max_accounts: usize,
+        batch_size: usize,
+    ) -> Result<AccountFactory> {
+        ensure!(batch_size > 0, "batch_size must be positive");
+        let client = self
+            .validators()
+            .next()
+            .ok_or_else(|| format_err!("No validator"))?
+            .rest_client();
+        let chain_info = self.chain_info();
+        let transaction_factory = TransactionFactory::new(chain_info.chain_id())
+            .with_gas_unit_price(1)
+            .with_max_gas_amount(MAX_GAS_PER_TXN);
+        let mut rng = StdRng::from_seed(OsRng.gen());
+
+        let mut funder = LocalAccount::generate(&mut rng);
+        let budget = (amount + MAX_GAS_PER_TXN) * max_accounts as u64;
+        let fund_funder = create_and_fund_account_request(
+            chain_info.root_account,
+            budget,
+            funder.public_key(),
+            &transaction_factory,
+        );
+        execute_and_wait_transactions(
+            &client,
+            chain_info.root_account,
+            vec![fund_funder],
+            &AtomicUsize::new(0),
+        )
+        .await?;
+
+        let (sender, accounts) = mpsc::channel(batch_size);
+        let task = tokio::spawn(async move {
+            let mut created = 0;
+            while created < max_accounts {
+                let batch = create_batch(
+                    &client,
+                    &transaction_factory,
+                    &mut funder,
+                    &mut rng,
+                    amount,
+                    batch_size.min(max_accounts - created),
+                )
+                .await;
+                let accounts = match batch {
+                    Ok(accounts) => accounts,
+                    Err(e) => {
+                        warn!("Account factory failed to create accounts: {:#}", e);
+                        let _ = sender.send(Err(e)).await;
+                        return;
+                    }
+                };
+                created += accounts.len();
+                for account in accounts {
+                    if sender.send(Ok(account)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(AccountFactory {
+            accounts,
+            remaining: max_accounts,
+            task,
+        })
+    }
+}
+
+async fn create_batch(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    funder: &mut LocalAccount,
+    rng: &mut StdRng,
+    amount: u64,
+    size: usize,
+) -> Result<Vec<LocalAccount>> {
+    let accounts: Vec<_> = (0..size).map(|_| LocalAccount::generate(rng)).collect();
+    let requests = accounts
+        .iter()
+        .map(|account| {
+            create_and_fund_account_request(
+                funder,
+                amount,
+                account.public_key(),
+                transaction_factory,
+            )
+        })
+        .collect();
+    execute_and_wait_transactions(client, funder, requests, &AtomicUsize::new(0)).await?;
+    Ok(accounts)
+}
@@ -135,6 +135,31 @@ pub fn git_get_upstream_remote() -> Result<String> {
     }
 }
 
+/// Makes sure `tag` is available locally, fetching it from the upstream remote if needed.
+pub fn git_fetch_tag(tag: &str) -> Result<()> {
+    let metadata = metadata()?;
+    if git_rev_parse(&metadata, format!("{}^{{commit}}", tag)).is_ok() {
+        return Ok(());
+    }
+    let remote = git_get_upstream_remote()?;
+    info!("Fetching tag {} from {}", tag, remote);
+    let output = Command::new("git")
+        .current_dir(&metadata.workspace_root)
+        .args(&["fetch", "--no-tags", &remote, "tag", tag])
+        .output()
+        .context("Failed to fetch tag")?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        bail!(
+            "Failed to fetch tag {} from {}: {}",
+            tag,
+            remote,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+}
+
 pub fn git_merge_base<R: AsRef<str>>(rev: R) -> Result<String> {
     let rev = rev.as_ref();
     let output = Command::new("git")
@@ -153,6 +178,35 @@ pub fn git_merge_base<R: AsRef<str>>(rev: R) -> Result<String> {
 }
 
 fn cargo_build_aptos_node<D, T>(directory: D, target_directory: T) -> Result<PathBuf>
+where
+    D: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    cargo_build_bin(
+        directory,
+        target_directory,
+        "aptos-node",
+        &["--features=failpoints"],
+    )
+}
+
+/// Build a binary of the workspace in the current working directory, e.g. the db tools.
+pub fn get_bin_from_worktree(bin: &str) -> Result<PathBuf> {
+    let metadata = metadata()?;
+    cargo_build_bin(
+        &metadata.workspace_root,
+        &metadata.target_directory,
+        bin,
+        &[],
+    )
+}
+
+fn cargo_build_bin<D, T>(
+    directory: D,
+    target_directory: T,
+    bin: &str,
+    extra_args: &[&str],
+) -> Result<PathBuf>
 where
     D: AsRef<Path>,
     T: AsRef<Path>,
@@ -162,7 +216,9 @@ where
     let target_directory = target_directory.as_ref();
     let directory = directory.as_ref();
 
-    let mut args = vec!["build", "--bin=aptos-node", "--features=failpoints"];
+    let bin_arg = format!("--bin={}", bin);
+    let mut args = vec!["build", bin_arg.as_str()];
+    args.extend_from_slice(extra_args);
     if use_release {
         args.push("--release");
     }
@@ -172,30 +228,29 @@ where
         .env("CARGO_TARGET_DIR", target_directory)
         .args(&args)
         .output()
-        .context("Failed to build aptos-node")?;
+        .with_context(|| format!("Failed to build {}", bin))?;
 
     if output.status.success() {
         let bin_path = target_directory.join(format!(
             "{}/{}{}",
             if use_release { "release" } else { "debug" },
-            "aptos-node",
+            bin,
             env::consts::EXE_SUFFIX
         ));
         if !bin_path.exists() {
-            bail!(
-                "Can't find binary aptos-node at expected path {:?}",
-                bin_path
-            );
+            bail!("Can't find binary {} at expected path {:?}", bin, bin_path);
         }
-        info!("Local swarm node binary path: {:?}", bin_path);
+        info!("Local swarm {} binary path: {:?}", bin, bin_path);
         Ok(bin_path)
     } else {
         io::stderr().write_all(&output.stderr)?;
 
         bail!(
-            "Failed to build aptos-node: 'cd {} && CARGO_TARGET_DIR={} cargo build --bin=aptos-node",
+            "Failed to build {}: 'cd {} && CARGO_TARGET_DIR={} cargo build --bin={}",
+            bin,
             directory.display(),
             target_directory.display(),
+            bin,
         );
     }
 }
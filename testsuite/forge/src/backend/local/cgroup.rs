@@ -0,0 +1,191 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! CPU and memory limits of local nodes, enforced by a cgroup v2 of their own, e.g. to run a
+//! weak validator or get a node OOM killed deterministically. The cgroups are created under
+//! `/sys/fs/cgroup/forge`, so like network isolation, this needs root. Linux only: other
+//! systems have no cgroups, and nodes with limits fail to start there with
+//! `UnsupportedResourceLimits`, unless they run in containers. Job objects, the Windows
+//! counterpart, are deliberately left out, as local nodes are stopped and paused with POSIX
+//! signals, so the local backend doesn't run on Windows anyway.
+
+use anyhow::{ensure, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use thiserror::Error;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+/// Parent of the cgroups of the nodes, under `CGROUP_ROOT`
+const FORGE_CGROUP: &str = "forge";
+/// Period of the CPU quota, in microseconds
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Resources a node can use, unlimited if not set.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// CPU time the node can use, in cores, e.g. 0.5 for half a core
+    pub cpus: Option<f64>,
+    /// Memory above which the node is OOM killed, without swapping first
+    pub memory_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn with_cpus(mut self, cpus: f64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+
+    pub fn with_memory_bytes(mut self, memory_bytes: u64) -> Self {
+        self.memory_bytes = Some(memory_bytes);
+        self
+    }
+
+    /// Value of `cpu.max`, the quota and period in microseconds.
+    fn cpu_max(&self) -> String {
+        match self.cpus {
+            Some(cpus) => format!("{} {}", (cpus * CPU_PERIOD_US as f64) as u64, CPU_PERIOD_US),
+            None => format!("max {}", CPU_PERIOD_US),
+        }
+    }
+
+    fn memory_max(&self) -> String {
+        self.memory_bytes
+            .map_or_else(|| "max".to_string(), |bytes| bytes.to_string())
+    }
+
+    /// Arguments of `docker run` applying the limits to a container.
+    pub(crate) fn docker_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(cpus) = self.cpus {
+            args.push(format!("--cpus={}", cpus));
+        }
+        if let Some(memory_bytes) = self.memory_bytes {
+            args.push(format!("--memory={}", memory_bytes));
+            args.push(format!("--memory-swap={}", memory_bytes));
+        }
+        args
+    }
+}
+
+/// Returned when starting a node with resource limits on a system without cgroups, so that tests
+/// relying on them can be told apart from tests that actually failed.
+#[derive(Debug, Error)]
+#[error("Resource limits of local nodes are not supported on {0}")]
+pub struct UnsupportedResourceLimits(pub &'static str);
+
+/// The cgroup of a node, removed when dropped if no process is left in it.
+#[derive(Debug)]
+pub(crate) struct NodeCgroup {
+    path: PathBuf,
+}
+
+impl NodeCgroup {
+    /// Creates the cgroup `name` with `limits`, or updates the limits of an existing one. Fails
+    /// with `UnsupportedResourceLimits` on systems other than Linux.
+    pub(crate) fn create(name: &str, limits: &ResourceLimits) -> Result<Self> {
+        if !cfg!(target_os = "linux") {
+            return Err(UnsupportedResourceLimits(std::env::consts::OS).into());
+        }
+        let root = Path::new(CGROUP_ROOT);
+        ensure!(
+            root.join("cgroup.controllers").exists(),
+            "Resource limits need cgroup v2 mounted at {}",
+            CGROUP_ROOT
+        );
+        let parent = root.join(FORGE_CGROUP);
+        fs::create_dir_all(&parent)
+            .with_context(|| format!("Failed to create cgroup {:?}", parent))?;
+        for cgroup in [root, parent.as_path()] {
+            let subtree_control = cgroup.join("cgroup.subtree_control");
+            fs::write(&subtree_control, "+cpu +memory").with_context(|| {
+                format!(
+                    "Failed to enable the cpu and memory controllers in {:?}",
+                    cgroup
+                )
+            })?;
+        }
+
+        let path = parent.join(name);
+        fs::create_dir_all(&path).with_context(|| format!("Failed to create cgroup {:?}", path))?;
+        let cgroup = Self { path };
+        cgroup.write("cpu.max", &limits.cpu_max())?;
+        cgroup.write("memory.max", &limits.memory_max())?;
+        if limits.memory_bytes.is_some() && cgroup.path.join("memory.swap.max").exists() {
+            cgroup.write("memory.swap.max", "0")?;
+        }
+        Ok(cgroup)
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        let path = self.path.join(file);
+        fs::write(&path, value).with_context(|| format!("Failed to write {} to {:?}", value, path))
+    }
+
+    /// Wraps `command` so that it runs in the cgroup from the start.
+    pub(crate) fn wrap(&self, command: Command) -> Command {
+        let mut wrapped = Command::new("sh");
+        wrapped
+            .arg("-c")
+            .arg("echo $$ > \"$0\" && exec \"$@\"")
+            .arg(self.path.join("cgroup.procs"))
+            .arg(command.get_program())
+            .args(command.get_args());
+        wrapped
+    }
+
+    /// How many times a process of the cgroup was OOM killed.
+    pub(crate) fn oom_kills(&self) -> Result<u64> {
+        let path = self.path.join("memory.events");
+        let events =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(parse_oom_kills(&events))
+    }
+}
+
+impl Drop for NodeCgroup {
+    fn drop(&mut self) {
+        // Fails while processes are left in it, e.g. a node that isn't reaped yet.
+        let _ = fs::remove_dir(&self.path);
+    }
+}
+
+fn parse_oom_kills(memory_events: &str) -> u64 {
+    memory_events
+        .lines()
+        .filter_map(|line| line.strip_prefix("oom_kill "))
+        .find_map(|count| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_limits() {
+        let limits = ResourceLimits::default();
+        assert_eq!(limits.cpu_max(), "max 100000");
+        assert_eq!(limits.memory_max(), "max");
+        assert!(limits.docker_args().is_empty());
+
+        let limits = limits.with_cpus(0.5).with_memory_bytes(1 << 30);
+        assert_eq!(limits.cpu_max(), "50000 100000");
+        assert_eq!(limits.memory_max(), "1073741824");
+        assert_eq!(
+            limits.docker_args(),
+            vec![
+                "--cpus=0.5",
+                "--memory=1073741824",
+                "--memory-swap=1073741824"
+            ]
+        );
+
+        assert_eq!(
+            parse_oom_kills("low 0\nhigh 0\nmax 12\noom 2\noom_kill 2\noom_group_kill 0\n"),
+            2
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Chaos of a LocalSwarm. Network chaos is applied with tc to the interface of the network
+//! namespace of every validator, so the swarm network has to be isolated first. Delay and loss
+//! are applied by a netem qdisc, and bandwidth by a tbf qdisc under it. As on k8s, only
+//! validators are targeted, and a packet is only affected when it leaves a validator. Paused
+//! nodes are sent SIGSTOP, and SIGCONT once the pause is over.
+
+use super::{netns::NetworkNamespace, node::send_signal};
+use crate::{Result, SwarmChaos, SwarmChaosKind};
+use anyhow::bail;
+use aptos_logger::info;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The kinds of network chaos a LocalSwarm with an isolated network can inject.
+pub(crate) const LOCAL_CHAOS: [SwarmChaosKind; 3] = [
+    SwarmChaosKind::Delay,
+    SwarmChaosKind::Loss,
+    SwarmChaosKind::Bandwidth,
+];
+
+/// Arguments of the tc commands setting up the qdiscs of `interface` for all of `chaoses`, at
+/// most one of every kind, on an interface without qdisc. Nothing is needed without chaos.
+fn tc_commands<'a>(
+    interface: &str,
+    chaoses: impl IntoIterator<Item = &'a SwarmChaos>,
+) -> Result<Vec<Vec<String>>> {
+    let mut netem = vec![];
+    let mut tbf = None;
+    for chaos in chaoses {
+        match chaos {
+            SwarmChaos::Delay(delay) => netem.extend(vec![
+                "delay".to_string(),
+                format!("{}ms", delay.latency_ms),
+                format!("{}ms", delay.jitter_ms),
+                format!("{}%", delay.correlation_percentage),
+            ]),
+            SwarmChaos::Loss(loss) => netem.extend(vec![
+                "loss".to_string(),
+                format!("{}%", loss.loss_percentage),
+                format!("{}%", loss.correlation_percentage),
+            ]),
+            SwarmChaos::Bandwidth(bandwidth) => {
+                tbf = Some(vec![
+                    "rate".to_string(),
+                    format!("{}mbit", bandwidth.rate),
+                    "burst".to_string(),
+                    bandwidth.buffer.to_string(),
+                    "limit".to_string(),
+                    bandwidth.limit.to_string(),
+                ])
+            }
+            _ => bail!("{} chaos can't be applied with tc", chaos.kind()),
+        }
+    }
+    if netem.is_empty() && tbf.is_none() {
+        return Ok(vec![]);
+    }
+
+    let qdisc = |args: &[&str]| {
+        ["qdisc", "add", "dev", interface]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+    };
+    let mut root = qdisc(&["root", "handle", "1:", "netem"]);
+    root.extend(netem);
+    let mut commands = vec![root];
+    if let Some(tbf) = tbf {
+        let mut child = qdisc(&["parent", "1:1", "handle", "10:", "tbf"]);
+        child.extend(tbf);
+        commands.push(child);
+    }
+    Ok(commands)
+}
+
+/// Replaces the chaos applied to the interface of `namespace` by `chaoses`.
+pub(crate) fn apply_network_chaos<'a>(
+    namespace: &NetworkNamespace,
+    chaoses: impl IntoIterator<Item = &'a SwarmChaos>,
+) -> Result<()> {
+    let commands = tc_commands(namespace.interface(), chaoses)?;
+    // Fails if the interface has no qdisc of its own, which is fine.
+    let _ = namespace.exec(
+        "tc",
+        &["qdisc", "del", "dev", namespace.interface(), "root"],
+    );
+    for command in commands {
+        let args: Vec<_> = command.iter().map(String::as_str).collect();
+        namespace.exec("tc", &args)?;
+    }
+    Ok(())
+}
+
+/// Resumes paused nodes once their pause is over, unless dropped before.
+#[derive(Debug)]
+pub(crate) struct PauseTimer {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PauseTimer {
+    /// Sends SIGCONT to `targets`, as understood by `kill`, after `duration`.
+    pub(crate) fn start(targets: Vec<String>, duration: Duration) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let thread_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            thread::sleep(duration);
+            if thread_cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            info!("Pause of {:?} is over, resuming them", targets);
+            for target in targets {
+                send_signal(&target, "CONT");
+            }
+        });
+        Self { cancelled }
+    }
+}
+
+impl Drop for PauseTimer {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SwarmCpuStress, SwarmNetworkBandwidth, SwarmNetworkDelay, SwarmNetworkLoss};
+
+    #[test]
+    fn test_tc_commands() {
+        let delay = SwarmChaos::Delay(SwarmNetworkDelay {
+            latency_ms: 100,
+            jitter_ms: 10,
+            correlation_percentage: 50,
+        });
+        let loss = SwarmChaos::Loss(SwarmNetworkLoss {
+            loss_percentage: 5,
+            correlation_percentage: 25,
+        });
+        let bandwidth = SwarmChaos::Bandwidth(SwarmNetworkBandwidth {
+            rate: 100,
+            limit: 20971520,
+            buffer: 10000,
+        });
+        let join = |commands: Vec<Vec<String>>| {
+            commands
+                .into_iter()
+                .map(|command| command.join(" "))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(tc_commands("eth0", std::iter::empty()).unwrap().is_empty());
+        assert_eq!(
+            join(tc_commands("eth0", &[delay, loss]).unwrap()),
+            vec!["qdisc add dev eth0 root handle 1: netem delay 100ms 10ms 50% loss 5% 25%"]
+        );
+        assert_eq!(
+            join(tc_commands("eth0", &[bandwidth]).unwrap()),
+            vec![
+                "qdisc add dev eth0 root handle 1: netem",
+                "qdisc add dev eth0 parent 1:1 handle 10: tbf rate 100mbit burst 10000 limit 20971520",
+            ]
+        );
+        assert!(tc_commands(
+            "eth0",
+            &[SwarmChaos::Cpu(SwarmCpuStress {
+                num_workers: 1,
+                load_percentage: 50
+            })]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runs the same upgrade-under-load scenario for every ordered pair of versions: old
+//! validators with new fullnodes, a rolling upgrade of the validators, then a downgrade
+//! attempt, and reports the outcome of every pair as a compatibility matrix.
+
+use crate::{
+    emit_transactions_with_swarm,
+    interface::swarm::{emit_clients, emitter_for},
+    ActiveNodesGuard, EmitJobMode, EmitJobRequest, LocalFactory, LocalSwarm, Node, NodeExt, Result,
+    Swarm, SwarmExt, TxnStats, Version,
+};
+use anyhow::ensure;
+use aptos_config::config::NodeConfig;
+use aptos_logger::{info, warn};
+use aptos_sdk::types::{chain_id::ChainId, PeerId};
+use rand::rngs::OsRng;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug)]
+pub struct CompatibilityMatrixConfig {
+    pub num_validators: NonZeroUsize,
+    /// How long load is emitted for at every step
+    pub load_duration: Duration,
+    /// How long a node has to become healthy once upgraded or downgraded
+    pub upgrade_timeout: Duration,
+}
+
+impl Default for CompatibilityMatrixConfig {
+    fn default() -> Self {
+        Self {
+            num_validators: NonZeroUsize::new(4).unwrap(),
+            load_duration: Duration::from_secs(20),
+            upgrade_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl CompatibilityMatrixConfig {
+    pub fn with_num_validators(mut self, num_validators: NonZeroUsize) -> Self {
+        self.num_validators = num_validators;
+        self
+    }
+
+    pub fn with_load_duration(mut self, load_duration: Duration) -> Self {
+        self.load_duration = load_duration;
+        self
+    }
+
+    pub fn with_upgrade_timeout(mut self, upgrade_timeout: Duration) -> Self {
+        self.upgrade_timeout = upgrade_timeout;
+        self
+    }
+}
+
+/// A step of the scenario run for a pair of versions, with the stats of its load.
+#[derive(Debug)]
+pub struct CompatibilityStep {
+    pub name: &'static str,
+    pub result: std::result::Result<TxnStats, String>,
+}
+
+/// Outcome of the scenario for a pair of versions. The steps stop at the first failure, the
+/// downgrade attempt aside, which is only reported.
+#[derive(Debug, Default)]
+pub struct PairOutcome {
+    pub steps: Vec<CompatibilityStep>,
+}
+
+const LAUNCH: &str = "launch";
+const MIXED_FULLNODES: &str = "mixed-fullnodes";
+const ROLLING_UPGRADE: &str = "rolling-upgrade";
+const DOWNGRADE: &str = "downgrade";
+
+impl PairOutcome {
+    fn record(&mut self, name: &'static str, result: Result<TxnStats>) -> bool {
+        if let Err(e) = &result {
+            warn!("Compatibility step {} failed: {:#}", name, e);
+        }
+        let ok = result.is_ok();
+        self.steps.push(CompatibilityStep {
+            name,
+            result: result.map_err(|e| format!("{:#}", e)),
+        });
+        ok
+    }
+
+    fn step(&self, name: &str) -> Option<&CompatibilityStep> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+
+    /// Whether new fullnodes ran with old validators and the rolling upgrade went through.
+    pub fn is_compatible(&self) -> bool {
+        [MIXED_FULLNODES, ROLLING_UPGRADE]
+            .iter()
+            .all(|name| matches!(self.step(name), Some(step) if step.result.is_ok()))
+    }
+
+    pub fn can_downgrade(&self) -> bool {
+        matches!(self.step(DOWNGRADE), Some(step) if step.result.is_ok())
+    }
+}
+
+/// Outcomes by (old, new) version. Displayed as a grid of old versions by new versions, "ok"
+/// for compatible pairs, "ok*" if the downgrade failed, followed by the failed steps.
+#[derive(Debug, Default)]
+pub struct CompatibilityMatrix {
+    pub versions: Vec<Version>,
+    pub pairs: BTreeMap<(Version, Version), PairOutcome>,
+}
+
+impl CompatibilityMatrix {
+    pub fn is_compatible(&self) -> bool {
+        self.pairs.values().all(PairOutcome::is_compatible)
+    }
+}
+
+impl fmt::Display for CompatibilityMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .versions
+            .iter()
+            .map(|v| v.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max(9);
+        write!(f, "{:width$}", "old \\ new", width = width)?;
+        for new in &self.versions {
+            write!(f, " | {:width$}", new.to_string(), width = width)?;
+        }
+        writeln!(f)?;
+        for old in &self.versions {
+            write!(f, "{:width$}", old.to_string(), width = width)?;
+            for new in &self.versions {
+                let cell = match self.pairs.get(&(old.clone(), new.clone())) {
+                    None => "-",
+                    Some(outcome) if !outcome.is_compatible() => "FAIL",
+                    Some(outcome) if !outcome.can_downgrade() => "ok*",
+                    Some(_) => "ok",
+                };
+                write!(f, " | {:width$}", cell, width = width)?;
+            }
+            writeln!(f)?;
+        }
+        for ((old, new), outcome) in &self.pairs {
+            for step in &outcome.steps {
+                if let Err(e) = &step.result {
+                    writeln!(f, "{} -> {}: {} failed: {}", old, new, step.name, e)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the scenario for every ordered pair of distinct `versions`, each on its own swarm.
+/// Failures are reported in the matrix, see `CompatibilityMatrix::is_compatible`.
+pub async fn run_compatibility_matrix(
+    factory: &LocalFactory,
+    versions: &[Version],
+    config: &CompatibilityMatrixConfig,
+) -> CompatibilityMatrix {
+    let mut matrix = CompatibilityMatrix {
+        versions: versions.to_vec(),
+        pairs: BTreeMap::new(),
+    };
+    for old in versions {
+        for new in versions.iter().filter(|new| *new != old) {
+            info!("Checking the compatibility of {} -> {}", old, new);
+            let outcome = run_pair(factory, old, new, config).await;
+            matrix.pairs.insert((old.clone(), new.clone()), outcome);
+        }
+    }
+    matrix
+}
+
+async fn run_pair(
+    factory: &LocalFactory,
+    old: &Version,
+    new: &Version,
+    config: &CompatibilityMatrixConfig,
+) -> PairOutcome {
+    let mut outcome = PairOutcome::default();
+    let mut swarm = match factory
+        .new_swarm_with_version(
+            OsRng,
+            config.num_validators,
+            0,
+            old,
+            None,
+            ChainId::test(),
+            None,
+            None,
+            None,
+            ActiveNodesGuard::unguarded(),
+        )
+        .await
+    {
+        Ok(swarm) => swarm,
+        Err(e) => {
+            outcome.record(LAUNCH, Err(e));
+            return outcome;
+        }
+    };
+
+    if outcome.record(
+        MIXED_FULLNODES,
+        mixed_fullnodes(&mut swarm, new, config).await,
+    ) && outcome.record(
+        ROLLING_UPGRADE,
+        rolling_upgrade(&mut swarm, new, config).await,
+    ) {
+        outcome.record(DOWNGRADE, downgrade(&mut swarm, old, config).await);
+    }
+    outcome
+}
+
+fn load_request() -> EmitJobRequest {
+    EmitJobRequest::default()
+        .gas_price(1)
+        .mode(EmitJobMode::ConstTps { tps: 20 })
+}
+
+fn check_committed(stats: TxnStats) -> Result<TxnStats> {
+    ensure!(stats.committed > 0, "No transaction committed: {}", stats);
+    Ok(stats)
+}
+
+/// A fullnode at `new` next to every validator, with load submitted to the fullnodes.
+async fn mixed_fullnodes(
+    swarm: &mut LocalSwarm,
+    new: &Version,
+    config: &CompatibilityMatrixConfig,
+) -> Result<TxnStats> {
+    let validators = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
+    let mut fullnodes = vec![];
+    for validator in validators {
+        fullnodes.push(swarm.add_validator_fullnode(
+            new,
+            NodeConfig::default_for_validator_full_node(),
+            validator,
+        )?);
+    }
+    swarm.wait_all_alive(config.upgrade_timeout).await?;
+    check_committed(
+        emit_transactions_with_swarm(swarm, &fullnodes, load_request(), config.load_duration)
+            .await?,
+    )
+}
+
+/// Upgrades the validators to `new` one at a time while load runs against every node.
+async fn rolling_upgrade(
+    swarm: &mut LocalSwarm,
+    new: &Version,
+    config: &CompatibilityMatrixConfig,
+) -> Result<TxnStats> {
+    let validators = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
+    let nodes = validators
+        .iter()
+        .copied()
+        .chain(swarm.full_nodes().map(|n| n.peer_id()))
+        .collect::<Vec<_>>();
+    let clients = emit_clients(swarm, &nodes)?;
+    let chain_info = swarm.chain_info();
+    let mut emitter = emitter_for(&chain_info)?;
+    let job = emitter
+        .start_job(
+            chain_info.root_account,
+            load_request().rest_clients(clients),
+        )
+        .await?;
+
+    let mut upgrade = Ok(());
+    for validator in validators {
+        upgrade = upgrade_node(swarm, validator, new, config.upgrade_timeout).await;
+        if upgrade.is_err() {
+            break;
+        }
+    }
+    if upgrade.is_ok() {
+        tokio::time::sleep(config.load_duration).await;
+    }
+    let stats = emitter.stop_job(job).await;
+    upgrade?;
+    swarm
+        .check_state_consistency(&[], config.upgrade_timeout)
+        .await?;
+    check_committed(stats)
+}
+
+/// Moves the first validator back to `old`, with load submitted to the validators.
+async fn downgrade(
+    swarm: &mut LocalSwarm,
+    old: &Version,
+    config: &CompatibilityMatrixConfig,
+) -> Result<TxnStats> {
+    let validators = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
+    upgrade_node(swarm, validators[0], old, config.upgrade_timeout).await?;
+    let stats =
+        emit_transactions_with_swarm(swarm, &validators, load_request(), config.load_duration)
+            .await?;
+    swarm
+        .check_state_consistency(&[], config.upgrade_timeout)
+        .await?;
+    check_committed(stats)
+}
+
+async fn upgrade_node(
+    swarm: &mut LocalSwarm,
+    id: PeerId,
+    version: &Version,
+    timeout: Duration,
+) -> Result<()> {
+    swarm.upgrade_validator(id, version).await?;
+    swarm
+        .validator_mut(id)
+        .expect("Upgraded validator must exist")
+        .wait_until_healthy(Instant::now() + timeout)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compatibility_matrix() {
+        let old = Version::new(0, "v1".to_string());
+        let new = Version::new(1, "v2".to_string());
+        let mut compatible = PairOutcome::default();
+        compatible.record(MIXED_FULLNODES, Ok(TxnStats::default()));
+        compatible.record(ROLLING_UPGRADE, Ok(TxnStats::default()));
+        compatible.record(DOWNGRADE, Err(anyhow::anyhow!("stuck")));
+        let mut incompatible = PairOutcome::default();
+        incompatible.record(MIXED_FULLNODES, Err(anyhow::anyhow!("no sync")));
+
+        let mut matrix = CompatibilityMatrix {
+            versions: vec![old.clone(), new.clone()],
+            pairs: BTreeMap::new(),
+        };
+        matrix.pairs.insert((old.clone(), new.clone()), compatible);
+        assert!(matrix.is_compatible());
+        matrix.pairs.insert((new, old), incompatible);
+        assert!(!matrix.is_compatible());
+
+        let report = matrix.to_string();
+        let rows: Vec<_> = report.lines().collect();
+        assert_eq!(rows[1], "v1        | -         | ok*      ");
+        assert_eq!(rows[2], "v2        | FAIL      | -        ");
+        assert!(report.contains("v1 -> v2: downgrade failed: stuck"));
+        assert!(report.contains("v2 -> v1: mixed-fullnodes failed: no sync"));
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const CORE_PATTERN_PATH: &str = "/proc/sys/kernel/core_pattern";
+
+/// Wraps `command` so that it runs without a core file size limit.
+pub fn with_core_limit(command: Command) -> Command {
+    let mut wrapped = Command::new("sh");
+    wrapped
+        .arg("-c")
+        .arg("ulimit -c unlimited && exec \"$@\"")
+        .arg("sh")
+        .arg(command.get_program())
+        .args(command.get_args());
+    wrapped
+}
+
+/// Finds the core dump written for the crashed process `pid`, and moves it to `to_dir`. Core
+/// files written directly by the kernel are looked up according to the system's core_pattern
+/// (relative patterns are relative to `working_dir`), and systemd-coredump is asked for the
+/// ones it captured.
+pub fn collect_core_dump(pid: u32, working_dir: &Path, to_dir: &Path) -> Result<PathBuf> {
+    let pattern = fs::read_to_string(CORE_PATTERN_PATH)
+        .with_context(|| format!("Failed to read {}", CORE_PATTERN_PATH))?;
+    let pattern = pattern.trim();
+    fs::create_dir_all(to_dir)?;
+    let destination = to_dir.join(format!("core.{}", pid));
+
+    if let Some(handler) = pattern.strip_prefix('|') {
+        if !handler.contains("systemd-coredump") {
+            bail!("Unsupported core dump handler: {}", handler);
+        }
+        let output = Command::new("coredumpctl")
+            .arg("dump")
+            .arg(pid.to_string())
+            .arg("--output")
+            .arg(&destination)
+            .output()
+            .context("Failed to run coredumpctl")?;
+        if !output.status.success() {
+            bail!(
+                "coredumpctl has no core dump for {}: {}",
+                pid,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        return Ok(destination);
+    }
+
+    let pattern = working_dir.join(pattern);
+    let dir = pattern.parent().unwrap_or(working_dir);
+    let file_pattern = pattern
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let prefix = file_pattern.split('%').next().unwrap_or_default();
+    let core = find_core_file(dir, prefix, pid)?;
+    // Cores can be large and on another filesystem, fall back to copying.
+    if fs::rename(&core, &destination).is_err() {
+        fs::copy(&core, &destination)?;
+        fs::remove_file(&core)?;
+    }
+    Ok(destination)
+}
+
+/// Picks the most recent file starting with `prefix`, preferring the ones naming `pid`.
+fn find_core_file(dir: &Path, prefix: &str, pid: u32) -> Result<PathBuf> {
+    let pid = pid.to_string();
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let names_pid = name
+            .split(|c: char| !c.is_ascii_digit())
+            .any(|part| part == pid);
+        // Without a literal prefix, only the pid tells cores apart from other files.
+        if !name.starts_with(prefix)
+            || (prefix.is_empty() && !names_pid)
+            || !entry.file_type()?.is_file()
+        {
+            continue;
+        }
+        candidates.push((names_pid, entry.metadata()?.modified()?, entry.path()));
+    }
+    candidates.sort();
+    match candidates.pop() {
+        Some((_, _, path)) => Ok(path),
+        None => bail!("No core file found in {}", dir.display()),
+    }
+}
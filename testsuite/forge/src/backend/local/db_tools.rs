@@ -0,0 +1,148 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use super::cargo;
+use crate::Result;
+use anyhow::{bail, Context};
+use aptos_logger::info;
+use backup_cli::metadata::view::BackupStorageState;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output},
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+/// How long a backup may take to catch up with the node.
+const BACKUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The db-backup and db-restore binaries used to back up and restore LocalNode databases.
+#[derive(Clone, Debug)]
+pub struct DbTools {
+    backup_bin: PathBuf,
+    restore_bin: PathBuf,
+}
+
+impl DbTools {
+    pub fn new(backup_bin: PathBuf, restore_bin: PathBuf) -> Self {
+        Self {
+            backup_bin,
+            restore_bin,
+        }
+    }
+
+    /// Builds the tools from the current workspace.
+    pub fn from_workspace() -> Result<Self> {
+        Ok(Self::new(
+            cargo::get_bin_from_worktree("db-backup")?,
+            cargo::get_bin_from_worktree("db-restore")?,
+        ))
+    }
+
+    /// Backs up the node behind `backup_service_address` to `dest`, until the backup covers
+    /// `epoch` and `version`.
+    pub async fn backup(
+        &self,
+        backup_service_address: SocketAddr,
+        dest: &Path,
+        epoch: u64,
+        version: u64,
+    ) -> Result<()> {
+        let coordinator_cache = TempDir::new()?;
+        let query_cache = TempDir::new()?;
+        let mut coordinator = KillOnDrop(
+            Command::new(&self.backup_bin)
+                .arg("coordinator")
+                .arg("run")
+                .arg("--backup-service-address")
+                .arg(format!("http://{}", backup_service_address))
+                .arg("--metadata-cache-dir")
+                .arg(coordinator_cache.path())
+                .arg("local-fs")
+                .arg("--dir")
+                .arg(dest)
+                .spawn()
+                .context("Failed to spawn the backup coordinator")?,
+        );
+
+        let deadline = Instant::now() + BACKUP_TIMEOUT;
+        loop {
+            if let Some(status) = coordinator.0.try_wait()? {
+                bail!("Backup coordinator exited with {}", status);
+            }
+            let output = Command::new(&self.backup_bin)
+                .args(&["one-shot", "query", "backup-storage-state"])
+                .arg("--metadata-cache-dir")
+                .arg(query_cache.path())
+                .arg("local-fs")
+                .arg("--dir")
+                .arg(dest)
+                .output()
+                .context("Failed to query the backup storage state")?;
+            let state: BackupStorageState = check_output("db-backup", &output)?.parse()?;
+            // Epochs before the current one have ended, the current one is still ongoing.
+            let epochs_covered = epoch == 0
+                || state
+                    .latest_epoch_ending_epoch
+                    .map_or(false, |e| e + 1 >= epoch);
+            if epochs_covered
+                && state.latest_state_snapshot_epoch.is_some()
+                && state
+                    .latest_transaction_version
+                    .map_or(false, |v| v >= version)
+            {
+                info!("Backup to {:?} done: {}", dest, state);
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                bail!(
+                    "Backup didn't reach epoch {} and version {} in time: {}",
+                    epoch,
+                    version,
+                    state
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Restores the backup in `src` into the (empty) database directory `db_dir`.
+    pub fn restore(&self, src: &Path, db_dir: &Path) -> Result<()> {
+        let metadata_cache = TempDir::new()?;
+        let output = Command::new(&self.restore_bin)
+            .arg("--target-db-dir")
+            .arg(db_dir)
+            .arg("auto")
+            .arg("--metadata-cache-dir")
+            .arg(metadata_cache.path())
+            .arg("local-fs")
+            .arg("--dir")
+            .arg(src)
+            .output()
+            .context("Failed to run db-restore")?;
+        check_output("db-restore", &output)?;
+        Ok(())
+    }
+}
+
+fn check_output<'a>(tool: &str, output: &'a Output) -> Result<&'a str> {
+    if !output.status.success() {
+        bail!(
+            "{} failed with {}: {}",
+            tool,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(std::str::from_utf8(&output.stdout)?)
+}
+
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
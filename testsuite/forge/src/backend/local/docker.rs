@@ -0,0 +1,82 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use super::cgroup::ResourceLimits;
+use anyhow::{bail, Context, Result};
+use std::{env, path::Path, process::Command, time::Duration};
+
+/// Where the aptos-node binary is in the released images.
+pub const DEFAULT_IMAGE_BIN: &str = "/usr/local/bin/aptos-node";
+
+/// Builds the command running `bin` from `image` in the container `name`, arguments for `bin`
+/// can be appended to it. The container uses the host network and has `node_dir` mounted at
+/// the same path, so that the node config works as is.
+pub fn run_command(
+    image: &str,
+    bin: &Path,
+    name: &str,
+    node_dir: &Path,
+    limits: Option<&ResourceLimits>,
+) -> Command {
+    let mut command = Command::new("docker");
+    command
+        .arg("run")
+        .arg("--rm")
+        .arg("--name")
+        .arg(name)
+        .arg("--network")
+        .arg("host")
+        .arg("--volume")
+        .arg(format!("{0}:{0}", node_dir.display()))
+        .arg("--workdir")
+        .arg(node_dir)
+        .arg("--env")
+        .arg(format!(
+            "RUST_LOG={}",
+            env::var("RUST_LOG").unwrap_or_else(|_| "debug".to_string())
+        ));
+    // Keep the files written by the node owned by the user running forge.
+    #[cfg(unix)]
+    if let Ok(metadata) = node_dir.metadata() {
+        use std::os::unix::fs::MetadataExt;
+        command
+            .arg("--user")
+            .arg(format!("{}:{}", metadata.uid(), metadata.gid()));
+    }
+    if let Some(limits) = limits {
+        command.args(limits.docker_args());
+    }
+    command.arg("--entrypoint").arg(bin).arg(image);
+    command
+}
+
+/// Stops and removes the container `name`. If a grace period is given, the container is sent
+/// SIGTERM and only killed if it didn't exit by then.
+pub fn remove_container(name: &str, grace_period: Option<Duration>) -> Result<()> {
+    if let Some(grace_period) = grace_period {
+        // Removed by docker once stopped, as it is run with --rm.
+        let stopped = Command::new("docker")
+            .arg("stop")
+            .arg("--time")
+            .arg(grace_period.as_secs().to_string())
+            .arg(name)
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        if stopped {
+            return Ok(());
+        }
+    }
+    let output = Command::new("docker")
+        .args(&["rm", "--force", name])
+        .output()
+        .context("Failed to run docker, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to remove container {}: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! On-chain framework upgrades through governance: every package of a release bundle is
+//! proposed by a validator, voted for by all of them, and published by resolving the proposal.
+
+use crate::Result;
+use anyhow::{anyhow, bail, Context};
+use aptos_logger::info;
+use aptos_rest_client::{Client as RestClient, Transaction};
+use aptos_sdk::{
+    crypto::HashValue,
+    move_types::transaction_argument::TransactionArgument,
+    transaction_builder::{aptos_stdlib, TransactionFactory},
+    types::{
+        transaction::{Script, TransactionPayload},
+        LocalAccount,
+    },
+};
+use framework::{BuildOptions, BuiltPackage, ReleaseBundle, ReleasePackage};
+use move_deps::move_binary_format::access::ModuleAccess;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+/// Publishing a whole package is expensive.
+const UPGRADE_MAX_GAS_AMOUNT: u64 = 2_000_000;
+
+/// Coins minted to the validators so they can pay for proposing and voting.
+const VOTER_FUNDS: u64 = 100_000_000;
+
+const CREATE_PROPOSAL_EVENT: &str = "0x1::aptos_governance::CreateProposalEvent";
+
+/// Upgrades the framework to `bundle`, one package at a time, with `validators` proposing and
+/// voting. The root account funds the validators and resolves the proposals.
+///
+/// A proposal can usually only be resolved once its voting period is over, as the validators
+/// hold too small a part of the coin supply of a test chain to resolve it early. The genesis
+/// should then use a short `voting_duration_secs`, that has to end before `deadline`.
+pub async fn upgrade_framework(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    root_account: &mut LocalAccount,
+    validators: &mut [LocalAccount],
+    bundle: &ReleaseBundle,
+    deadline: Instant,
+) -> Result<()> {
+    if validators.is_empty() {
+        bail!("No validator to propose the framework upgrade");
+    }
+    for validator in validators.iter_mut() {
+        submit(
+            client,
+            transaction_factory,
+            root_account,
+            aptos_stdlib::aptos_coin_mint(validator.address(), VOTER_FUNDS),
+        )
+        .await?;
+        *validator.sequence_number_mut() = client
+            .get_account(validator.address())
+            .await?
+            .into_inner()
+            .sequence_number;
+    }
+
+    for package in &bundle.packages {
+        let script = compile_upgrade_script(package)?;
+        let proposal_id = propose(client, transaction_factory, &mut validators[0], &script)
+            .await
+            .with_context(|| format!("Failed to propose the upgrade of {}", package.name()))?;
+        for validator in validators.iter_mut() {
+            let stake_pool = validator.address();
+            submit(
+                client,
+                transaction_factory,
+                validator,
+                aptos_stdlib::aptos_governance_vote(stake_pool, proposal_id, true),
+            )
+            .await?;
+        }
+        resolve(
+            client,
+            transaction_factory,
+            root_account,
+            script,
+            proposal_id,
+            deadline,
+        )
+        .await
+        .with_context(|| format!("Failed to upgrade {}", package.name()))?;
+        info!(
+            "Upgraded framework package {} through proposal {}",
+            package.name(),
+            proposal_id
+        );
+    }
+    Ok(())
+}
+
+/// Compiles the governance script publishing `package`.
+pub fn compile_upgrade_script(package: &ReleasePackage) -> Result<Vec<u8>> {
+    let address = *package.compiled_module_at(0)?.self_id().address();
+    let dir = tempfile::TempDir::new()?;
+    fs::write(
+        dir.path().join("Move.toml"),
+        format!(
+            "[package]\nname = \"Upgrade\"\nversion = \"1.0.0\"\n\n[dependencies]\n\
+             AptosFramework = {{ local = \"{}\" }}\n",
+            framework::path_in_crate("aptos-framework").display()
+        ),
+    )?;
+    let sources = dir.path().join("sources");
+    fs::create_dir_all(&sources)?;
+    package.generate_script_proposal(address, sources.join("upgrade.move"))?;
+    let built = BuiltPackage::build(dir.path().to_path_buf(), BuildOptions::default())?;
+    built
+        .extract_script_code()
+        .pop()
+        .ok_or_else(|| anyhow!("No script compiled for package {}", package.name()))
+}
+
+/// Proposes the execution of `script`, returns the id of the proposal.
+async fn propose(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    proposer: &mut LocalAccount,
+    script: &[u8],
+) -> Result<u64> {
+    let execution_hash = HashValue::sha3_256_of(script);
+    let metadata_location = b"forge-framework-upgrade".to_vec();
+    let metadata_hash = HashValue::sha3_256_of(&metadata_location).to_hex();
+    let stake_pool = proposer.address();
+    let transaction = submit(
+        client,
+        transaction_factory,
+        proposer,
+        aptos_stdlib::aptos_governance_create_proposal(
+            stake_pool,
+            execution_hash.to_vec(),
+            metadata_location,
+            metadata_hash.into_bytes(),
+        ),
+    )
+    .await?;
+    let events = match transaction {
+        Transaction::UserTransaction(transaction) => transaction.events,
+        _ => bail!("Proposal is not a user transaction"),
+    };
+    let event = events
+        .into_iter()
+        .find(|event| event.typ.to_string() == CREATE_PROPOSAL_EVENT)
+        .ok_or_else(|| anyhow!("No {} emitted", CREATE_PROPOSAL_EVENT))?;
+    event
+        .data
+        .get("proposal_id")
+        .and_then(|id| id.as_str())
+        .ok_or_else(|| anyhow!("No proposal_id in {}", event.data))?
+        .parse()
+        .map_err(Into::into)
+}
+
+/// Executes `script` to resolve the proposal, retrying until its voting period is over.
+async fn resolve(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    account: &mut LocalAccount,
+    script: Vec<u8>,
+    proposal_id: u64,
+    deadline: Instant,
+) -> Result<()> {
+    let payload = TransactionPayload::Script(Script::new(
+        script,
+        vec![],
+        vec![TransactionArgument::U64(proposal_id)],
+    ));
+    loop {
+        match submit(client, transaction_factory, account, payload.clone()).await {
+            Ok(_) => return Ok(()),
+            Err(e) if Instant::now() > deadline => {
+                return Err(e.context(format!(
+                    "Proposal {} not resolved in time, is the voting duration short enough?",
+                    proposal_id
+                )))
+            }
+            Err(e) => {
+                info!("Proposal {} not resolvable yet: {:#}", proposal_id, e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Submits `payload` and waits for it to be executed successfully. The sequence number of
+/// `account` is kept in sync with the chain even if it fails.
+pub(super) async fn submit(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    account: &mut LocalAccount,
+    payload: TransactionPayload,
+) -> Result<Transaction> {
+    let txn = account.sign_with_transaction_builder(
+        transaction_factory
+            .clone()
+            .with_max_gas_amount(UPGRADE_MAX_GAS_AMOUNT)
+            .payload(payload),
+    );
+    match client.submit_and_wait(&txn).await {
+        Ok(transaction) => Ok(transaction.into_inner()),
+        Err(e) => {
+            *account.sequence_number_mut() = client
+                .get_account(account.address())
+                .await?
+                .into_inner()
+                .sequence_number;
+            Err(e.into())
+        }
+    }
+}
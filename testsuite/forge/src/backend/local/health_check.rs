@@ -0,0 +1,33 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::Duration;
+
+/// What the health check of a LocalNode probes, and how long a LocalSwarm waits for its nodes
+/// to pass it when starting up.
+#[derive(Clone, Debug)]
+pub struct HealthCheckConfig {
+    /// Number of health checks run on a starting node before giving up on it
+    pub attempts: usize,
+    /// Wait between two attempts
+    pub interval: Duration,
+    /// Check that the REST API serves the ledger info
+    pub rest: bool,
+    /// Check that metrics can be scraped from the inspection service
+    pub metrics: bool,
+    /// Check that the ledger version advanced since the previous health check of the node,
+    /// implies `rest`. The first check after the node starts only records its version.
+    pub ledger_progress: bool,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 30,
+            interval: Duration::from_secs(1),
+            rest: true,
+            metrics: true,
+            ledger_progress: false,
+        }
+    }
+}
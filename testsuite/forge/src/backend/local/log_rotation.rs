@@ -0,0 +1,169 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use aptos_logger::warn;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// When to rotate a node's log files, and how many rotated files to keep. Rotated files are
+/// named `<log>.1` (most recent) to `<log>.<retain>`.
+#[derive(Clone, Debug)]
+pub struct LogRotation {
+    /// Rotate once the file would grow past this many bytes
+    pub max_size: u64,
+    /// Rotate once the file has been written to for this long
+    pub max_age: Option<Duration>,
+    /// Number of rotated files to keep, older ones are deleted
+    pub retain: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_size: 100 * 1024 * 1024,
+            max_age: None,
+            retain: 5,
+        }
+    }
+}
+
+/// A log file that rotates itself according to a `LogRotation`.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    file: File,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, rotation: LogRotation) -> Result<Self> {
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            rotation,
+            file,
+            size,
+            opened_at: Instant::now(),
+        })
+    }
+
+    fn should_rotate(&self, incoming: usize) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        self.size + incoming as u64 > self.rotation.max_size
+            || self
+                .rotation
+                .max_age
+                .map(|max_age| self.opened_at.elapsed() >= max_age)
+                .unwrap_or(false)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.rotation.retain == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(rotated_path(&self.path, self.rotation.retain));
+            for index in (1..self.rotation.retain).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}", index));
+    path.with_file_name(file_name)
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Copies everything read from `reader` (e.g. a child's stdout) into `file` on a background
+/// thread, until `reader` is closed.
+pub fn spawn_log_writer<R: Read + Send + 'static>(mut reader: R, mut file: RotatingFile) {
+    thread::spawn(move || {
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => {
+                    if let Err(e) = file.write_all(&buf[..read]) {
+                        warn!("Failed to write to log {:?}: {}", file.path, e);
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+        let _ = file.flush();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_keeps_retained_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let rotation = LogRotation {
+            max_size: 10,
+            max_age: None,
+            retain: 2,
+        };
+        let mut file = RotatingFile::open(path.clone(), rotation).unwrap();
+        for line in ["first line\n", "second line\n", "third line\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "third line\n"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+            "second line\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Async tailing of the log files of local nodes, which follows them across rotations and
+//! restarts. Files are polled rather than watched, as their writers are other processes, and
+//! read on the blocking threads of the runtime.
+
+use futures::{stream, Stream};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// How often a log file is checked for new lines
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Lines appended to the file at `path` from now on, as `tail -F` would print them, without
+/// end. Lines are only yielded once complete.
+pub(crate) fn tail_lines(path: PathBuf) -> impl Stream<Item = String> + Send + Unpin {
+    Box::pin(stream::unfold(LogTail::new(path), |mut tail| async move {
+        loop {
+            if let Some(line) = tail.lines.pop_front() {
+                return Some((line, tail));
+            }
+            // Ends the stream if polling panicked
+            let (polled, result) = tokio::task::spawn_blocking(move || {
+                let result = tail.poll();
+                (tail, result)
+            })
+            .await
+            .ok()?;
+            tail = polled;
+            // Transient failures, e.g. the file being rotated, are retried at the next poll
+            if result.is_err() || tail.lines.is_empty() {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }))
+}
+
+#[derive(Debug)]
+struct LogTail {
+    path: PathBuf,
+    /// The file being read, which may have been rotated away from `path` since
+    file: Option<File>,
+    file_id: u64,
+    position: u64,
+    /// Bytes read past the last complete line
+    partial: Vec<u8>,
+    lines: VecDeque<String>,
+}
+
+impl LogTail {
+    /// Starts at the end of the file at `path`, or at the start of the file created there
+    /// next if there is none.
+    fn new(path: PathBuf) -> Self {
+        let mut tail = Self {
+            path,
+            file: None,
+            file_id: 0,
+            position: 0,
+            partial: vec![],
+            lines: VecDeque::new(),
+        };
+        if let Ok(mut file) = File::open(&tail.path) {
+            if let (Ok(metadata), Ok(position)) = (file.metadata(), file.seek(SeekFrom::End(0))) {
+                tail.file_id = file_id(&metadata);
+                tail.position = position;
+                tail.file = Some(file);
+            }
+        }
+        tail
+    }
+
+    /// Reads the lines appended since the last poll. Once the file was replaced, e.g. rotated,
+    /// the rest of the old one is read before moving on to the new one.
+    fn poll(&mut self) -> io::Result<()> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return self.read_appended(),
+            Err(e) => return Err(e),
+        };
+        if self.file.is_none() || file_id(&metadata) != self.file_id {
+            self.read_appended()?;
+            self.partial.clear();
+            self.file = Some(File::open(&self.path)?);
+            self.file_id = file_id(&metadata);
+            self.position = 0;
+        } else if metadata.len() < self.position {
+            // Truncated, or replaced by a shorter file where files have no id, what's left is new
+            self.partial.clear();
+            self.file = Some(File::open(&self.path)?);
+            self.position = 0;
+        }
+        self.read_appended()
+    }
+
+    fn read_appended(&mut self) -> io::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        self.position += file.read_to_end(&mut self.partial)? as u64;
+        while let Some(end) = self.partial.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<_> = self.partial.drain(..=end).collect();
+            self.lines
+                .push_back(String::from_utf8_lossy(&line[..end]).into_owned());
+        }
+        Ok(())
+    }
+}
+
+/// Tells apart the files found at the same path, e.g. before and after a rotation.
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+/// Without inodes, files are told apart by their creation time, where the system records it.
+/// Otherwise, a replaced file is only noticed once it is shorter than what was read.
+#[cfg(not(unix))]
+fn file_id(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .created()
+        .ok()
+        .and_then(|created| created.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |created| created.as_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use std::{fs::OpenOptions, io::Write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_log_tail() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log");
+        let append = |text: &str| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap()
+                .write_all(text.as_bytes())
+                .unwrap()
+        };
+        fn polled(tail: &mut LogTail) -> Vec<String> {
+            tail.poll().unwrap();
+            tail.lines.drain(..).collect()
+        }
+
+        append("before\n");
+        let mut tail = LogTail::new(path.clone());
+        append("first\nsecond");
+        assert_eq!(polled(&mut tail), vec!["first"]);
+        append(" half\n");
+        assert_eq!(polled(&mut tail), vec!["second half"]);
+
+        // Rotated, with a last line written to the old file
+        append("last\n");
+        fs::rename(&path, dir.path().join("log.1")).unwrap();
+        append("rotated\n");
+        assert_eq!(polled(&mut tail), vec!["last", "rotated"]);
+
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(polled(&mut tail), vec!["new"]);
+    }
+
+    #[tokio::test]
+    async fn test_tail_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log");
+        fs::write(&path, "before\n").unwrap();
+        let mut lines = tail_lines(path.clone());
+        let timeout = Duration::from_secs(10);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"appended\n").unwrap();
+        let line = tokio::time::timeout(timeout, lines.next()).await.unwrap();
+        assert_eq!(line.unwrap(), "appended");
+
+        fs::rename(&path, dir.path().join("log.1")).unwrap();
+        fs::write(&path, "rotated\n").unwrap();
+        let line = tokio::time::timeout(timeout, lines.next()).await.unwrap();
+        assert_eq!(line.unwrap(), "rotated");
+    }
+}
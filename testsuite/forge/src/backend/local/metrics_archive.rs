@@ -0,0 +1,277 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Archives of the metrics of a node, scraped periodically while it runs. An archive is a
+//! gzip file made of one gzip member per scrape, so that a run killed mid-way still leaves a
+//! readable archive. Every scrape is stored as `# scrape <unix millis>` followed by the
+//! metrics in the Prometheus text format, as served by the node.
+//!
+//! `export_openmetrics` converts archives to the OpenMetrics format, which can be imported
+//! into a Prometheus with `promtool tsdb create-blocks-from openmetrics`.
+
+use crate::{MetricType, Metrics};
+use anyhow::{format_err, Context, Result};
+use aptos_infallible::Mutex;
+use aptos_logger::warn;
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+const SCRAPE_HEADER: &str = "# scrape ";
+
+#[derive(Debug, Default)]
+struct RecorderState {
+    /// Metrics endpoint of the node, recording pauses while the node isn't running
+    url: Option<Url>,
+    /// Scrapes that failed while the node was meant to be running, left as gaps in the archive
+    failures: u64,
+    stopped: bool,
+}
+
+/// Records the metrics of a node to an archive on a background thread, until dropped.
+#[derive(Debug)]
+pub struct MetricsRecorder {
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl MetricsRecorder {
+    /// Appends a scrape of the node's metrics to the archive at `path` every `interval`.
+    pub fn start(path: PathBuf, interval: Duration) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let state = Arc::new(Mutex::new(RecorderState::default()));
+        let thread_state = state.clone();
+        let client = reqwest::blocking::Client::builder()
+            .timeout(interval)
+            .build()?;
+        thread::spawn(move || loop {
+            let url = {
+                let state = thread_state.lock();
+                if state.stopped {
+                    break;
+                }
+                state.url.clone()
+            };
+            if let Some(url) = url {
+                let scrape = client
+                    .get(url)
+                    .send()
+                    .and_then(|response| response.error_for_status())
+                    .and_then(|response| response.text());
+                // The node may be restarting, scrapes are only best effort.
+                match scrape {
+                    Ok(text) => {
+                        if let Err(e) = append_scrape(&path, SystemTime::now(), &text) {
+                            warn!("Failed to record metrics to {}: {}", path.display(), e);
+                        }
+                    }
+                    Err(_) => thread_state.lock().failures += 1,
+                }
+            }
+            thread::sleep(interval);
+        });
+        Ok(Self { state })
+    }
+
+    pub fn set_url(&self, url: Option<Url>) {
+        self.state.lock().url = url;
+    }
+
+    /// Number of scrapes that failed so far.
+    pub fn failures(&self) -> u64 {
+        self.state.lock().failures
+    }
+}
+
+impl Drop for MetricsRecorder {
+    fn drop(&mut self) {
+        self.state.lock().stopped = true;
+    }
+}
+
+fn append_scrape(path: &Path, time: SystemTime, text: &str) -> Result<()> {
+    let millis = time.duration_since(UNIX_EPOCH)?.as_millis();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    writeln!(encoder, "{}{}", SCRAPE_HEADER, millis)?;
+    encoder.write_all(text.as_bytes())?;
+    let member = encoder.finish()?;
+    // A single write, so that the archive only ever misses whole scrapes.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&member)?;
+    Ok(())
+}
+
+/// Reads the scrapes of an archive, oldest first.
+pub fn read_metrics_archive(path: &Path) -> Result<Vec<(SystemTime, Metrics)>> {
+    let mut text = String::new();
+    MultiGzDecoder::new(File::open(path)?)
+        .read_to_string(&mut text)
+        .with_context(|| format!("Failed to read metrics archive {}", path.display()))?;
+    parse_archive(&text)
+}
+
+fn parse_archive(text: &str) -> Result<Vec<(SystemTime, Metrics)>> {
+    let mut scrapes = Vec::new();
+    for scrape in text
+        .split(SCRAPE_HEADER)
+        .filter(|scrape| !scrape.is_empty())
+    {
+        let (millis, metrics) = scrape.split_once('\n').unwrap_or((scrape, ""));
+        let millis: u64 = millis
+            .trim()
+            .parse()
+            .map_err(|e| format_err!("Invalid scrape time '{}': {}", millis, e))?;
+        scrapes.push((UNIX_EPOCH + Duration::from_millis(millis), metrics.parse()?));
+    }
+    Ok(scrapes)
+}
+
+/// Writes the scrapes of the archives of several nodes, given by node name, in the OpenMetrics
+/// format. Samples get an `instance` label with the name of their node.
+pub fn export_openmetrics(archives: &[(String, PathBuf)], out: &mut dyn Write) -> Result<()> {
+    let mut scrapes = Vec::new();
+    for (node, path) in archives {
+        scrapes.push((node.as_str(), read_metrics_archive(path)?));
+    }
+    write_openmetrics(&scrapes, out)
+}
+
+fn write_openmetrics(
+    scrapes: &[(&str, Vec<(SystemTime, Metrics)>)],
+    out: &mut dyn Write,
+) -> Result<()> {
+    // OpenMetrics doesn't allow interleaving the samples of different metric families.
+    let mut families: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut types = BTreeMap::new();
+    for (node, node_scrapes) in scrapes {
+        for (time, metrics) in node_scrapes {
+            let seconds = time.duration_since(UNIX_EPOCH)?.as_secs_f64();
+            for sample in metrics.samples() {
+                let family = family_name(metrics, &sample.name);
+                if let Some(metric_type) = metrics.metric_type(family) {
+                    types.insert(family, metric_type);
+                }
+                let mut labels = sample
+                    .labels
+                    .iter()
+                    .filter(|(key, _)| key.as_str() != "instance")
+                    .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+                    .collect::<Vec<_>>();
+                labels.sort();
+                labels.push(format!("instance=\"{}\"", escape(node)));
+                families.entry(family).or_default().push(format!(
+                    "{}{{{}}} {} {:.3}",
+                    sample.name,
+                    labels.join(","),
+                    format_value(sample.value),
+                    seconds
+                ));
+            }
+        }
+    }
+    for (family, samples) in families {
+        let metric_type = match types.get(family) {
+            Some(MetricType::Counter) => "counter",
+            Some(MetricType::Gauge) => "gauge",
+            Some(MetricType::Histogram) => "histogram",
+            Some(MetricType::Summary) => "summary",
+            Some(MetricType::Untyped) | None => "unknown",
+        };
+        writeln!(out, "# TYPE {} {}", family, metric_type)?;
+        for sample in samples {
+            writeln!(out, "{}", sample)?;
+        }
+    }
+    writeln!(out, "# EOF")?;
+    Ok(())
+}
+
+/// The metric family of the sample `name`, e.g. `x` for `x_bucket` if `x` is a histogram.
+fn family_name<'a>(metrics: &Metrics, name: &'a str) -> &'a str {
+    for suffix in ["_bucket", "_count", "_sum"] {
+        if let Some(family) = name.strip_suffix(suffix) {
+            if matches!(
+                metrics.metric_type(family),
+                Some(MetricType::Histogram) | Some(MetricType::Summary)
+            ) {
+                return family;
+            }
+        }
+    }
+    name
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.gz");
+        let time = UNIX_EPOCH + Duration::from_millis(1_660_000_000_123);
+        append_scrape(
+            &path,
+            time,
+            "# TYPE aptos_requests counter\naptos_requests{kind=\"a\"} 1\n",
+        )
+        .unwrap();
+        append_scrape(
+            &path,
+            time + Duration::from_secs(10),
+            "# TYPE aptos_latency histogram\naptos_latency_bucket{le=\"+Inf\"} 2\n\
+             aptos_latency_sum 0.5\naptos_latency_count 2\n",
+        )
+        .unwrap();
+
+        let scrapes = read_metrics_archive(&path).unwrap();
+        assert_eq!(scrapes.len(), 2);
+        assert_eq!(scrapes[0].0, time);
+        assert_eq!(
+            scrapes[0].1.get("aptos_requests", &[("kind", "a")]),
+            Some(1.0)
+        );
+
+        let mut out = Vec::new();
+        write_openmetrics(&[("0", scrapes)], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# TYPE aptos_latency histogram\n\
+             aptos_latency_bucket{le=\"+Inf\",instance=\"0\"} 2 1660000010.123\n\
+             aptos_latency_sum{instance=\"0\"} 0.5 1660000010.123\n\
+             aptos_latency_count{instance=\"0\"} 2 1660000010.123\n\
+             # TYPE aptos_requests counter\n\
+             aptos_requests{kind=\"a\",instance=\"0\"} 1 1660000000.123\n\
+             # EOF\n"
+        );
+    }
+}
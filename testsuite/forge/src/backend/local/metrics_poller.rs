@@ -0,0 +1,124 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Periodic in-memory scrapes of the nodes of a LocalSwarm, so that range functions of
+//! `query_metrics` such as `rate` have scrapes to compare to from the first query, rather than
+//! only the scrapes of earlier queries.
+
+use crate::{Metrics, MetricsSnapshot};
+use anyhow::Result;
+use aptos_infallible::Mutex;
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+#[derive(Debug, Default)]
+struct PollerState {
+    /// Metrics endpoint of every node, by node name
+    targets: BTreeMap<String, Url>,
+    stopped: bool,
+}
+
+/// Adds a snapshot of every node to a metrics history on a background thread, until dropped.
+#[derive(Debug)]
+pub struct MetricsPoller {
+    state: Arc<Mutex<PollerState>>,
+}
+
+impl MetricsPoller {
+    /// Scrapes the targets every `interval`, adding the snapshots to `history` with
+    /// `record_snapshot`.
+    pub fn start(
+        history: Arc<Mutex<Vec<MetricsSnapshot>>>,
+        retention: Duration,
+        interval: Duration,
+    ) -> Result<Self> {
+        let state = Arc::new(Mutex::new(PollerState::default()));
+        let thread_state = state.clone();
+        let client = reqwest::blocking::Client::builder()
+            .timeout(interval)
+            .build()?;
+        thread::spawn(move || loop {
+            let start = Instant::now();
+            let targets = {
+                let state = thread_state.lock();
+                if state.stopped {
+                    break;
+                }
+                state.targets.clone()
+            };
+            // Nodes may be restarting, failed scrapes are left as gaps in the snapshot.
+            let snapshot = MetricsSnapshot::from_scrapes(targets.into_iter().map(|(node, url)| {
+                let scrape = client
+                    .get(url)
+                    .send()
+                    .and_then(|response| response.error_for_status())
+                    .and_then(|response| response.text())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|text| text.parse::<Metrics>());
+                (node, scrape)
+            }));
+            record_snapshot(&history, snapshot, retention);
+            thread::sleep(interval.saturating_sub(start.elapsed()));
+        });
+        Ok(Self { state })
+    }
+
+    /// Scrapes the node `name` at `url`, which replaces its previous endpoint if any.
+    pub fn set_target(&self, name: &str, url: Url) {
+        self.state.lock().targets.insert(name.to_string(), url);
+    }
+
+    pub fn remove_target(&self, name: &str) {
+        self.state.lock().targets.remove(name);
+    }
+}
+
+impl Drop for MetricsPoller {
+    fn drop(&mut self) {
+        self.state.lock().stopped = true;
+    }
+}
+
+/// Adds `snapshot` to `history`, keeping it sorted oldest first and dropping the snapshots
+/// older than `retention`, and returns the resulting history.
+pub fn record_snapshot(
+    history: &Mutex<Vec<MetricsSnapshot>>,
+    snapshot: MetricsSnapshot,
+    retention: Duration,
+) -> Vec<MetricsSnapshot> {
+    let mut history = history.lock();
+    let index = history.partition_point(|earlier| earlier.time <= snapshot.time);
+    history.insert(index, snapshot);
+    let latest = history.last().expect("History can't be empty").time;
+    history.retain(|earlier| {
+        latest
+            .duration_since(earlier.time)
+            .map_or(true, |age| age <= retention)
+    });
+    history.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_snapshot() {
+        let history = Mutex::new(vec![]);
+        let snapshot = |secs_ago: u64| {
+            let mut snapshot = MetricsSnapshot::new(BTreeMap::new());
+            snapshot.time -= Duration::from_secs(secs_ago);
+            snapshot
+        };
+        record_snapshot(&history, snapshot(100), Duration::from_secs(60));
+        record_snapshot(&history, snapshot(10), Duration::from_secs(60));
+        let recorded = record_snapshot(&history, snapshot(30), Duration::from_secs(60));
+        assert_eq!(recorded.len(), 2);
+        assert!(recorded[0].time < recorded[1].time);
+    }
+}
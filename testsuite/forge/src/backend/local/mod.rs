@@ -1,26 +1,70 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{Factory, GenesisConfig, GenesisConfigFn, NodeConfigFn, Result, Swarm, Version};
+use crate::{Factory, GenesisConfig, GenesisConfigFn, Node, NodeConfigFn, Result, Swarm, Version};
 use anyhow::{bail, Context};
 use aptos_config::config::NodeConfig;
 use aptos_genesis::builder::{InitConfigFn, InitGenesisConfigFn};
-use aptos_infallible::Mutex;
+use aptos_sdk::types::chain_id::ChainId;
 use framework::ReleaseBundle;
 use rand::rngs::StdRng;
+use sha2::{Digest, Sha256};
 use std::time::Duration;
 use std::{
     collections::HashMap,
+    fs,
+    io::Read,
     num::NonZeroUsize,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
+mod account_factory;
 mod cargo;
+mod cgroup;
+mod chaos;
+mod compat_matrix;
+mod core_dump;
+mod db_tools;
+mod docker;
+mod framework_upgrade;
+mod health_check;
+mod log_rotation;
+mod log_tail;
+mod metrics_archive;
+mod metrics_poller;
+mod multi_swarm;
+mod netns;
 mod node;
+mod observability;
+mod ports;
+mod profiling;
+mod promql;
+mod resource_usage;
+mod safety;
 mod swarm;
-pub use node::LocalNode;
-pub use swarm::{LocalSwarm, SwarmDirectory};
+mod validator_set;
+mod version_manager;
+pub use account_factory::AccountFactory;
+pub use cgroup::{ResourceLimits, UnsupportedResourceLimits};
+pub use compat_matrix::{
+    run_compatibility_matrix, CompatibilityMatrix, CompatibilityMatrixConfig, CompatibilityStep,
+    PairOutcome,
+};
+pub use db_tools::DbTools;
+pub use health_check::HealthCheckConfig;
+pub use log_rotation::LogRotation;
+pub use metrics_archive::{export_openmetrics, read_metrics_archive, MetricsRecorder};
+pub use multi_swarm::{LocalMultiSwarm, LocalNetworkSpec};
+pub use netns::NetworkNamespace;
+pub use node::{LocalNode, UnexpectedExit};
+pub use observability::{ObservabilityStack, ScrapeTarget};
+pub use profiling::{AllocationTracker, CpuProfiler, HeapProfiler};
+pub use promql::{evaluate_promql, to_promql_result, QuerySeries};
+pub use resource_usage::{ResourceBudgetTracker, ResourceUsage};
+pub use safety::{ConsensusSafetySnapshot, ValidatorSafetyRecord};
+pub use swarm::{ExtraArgsFn, LocalSwarm, SwarmDirectory};
+pub use version_manager::VersionManager;
 
 pub use self::swarm::ActiveNodesGuard;
 
@@ -28,38 +72,168 @@ pub use self::swarm::ActiveNodesGuard;
 pub struct LocalVersion {
     bin: PathBuf,
     version: Version,
+    /// If set, nodes run as containers of this image, `bin` being the path of aptos-node in it
+    docker_image: Option<String>,
+    /// Hex encoded SHA-256 of `bin` when the version was registered
+    sha256: Option<String>,
+    /// Framework released with this version, published on chain when a swarm is upgraded to it
+    framework: Option<Arc<ReleaseBundle>>,
 }
 
 impl LocalVersion {
+    /// Registers `bin` as `version`, recording its checksum.
     pub fn new(bin: PathBuf, version: Version) -> Self {
-        Self { bin, version }
+        let sha256 = sha256_file(&bin).ok();
+        Self {
+            bin,
+            version,
+            docker_image: None,
+            sha256,
+            framework: None,
+        }
+    }
+
+    /// A version whose nodes run as containers of the Docker `image`, which has aptos-node at
+    /// the usual path of the released images.
+    pub fn from_docker_image(image: String, version: Version) -> Self {
+        Self {
+            bin: PathBuf::from(docker::DEFAULT_IMAGE_BIN),
+            version,
+            docker_image: Some(image),
+            sha256: None,
+            framework: None,
+        }
+    }
+
+    /// Associates the framework `bundle` with this version, see `LocalSwarm::upgrade_to`.
+    pub fn with_framework(mut self, bundle: ReleaseBundle) -> Self {
+        self.framework = Some(Arc::new(bundle));
+        self
     }
 
     pub fn bin(&self) -> &Path {
         &self.bin
     }
 
+    pub fn docker_image(&self) -> Option<&str> {
+        self.docker_image.as_deref()
+    }
+
     pub fn version(&self) -> Version {
         self.version.clone()
     }
+
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    pub fn framework(&self) -> Option<&ReleaseBundle> {
+        self.framework.as_deref()
+    }
+
+    /// Checks that the binary is still the one registered for this version, e.g. that a cache
+    /// didn't replace it since.
+    pub fn verify(&self) -> Result<()> {
+        if let Some(expected) = &self.sha256 {
+            let actual = sha256_file(&self.bin)?;
+            if &actual != expected {
+                bail!(
+                    "Wrong binary for version {}: {:?} has sha256 {}, registered with {}",
+                    self.version,
+                    self.bin,
+                    actual,
+                    expected
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hex encoded SHA-256 of the file at `path`.
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
 }
 
 pub struct LocalFactory {
     versions: Arc<HashMap<Version, LocalVersion>>,
+    health_check_config: HealthCheckConfig,
+    validators_in_process: bool,
+    observability: bool,
+    metrics_recording_interval: Option<Duration>,
+    resource_limits: HashMap<usize, ResourceLimits>,
+    base_port: Option<u16>,
 }
 
 impl LocalFactory {
     pub fn new(versions: HashMap<Version, LocalVersion>) -> Self {
         Self {
             versions: Arc::new(versions),
+            health_check_config: HealthCheckConfig::default(),
+            validators_in_process: false,
+            observability: false,
+            metrics_recording_interval: None,
+            resource_limits: HashMap::new(),
+            base_port: None,
         }
     }
 
+    /// Health check of the nodes of the swarms launched by this factory.
+    pub fn with_health_check_config(mut self, health_check_config: HealthCheckConfig) -> Self {
+        self.health_check_config = health_check_config;
+        self
+    }
+
+    /// Runs the validators of the swarms launched by this factory in process, see
+    /// `LocalNode::set_in_process`. Nodes still run as subprocesses by default, for isolation.
+    pub fn with_validators_in_process(mut self) -> Self {
+        self.validators_in_process = true;
+        self
+    }
+
+    /// Starts a Prometheus and a Grafana along with the swarms launched by this factory, see
+    /// `LocalSwarm::start_observability`, which logs their URLs.
+    pub fn with_observability(mut self) -> Self {
+        self.observability = true;
+        self
+    }
+
+    /// Records the metrics of every node of the swarms launched by this factory every
+    /// `interval`, see `LocalSwarm::start_metrics_recording`.
+    pub fn with_metrics_recording(mut self, interval: Duration) -> Self {
+        self.metrics_recording_interval = Some(interval);
+        self
+    }
+
+    /// Limits the resources of the validators of the swarms launched by this factory, by index,
+    /// see `LocalSwarm::build`.
+    pub fn with_resource_limits(mut self, resource_limits: HashMap<usize, ResourceLimits>) -> Self {
+        self.resource_limits = resource_limits;
+        self
+    }
+
+    /// Has the nodes of the swarms launched by this factory listen on deterministic ports from
+    /// `base_port`, by node index, see `LocalSwarm::build`. Launching fails if one is in use.
+    pub fn with_base_port(mut self, base_port: u16) -> Self {
+        self.base_port = Some(base_port);
+        self
+    }
+
     pub fn from_workspace() -> Result<Self> {
         let mut versions = HashMap::new();
         let new_version = cargo::get_aptos_node_binary_from_worktree().map(|(revision, bin)| {
             let version = Version::new(usize::max_value(), revision);
-            LocalVersion { bin, version }
+            LocalVersion::new(bin, version)
         })?;
 
         versions.insert(new_version.version.clone(), new_version);
@@ -71,22 +245,50 @@ impl LocalFactory {
         let new_version =
             cargo::get_aptos_node_binary_at_revision(revision).map(|(revision, bin)| {
                 let version = Version::new(usize::max_value(), revision);
-                LocalVersion { bin, version }
+                LocalVersion::new(bin, version)
             })?;
 
         versions.insert(new_version.version.clone(), new_version);
         Ok(Self::new(versions))
     }
 
+    /// Create a LocalFactory with the aptos-node binaries of the given release tags, oldest
+    /// first, fetched through `manager`.
+    pub fn from_release_tags(manager: &VersionManager, tags: &[&str]) -> Result<Self> {
+        let mut versions = HashMap::new();
+        for (index, tag) in tags.iter().enumerate() {
+            let version = manager.local_version(tag, index)?;
+            versions.insert(version.version(), version);
+        }
+        Ok(Self::new(versions))
+    }
+
+    /// Create a LocalFactory running nodes as containers of the given Docker images, oldest
+    /// first, e.g. released images, without building any binary.
+    pub fn from_docker_images(images: &[&str]) -> Self {
+        let versions = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                let version = Version::new(index, image.to_string());
+                (
+                    version.clone(),
+                    LocalVersion::from_docker_image(image.to_string(), version),
+                )
+            })
+            .collect();
+        Self::new(versions)
+    }
+
     pub fn with_revision_and_workspace(revision: &str) -> Result<Self> {
         let workspace = cargo::get_aptos_node_binary_from_worktree().map(|(revision, bin)| {
             let version = Version::new(usize::max_value(), revision);
-            LocalVersion { bin, version }
+            LocalVersion::new(bin, version)
         })?;
         let revision =
             cargo::get_aptos_node_binary_at_revision(revision).map(|(revision, bin)| {
                 let version = Version::new(usize::min_value(), revision);
-                LocalVersion { bin, version }
+                LocalVersion::new(bin, version)
             })?;
 
         let mut versions = HashMap::new();
@@ -117,8 +319,45 @@ impl LocalFactory {
         number_of_fullnodes: usize,
         version: &Version,
         genesis_framework: Option<ReleaseBundle>,
+        chain_id: ChainId,
+        init_config: Option<InitConfigFn>,
+        init_genesis_config: Option<InitGenesisConfigFn>,
+        extra_args: Option<ExtraArgsFn>,
+        guard: ActiveNodesGuard,
+    ) -> Result<LocalSwarm>
+    where
+        R: ::rand::RngCore + ::rand::CryptoRng,
+    {
+        self.new_swarm_with_node_versions(
+            rng,
+            number_of_validators,
+            number_of_fullnodes,
+            version,
+            HashMap::new(),
+            genesis_framework,
+            chain_id,
+            init_config,
+            init_genesis_config,
+            extra_args,
+            guard,
+        )
+        .await
+    }
+
+    /// Like `new_swarm_with_version`, except that the validators of `node_versions`, by index,
+    /// run their own version from genesis, as do their fullnodes.
+    pub async fn new_swarm_with_node_versions<R>(
+        &self,
+        rng: R,
+        number_of_validators: NonZeroUsize,
+        number_of_fullnodes: usize,
+        version: &Version,
+        node_versions: HashMap<usize, Version>,
+        genesis_framework: Option<ReleaseBundle>,
+        chain_id: ChainId,
         init_config: Option<InitConfigFn>,
         init_genesis_config: Option<InitGenesisConfigFn>,
+        extra_args: Option<ExtraArgsFn>,
         guard: ActiveNodesGuard,
     ) -> Result<LocalSwarm>
     where
@@ -130,12 +369,27 @@ impl LocalFactory {
             number_of_validators,
             self.versions.clone(),
             Some(version.clone()),
+            node_versions,
+            self.resource_limits.clone(),
+            self.base_port,
             init_config,
             init_genesis_config,
             None,
             genesis_framework,
+            chain_id,
             guard,
         )?;
+        swarm.set_health_check_config(self.health_check_config.clone());
+        swarm.set_validators_in_process(self.validators_in_process);
+        if let Some(extra_args) = extra_args {
+            swarm.set_extra_args(extra_args);
+        }
+        if self.observability {
+            swarm.start_observability()?;
+        }
+        if let Some(interval) = self.metrics_recording_interval {
+            swarm.start_metrics_recording(interval)?;
+        }
 
         // Launch the swarm
         swarm
@@ -143,12 +397,15 @@ impl LocalFactory {
             .await
             .with_context(|| format!("Swarm logs can be found here: {}", swarm.logs_location()))?;
 
-        // Add and launch the fullnodes
-        let validator_peer_ids = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
-        for validator_peer_id in validator_peer_ids.iter().take(number_of_fullnodes) {
+        // Add and launch the fullnodes, at the version of their validator
+        let validators = swarm
+            .validators()
+            .map(|v| (v.peer_id(), v.version()))
+            .collect::<Vec<_>>();
+        for (validator_peer_id, validator_version) in validators.iter().take(number_of_fullnodes) {
             let _ = swarm
                 .add_validator_fullnode(
-                    version,
+                    validator_version,
                     NodeConfig::default_for_validator_full_node(),
                     *validator_peer_id,
                 )
@@ -188,9 +445,6 @@ impl Factory for LocalFactory {
             None => None,
         };
 
-        // no guarding, as this code path is not used in parallel
-        let guard = ActiveNodesGuard::grab(1, Arc::new(Mutex::new(0))).await;
-
         let swarm = self
             .new_swarm_with_version(
                 rng,
@@ -198,9 +452,11 @@ impl Factory for LocalFactory {
                 num_fullnodes,
                 version,
                 framework,
+                ChainId::test(),
+                None,
                 None,
                 None,
-                guard,
+                ActiveNodesGuard::unguarded(),
             )
             .await?;
 
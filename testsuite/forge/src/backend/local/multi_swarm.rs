@@ -0,0 +1,160 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Independent local swarms running side by side, each on its own chain, e.g. to test tooling
+//! spanning several networks such as bridges, relayers or multi-network wallets.
+
+use crate::{ActiveNodesGuard, LocalFactory, LocalSwarm, NodeExt, Result, Swarm, Version};
+use anyhow::{bail, format_err};
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::{
+    crypto::ed25519::Ed25519PrivateKey,
+    types::{chain_id::ChainId, LocalAccount},
+};
+use rand::rngs::OsRng;
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::TryFrom,
+    num::NonZeroUsize,
+};
+
+/// A network of a `LocalMultiSwarm`.
+#[derive(Clone, Debug)]
+pub struct LocalNetworkSpec {
+    pub name: String,
+    pub chain_id: ChainId,
+    pub num_validators: NonZeroUsize,
+    pub num_fullnodes: usize,
+}
+
+impl LocalNetworkSpec {
+    pub fn new(name: &str, chain_id: ChainId, num_validators: NonZeroUsize) -> Self {
+        Self {
+            name: name.to_string(),
+            chain_id,
+            num_validators,
+            num_fullnodes: 0,
+        }
+    }
+
+    pub fn with_num_fullnodes(mut self, num_fullnodes: usize) -> Self {
+        self.num_fullnodes = num_fullnodes;
+        self
+    }
+}
+
+/// Local swarms with distinct chain ids, by network name.
+pub struct LocalMultiSwarm {
+    swarms: BTreeMap<String, LocalSwarm>,
+}
+
+impl LocalMultiSwarm {
+    /// Launches a swarm running `version` for every network, one after the other.
+    pub async fn launch(
+        factory: &LocalFactory,
+        version: &Version,
+        networks: &[LocalNetworkSpec],
+    ) -> Result<Self> {
+        let mut names = HashSet::new();
+        let mut chain_ids = HashSet::new();
+        for network in networks {
+            if !names.insert(&network.name) {
+                bail!("Network {} is defined more than once", network.name);
+            }
+            if !chain_ids.insert(network.chain_id) {
+                bail!(
+                    "Chain id {} is used by more than one network",
+                    network.chain_id
+                );
+            }
+        }
+
+        let mut swarms = BTreeMap::new();
+        for network in networks {
+            let swarm = factory
+                .new_swarm_with_version(
+                    OsRng,
+                    network.num_validators,
+                    network.num_fullnodes,
+                    version,
+                    None,
+                    network.chain_id,
+                    None,
+                    None,
+                    None,
+                    ActiveNodesGuard::unguarded(),
+                )
+                .await?;
+            swarms.insert(network.name.clone(), swarm);
+        }
+        Ok(Self { swarms })
+    }
+
+    pub fn networks(&self) -> impl Iterator<Item = &str> {
+        self.swarms.keys().map(String::as_str)
+    }
+
+    pub fn swarm(&self, network: &str) -> Result<&LocalSwarm> {
+        self.swarms
+            .get(network)
+            .ok_or_else(|| format_err!("No network {}", network))
+    }
+
+    pub fn swarm_mut(&mut self, network: &str) -> Result<&mut LocalSwarm> {
+        self.swarms
+            .get_mut(network)
+            .ok_or_else(|| format_err!("No network {}", network))
+    }
+
+    /// Client of the first validator of `network`.
+    pub fn rest_client(&self, network: &str) -> Result<RestClient> {
+        self.swarm(network)?
+            .validators()
+            .next()
+            .map(|validator| validator.rest_client())
+            .ok_or_else(|| format_err!("Network {} has no validator", network))
+    }
+
+    /// Creates an account with the same key on every network, funded with `amount` on each,
+    /// returning it by network as every network has its own sequence number.
+    pub async fn create_mirrored_account(
+        &mut self,
+        amount: u64,
+    ) -> Result<BTreeMap<String, LocalAccount>> {
+        let account = LocalAccount::generate(&mut OsRng);
+        let mut accounts = BTreeMap::new();
+        for (network, swarm) in self.swarms.iter_mut() {
+            let mut info = swarm.aptos_public_info();
+            info.create_user_account(account.public_key()).await?;
+            info.mint(account.address(), amount).await?;
+            accounts.insert(network.clone(), copy_account(&account, 0)?);
+        }
+        Ok(accounts)
+    }
+
+    /// The account with the same key as `account` on `network`, with its sequence number on
+    /// that network. The account must exist on `network`.
+    pub async fn bridge_account(
+        &self,
+        account: &LocalAccount,
+        network: &str,
+    ) -> Result<LocalAccount> {
+        let sequence_number = self
+            .rest_client(network)?
+            .get_account(account.address())
+            .await?
+            .into_inner()
+            .sequence_number;
+        copy_account(account, sequence_number)
+    }
+}
+
+fn copy_account(account: &LocalAccount, sequence_number: u64) -> Result<LocalAccount> {
+    let private_key = Ed25519PrivateKey::try_from(account.private_key().to_bytes().as_ref())
+        .map_err(|e| format_err!("Failed to copy the key of {}: {}", account.address(), e))?;
+    Ok(LocalAccount::new(
+        account.address(),
+        private_key,
+        sequence_number,
+    ))
+}
@@ -0,0 +1,275 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Linux network namespaces giving every node of a LocalSwarm its own network stack, so that
+//! network chaos applied to a node only affects that node. The namespaces are connected through
+//! veth pairs to a bridge on the host. Setting them up needs root, iproute2 and iptables.
+//!
+//! Nodes address each other as `127.0.0.1:<port>` (or `0.0.0.0:<port>`), as ports are unique
+//! within a swarm. Inside every namespace, connections to the ports of the other nodes are
+//! forwarded to their namespaces, so neither the configs nor the genesis need to change. No
+//! host-wide iptables rule is added, but hosts filtering bridged traffic (br_netfilter with a
+//! dropping FORWARD policy, as set up by Docker) also drop the traffic between namespaces.
+
+use anyhow::{bail, ensure, Context, Result};
+use aptos_logger::warn;
+use std::{ffi::OsStr, net::Ipv4Addr, process::Command};
+
+/// Name of the node's end of its veth pair, inside its namespace.
+const NAMESPACE_INTERFACE: &str = "eth0";
+
+/// Prefix length of the subnet of a bridge.
+const PREFIX_LEN: u8 = 16;
+
+/// A host bridge connecting network namespaces, on a /16 subnet. The host is the first address
+/// of the subnet, namespaces get the next ones.
+#[derive(Debug)]
+pub struct NetworkBridge {
+    name: String,
+    subnet: [u8; 2],
+    /// Index in the subnet of the next namespace
+    next_host: u16,
+}
+
+impl NetworkBridge {
+    /// Creates the bridge of the subnet `<subnet[0]>.<subnet[1]>.0.0/16`. Concurrent swarms need
+    /// different subnets.
+    pub fn create(subnet: [u8; 2]) -> Result<Self> {
+        let name = format!("fbr-{}-{}", subnet[0], subnet[1]);
+        run("ip", &["link", "add", "name", &name, "type", "bridge"])?;
+        // Deletes the bridge if any of the following fails.
+        let bridge = Self {
+            name,
+            subnet,
+            next_host: 2,
+        };
+        run(
+            "ip",
+            &[
+                "addr",
+                "add",
+                &format!("{}/{}", bridge.host_address(1), PREFIX_LEN),
+                "dev",
+                &bridge.name,
+            ],
+        )?;
+        run("ip", &["link", "set", &bridge.name, "up"])?;
+        Ok(bridge)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Address of the host on the bridge.
+    pub fn gateway(&self) -> Ipv4Addr {
+        self.host_address(1)
+    }
+
+    /// Creates the namespace `name`, connected to the bridge with the next free address.
+    pub fn add_namespace(&mut self, name: &str) -> Result<NetworkNamespace> {
+        ensure!(
+            self.next_host < u16::MAX,
+            "no address left on bridge {}",
+            self.name
+        );
+        let host = self.next_host;
+        self.next_host += 1;
+        NetworkNamespace::create(
+            name.to_string(),
+            format!("fv-{}-{}", self.subnet[1], host),
+            &self.name,
+            self.host_address(host),
+        )
+    }
+
+    fn host_address(&self, host: u16) -> Ipv4Addr {
+        let [high, low] = host.to_be_bytes();
+        Ipv4Addr::new(self.subnet[0], self.subnet[1], high, low)
+    }
+}
+
+impl Drop for NetworkBridge {
+    fn drop(&mut self) {
+        if let Err(e) = run("ip", &["link", "delete", &self.name]) {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// A network namespace connected to a NetworkBridge, deleted when dropped. Processes still
+/// running in it keep it alive until they exit.
+#[derive(Debug)]
+pub struct NetworkNamespace {
+    name: String,
+    /// The host's end of the veth pair
+    host_interface: String,
+    address: Ipv4Addr,
+}
+
+impl NetworkNamespace {
+    fn create(
+        name: String,
+        host_interface: String,
+        bridge: &str,
+        address: Ipv4Addr,
+    ) -> Result<Self> {
+        run("ip", &["netns", "add", &name])?;
+        // Deletes the namespace if any of the following fails.
+        let namespace = Self {
+            name,
+            host_interface,
+            address,
+        };
+        run(
+            "ip",
+            &[
+                "link",
+                "add",
+                &namespace.host_interface,
+                "type",
+                "veth",
+                "peer",
+                "name",
+                NAMESPACE_INTERFACE,
+                "netns",
+                &namespace.name,
+            ],
+        )?;
+        run(
+            "ip",
+            &["link", "set", &namespace.host_interface, "master", bridge],
+        )?;
+        run("ip", &["link", "set", &namespace.host_interface, "up"])?;
+        namespace.exec(
+            "ip",
+            &[
+                "addr",
+                "add",
+                &format!("{}/{}", address, PREFIX_LEN),
+                "dev",
+                NAMESPACE_INTERFACE,
+            ],
+        )?;
+        namespace.exec("ip", &["link", "set", NAMESPACE_INTERFACE, "up"])?;
+        namespace.exec("ip", &["link", "set", "lo", "up"])?;
+        // Let connections to the loopback address be forwarded to the other namespaces, with
+        // the namespace's address as source.
+        namespace.exec(
+            "sysctl",
+            &["-q", "-w", "net.ipv4.conf.all.route_localnet=1"],
+        )?;
+        namespace.exec(
+            "iptables",
+            &[
+                "-t",
+                "nat",
+                "-A",
+                "POSTROUTING",
+                "-s",
+                "127.0.0.0/8",
+                "-o",
+                NAMESPACE_INTERFACE,
+                "-j",
+                "SNAT",
+                "--to-source",
+                &address.to_string(),
+            ],
+        )?;
+        Ok(namespace)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Address of the namespace on the bridge, the host reaches the node through it.
+    pub fn address(&self) -> Ipv4Addr {
+        self.address
+    }
+
+    /// Name of the interface connecting the namespace to the bridge, inside the namespace.
+    pub fn interface(&self) -> &str {
+        NAMESPACE_INTERFACE
+    }
+
+    /// Name of the host's end of the namespace's veth pair.
+    pub fn host_interface(&self) -> &str {
+        &self.host_interface
+    }
+
+    /// Builds a command running `program` inside the namespace.
+    pub fn command<S: AsRef<OsStr>>(&self, program: S) -> Command {
+        let mut command = Command::new("ip");
+        command
+            .arg("netns")
+            .arg("exec")
+            .arg(&self.name)
+            .arg(program);
+        command
+    }
+
+    /// Moves `command` into the namespace. Its arguments are kept, its environment and working
+    /// directory need to be set afterwards.
+    pub fn wrap(&self, command: Command) -> Command {
+        let mut wrapped = self.command(command.get_program());
+        wrapped.args(command.get_args());
+        wrapped
+    }
+
+    /// Runs `program` inside the namespace, failing if it does.
+    pub fn exec(&self, program: &str, args: &[&str]) -> Result<()> {
+        check(program, self.command(program).args(args))
+    }
+
+    /// Forwards the connections made inside the namespace to `127.0.0.1:<port>` to
+    /// `<to>:<port>`.
+    pub fn forward_port(&self, port: u16, to: Ipv4Addr) -> Result<()> {
+        self.exec(
+            "iptables",
+            &[
+                "-t",
+                "nat",
+                "-A",
+                "OUTPUT",
+                "-d",
+                "127.0.0.1/32",
+                "-p",
+                "tcp",
+                "--dport",
+                &port.to_string(),
+                "-j",
+                "DNAT",
+                "--to-destination",
+                &format!("{}:{}", to, port),
+            ],
+        )
+    }
+}
+
+impl Drop for NetworkNamespace {
+    fn drop(&mut self) {
+        // Also deletes the veth pair.
+        if let Err(e) = run("ip", &["netns", "delete", &self.name]) {
+            warn!("{}", e);
+        }
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    check(program, Command::new(program).args(args))
+}
+
+fn check(program: &str, command: &mut Command) -> Result<()> {
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {}, is it installed?", program))?;
+    if !output.status.success() {
+        bail!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
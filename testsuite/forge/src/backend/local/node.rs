@@ -1,58 +1,237 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{FullNode, HealthCheckError, LocalVersion, Node, NodeExt, Validator, Version};
+use super::{
+    cgroup::{NodeCgroup, ResourceLimits},
+    core_dump::{collect_core_dump, with_core_limit},
+    db_tools::DbTools,
+    docker,
+    health_check::HealthCheckConfig,
+    log_rotation::{spawn_log_writer, LogRotation, RotatingFile},
+    log_tail::tail_lines,
+    metrics_archive::MetricsRecorder,
+    netns::NetworkNamespace,
+    profiling::{AllocationTracker, CpuProfiler, HeapProfiler},
+    resource_usage::{self, ResourceBudgetTracker, ResourceSampler, ResourceUsage},
+};
+use crate::{
+    record_event, FullNode, HealthCheckError, HealthCheckFailure, LocalVersion, MetricType, Node,
+    NodeExt, NotRunningReason, TimelineEventKind, Validator, Version,
+};
 use anyhow::{anyhow, ensure, Context, Result};
-use aptos_config::{config::NodeConfig, keys::ConfigKey};
-use aptos_logger::{debug, info};
+use aptos_config::{
+    config::{NodeConfig, NO_OP_STORAGE_PRUNER_CONFIG},
+    keys::ConfigKey,
+};
+use aptos_logger::{debug, info, warn};
+use aptos_node::AptosHandle;
+use aptos_rest_client::error::RestError;
 use aptos_sdk::{
     crypto::ed25519::Ed25519PrivateKey,
     types::{account_address::AccountAddress, PeerId},
 };
 use aptos_secure_storage::SECURE_STORAGE_DB_NAME;
-use aptosdb::{LEDGER_DB_NAME, STATE_MERKLE_DB_NAME};
+use aptosdb::{AptosDB, LEDGER_DB_NAME, STATE_MERKLE_DB_NAME};
+use consensus::CONSENSUS_DB_NAME;
+use futures::Stream;
 use state_sync_driver::metadata_storage::STATE_SYNC_DB_NAME;
 use std::{
-    env,
+    env, fmt,
     fs::{self, OpenOptions},
-    path::PathBuf,
-    process::{Child, Command},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
     str::FromStr,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tokio::net::TcpStream;
 use url::Url;
 
+/// How long to wait for a port to accept a connection when diagnosing a failed health check.
+const PORT_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[derive(Debug)]
-struct Process(Child);
+struct Process {
+    child: Child,
+    /// If set, the process is sent `stop_signal` and given this long to exit before being
+    /// killed, e.g. so that the node can close its databases cleanly
+    grace_period: Option<Duration>,
+    /// Signal name, as understood by `kill`
+    stop_signal: &'static str,
+    /// The Docker container the process is the client of, if any
+    container: Option<String>,
+    /// Whether the process leads its own process group, which is then signaled as a whole
+    process_group: bool,
+    /// Whether `stop_signal` was sent ahead of the stop, see `LocalNode::signal_stop`
+    stop_signaled: bool,
+}
+
+impl Process {
+    /// Sends `stop_signal` and waits for the process to exit, returns whether it did in time.
+    fn signal_and_wait(&mut self, grace_period: Duration) -> bool {
+        if !self.signal(self.stop_signal) {
+            return false;
+        }
+        let deadline = Instant::now() + grace_period;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        false
+    }
+
+    /// Sends `signal` to the process, or to its process group. Returns whether it was sent.
+    fn signal(&self, signal: &str) -> bool {
+        send_signal(&self.signal_target(), signal)
+    }
+
+    /// The process, or its process group, as understood by `kill`
+    fn signal_target(&self) -> String {
+        if self.process_group {
+            format!("-{}", self.child.id())
+        } else {
+            self.child.id().to_string()
+        }
+    }
+}
+
+/// Sends `signal` to `target`, as understood by `kill`. Returns whether it was sent.
+pub(crate) fn send_signal(target: &str, signal: &str) -> bool {
+    Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg("--")
+        .arg(target)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// State of the process `pid`, as shown by /proc, None if there is no such process.
+fn process_state(pid: u32) -> Option<char> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The state follows the command name, which is in parentheses.
+    stat.rsplit(')')
+        .next()
+        .and_then(|rest| rest.trim_start().chars().next())
+}
 
 impl Drop for Process {
     // When the Process struct goes out of scope we need to kill the child process
     fn drop(&mut self) {
+        // Killing the docker client would leave the container running. Once the container is
+        // removed, the client exits by itself.
+        if let Some(container) = &self.container {
+            if let Err(e) = docker::remove_container(container, self.grace_period) {
+                warn!("{}", e);
+            }
+        }
+
         // check if the process has already been terminated
-        match self.0.try_wait() {
+        match self.child.try_wait() {
             // The child process has already terminated, perhaps due to a crash
             Ok(Some(_)) => {}
 
             // The process is still running so we need to attempt to kill it
             _ => {
-                self.0.kill().expect("Process wasn't running");
-                self.0.wait().unwrap();
+                if let Some(grace_period) = self.grace_period {
+                    if self.signal_and_wait(grace_period) {
+                        return;
+                    }
+                    warn!(
+                        "Process {} didn't exit {:?} after SIG{}, killing it",
+                        self.child.id(),
+                        grace_period,
+                        self.stop_signal
+                    );
+                }
+                if self.process_group {
+                    self.signal("KILL");
+                } else {
+                    self.child.kill().expect("Process wasn't running");
+                }
+                self.child.wait().unwrap();
             }
         }
     }
 }
 
+/// A process of a node that exited without being stopped, e.g. because it crashed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnexpectedExit {
+    pub pid: u32,
+    pub started_at: SystemTime,
+    /// When the exit was noticed, it happened at some point before
+    pub noticed_at: SystemTime,
+}
+
+/// A node running inside this process. Its runtimes are shut down when it is dropped.
+struct InProcessNode(Option<AptosHandle>);
+
+impl fmt::Debug for InProcessNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InProcessNode").finish_non_exhaustive()
+    }
+}
+
+impl Drop for InProcessNode {
+    fn drop(&mut self) {
+        // Runtimes can't be dropped from within an async context, which nodes are usually
+        // stopped from.
+        if let Some(handle) = self.0.take() {
+            let _ = thread::spawn(move || drop(handle)).join();
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct LocalNode {
     version: LocalVersion,
     process: Option<Process>,
+    /// Run the node inside this process instead of spawning its binary
+    in_process: bool,
+    in_process_node: Option<InProcessNode>,
     name: String,
     account_private_key: Option<ConfigKey<Ed25519PrivateKey>>,
     peer_id: AccountAddress,
     directory: PathBuf,
     config: NodeConfig,
+    /// Rotation of the stdout and stderr logs, they grow unbounded if not set
+    log_rotation: Option<LogRotation>,
+    cpu_profiler: Option<CpuProfiler>,
+    heap_profiler: Option<HeapProfiler>,
+    allocation_tracker: Option<AllocationTracker>,
+    /// Where core dumps of the node are moved to if it crashes, core dumps are disabled if
+    /// not set
+    core_dumps_dir: Option<PathBuf>,
+    /// How long the node is given to exit after SIGTERM before being killed, it is killed
+    /// right away if not set
+    stop_grace_period: Option<Duration>,
+    health_check_config: HealthCheckConfig,
+    /// Passed to the node binary after its config, on every start
+    extra_args: Vec<String>,
+    /// Network namespace the node runs in, it shares the host's network if not set
+    network_namespace: Option<Arc<NetworkNamespace>>,
+    /// When the node was started, oldest first
+    start_times: Vec<SystemTime>,
+    /// Processes of the node that exited without being stopped, oldest first
+    unexpected_exits: Vec<UnexpectedExit>,
+    resource_sampler: Option<ResourceSampler>,
+    metrics_recorder: Option<MetricsRecorder>,
+    /// Ledger version seen by the previous health check, to check for progress
+    last_ledger_version: Option<u64>,
+    /// CPU and memory limits of the node, it is unlimited if not set
+    resource_limits: Option<ResourceLimits>,
+    /// Enforces `resource_limits` on the current process. Dropped after the process.
+    cgroup: Option<NodeCgroup>,
 }
 
 impl LocalNode {
+    pub const DEFAULT_STOP_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
     pub fn new(
         version: LocalVersion,
         name: String,
@@ -69,11 +248,29 @@ impl LocalNode {
         Ok(Self {
             version,
             process: None,
+            in_process: false,
+            in_process_node: None,
             name,
             account_private_key,
             peer_id,
             directory,
             config,
+            log_rotation: None,
+            cpu_profiler: None,
+            heap_profiler: None,
+            allocation_tracker: None,
+            core_dumps_dir: None,
+            stop_grace_period: Some(Self::DEFAULT_STOP_GRACE_PERIOD),
+            health_check_config: HealthCheckConfig::default(),
+            extra_args: Vec::new(),
+            network_namespace: None,
+            start_times: Vec::new(),
+            unexpected_exits: Vec::new(),
+            resource_sampler: None,
+            metrics_recorder: None,
+            last_ledger_version: None,
+            resource_limits: None,
+            cgroup: None,
         })
     }
 
@@ -85,6 +282,168 @@ impl LocalNode {
         self.directory.join("log")
     }
 
+    /// Stderr is kept apart from the regular logs, so that panics are easy to find.
+    pub fn stderr_log_path(&self) -> PathBuf {
+        self.directory.join("stderr")
+    }
+
+    /// Runs the node from its next start as tasks of this process, through the aptos-node
+    /// library rather than the version's binary. Starts much faster and can be debugged along
+    /// with the test, but isn't isolated: metrics, failpoints and panics are shared with
+    /// everything else in the process, and logs go to the process' logger. Profilers and core
+    /// dumps are not supported.
+    pub fn set_in_process(&mut self, in_process: bool) {
+        self.in_process = in_process;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.process.is_some() || self.in_process_node.is_some()
+    }
+
+    /// Applies from the next start of the node.
+    pub fn set_log_rotation(&mut self, log_rotation: Option<LogRotation>) {
+        self.log_rotation = log_rotation;
+    }
+
+    /// Runs the node under a CPU profiler, from its next start.
+    pub fn set_cpu_profiler(&mut self, cpu_profiler: Option<CpuProfiler>) {
+        self.cpu_profiler = cpu_profiler;
+    }
+
+    /// Enables heap profiling of the node, from its next start.
+    pub fn set_heap_profiler(&mut self, heap_profiler: Option<HeapProfiler>) {
+        self.heap_profiler = heap_profiler;
+    }
+
+    /// Runs the node in `network_namespace` from its next start. The REST API and the backup
+    /// service are made to listen on all interfaces, so that they can be reached from the host
+    /// through the namespace's address.
+    pub fn set_network_namespace(
+        &mut self,
+        network_namespace: Option<Arc<NetworkNamespace>>,
+    ) -> Result<()> {
+        if network_namespace.is_some() {
+            self.modify_config(|config| {
+                config.api.address.set_ip(Ipv4Addr::UNSPECIFIED.into());
+                config
+                    .storage
+                    .backup_service_address
+                    .set_ip(Ipv4Addr::UNSPECIFIED.into());
+            })?;
+        }
+        self.network_namespace = network_namespace;
+        Ok(())
+    }
+
+    pub fn network_namespace(&self) -> Option<&NetworkNamespace> {
+        self.network_namespace.as_deref()
+    }
+
+    /// Address the node is reached at from the host.
+    pub fn ip(&self) -> IpAddr {
+        match &self.network_namespace {
+            Some(network_namespace) => network_namespace.address().into(),
+            None => Ipv4Addr::LOCALHOST.into(),
+        }
+    }
+
+    /// TCP ports the node listens on.
+    pub fn listen_ports(&self) -> Vec<u16> {
+        let mut ports = vec![
+            self.config.api.address.port(),
+            self.config.inspection_service.port,
+            self.config.storage.backup_service_address.port(),
+        ];
+        ports.extend(
+            self.config
+                .validator_network
+                .iter()
+                .chain(self.config.full_node_networks.iter())
+                .filter_map(|network| network.listen_address.find_port()),
+        );
+        ports
+    }
+
+    /// Runs the node under an allocation tracker, from its next start. Can't be combined with
+    /// the CPU profiler.
+    pub fn set_allocation_tracker(&mut self, allocation_tracker: Option<AllocationTracker>) {
+        self.allocation_tracker = allocation_tracker;
+    }
+
+    /// Dumps the heap profile of the running node, which needs to have been started with a
+    /// heap profiler.
+    pub fn dump_heap_profile(&self) -> Result<PathBuf> {
+        ensure!(
+            self.heap_profiler.is_some(),
+            "node {} was not started with a heap profiler",
+            self.name
+        );
+        let pid = self
+            .process
+            .as_ref()
+            .map(|p| p.child.id())
+            .ok_or_else(|| anyhow!("node {} is not running", self.name))?;
+        HeapProfiler::dump(pid, &self.profiles_dir())
+    }
+
+    /// Heap dumps of this node so far, oldest first.
+    pub fn heap_profiles(&self) -> Result<Vec<PathBuf>> {
+        HeapProfiler::dumps(&self.profiles_dir())
+    }
+
+    /// Enables core dumps, from the next start of the node. If the node crashes, its core dump
+    /// is moved to `core_dumps_dir` and referenced in the health check failure.
+    pub fn set_core_dumps_dir(&mut self, core_dumps_dir: Option<PathBuf>) {
+        self.core_dumps_dir = core_dumps_dir;
+    }
+
+    /// Limits the CPU and memory of the node from its next start, with a cgroup of its own, or
+    /// the container limits if it runs in a container. See the `cgroup` module.
+    pub fn set_resource_limits(&mut self, resource_limits: Option<ResourceLimits>) {
+        self.resource_limits = resource_limits;
+    }
+
+    pub fn resource_limits(&self) -> Option<ResourceLimits> {
+        self.resource_limits
+    }
+
+    /// How many times the node was OOM killed under its memory limit, while it ran with limits.
+    pub fn oom_kills(&self) -> Result<u64> {
+        self.cgroup
+            .as_ref()
+            .ok_or_else(|| anyhow!("Node {} runs without a cgroup of its own", self.name))?
+            .oom_kills()
+    }
+
+    /// Applies from the next start of the node. Without a grace period, the node is killed
+    /// right away when stopped.
+    pub fn set_stop_grace_period(&mut self, stop_grace_period: Option<Duration>) {
+        self.stop_grace_period = stop_grace_period;
+    }
+
+    pub fn extra_args(&self) -> &[String] {
+        &self.extra_args
+    }
+
+    /// Sets command line arguments passed to the node binary, from its next start and across
+    /// upgrades.
+    pub fn set_extra_args(&mut self, extra_args: Vec<String>) {
+        self.extra_args = extra_args;
+    }
+
+    pub fn health_check_config(&self) -> &HealthCheckConfig {
+        &self.health_check_config
+    }
+
+    pub fn set_health_check_config(&mut self, health_check_config: HealthCheckConfig) {
+        self.health_check_config = health_check_config;
+    }
+
+    /// Where profiles of this node are written to.
+    pub fn profiles_dir(&self) -> PathBuf {
+        self.directory.join("profiles")
+    }
+
     pub fn peer_id(&self) -> PeerId {
         self.peer_id
     }
@@ -98,32 +457,117 @@ impl LocalNode {
     }
 
     pub fn start(&mut self) -> Result<()> {
-        ensure!(self.process.is_none(), "node {} already running", self.name);
-
-        // Ensure log file exists
-        let log_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(true)
-            .open(self.log_path())?;
+        ensure!(!self.is_running(), "node {} already running", self.name);
+        // Ledger progress is measured from the first health check after this start
+        self.last_ledger_version = None;
+        if self.in_process {
+            return self.start_in_process();
+        }
 
         // Start node process
-        let mut node_command = Command::new(self.version.bin());
+        let mut container = None;
+        ensure!(
+            self.cpu_profiler.is_none() || self.allocation_tracker.is_none(),
+            "node {} can't run under both a CPU profiler and an allocation tracker",
+            self.name
+        );
+        let mut node_command = match (
+            self.version.docker_image(),
+            &self.cpu_profiler,
+            &self.allocation_tracker,
+        ) {
+            (Some(image), _, _) => {
+                ensure!(
+                    self.cpu_profiler.is_none()
+                        && self.heap_profiler.is_none()
+                        && self.allocation_tracker.is_none()
+                        && self.core_dumps_dir.is_none()
+                        && self.network_namespace.is_none(),
+                    "node {} runs in a container, profilers, core dumps and network namespaces \
+                     are not supported",
+                    self.name
+                );
+                let name = format!("forge-{}-{}", self.name, self.peer_id.short_str_lossless());
+                let command = docker::run_command(
+                    image,
+                    self.version.bin(),
+                    &name,
+                    &self.directory,
+                    self.resource_limits.as_ref(),
+                );
+                container = Some(name);
+                command
+            }
+            (None, Some(cpu_profiler), _) => {
+                fs::create_dir_all(self.profiles_dir())?;
+                cpu_profiler.command(self.version.bin(), &self.profiles_dir())
+            }
+            (None, None, Some(allocation_tracker)) => {
+                fs::create_dir_all(self.profiles_dir())?;
+                allocation_tracker.command(self.version.bin(), &self.profiles_dir())
+            }
+            (None, None, None) => Command::new(self.version.bin()),
+        };
+        if self.core_dumps_dir.is_some() {
+            node_command = with_core_limit(node_command);
+        }
+        if let Some(network_namespace) = &self.network_namespace {
+            node_command = network_namespace.wrap(node_command);
+        }
+        // Containers are limited by Docker. The previous cgroup goes first, as it has the same
+        // path.
+        self.cgroup = None;
+        if let (Some(limits), None) = (&self.resource_limits, &container) {
+            let name = format!("{}-{}", self.name, self.peer_id.short_str_lossless());
+            let cgroup = NodeCgroup::create(&name, limits)?;
+            node_command = cgroup.wrap(node_command);
+            self.cgroup = Some(cgroup);
+        }
         node_command
             .current_dir(&self.directory)
             .arg("-f")
-            .arg(self.config_path());
+            .arg(self.config_path())
+            .args(&self.extra_args);
+        if let Some(heap_profiler) = &self.heap_profiler {
+            fs::create_dir_all(self.profiles_dir())?;
+            heap_profiler.configure(&mut node_command, &self.profiles_dir());
+        }
         if env::var("RUST_LOG").is_err() {
             // Only set our RUST_LOG if its not present in environment
             node_command.env("RUST_LOG", "debug");
         }
-        node_command.stdout(log_file.try_clone()?).stderr(log_file);
-        let process = node_command.spawn().with_context(|| {
+        if self.log_rotation.is_some() {
+            node_command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        } else {
+            // Ensure log files exist
+            let log_file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(true)
+                .open(self.log_path())?;
+            let stderr_file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(true)
+                .open(self.stderr_log_path())?;
+            node_command.stdout(log_file).stderr(stderr_file);
+        }
+        let mut process = node_command.spawn().with_context(|| {
             format!(
                 "Error launching node process with binary: {:?}",
                 self.version.bin()
             )
         })?;
+        if let Some(log_rotation) = &self.log_rotation {
+            if let Some(stdout) = process.stdout.take() {
+                let log_file = RotatingFile::open(self.log_path(), log_rotation.clone())?;
+                spawn_log_writer(stdout, log_file);
+            }
+            if let Some(stderr) = process.stderr.take() {
+                let stderr_file = RotatingFile::open(self.stderr_log_path(), log_rotation.clone())?;
+                spawn_log_writer(stderr, stderr_file);
+            }
+        }
 
         // We print out the commands and PIDs for debugging of local swarms
         info!(
@@ -135,22 +579,301 @@ impl LocalNode {
 
         // We print out the API endpoints of each node for local debugging
         info!(
-            "Node {}: REST API is listening at: http://127.0.0.1:{}",
+            "Node {}: REST API is listening at: http://{}:{}",
             self.name,
+            self.ip(),
             self.config.api.address.port()
         );
         info!(
-            "Node {}: Inspection service is listening at http://127.0.0.1:{}",
-            self.name, self.config.inspection_service.port
+            "Node {}: Inspection service is listening at http://{}:{}",
+            self.name,
+            self.ip(),
+            self.config.inspection_service.port
         );
 
-        self.process = Some(Process(process));
+        let (stop_signal, grace_period) = match (&self.cpu_profiler, &self.allocation_tracker) {
+            // perf needs to be interrupted to write the profile
+            (Some(_), _) => ("INT", Some(CpuProfiler::STOP_GRACE_PERIOD)),
+            (None, Some(_)) => ("TERM", Some(AllocationTracker::STOP_GRACE_PERIOD)),
+            (None, None) => ("TERM", self.stop_grace_period),
+        };
+        self.process = Some(Process {
+            child: process,
+            grace_period,
+            stop_signal,
+            container,
+            process_group: self
+                .allocation_tracker
+                .as_ref()
+                .map_or(false, AllocationTracker::is_process_group),
+            stop_signaled: false,
+        });
+        self.on_started();
+
+        Ok(())
+    }
+
+    fn start_in_process(&mut self) -> Result<()> {
+        ensure!(
+            self.cpu_profiler.is_none()
+                && self.heap_profiler.is_none()
+                && self.allocation_tracker.is_none()
+                && self.core_dumps_dir.is_none()
+                && self.extra_args.is_empty()
+                && self.network_namespace.is_none()
+                && self.resource_limits.is_none(),
+            "node {} runs in process, profilers, core dumps, extra args, network namespaces and \
+             resource limits are not supported",
+            self.name
+        );
+        let handle = aptos_node::setup_environment(self.config.clone(), None)
+            .with_context(|| format!("Error starting node {} in process", self.name))?;
+        info!(
+            "Started node {} in process, REST API is listening at: http://127.0.0.1:{}",
+            self.name,
+            self.config.api.address.port()
+        );
+        self.in_process_node = Some(InProcessNode(Some(handle)));
+        self.on_started();
+        Ok(())
+    }
+
+    fn on_started(&mut self) {
+        self.start_times.push(SystemTime::now());
+        record_event(TimelineEventKind::NodeStarted {
+            node: self.name.clone(),
+        });
+        if let Some(sampler) = &self.resource_sampler {
+            sampler.set_pid(self.pid());
+        }
+        if let Some(recorder) = &self.metrics_recorder {
+            recorder.set_url(Some(self.metrics_url()));
+        }
+    }
+
+    /// Pid of the running node. Nodes running in process share this process' pid, and
+    /// containerized ones are represented by their docker client.
+    pub fn pid(&self) -> Option<u32> {
+        match (&self.process, &self.in_process_node) {
+            (Some(process), _) => Some(process.child.id()),
+            (None, Some(_)) => Some(std::process::id()),
+            (None, None) => None,
+        }
+    }
+
+    /// Measures the resources currently used by the running node, blocks for a short while
+    /// to measure its CPU usage.
+    pub fn resource_usage(&self) -> Result<ResourceUsage> {
+        let pid = self
+            .pid()
+            .ok_or_else(|| anyhow!("node {} is not running", self.name))?;
+        resource_usage::measure(pid, &self.directory, Duration::from_millis(200))
+    }
+
+    /// Samples the resources used by the node every `interval` in the background, while it
+    /// is running. Replaces the samples taken so far.
+    pub fn start_resource_sampling(&mut self, interval: Duration) {
+        let sampler = ResourceSampler::start(self.directory.clone(), interval);
+        sampler.set_pid(self.pid());
+        self.resource_sampler = Some(sampler);
+    }
+
+    pub fn stop_resource_sampling(&mut self) {
+        self.resource_sampler = None;
+    }
+
+    /// Adds the resources sampled from now on to `budget`, see `ResourceSampler::set_budget`.
+    /// Only effective while sampling.
+    pub fn set_resource_budget(&self, budget: Option<Arc<ResourceBudgetTracker>>) {
+        if let Some(sampler) = &self.resource_sampler {
+            sampler.set_budget(budget);
+        }
+    }
+
+    /// Resources used by the node over time, oldest first, see `start_resource_sampling`.
+    pub fn resource_samples(&self) -> Vec<ResourceUsage> {
+        self.resource_sampler
+            .as_ref()
+            .map(ResourceSampler::samples)
+            .unwrap_or_default()
+    }
 
+    /// Records the metrics of the node every `interval` to the archive at `path`, while it is
+    /// running, see `read_metrics_archive`. Appends to the archive if it already exists.
+    pub fn start_metrics_recording(&mut self, path: PathBuf, interval: Duration) -> Result<()> {
+        let recorder = MetricsRecorder::start(path, interval)?;
+        if self.is_running() {
+            recorder.set_url(Some(self.metrics_url()));
+        }
+        self.metrics_recorder = Some(recorder);
         Ok(())
     }
 
+    pub fn stop_metrics_recording(&mut self) {
+        self.metrics_recorder = None;
+    }
+
+    /// Number of scrapes the metrics recording missed while the node was meant to be running.
+    pub fn metrics_recording_failures(&self) -> u64 {
+        self.metrics_recorder
+            .as_ref()
+            .map_or(0, MetricsRecorder::failures)
+    }
+
+    pub(crate) fn metrics_url(&self) -> Url {
+        let mut url = self.inspection_service_endpoint();
+        url.set_path("metrics");
+        url
+    }
+
+    /// Number of times the node was started again after its first start.
+    pub fn restart_count(&self) -> usize {
+        self.start_times.len().saturating_sub(1)
+    }
+
+    /// When the node was started, oldest first.
+    pub fn start_times(&self) -> &[SystemTime] {
+        &self.start_times
+    }
+
+    /// Processes of the node that exited without being stopped, including the current one if
+    /// it did. Nodes running in process are not covered.
+    pub fn unexpected_exits(&self) -> Vec<UnexpectedExit> {
+        let mut exits = self.unexpected_exits.clone();
+        if let Some(exit) = self.current_exit() {
+            if !exits.iter().any(|recorded| recorded.pid == exit.pid) {
+                exits.push(exit);
+            }
+        }
+        exits
+    }
+
+    /// The exit of the current process, if it exited. Waiting on the process needs exclusive
+    /// access, so whether it exited is read from /proc, where it lingers as a zombie until
+    /// waited on.
+    fn current_exit(&self) -> Option<UnexpectedExit> {
+        let pid = self.process.as_ref()?.child.id();
+        let exited = matches!(process_state(pid), None | Some('Z') | Some('X'));
+        if !exited {
+            return None;
+        }
+        Some(UnexpectedExit {
+            pid,
+            started_at: self.start_times.last().copied().unwrap_or(UNIX_EPOCH),
+            noticed_at: SystemTime::now(),
+        })
+    }
+
+    fn record_unexpected_exit(&mut self) {
+        let pid = match &self.process {
+            Some(process) => process.child.id(),
+            None => return,
+        };
+        if self.unexpected_exits.iter().any(|exit| exit.pid == pid) {
+            return;
+        }
+        warn!(
+            "Node {} (pid {}) exited without being stopped",
+            self.name, pid
+        );
+        self.unexpected_exits.push(UnexpectedExit {
+            pid,
+            started_at: self.start_times.last().copied().unwrap_or(UNIX_EPOCH),
+            noticed_at: SystemTime::now(),
+        });
+    }
+
+    /// How long the node has been running since its last start, if it is running.
+    pub fn uptime(&self) -> Option<Duration> {
+        if !self.is_running() {
+            return None;
+        }
+        self.start_times
+            .last()
+            .map(|started| started.elapsed().unwrap_or_default())
+    }
+
     pub fn stop(&mut self) {
+        // A paused node would only get the stop signal once resumed, and be killed instead.
+        if self.is_paused() {
+            let _ = self.resume();
+        }
+        if let Some(process) = &mut self.process {
+            if let (Ok(Some(_)), false) = (process.child.try_wait(), process.stop_signaled) {
+                self.record_unexpected_exit();
+            }
+        }
+        if self.is_running() {
+            record_event(TimelineEventKind::NodeStopped {
+                node: self.name.clone(),
+            });
+        }
+        if let Some(sampler) = &self.resource_sampler {
+            sampler.set_pid(None);
+        }
+        if let Some(recorder) = &self.metrics_recorder {
+            recorder.set_url(None);
+        }
         self.process = None;
+        self.in_process_node = None;
+    }
+
+    /// Freezes the node with SIGSTOP. Unlike a stopped node, it keeps its connections open and
+    /// picks up where it was once resumed, while its peers get no answer in the meantime. Only
+    /// for nodes running as processes of their own.
+    pub fn pause(&mut self) -> Result<()> {
+        let target = self.pause_target()?;
+        ensure!(
+            send_signal(&target, "STOP"),
+            "Failed to pause node {}",
+            self.name
+        );
+        info!("Paused node {}", self.name);
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<()> {
+        let target = self.pause_target()?;
+        ensure!(
+            send_signal(&target, "CONT"),
+            "Failed to resume node {}",
+            self.name
+        );
+        info!("Resumed node {}", self.name);
+        Ok(())
+    }
+
+    /// Whether the node is paused, see `pause`.
+    pub fn is_paused(&self) -> bool {
+        self.process.as_ref().map_or(false, |process| {
+            process_state(process.child.id()) == Some('T')
+        })
+    }
+
+    /// What `pause` and `resume` signal.
+    pub(crate) fn pause_target(&self) -> Result<String> {
+        let process = self
+            .process
+            .as_ref()
+            .ok_or_else(|| anyhow!("Node {} is not running as a process", self.name))?;
+        // Signaling the Docker client would leave the container running
+        ensure!(
+            process.container.is_none(),
+            "Node {} runs in a container, it can't be paused",
+            self.name
+        );
+        Ok(process.signal_target())
+    }
+
+    /// Sends the stop signal to the node without waiting for it to exit, so that nodes stopped
+    /// together shut down concurrently rather than one grace period after the other. The node
+    /// still has to be stopped. Nothing is sent without a grace period, or to a container.
+    pub(crate) fn signal_stop(&mut self) {
+        if let Some(process) = &mut self.process {
+            if process.grace_period.is_some() && process.container.is_none() {
+                process.stop_signaled = process.signal(process.stop_signal);
+            }
+        }
     }
 
     pub fn port(&self) -> u16 {
@@ -169,25 +892,275 @@ impl LocalNode {
         &mut self.config
     }
 
+    /// Applies `f` to the node config on disk, and keeps the config of this LocalNode in sync.
+    /// The config is left untouched if the modified one doesn't load. A running node picks up
+    /// the change from its next start.
+    pub fn modify_config<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut NodeConfig),
+    {
+        let config_path = self.config_path();
+        let original = fs::read(&config_path)?;
+        let mut config = NodeConfig::load(&config_path)
+            .with_context(|| format!("Failed to load NodeConfig from file: {:?}", config_path))?;
+        f(&mut config);
+        config.save(&config_path)?;
+        match NodeConfig::load(&config_path) {
+            Ok(config) => {
+                self.config = config;
+                Ok(())
+            }
+            Err(e) => {
+                fs::write(&config_path, original)?;
+                Err(anyhow!("Invalid config for node {}: {}", self.name, e))
+            }
+        }
+    }
+
+    /// Like `modify_config`, then restarts the node and waits until it is healthy.
+    pub async fn modify_config_and_restart<F>(&mut self, f: F, deadline: Instant) -> Result<()>
+    where
+        F: FnOnce(&mut NodeConfig),
+    {
+        self.modify_config(f)?;
+        self.stop();
+        self.start()?;
+        self.wait_until_healthy(deadline).await
+    }
+
+    /// Restarts the node with `version`, unless it already runs it. Fails without stopping the
+    /// node if the binary of `version` changed since it was registered.
     pub fn upgrade(&mut self, version: LocalVersion) -> Result<()> {
+        version.verify()?;
+        if self.version.version() == version.version() && self.is_running() {
+            info!(
+                "Node {} already runs version {}",
+                self.name,
+                version.version()
+            );
+            return Ok(());
+        }
+        if let (Some(current), Some(new)) = (self.version.sha256(), version.sha256()) {
+            // Compat tests may register the same binary as two versions on purpose
+            if current == new && self.version.version() != version.version() {
+                warn!(
+                    "Upgrading node {} from {} to {}, but both have the binary {:?}",
+                    self.name,
+                    self.version.version(),
+                    version.version(),
+                    new
+                );
+            }
+        }
         self.stop();
         self.version = version;
         self.start()
     }
 
+    /// Like `upgrade`, but copies the node's databases first. If the upgraded node isn't healthy
+    /// by `deadline`, its databases are restored and it is started again with its previous
+    /// version, and the upgrade failure is returned.
+    pub async fn upgrade_with_rollback(
+        &mut self,
+        version: LocalVersion,
+        deadline: Instant,
+    ) -> Result<()> {
+        let snapshot_dir = self.directory.join("upgrade-snapshot");
+        if snapshot_dir.exists() {
+            fs::remove_dir_all(&snapshot_dir)?;
+        }
+        self.stop();
+        for (db, snapshot) in self.rollback_dbs(&snapshot_dir) {
+            if db.exists() {
+                copy_dir_all(&db, &snapshot)
+                    .with_context(|| format!("Failed to snapshot {:?}", db))?;
+            }
+        }
+
+        let previous_version = self.version.clone();
+        let upgraded = match self.upgrade(version) {
+            Ok(()) => self.wait_until_healthy(deadline).await,
+            Err(e) => Err(e),
+        };
+        let error = match upgraded {
+            Ok(()) => {
+                fs::remove_dir_all(&snapshot_dir)?;
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        warn!(
+            "Upgrade of node {} failed, rolling back to version {}: {:?}",
+            self.name,
+            previous_version.version(),
+            error
+        );
+        self.stop();
+        for (db, snapshot) in self.rollback_dbs(&snapshot_dir) {
+            if db.exists() {
+                fs::remove_dir_all(&db)?;
+            }
+            if snapshot.exists() {
+                fs::rename(&snapshot, &db)?;
+            }
+        }
+        fs::remove_dir_all(&snapshot_dir)?;
+        self.version = previous_version;
+        self.start()?;
+        Err(error.context(format!(
+            "Upgrade of node {} failed, rolled back to version {}",
+            self.name,
+            self.version.version()
+        )))
+    }
+
+    /// The databases restored on a failed upgrade, with where they are snapshotted.
+    fn rollback_dbs(&self, snapshot_dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+        vec![
+            (self.config.storage.dir(), snapshot_dir.join("db")),
+            (
+                self.config.base.data_dir.join(SECURE_STORAGE_DB_NAME),
+                snapshot_dir.join(SECURE_STORAGE_DB_NAME),
+            ),
+        ]
+    }
+
+    /// Backs up the database of the running node to `dest`, through the node's backup service,
+    /// up to the node's current ledger version.
+    pub async fn backup_db(&self, tools: &DbTools, dest: &Path) -> Result<()> {
+        let state = self
+            .rest_client()
+            .get_ledger_information()
+            .await?
+            .into_inner();
+        tools
+            .backup(
+                SocketAddr::new(self.ip(), self.config.storage.backup_service_address.port()),
+                dest,
+                state.epoch,
+                state.version,
+            )
+            .await
+    }
+
+    /// Replaces the database of the node with the backup in `src`. The node is stopped, its
+    /// storage directory wiped and restored, and the node started again.
+    pub fn restore_db(&mut self, tools: &DbTools, src: &Path) -> Result<()> {
+        self.stop();
+        let db_dir = self.config.storage.dir();
+        if db_dir.exists() {
+            fs::remove_dir_all(&db_dir)
+                .with_context(|| format!("Failed to wipe storage dir {:?}", db_dir))?;
+        }
+        tools.restore(src, &db_dir)?;
+        self.start()
+    }
+
+    /// Writes a consistent copy of the node's database to `dest`, as a RocksDB checkpoint (hard
+    /// linked where possible, so it is cheap). A running node is stopped meanwhile.
+    pub fn checkpoint_db(&mut self, dest: &Path) -> Result<()> {
+        let was_running = self.is_running();
+        self.stop();
+        let storage = &self.config.storage;
+        let result = AptosDB::open(
+            storage.dir(),
+            false, /* readonly */
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            storage.rocksdb_configs,
+            false, /* enable_indexer */
+            storage.target_snapshot_size,
+            storage.max_num_nodes_per_lru_cache_shard,
+        )
+        .and_then(|db| db.create_checkpoint(dest))
+        .with_context(|| format!("Failed to checkpoint the db of node {}", self.name));
+        if was_running {
+            self.start()?;
+        }
+        result
+    }
+
+    /// Stops the node and removes its ledger, state, consensus and state sync databases. The
+    /// node keeps its keys and consensus safety data.
+    pub fn remove_db(&mut self) -> Result<()> {
+        self.stop();
+        let storage_dir = self.config.storage.dir();
+        for db in [
+            LEDGER_DB_NAME,
+            STATE_MERKLE_DB_NAME,
+            CONSENSUS_DB_NAME,
+            STATE_SYNC_DB_NAME,
+        ] {
+            let path = storage_dir.join(db);
+            if path.exists() {
+                fs::remove_dir_all(&path)
+                    .with_context(|| format!("Failed to delete {:?}", path))?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_log_contents(&self) -> Result<String> {
         fs::read_to_string(self.log_path()).map_err(Into::into)
     }
 
+    pub fn get_stderr_contents(&self) -> Result<String> {
+        fs::read_to_string(self.stderr_log_path()).map_err(Into::into)
+    }
+
+    /// Lines written to the log file of the node from now on, following it across rotations
+    /// and restarts. The stream never ends, and stays empty for a node running in process.
+    pub fn log_stream(&self) -> impl Stream<Item = String> + Send + Unpin {
+        tail_lines(self.log_path())
+    }
+
+    /// Sum of the counter `name` over the samples having all the given `labels`, None if it has
+    /// no such samples.
+    pub async fn get_counter(&self, name: &str, labels: &[(&str, &str)]) -> Result<Option<f64>> {
+        self.metrics()
+            .await?
+            .get_typed(name, labels, MetricType::Counter)
+    }
+
+    /// Sum of the gauge `name` over the samples having all the given `labels`, None if it has
+    /// no such samples.
+    pub async fn get_gauge(&self, name: &str, labels: &[(&str, &str)]) -> Result<Option<f64>> {
+        self.metrics()
+            .await?
+            .get_typed(name, labels, MetricType::Gauge)
+    }
+
     pub async fn health_check(&mut self) -> Result<(), HealthCheckError> {
         debug!("Health check on node '{}'", self.name);
 
         if let Some(p) = &mut self.process {
-            match p.0.try_wait() {
+            match p.child.try_wait() {
                 // This would mean the child process has crashed
                 Ok(Some(status)) => {
-                    let error = format!("Node '{}' crashed with: {}", self.name, status);
-                    return Err(HealthCheckError::NotRunning(error));
+                    let mut signal = None;
+                    let mut core_dump = None;
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::process::ExitStatusExt;
+                        signal = status.signal();
+                        if let (Some(core_dumps_dir), true) =
+                            (&self.core_dumps_dir, status.core_dumped())
+                        {
+                            match collect_core_dump(p.child.id(), &self.directory, core_dumps_dir) {
+                                Ok(core) => core_dump = Some(core),
+                                Err(e) => warn!(
+                                    "Failed to collect core dump of node '{}': {}",
+                                    self.name, e
+                                ),
+                            }
+                        }
+                    }
+                    self.record_unexpected_exit();
+                    return Err(HealthCheckError::NotRunning(NotRunningReason::Exited {
+                        code: status.code(),
+                        signal,
+                        core_dump,
+                    }));
                 }
 
                 // This is the case where the node is still running
@@ -198,22 +1171,68 @@ impl LocalNode {
                     return Err(HealthCheckError::Unknown(e.into()));
                 }
             }
-        } else {
-            let error = format!("Node '{}' is stopped", self.name);
-            return Err(HealthCheckError::NotRunning(error));
+        } else if self.in_process_node.is_none() {
+            return Err(HealthCheckError::NotRunning(NotRunningReason::Stopped));
         }
 
-        self.inspection_client()
-            .get_node_metrics()
-            .await
-            .map(|_| ())
-            .map_err(HealthCheckError::Failure)?;
+        if self.health_check_config.metrics {
+            if let Err(e) = self.inspection_client().get_node_metrics().await {
+                let port = self.config.inspection_service.port;
+                return Err(HealthCheckError::Failure(
+                    self.request_failure("inspection service", port, e).await,
+                ));
+            }
+        }
 
-        self.rest_client()
-            .get_ledger_information()
-            .await
-            .map(|_| ())
-            .map_err(|err| HealthCheckError::Failure(err.into()))
+        if self.health_check_config.rest || self.health_check_config.ledger_progress {
+            let version = match self.rest_client().get_ledger_information().await {
+                Ok(state) => state.into_inner().version,
+                Err(RestError::Api(e)) => {
+                    return Err(HealthCheckError::Failure(HealthCheckFailure::RestStatus {
+                        status: e.status_code.as_u16(),
+                        message: e.error.message,
+                    }))
+                }
+                Err(RestError::Http(status)) => {
+                    return Err(HealthCheckError::Failure(HealthCheckFailure::RestStatus {
+                        status: status.as_u16(),
+                        message: status.to_string(),
+                    }))
+                }
+                Err(e) => {
+                    let port = self.config.api.address.port();
+                    return Err(HealthCheckError::Failure(
+                        self.request_failure("REST API", port, e.into()).await,
+                    ));
+                }
+            };
+            if self.health_check_config.ledger_progress {
+                // The first check of a run only records where the ledger is
+                let last_version = self.last_ledger_version.replace(version);
+                if matches!(last_version, Some(last_version) if version <= last_version) {
+                    return Err(HealthCheckError::Failure(
+                        HealthCheckFailure::LedgerNotProgressing { version },
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tells apart a request to `service` failing because nothing listens on its `port` from
+    /// one failing because the service misbehaves.
+    async fn request_failure(
+        &self,
+        service: &'static str,
+        port: u16,
+        error: anyhow::Error,
+    ) -> HealthCheckFailure {
+        let address = SocketAddr::new(self.ip(), port);
+        match tokio::time::timeout(PORT_PROBE_TIMEOUT, TcpStream::connect(address)).await {
+            Ok(Ok(_)) => HealthCheckFailure::ServiceError { service, error },
+            _ => HealthCheckFailure::PortClosed { service, port },
+        }
     }
 }
 
@@ -232,14 +1251,18 @@ impl Node for LocalNode {
     }
 
     fn rest_api_endpoint(&self) -> Url {
-        let ip = self.config().api.address.ip();
+        let ip = match self.network_namespace() {
+            Some(network_namespace) => network_namespace.address().into(),
+            None => self.config().api.address.ip(),
+        };
         let port = self.config().api.address.port();
         Url::from_str(&format!("http://{}:{}/v1", ip, port)).expect("Invalid URL.")
     }
 
     fn inspection_service_endpoint(&self) -> Url {
         Url::parse(&format!(
-            "http://localhost:{}",
+            "http://{}:{}",
+            self.ip(),
             self.inspection_service_port()
         ))
         .unwrap()
@@ -320,3 +1343,17 @@ impl Node for LocalNode {
 
 impl Validator for LocalNode {}
 impl FullNode for LocalNode {}
+
+fn copy_dir_all(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
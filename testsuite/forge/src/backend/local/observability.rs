@@ -0,0 +1,279 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! A Prometheus and a Grafana run in Docker next to a LocalSwarm, to look at its metrics while
+//! debugging it. Prometheus rereads the list of targets from a file, so that nodes added to the
+//! swarm later are scraped too. Grafana is provisioned with Prometheus as its data source and
+//! with the dashboards of the workspace, and lets anyone in as admin.
+//!
+//! Both containers use the host network, so they reach the nodes on the addresses the nodes
+//! listen on, and only listen on the loopback interface.
+
+use super::docker;
+use anyhow::{anyhow, bail, Context, Result};
+use aptos_config::utils::get_available_port;
+use aptos_logger::warn;
+use prometheus_http_query::Client as PrometheusClient;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+use url::Url;
+
+pub const PROMETHEUS_IMAGE: &str = "prom/prometheus:v2.39.1";
+pub const GRAFANA_IMAGE: &str = "grafana/grafana:9.1.7";
+
+/// How often targets are scraped, and how often the list of targets is reread.
+const SCRAPE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A metrics endpoint scraped by Prometheus, at `/metrics`.
+#[derive(Clone, Debug)]
+pub struct ScrapeTarget {
+    pub address: SocketAddr,
+    /// Labels added to every sample scraped from the target. `job` defaults to `forge`.
+    pub labels: BTreeMap<String, String>,
+}
+
+/// A target group of the Prometheus file based service discovery.
+#[derive(Serialize)]
+struct TargetGroup<'a> {
+    targets: [String; 1],
+    labels: &'a BTreeMap<String, String>,
+}
+
+/// Prometheus and Grafana containers, removed when dropped. Their data is kept in their
+/// directory.
+#[derive(Debug)]
+pub struct ObservabilityStack {
+    dir: PathBuf,
+    prometheus_port: u16,
+    grafana_port: u16,
+    /// Dashboards Grafana is provisioned with
+    dashboards_dir: Option<PathBuf>,
+    /// Targets by name
+    targets: BTreeMap<String, ScrapeTarget>,
+    /// Names of the running containers
+    containers: Vec<String>,
+}
+
+impl ObservabilityStack {
+    /// Starts Prometheus and Grafana, keeping their configs and data in `dir`. Grafana is
+    /// provisioned with the dashboards in `dashboards_dir`, if any.
+    pub fn start(dir: &Path, dashboards_dir: Option<&Path>) -> Result<Self> {
+        let mut stack = Self {
+            dir: dir.to_path_buf(),
+            prometheus_port: get_available_port(),
+            grafana_port: get_available_port(),
+            dashboards_dir: dashboards_dir.map(Path::to_path_buf),
+            targets: BTreeMap::new(),
+            containers: Vec::new(),
+        };
+        stack.write_configs()?;
+
+        let prometheus_dir = stack.dir.join("prometheus");
+        stack.run_container(
+            format!("forge-prometheus-{}", stack.prometheus_port),
+            PROMETHEUS_IMAGE,
+            &[],
+            &[
+                format!(
+                    "--config.file={}",
+                    prometheus_dir.join("prometheus.yml").display()
+                ),
+                format!(
+                    "--storage.tsdb.path={}",
+                    prometheus_dir.join("data").display()
+                ),
+                format!("--web.listen-address=127.0.0.1:{}", stack.prometheus_port),
+            ],
+        )?;
+
+        let grafana_dir = stack.dir.join("grafana");
+        stack.run_container(
+            format!("forge-grafana-{}", stack.grafana_port),
+            GRAFANA_IMAGE,
+            &[
+                ("GF_SERVER_HTTP_ADDR", "127.0.0.1".to_string()),
+                ("GF_SERVER_HTTP_PORT", stack.grafana_port.to_string()),
+                (
+                    "GF_PATHS_PROVISIONING",
+                    grafana_dir.join("provisioning").display().to_string(),
+                ),
+                (
+                    "GF_PATHS_DATA",
+                    grafana_dir.join("data").display().to_string(),
+                ),
+                ("GF_AUTH_ANONYMOUS_ENABLED", "true".to_string()),
+                ("GF_AUTH_ANONYMOUS_ORG_ROLE", "Admin".to_string()),
+                ("GF_AUTH_DISABLE_LOGIN_FORM", "true".to_string()),
+            ],
+            &[],
+        )?;
+        Ok(stack)
+    }
+
+    pub fn prometheus_url(&self) -> Url {
+        Url::parse(&format!("http://127.0.0.1:{}", self.prometheus_port)).unwrap()
+    }
+
+    pub fn grafana_url(&self) -> Url {
+        Url::parse(&format!("http://127.0.0.1:{}", self.grafana_port)).unwrap()
+    }
+
+    pub fn prometheus_client(&self) -> Result<PrometheusClient> {
+        PrometheusClient::try_from(self.prometheus_url().to_string())
+            .map_err(|e| anyhow!("Failed to create prometheus client: {}", e))
+    }
+
+    pub fn targets(&self) -> &BTreeMap<String, ScrapeTarget> {
+        &self.targets
+    }
+
+    /// Scrapes `target` from now on, under `name`, which is also its `instance` label. Replaces
+    /// the target already scraped under `name`, if any.
+    pub fn add_target(&mut self, name: &str, mut target: ScrapeTarget) -> Result<()> {
+        target
+            .labels
+            .insert("instance".to_string(), name.to_string());
+        self.targets.insert(name.to_string(), target);
+        self.write_targets()
+    }
+
+    /// Stops scraping the target `name`. The samples scraped so far are kept.
+    pub fn remove_target(&mut self, name: &str) -> Result<()> {
+        if self.targets.remove(name).is_some() {
+            self.write_targets()?;
+        }
+        Ok(())
+    }
+
+    fn targets_file(&self) -> PathBuf {
+        self.dir.join("prometheus").join("targets.json")
+    }
+
+    fn write_configs(&self) -> Result<()> {
+        let prometheus_dir = self.dir.join("prometheus");
+        fs::create_dir_all(prometheus_dir.join("data"))?;
+        self.write_targets()?;
+        fs::write(
+            prometheus_dir.join("prometheus.yml"),
+            format!(
+                "global:\n  scrape_interval: {interval}s\n  evaluation_interval: {interval}s\n\
+                 scrape_configs:\n  - job_name: forge\n    file_sd_configs:\n      \
+                 - files: ['{targets}']\n        refresh_interval: {interval}s\n",
+                interval = SCRAPE_INTERVAL.as_secs(),
+                targets = self.targets_file().display(),
+            ),
+        )?;
+
+        let provisioning_dir = self.dir.join("grafana").join("provisioning");
+        fs::create_dir_all(self.dir.join("grafana").join("data"))?;
+        fs::create_dir_all(provisioning_dir.join("datasources"))?;
+        fs::create_dir_all(provisioning_dir.join("dashboards"))?;
+        fs::write(
+            provisioning_dir.join("datasources").join("prometheus.yml"),
+            format!(
+                "apiVersion: 1\ndatasources:\n  - name: Prometheus\n    type: prometheus\n    \
+                 access: proxy\n    isDefault: true\n    url: {}\n",
+                self.prometheus_url()
+            ),
+        )?;
+        if let Some(dashboards_dir) = &self.dashboards_dir {
+            fs::write(
+                provisioning_dir.join("dashboards").join("forge.yml"),
+                format!(
+                    "apiVersion: 1\nproviders:\n  - name: forge\n    type: file\n    \
+                     options:\n      path: {}\n",
+                    dashboards_dir.display()
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the targets file, atomically as Prometheus may be reading it.
+    fn write_targets(&self) -> Result<()> {
+        let groups = self
+            .targets
+            .values()
+            .map(|target| TargetGroup {
+                targets: [target.address.to_string()],
+                labels: &target.labels,
+            })
+            .collect::<Vec<_>>();
+        let file = self.targets_file();
+        let temp_file = file.with_extension("json.tmp");
+        fs::write(&temp_file, serde_json::to_vec_pretty(&groups)?)?;
+        fs::rename(&temp_file, &file)?;
+        Ok(())
+    }
+
+    /// Runs `image` detached in the container `name`, with the stack's directory mounted at the
+    /// same path.
+    fn run_container(
+        &mut self,
+        name: String,
+        image: &str,
+        envs: &[(&str, String)],
+        args: &[String],
+    ) -> Result<()> {
+        let mut command = Command::new("docker");
+        command
+            .arg("run")
+            .arg("--rm")
+            .arg("--detach")
+            .arg("--name")
+            .arg(&name)
+            .arg("--network")
+            .arg("host")
+            .arg("--volume")
+            .arg(format!("{0}:{0}", self.dir.display()));
+        // The dashboards are read from the workspace.
+        if let Some(dashboards_dir) = &self.dashboards_dir {
+            command
+                .arg("--volume")
+                .arg(format!("{0}:{0}:ro", dashboards_dir.display()));
+        }
+        // Keep the files written by the container owned by the user running forge.
+        #[cfg(unix)]
+        if let Ok(metadata) = self.dir.metadata() {
+            use std::os::unix::fs::MetadataExt;
+            command
+                .arg("--user")
+                .arg(format!("{}:{}", metadata.uid(), metadata.gid()));
+        }
+        for (key, value) in envs {
+            command.arg("--env").arg(format!("{}={}", key, value));
+        }
+        command.arg(image).args(args);
+
+        let output = command
+            .output()
+            .context("Failed to run docker, is it installed?")?;
+        if !output.status.success() {
+            bail!(
+                "Failed to start {}: {}",
+                image,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        self.containers.push(name);
+        Ok(())
+    }
+}
+
+impl Drop for ObservabilityStack {
+    fn drop(&mut self) {
+        for container in &self.containers {
+            if let Err(e) = docker::remove_container(container, None) {
+                warn!("{}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Deterministic ports of local nodes, for swarms whose nodes have to be reachable at known
+//! addresses, e.g. behind a firewall or by tools configured ahead of time. Node `i` gets the
+//! `PORTS_PER_NODE` ports from `base_port + i * PORTS_PER_NODE`, `i` being the counter naming
+//! the nodes of the swarm.
+
+use anyhow::{anyhow, Context, Result};
+use aptos_config::{config::NodeConfig, network_id::NetworkId};
+use aptos_sdk::types::network_address::NetworkAddress;
+use std::{
+    convert::TryFrom,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+};
+
+/// Ports reserved for every node, a few more than used so that the layout can grow
+pub(crate) const PORTS_PER_NODE: u16 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct NodePorts {
+    api: u16,
+    inspection: u16,
+    backup: u16,
+    validator_network: u16,
+    public_network: u16,
+    vfn_network: u16,
+}
+
+impl NodePorts {
+    /// Ports of the node `index` of a swarm starting at `base_port`.
+    pub(crate) fn new(base_port: u16, index: u64) -> Result<Self> {
+        let port = |offset: u64| {
+            index
+                .checked_mul(PORTS_PER_NODE as u64)
+                .and_then(|first| first.checked_add(base_port as u64 + offset))
+                .and_then(|port| u16::try_from(port).ok())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Node {} has no ports left above base port {}",
+                        index,
+                        base_port
+                    )
+                })
+        };
+        Ok(Self {
+            api: port(0)?,
+            inspection: port(1)?,
+            backup: port(2)?,
+            validator_network: port(3)?,
+            public_network: port(4)?,
+            vfn_network: port(5)?,
+        })
+    }
+
+    fn all(&self) -> [(&'static str, u16); 6] {
+        [
+            ("REST API", self.api),
+            ("inspection service", self.inspection),
+            ("backup service", self.backup),
+            ("validator network", self.validator_network),
+            ("public network", self.public_network),
+            ("VFN network", self.vfn_network),
+        ]
+    }
+
+    /// Fails if another process listens on a port of the node `name`.
+    pub(crate) fn check_available(&self, name: &str) -> Result<()> {
+        for (service, port) in self.all() {
+            TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).with_context(|| {
+                format!(
+                    "Port {} of the {} of node {} is already in use",
+                    port, service, name
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Moves the services and networks of `config` to these ports. The public network of a VFN
+    /// is the one of its validator, which is on chain, so it can be kept as is.
+    pub(crate) fn apply(&self, config: &mut NodeConfig, keep_public_network: bool) {
+        config.api.address.set_port(self.api);
+        config.inspection_service.port = self.inspection;
+        config.storage.backup_service_address.set_port(self.backup);
+        if let Some(network) = config.validator_network.as_mut() {
+            network.listen_address = network_address(self.validator_network);
+        }
+        for network in config.full_node_networks.iter_mut() {
+            match network.network_id {
+                NetworkId::Public if !keep_public_network => {
+                    network.listen_address = network_address(self.public_network)
+                }
+                NetworkId::Vfn => network.listen_address = network_address(self.vfn_network),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn network_address(port: u16) -> NetworkAddress {
+    NetworkAddress::from(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_config::config::NetworkConfig;
+
+    #[test]
+    fn test_node_ports() {
+        let ports = NodePorts::new(20000, 3).unwrap();
+        assert_eq!(ports.api, 20030);
+        assert_eq!(ports.vfn_network, 20035);
+        assert!(NodePorts::new(65530, 0).is_err());
+        assert!(NodePorts::new(20000, u64::MAX).is_err());
+
+        let mut config = NodeConfig::default_for_validator();
+        let public = network_address(1234);
+        config.full_node_networks = vec![
+            NetworkConfig {
+                listen_address: public.clone(),
+                ..NetworkConfig::network_with_id(NetworkId::Public)
+            },
+            NetworkConfig::network_with_id(NetworkId::Vfn),
+        ];
+        ports.apply(&mut config, true);
+        assert_eq!(config.api.address.port(), 20030);
+        assert_eq!(config.inspection_service.port, 20031);
+        assert_eq!(config.storage.backup_service_address.port(), 20032);
+        assert_eq!(
+            config.validator_network.unwrap().listen_address.find_port(),
+            Some(20033)
+        );
+        assert_eq!(config.full_node_networks[0].listen_address, public);
+        assert_eq!(
+            config.full_node_networks[1].listen_address.find_port(),
+            Some(20035)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Runs a node under `perf record`. A new profile is written every time the node starts, and is
+/// complete once the node has been stopped.
+#[derive(Clone, Debug)]
+pub struct CpuProfiler {
+    /// Sampling frequency, in Hz
+    pub frequency: u32,
+    /// Record call graphs, much larger profiles but needed for flamegraphs
+    pub call_graph: bool,
+}
+
+impl Default for CpuProfiler {
+    fn default() -> Self {
+        Self {
+            frequency: 99,
+            call_graph: true,
+        }
+    }
+}
+
+impl CpuProfiler {
+    /// perf needs to be interrupted, not killed, to finish writing the profile.
+    pub const STOP_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+    /// Builds the command running `bin` under perf, arguments for `bin` can be appended to it.
+    pub fn command(&self, bin: &Path, profiles_dir: &Path) -> Command {
+        let mut command = Command::new("perf");
+        command
+            .arg("record")
+            .arg("-F")
+            .arg(self.frequency.to_string())
+            .arg("-o")
+            .arg(profiles_dir.join(format!("perf-{}.data", unix_timestamp())));
+        if self.call_graph {
+            command.arg("-g");
+        }
+        command.arg("--").arg(bin);
+        command
+    }
+}
+
+/// Runs a node under an allocation tracker. Every allocation is traced, which slows the node
+/// down a lot, so it is better enabled on a few nodes only. A new profile is written to the
+/// node's profiles directory every time the node starts, and is complete once the node has
+/// been stopped.
+#[derive(Clone, Debug)]
+pub enum AllocationTracker {
+    /// heaptrack, profiles are `heaptrack-<timestamp>.*` and can be read with
+    /// `heaptrack_print` or `heaptrack_gui`. heaptrack preloads its own allocation hooks, which
+    /// the node's statically linked jemalloc bypasses, so it only sees the allocations of
+    /// binaries built with the system allocator.
+    Heaptrack,
+    /// valgrind's massif, profiles are `massif-<timestamp>.out` and can be read with
+    /// `ms_print`. Allocations through the node's statically linked jemalloc are tracked.
+    Massif {
+        /// Track the pages mapped by the process rather than heap allocations, which also
+        /// covers memory not allocated through malloc
+        pages_as_heap: bool,
+    },
+}
+
+impl AllocationTracker {
+    /// The tracked node can take a while to exit, and the tracker to write its profile.
+    pub const STOP_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+    /// Builds the command running `bin` under the tracker, arguments for `bin` can be appended
+    /// to it. heaptrack runs the node from a wrapper script, so the returned command starts a
+    /// new process group, which should be signaled as a whole.
+    pub fn command(&self, bin: &Path, profiles_dir: &Path) -> Command {
+        match self {
+            AllocationTracker::Heaptrack => {
+                let mut command = Command::new("setsid");
+                command
+                    .arg("heaptrack")
+                    .arg("-o")
+                    .arg(profiles_dir.join(format!("heaptrack-{}", unix_timestamp())))
+                    .arg(bin);
+                command
+            }
+            AllocationTracker::Massif { pages_as_heap } => {
+                let mut command = Command::new("valgrind");
+                command
+                    .arg("--tool=massif")
+                    // Intercept the allocator linked into the binary instead of libc's.
+                    .arg("--soname-synonyms=somalloc=NONE")
+                    .arg(format!("--pages-as-heap={}", yes_no(*pages_as_heap)))
+                    .arg(format!(
+                        "--massif-out-file={}",
+                        profiles_dir
+                            .join(format!("massif-{}.out", unix_timestamp()))
+                            .display()
+                    ))
+                    .arg(bin);
+                command
+            }
+        }
+    }
+
+    /// Whether the command runs in its own process group, see `command`.
+    pub fn is_process_group(&self) -> bool {
+        matches!(self, AllocationTracker::Heaptrack)
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Enables jemalloc heap profiling of a node. Dumps are written to the node's profiles
+/// directory, as `jeprof.<pid>.<seq>.<kind>.heap`, and can be read with `jeprof`.
+#[derive(Clone, Debug)]
+pub struct HeapProfiler {
+    /// Average interval between allocation samples, as log2 of bytes
+    pub lg_prof_sample: u8,
+    /// If set, a dump is written every 2^lg_prof_interval allocated bytes
+    pub lg_prof_interval: Option<u8>,
+}
+
+impl Default for HeapProfiler {
+    fn default() -> Self {
+        Self {
+            lg_prof_sample: 19,
+            lg_prof_interval: None,
+        }
+    }
+}
+
+impl HeapProfiler {
+    const DUMP_PREFIX: &'static str = "jeprof";
+
+    /// Sets the jemalloc options on the node command.
+    pub fn configure(&self, command: &mut Command, profiles_dir: &Path) {
+        let mut conf = format!(
+            "prof:true,prof_active:true,lg_prof_sample:{},prof_prefix:{}",
+            self.lg_prof_sample,
+            profiles_dir.join(Self::DUMP_PREFIX).display()
+        );
+        if let Some(lg_prof_interval) = self.lg_prof_interval {
+            conf.push_str(&format!(",lg_prof_interval:{}", lg_prof_interval));
+        }
+        // The node uses unprefixed jemalloc where supported, the prefixed variable covers the
+        // other platforms.
+        command
+            .env("MALLOC_CONF", &conf)
+            .env("_RJEM_MALLOC_CONF", &conf);
+    }
+
+    /// Makes the process dump its heap profile right away, by calling into jemalloc through
+    /// gdb. Returns the written dump.
+    pub fn dump(pid: u32, profiles_dir: &Path) -> Result<PathBuf> {
+        let before = Self::dumps(profiles_dir)?;
+        let output = Command::new("gdb")
+            .args(&["-p", &pid.to_string(), "-batch", "-ex"])
+            .arg("call (int) mallctl(\"prof.dump\", 0, 0, 0, 0)")
+            .output()
+            .context("Failed to run gdb, is it installed?")?;
+        if !output.status.success() {
+            bail!(
+                "Failed to trigger a heap dump of process {}: {}",
+                pid,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Self::dumps(profiles_dir)?
+            .into_iter()
+            .find(|dump| !before.contains(dump))
+            .ok_or_else(|| {
+                anyhow!(
+                    "No heap dump written by process {}, was heap profiling enabled?",
+                    pid
+                )
+            })
+    }
+
+    /// Heap dumps in `profiles_dir`, oldest first.
+    pub fn dumps(profiles_dir: &Path) -> Result<Vec<PathBuf>> {
+        if !profiles_dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut dumps = Vec::new();
+        for entry in fs::read_dir(profiles_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(Self::DUMP_PREFIX) && name.ends_with(".heap") {
+                dumps.push((entry.metadata()?.modified()?, entry.path()));
+            }
+        }
+        dumps.sort();
+        Ok(dumps.into_iter().map(|(_, path)| path).collect())
+    }
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,531 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Evaluation of the common shapes of PromQL queries over metrics scraped by forge itself, so
+//! that tests querying metrics keep working on swarms without a Prometheus. Supported are
+//! selectors with `=`, `!=`, `=~` and `!~` label matchers, `rate` and `increase` of a selector
+//! over a range, and the `sum`, `avg`, `max`, `min` and `count` aggregations, with an
+//! optional `by` clause. Every sample gets an `instance` label with the name of its node.
+
+use crate::MetricsSnapshot;
+use anyhow::{bail, format_err, Result};
+use prometheus_http_query::response::PromqlResult;
+use regex::Regex;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// A series of the result of a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuerySeries {
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+}
+
+/// Evaluates `query` at the time of the latest snapshot of `history`, which is sorted oldest
+/// first. Range functions compare the latest snapshot to the earliest one within their range.
+pub fn evaluate_promql(query: &str, history: &[MetricsSnapshot]) -> Result<Vec<QuerySeries>> {
+    let expr = Parser::new(query).parse()?;
+    let latest = history
+        .last()
+        .ok_or_else(|| format_err!("No metrics scraped to evaluate '{}'", query))?;
+    expr.evaluate(latest, history)
+}
+
+/// The result of a query at `snapshot`'s time, as returned by the Prometheus API.
+pub fn to_promql_result(
+    snapshot: &MetricsSnapshot,
+    series: &[QuerySeries],
+) -> Result<PromqlResult> {
+    let time = snapshot
+        .time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let result: Vec<_> = series
+        .iter()
+        .map(|series| {
+            json!({
+                "metric": series.labels,
+                "value": [time, series.value.to_string()],
+            })
+        })
+        .collect();
+    Ok(serde_json::from_value(json!({
+        "resultType": "vector",
+        "result": result,
+    }))?)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Selector(Selector),
+    Range {
+        function: RangeFunction,
+        selector: Selector,
+        range: Duration,
+    },
+    Aggregation {
+        op: AggregationOp,
+        by: Vec<String>,
+        expr: Box<Expr>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum RangeFunction {
+    Rate,
+    Increase,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum AggregationOp {
+    Sum,
+    Avg,
+    Max,
+    Min,
+    Count,
+}
+
+#[derive(Debug)]
+struct Selector {
+    name: Option<String>,
+    matchers: Vec<Matcher>,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Equal(String, String),
+    NotEqual(String, String),
+    Regex(String, Regex),
+    NotRegex(String, Regex),
+}
+
+impl Matcher {
+    fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        // Missing labels match as empty ones.
+        let value = |label: &str| labels.get(label).map(String::as_str).unwrap_or("");
+        match self {
+            Matcher::Equal(label, expected) => value(label) == expected,
+            Matcher::NotEqual(label, expected) => value(label) != expected,
+            Matcher::Regex(label, regex) => regex.is_match(value(label)),
+            Matcher::NotRegex(label, regex) => !regex.is_match(value(label)),
+        }
+    }
+}
+
+impl Selector {
+    /// Samples of `snapshot` selected, by their labels, `__name__` and `instance` included.
+    fn select(&self, snapshot: &MetricsSnapshot) -> BTreeMap<BTreeMap<String, String>, f64> {
+        let mut selected = BTreeMap::new();
+        for (node, metrics) in &snapshot.nodes {
+            for sample in metrics.samples() {
+                if self
+                    .name
+                    .as_ref()
+                    .map_or(false, |name| name != &sample.name)
+                {
+                    continue;
+                }
+                let mut labels: BTreeMap<_, _> = sample
+                    .labels
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                labels.insert("__name__".to_string(), sample.name.clone());
+                labels.insert("instance".to_string(), node.clone());
+                if self.matchers.iter().all(|matcher| matcher.matches(&labels)) {
+                    selected.insert(labels, sample.value);
+                }
+            }
+        }
+        selected
+    }
+}
+
+impl Expr {
+    fn evaluate(
+        &self,
+        latest: &MetricsSnapshot,
+        history: &[MetricsSnapshot],
+    ) -> Result<Vec<QuerySeries>> {
+        Ok(match self {
+            Expr::Selector(selector) => selector
+                .select(latest)
+                .into_iter()
+                .map(|(labels, value)| QuerySeries { labels, value })
+                .collect(),
+            Expr::Range {
+                function,
+                selector,
+                range,
+            } => {
+                let earliest = history
+                    .iter()
+                    .find(|snapshot| {
+                        latest
+                            .time
+                            .duration_since(snapshot.time)
+                            .map_or(false, |age| age <= *range)
+                    })
+                    .filter(|earliest| earliest.time < latest.time)
+                    .ok_or_else(|| {
+                        format_err!(
+                            "Range functions need two scrapes within their {}s range",
+                            range.as_secs()
+                        )
+                    })?;
+                let window = latest
+                    .time
+                    .duration_since(earliest.time)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                let before = selector.select(earliest);
+                selector
+                    .select(latest)
+                    .into_iter()
+                    .filter_map(|(mut labels, value)| {
+                        let before = *before.get(&labels)?;
+                        // Counters lower than before were reset by a restart.
+                        let delta = if value < before {
+                            value
+                        } else {
+                            value - before
+                        };
+                        labels.remove("__name__");
+                        let value = match function {
+                            RangeFunction::Rate => delta / window,
+                            RangeFunction::Increase => delta,
+                        };
+                        Some(QuerySeries { labels, value })
+                    })
+                    .collect()
+            }
+            Expr::Aggregation { op, by, expr } => {
+                let mut groups: BTreeMap<BTreeMap<String, String>, Vec<f64>> = BTreeMap::new();
+                for series in expr.evaluate(latest, history)? {
+                    let labels = by
+                        .iter()
+                        .filter_map(|label| {
+                            Some((label.clone(), series.labels.get(label)?.clone()))
+                        })
+                        .collect();
+                    groups.entry(labels).or_default().push(series.value);
+                }
+                groups
+                    .into_iter()
+                    .map(|(labels, values)| QuerySeries {
+                        labels,
+                        value: op.apply(&values),
+                    })
+                    .collect()
+            }
+        })
+    }
+}
+
+impl AggregationOp {
+    fn apply(self, values: &[f64]) -> f64 {
+        match self {
+            AggregationOp::Sum => values.iter().sum(),
+            AggregationOp::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            AggregationOp::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            AggregationOp::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            AggregationOp::Count => values.len() as f64,
+        }
+    }
+}
+
+struct Parser<'a> {
+    query: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(query: &'a str) -> Self {
+        Self { query, rest: query }
+    }
+
+    fn parse(mut self) -> Result<Expr> {
+        let expr = self.expr()?;
+        if !self.rest.trim().is_empty() {
+            return self.error("unexpected trailing input");
+        }
+        Ok(expr)
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let name = self.identifier();
+        let op = match name.as_deref() {
+            Some("sum") => Some(AggregationOp::Sum),
+            Some("avg") => Some(AggregationOp::Avg),
+            Some("max") => Some(AggregationOp::Max),
+            Some("min") => Some(AggregationOp::Min),
+            Some("count") => Some(AggregationOp::Count),
+            _ => None,
+        };
+        if let Some(op) = op {
+            // The grouping goes either before or after the aggregated expression.
+            let mut by = self.by()?;
+            self.expect("(")?;
+            let expr = Box::new(self.expr()?);
+            self.expect(")")?;
+            if by.is_empty() {
+                by = self.by()?;
+            }
+            return Ok(Expr::Aggregation { op, by, expr });
+        }
+        let function = match name.as_deref() {
+            Some("rate") => Some(RangeFunction::Rate),
+            Some("increase") => Some(RangeFunction::Increase),
+            _ => None,
+        };
+        if let Some(function) = function {
+            self.expect("(")?;
+            let selector = self.selector(None)?;
+            self.expect("[")?;
+            let range = self.duration()?;
+            self.expect("]")?;
+            self.expect(")")?;
+            return Ok(Expr::Range {
+                function,
+                selector,
+                range,
+            });
+        }
+        Ok(Expr::Selector(self.selector(name)?))
+    }
+
+    /// An optional `by (label, ...)` clause.
+    fn by(&mut self) -> Result<Vec<String>> {
+        let before = self.rest;
+        if self.identifier().as_deref() != Some("by") {
+            self.rest = before;
+            return Ok(Vec::new());
+        }
+        self.expect("(")?;
+        let mut labels = Vec::new();
+        while !self.consume(")") {
+            match self.identifier() {
+                Some(label) => labels.push(label),
+                None => return self.error("expected a label"),
+            }
+            self.consume(",");
+        }
+        Ok(labels)
+    }
+
+    fn selector(&mut self, name: Option<String>) -> Result<Selector> {
+        let name = match name {
+            Some(name) => Some(name),
+            None => self.identifier(),
+        };
+        let mut matchers = Vec::new();
+        if self.consume("{") {
+            while !self.consume("}") {
+                let label = match self.identifier() {
+                    Some(label) => label,
+                    None => return self.error("expected a label"),
+                };
+                let op = ["=~", "!~", "!=", "="]
+                    .iter()
+                    .find(|op| self.consume(op))
+                    .copied();
+                let value = self.string()?;
+                matchers.push(match op {
+                    Some("=") => Matcher::Equal(label, value),
+                    Some("!=") => Matcher::NotEqual(label, value),
+                    Some("=~") => Matcher::Regex(label, anchored_regex(&value)?),
+                    Some("!~") => Matcher::NotRegex(label, anchored_regex(&value)?),
+                    _ => return self.error("expected a label matcher"),
+                });
+                self.consume(",");
+            }
+        }
+        if name.is_none() && matchers.is_empty() {
+            return self.error("expected a selector");
+        }
+        Ok(Selector { name, matchers })
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let len = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+            .unwrap_or(self.rest.len());
+        if len == 0 || self.rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let (identifier, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Some(identifier.to_string())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let quote = match self.rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return self.error("expected a string"),
+        };
+        let mut value = String::new();
+        let mut chars = self.rest[1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                c if c == quote => {
+                    self.rest = &self.rest[i + 2..];
+                    return Ok(value);
+                }
+                c => value.push(c),
+            }
+        }
+        self.error("unterminated string")
+    }
+
+    fn duration(&mut self) -> Result<Duration> {
+        self.skip_whitespace();
+        let len = self
+            .rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest.len());
+        let amount: u64 = match self.rest[..len].parse() {
+            Ok(amount) => amount,
+            Err(_) => return self.error("expected a duration"),
+        };
+        self.rest = &self.rest[len..];
+        let unit = match self.identifier().as_deref() {
+            Some("s") => 1,
+            Some("m") => 60,
+            Some("h") => 60 * 60,
+            Some("d") => 24 * 60 * 60,
+            _ => return self.error("expected a duration unit"),
+        };
+        Ok(Duration::from_secs(amount * unit))
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.consume(token) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", token))
+        }
+    }
+
+    fn consume(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        bail!(
+            "Unsupported query '{}' at position {}: {}",
+            self.query,
+            self.query.len() - self.rest.len(),
+            message
+        )
+    }
+}
+
+/// PromQL regexes match whole label values.
+fn anchored_regex(regex: &str) -> Result<Regex> {
+    Ok(Regex::new(&format!("^(?:{})$", regex))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Metrics;
+
+    fn snapshot(secs: u64, nodes: &[(&str, &str)]) -> MetricsSnapshot {
+        let mut snapshot = MetricsSnapshot::new(
+            nodes
+                .iter()
+                .map(|(node, text)| (node.to_string(), text.parse::<Metrics>().unwrap()))
+                .collect(),
+        );
+        snapshot.time = UNIX_EPOCH + Duration::from_secs(secs);
+        snapshot
+    }
+
+    fn values(query: &str, history: &[MetricsSnapshot]) -> Vec<(Vec<(String, String)>, f64)> {
+        evaluate_promql(query, history)
+            .unwrap()
+            .into_iter()
+            .map(|series| (series.labels.into_iter().collect(), series.value))
+            .collect()
+    }
+
+    fn label(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_evaluate_promql() {
+        let history = vec![
+            snapshot(
+                100,
+                &[
+                    (
+                        "0",
+                        "txns{stage=\"committed\"} 10\ntxns{stage=\"expired\"} 1",
+                    ),
+                    ("1", "txns{stage=\"committed\"} 20"),
+                ],
+            ),
+            snapshot(
+                110,
+                &[
+                    (
+                        "0",
+                        "txns{stage=\"committed\"} 30\ntxns{stage=\"expired\"} 1",
+                    ),
+                    // Restarted
+                    ("1", "txns{stage=\"committed\"} 5"),
+                ],
+            ),
+        ];
+
+        assert_eq!(
+            values("txns{instance=\"1\"}", &history),
+            vec![(
+                vec![
+                    label("__name__", "txns"),
+                    label("instance", "1"),
+                    label("stage", "committed")
+                ],
+                5.0
+            )]
+        );
+        assert_eq!(
+            values("sum(rate(txns{stage=~\"comm.*\"}[1m]))", &history),
+            vec![(vec![], 2.5)]
+        );
+        assert_eq!(
+            values("max by (instance) (increase(txns[30s]))", &history),
+            vec![
+                (vec![label("instance", "0")], 20.0),
+                (vec![label("instance", "1")], 5.0)
+            ]
+        );
+        assert_eq!(
+            values("count(txns{stage!=\"expired\"}) by (stage)", &history),
+            vec![(vec![label("stage", "committed")], 2.0)]
+        );
+        // The earlier snapshot is out of the range
+        assert!(evaluate_promql("rate(txns[5s])", &history).is_err());
+        assert!(evaluate_promql("txns / 2", &history).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{interface::system_metrics::SystemMetrics, ResourceBudget};
+use anyhow::{anyhow, bail, Result};
+use aptos_infallible::Mutex;
+use aptos_logger::warn;
+use prometheus_http_query::response::Sample;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+
+/// Resources used by a node at some point in time.
+#[derive(Clone, Debug)]
+pub struct ResourceUsage {
+    pub time: SystemTime,
+    /// Percent of a single core, above 100 when several cores are used
+    pub cpu_percent: f32,
+    pub rss_bytes: u64,
+    /// Size of the node directory, databases included
+    pub disk_bytes: u64,
+    /// Bytes read from and written to disk by the process since it started
+    pub disk_read_bytes: u64,
+    pub disk_written_bytes: u64,
+    /// Bytes received and sent since it was created by the network namespace of the process,
+    /// on other interfaces than the loopback one. Only reflects the node's traffic if it has
+    /// its own namespace, see `LocalSwarm::isolate_network`.
+    pub network_received_bytes: Option<u64>,
+    pub network_sent_bytes: Option<u64>,
+}
+
+/// Measures the resources used by process `pid` and the size of `dir`. CPU usage is averaged
+/// over `window`, which this blocks for.
+pub fn measure(pid: u32, dir: &Path, window: Duration) -> Result<ResourceUsage> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    if !system.refresh_process(pid) {
+        bail!("Process {} not found", pid);
+    }
+    thread::sleep(window);
+    system.refresh_process(pid);
+    let process = system
+        .process(pid)
+        .ok_or_else(|| anyhow!("Process {} exited", pid))?;
+    let disk_usage = process.disk_usage();
+    let network = network_bytes(pid.as_u32());
+    Ok(ResourceUsage {
+        time: SystemTime::now(),
+        cpu_percent: process.cpu_usage(),
+        // sysinfo reports KiB
+        rss_bytes: process.memory() * 1024,
+        disk_bytes: dir_size(dir),
+        disk_read_bytes: disk_usage.total_read_bytes,
+        disk_written_bytes: disk_usage.total_written_bytes,
+        network_received_bytes: network.map(|(received, _)| received),
+        network_sent_bytes: network.map(|(_, sent)| sent),
+    })
+}
+
+/// Bytes received and sent by the network namespace of process `pid`, excluding loopback.
+fn network_bytes(pid: u32) -> Option<(u64, u64)> {
+    let dev = fs::read_to_string(format!("/proc/{}/net/dev", pid)).ok()?;
+    parse_net_dev(&dev)
+}
+
+/// Sums the bytes received and sent by the interfaces listed in `/proc/net/dev`, which has two
+/// header lines, then `<interface>: <8 receive counters> <8 transmit counters>` per interface.
+fn parse_net_dev(dev: &str) -> Option<(u64, u64)> {
+    let mut total = (0, 0);
+    for line in dev.lines().skip(2) {
+        let (interface, counters) = line.split_once(':')?;
+        if interface.trim() == "lo" {
+            continue;
+        }
+        let counters = counters
+            .split_whitespace()
+            .map(str::parse::<u64>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        total.0 += counters.first()?;
+        total.1 += counters.get(8)?;
+    }
+    Some(total)
+}
+
+/// Size of the files under `dir`. Files removed while walking it, e.g. by compactions, are
+/// skipped.
+fn dir_size(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or_default(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// The resource samples of `nodes` taken between `start` and `end` as system metrics, the way
+/// they are queried from Prometheus on k8s: averaged over the nodes every `step`, CPU in cores,
+/// memory in bytes, and disk and network in bytes per second. Throughputs are computed between
+/// consecutive samples of a node, skipping the restarts of the node where its counters reset.
+pub fn system_metrics(
+    nodes: &[Vec<ResourceUsage>],
+    start: SystemTime,
+    end: SystemTime,
+    step: Duration,
+) -> Result<SystemMetrics> {
+    let step = step.as_secs().max(1);
+    let mut cpu = Series::new(step);
+    let mut memory = Series::new(step);
+    let mut disk_read = Series::new(step);
+    let mut disk_written = Series::new(step);
+    let mut network_received = Series::new(step);
+    let mut network_sent = Series::new(step);
+    for samples in nodes {
+        let mut previous: Option<&ResourceUsage> = None;
+        for usage in samples {
+            if usage.time >= start && usage.time <= end {
+                cpu.add(usage.time, usage.cpu_percent as f64 / 100.0);
+                memory.add(usage.time, usage.rss_bytes as f64);
+                if let Some(previous) = previous {
+                    let rate = |before: u64, after: u64| {
+                        let elapsed = usage.time.duration_since(previous.time).ok()?;
+                        if after < before || elapsed.is_zero() {
+                            return None;
+                        }
+                        Some((after - before) as f64 / elapsed.as_secs_f64())
+                    };
+                    disk_read.add_opt(
+                        usage.time,
+                        rate(previous.disk_read_bytes, usage.disk_read_bytes),
+                    );
+                    disk_written.add_opt(
+                        usage.time,
+                        rate(previous.disk_written_bytes, usage.disk_written_bytes),
+                    );
+                    if let (Some(before), Some(after)) = (
+                        previous.network_received_bytes,
+                        usage.network_received_bytes,
+                    ) {
+                        network_received.add_opt(usage.time, rate(before, after));
+                    }
+                    if let (Some(before), Some(after)) =
+                        (previous.network_sent_bytes, usage.network_sent_bytes)
+                    {
+                        network_sent.add_opt(usage.time, rate(before, after));
+                    }
+                }
+            }
+            previous = Some(usage);
+        }
+    }
+    Ok(SystemMetrics::new(cpu.samples()?, memory.samples()?)
+        .with_disk_metrics(disk_read.samples()?, disk_written.samples()?)
+        .with_network_metrics(network_received.samples()?, network_sent.samples()?))
+}
+
+/// Values of a metric averaged over buckets of `step` seconds.
+struct Series {
+    step: u64,
+    buckets: BTreeMap<u64, (f64, usize)>,
+}
+
+impl Series {
+    fn new(step: u64) -> Self {
+        Self {
+            step,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, time: SystemTime, value: f64) {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let bucket = self.buckets.entry(secs / self.step).or_default();
+        bucket.0 += value;
+        bucket.1 += 1;
+    }
+
+    fn add_opt(&mut self, time: SystemTime, value: Option<f64>) {
+        if let Some(value) = value {
+            self.add(time, value);
+        }
+    }
+
+    fn samples(&self) -> Result<Vec<Sample>> {
+        self.buckets
+            .iter()
+            .map(|(bucket, (sum, count))| {
+                let value = sum / *count as f64;
+                Ok(serde_json::from_value(json!([
+                    bucket * self.step,
+                    value.to_string()
+                ]))?)
+            })
+            .collect()
+    }
+}
+
+/// Adds up the resources used by the nodes of a swarm, as sampled, against a budget.
+#[derive(Debug)]
+pub struct ResourceBudgetTracker {
+    budget: ResourceBudget,
+    state: Mutex<BudgetState>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    cpu_seconds: f64,
+    /// Latest RSS of every sampled process
+    rss_bytes: HashMap<u32, u64>,
+    exceeded: Option<String>,
+}
+
+impl ResourceBudgetTracker {
+    pub fn new(budget: ResourceBudget) -> Self {
+        Self {
+            budget,
+            state: Mutex::new(BudgetState::default()),
+        }
+    }
+
+    /// Adds a sample of process `pid` measured over `window`. Returns whether the budget is
+    /// exceeded.
+    fn record(&self, pid: u32, usage: &ResourceUsage, window: Duration) -> bool {
+        let mut state = self.state.lock();
+        state.cpu_seconds += usage.cpu_percent as f64 / 100.0 * window.as_secs_f64();
+        state.rss_bytes.insert(pid, usage.rss_bytes);
+        if state.exceeded.is_none() {
+            let rss_bytes = state.rss_bytes.values().sum();
+            if let Err(e) = self.budget.check(state.cpu_seconds, rss_bytes) {
+                state.exceeded = Some(e.to_string());
+            }
+        }
+        state.exceeded.is_some()
+    }
+
+    /// Why the budget was exceeded, if it was.
+    pub fn exceeded(&self) -> Option<String> {
+        self.state.lock().exceeded.clone()
+    }
+}
+
+#[derive(Debug, Default)]
+struct SamplerState {
+    /// Process being sampled, sampling pauses while the node isn't running
+    pid: Option<u32>,
+    samples: Vec<ResourceUsage>,
+    budget: Option<Arc<ResourceBudgetTracker>>,
+    stopped: bool,
+}
+
+/// Samples the resources used by a node on a background thread, until dropped.
+#[derive(Debug)]
+pub struct ResourceSampler {
+    state: Arc<Mutex<SamplerState>>,
+}
+
+impl ResourceSampler {
+    pub fn start(dir: PathBuf, interval: Duration) -> Self {
+        let state = Arc::new(Mutex::new(SamplerState::default()));
+        let thread_state = state.clone();
+        thread::spawn(move || loop {
+            let pid = {
+                let state = thread_state.lock();
+                if state.stopped {
+                    break;
+                }
+                state.pid
+            };
+            match pid {
+                // The measurement window spaces the samples.
+                Some(pid) => {
+                    if let Ok(usage) = measure(pid, &dir, interval) {
+                        let mut state = thread_state.lock();
+                        // The node may have been restarted meanwhile.
+                        if state.pid == Some(pid) {
+                            let exceeded = state
+                                .budget
+                                .as_ref()
+                                .map_or(false, |budget| budget.record(pid, &usage, interval));
+                            state.samples.push(usage);
+                            if exceeded {
+                                kill_over_budget(pid);
+                            }
+                        }
+                    }
+                }
+                None => thread::sleep(interval),
+            }
+        });
+        Self { state }
+    }
+
+    pub fn set_pid(&self, pid: Option<u32>) {
+        self.state.lock().pid = pid;
+    }
+
+    /// Adds the samples taken from now on to `budget`. The node is killed once the budget is
+    /// exceeded, so that a test over budget fails fast instead of overloading the machine.
+    pub fn set_budget(&self, budget: Option<Arc<ResourceBudgetTracker>>) {
+        self.state.lock().budget = budget;
+    }
+
+    /// Samples taken so far, oldest first.
+    pub fn samples(&self) -> Vec<ResourceUsage> {
+        self.state.lock().samples.clone()
+    }
+}
+
+/// Kills a node process over budget. Nodes running in process can't be killed without forge.
+fn kill_over_budget(pid: u32) {
+    if pid == std::process::id() {
+        return;
+    }
+    warn!("Killing process {} as the resource budget is exceeded", pid);
+    if let Err(e) = Command::new("kill")
+        .args(["-KILL", &pid.to_string()])
+        .status()
+    {
+        warn!("Failed to kill process {}: {}", pid, e);
+    }
+}
+
+impl Drop for ResourceSampler {
+    fn drop(&mut self) {
+        self.state.lock().stopped = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::system_metrics::{MetricsThreshold, SystemMetricsThreshold};
+
+    #[test]
+    fn test_parse_net_dev() {
+        let dev = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 9000      90    0    0    0     0          0         0     9000      90    0    0    0     0       0          0
+  eth0: 1200      12    0    0    0     0          0         0      340       4    0    0    0     0       0          0
+  eth1:   30       1    0    0    0     0          0         0        5       1    0    0    0     0       0          0
+";
+        assert_eq!(parse_net_dev(dev), Some((1230, 345)));
+        assert_eq!(parse_net_dev("header\nheader\neth0: garbage\n"), None);
+    }
+
+    #[test]
+    fn test_system_metrics() {
+        let start = UNIX_EPOCH + Duration::from_secs(1000);
+        let usage = |secs: u64, cpu_percent: f32, disk_read_bytes: u64| ResourceUsage {
+            time: start + Duration::from_secs(secs),
+            cpu_percent,
+            rss_bytes: 1 << 20,
+            disk_bytes: 0,
+            disk_read_bytes,
+            disk_written_bytes: 0,
+            network_received_bytes: None,
+            network_sent_bytes: None,
+        };
+        let nodes = vec![
+            vec![
+                usage(0, 100.0, 0),
+                usage(1, 100.0, 100),
+                usage(2, 50.0, 300),
+            ],
+            // Restarted between its samples, its counters reset
+            vec![usage(0, 300.0, 0), usage(1, 100.0, 300), usage(2, 50.0, 10)],
+        ];
+        let metrics = system_metrics(
+            &nodes,
+            start,
+            start + Duration::from_secs(2),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        let threshold = |cpu: usize, disk: usize| {
+            SystemMetricsThreshold::new(
+                MetricsThreshold::new(cpu, 0),
+                MetricsThreshold::new(2 << 20, 0),
+            )
+            .with_disk_thresholds(MetricsThreshold::new(disk, 0), MetricsThreshold::new(0, 0))
+        };
+        // CPU averages 2 cores at first, disk reads 200 bytes per second at most.
+        threshold(2, 200).ensure_threshold(&metrics).unwrap();
+        threshold(1, 200).ensure_threshold(&metrics).unwrap_err();
+        threshold(2, 199).ensure_threshold(&metrics).unwrap_err();
+    }
+
+    #[test]
+    fn test_resource_budget_tracker() {
+        let usage = |cpu_percent: f32, rss_bytes: u64| ResourceUsage {
+            time: SystemTime::now(),
+            cpu_percent,
+            rss_bytes,
+            disk_bytes: 0,
+            disk_read_bytes: 0,
+            disk_written_bytes: 0,
+            network_received_bytes: None,
+            network_sent_bytes: None,
+        };
+        let tracker = ResourceBudgetTracker::new(ResourceBudget {
+            max_cpu_seconds: Some(10.0),
+            max_rss_bytes: Some(3 << 20),
+        });
+        let window = Duration::from_secs(2);
+        assert!(!tracker.record(1, &usage(200.0, 1 << 20), window));
+        // RSS is summed over processes, its latest sample only.
+        assert!(!tracker.record(2, &usage(100.0, 2 << 20), window));
+        assert!(!tracker.record(2, &usage(50.0, 1 << 20), window));
+        assert!(tracker.exceeded().is_none());
+        assert!(tracker.record(1, &usage(200.0, 1 << 20), window));
+        assert!(tracker.exceeded().unwrap().contains("CPU seconds"));
+    }
+}
@@ -0,0 +1,296 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Consensus safety checks from the databases of the validators: conflicting commits across
+//! their ledgers, and safety data that is inconsistent or went backwards.
+
+use crate::{LocalNode, LocalSwarm, NodeExt, Result};
+use anyhow::{bail, Context};
+use aptos_config::config::NO_OP_STORAGE_PRUNER_CONFIG;
+use aptos_global_constants::SAFETY_DATA;
+use aptos_logger::info;
+use aptos_sdk::{crypto::HashValue, types::ledger_info::LedgerInfoWithSignatures};
+use aptos_secure_storage::{KVStorage, Storage};
+use aptosdb::AptosDB;
+use consensus_types::safety_data::SafetyData;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+use storage_interface::DbReader;
+
+/// How long a validator has to become healthy again once its safety data has been read
+const SAFETY_READ_RESTART_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a validator's databases say about what it committed and voted for.
+#[derive(Clone, Debug)]
+pub struct ValidatorSafetyRecord {
+    /// Epoch ending ledger infos followed by the latest one
+    pub ledger_infos: Vec<LedgerInfoWithSignatures>,
+    /// None if the validator never persisted safety data
+    pub safety_data: Option<SafetyData>,
+}
+
+impl ValidatorSafetyRecord {
+    /// Reads the ledger and safety rules storage of `node`, which must be stopped as the safety
+    /// rules storage can't be shared.
+    pub fn read(node: &LocalNode) -> Result<Self> {
+        let config = node.config();
+        let db = AptosDB::open(
+            config.storage.dir(),
+            true, /* readonly */
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            config.storage.rocksdb_configs,
+            false, /* enable_indexer */
+            config.storage.target_snapshot_size,
+            config.storage.max_num_nodes_per_lru_cache_shard,
+        )
+        .with_context(|| format!("Failed to open the db of validator {}", node.name()))?;
+        let latest = db.get_latest_ledger_info()?;
+        let mut ledger_infos = vec![];
+        let mut epoch = 0;
+        while epoch < latest.ledger_info().epoch() {
+            let proof = db.get_epoch_ending_ledger_infos(epoch, latest.ledger_info().epoch())?;
+            epoch += proof.ledger_info_with_sigs.len() as u64;
+            ledger_infos.extend(proof.ledger_info_with_sigs);
+            if !proof.more {
+                break;
+            }
+        }
+        ledger_infos.push(latest);
+
+        let storage = Storage::from(&config.consensus.safety_rules.backend);
+        let safety_data = match storage.get::<SafetyData>(SAFETY_DATA) {
+            Ok(response) => Some(response.value),
+            Err(aptos_secure_storage::Error::KeyNotSet(_)) => None,
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Failed to read the safety data of validator {}",
+                        node.name()
+                    )
+                })
+            }
+        };
+        Ok(Self {
+            ledger_infos,
+            safety_data,
+        })
+    }
+}
+
+/// Safety records of every validator, by name.
+#[derive(Clone, Debug, Default)]
+pub struct ConsensusSafetySnapshot {
+    pub validators: BTreeMap<String, ValidatorSafetyRecord>,
+}
+
+impl ConsensusSafetySnapshot {
+    /// Conflicting commits across validators, and safety data breaking its own invariants.
+    pub fn violations(&self) -> Vec<String> {
+        let mut violations = vec![];
+        let mut blocks: BTreeMap<(u64, u64), (HashValue, &str)> = BTreeMap::new();
+        let mut versions: BTreeMap<u64, (HashValue, &str)> = BTreeMap::new();
+        for (name, record) in &self.validators {
+            for ledger_info in &record.ledger_infos {
+                let commit = ledger_info.commit_info();
+                let (block, committed_by) = *blocks
+                    .entry((commit.epoch(), commit.round()))
+                    .or_insert((commit.id(), name.as_str()));
+                if block != commit.id() {
+                    violations.push(format!(
+                        "{} committed block {} at epoch {} round {}, {} committed block {}",
+                        committed_by,
+                        block,
+                        commit.epoch(),
+                        commit.round(),
+                        name,
+                        commit.id()
+                    ));
+                }
+                let (state, committed_by) = *versions
+                    .entry(commit.version())
+                    .or_insert((commit.executed_state_id(), name.as_str()));
+                if state != commit.executed_state_id() {
+                    violations.push(format!(
+                        "{} committed state {} at version {}, {} committed state {}",
+                        committed_by,
+                        state,
+                        commit.version(),
+                        name,
+                        commit.executed_state_id()
+                    ));
+                }
+            }
+
+            if let Some(data) = &record.safety_data {
+                if data.preferred_round > data.one_chain_round {
+                    violations.push(format!(
+                        "{} has a 2-chain round {} above its 1-chain round {}",
+                        name, data.preferred_round, data.one_chain_round
+                    ));
+                }
+                if let Some(vote) = &data.last_vote {
+                    let round = vote.vote_data().proposed().round();
+                    if vote.epoch() != data.epoch || round > data.last_voted_round {
+                        violations.push(format!(
+                            "{} last voted at epoch {} round {}, but its safety data is at epoch {} round {}",
+                            name,
+                            vote.epoch(),
+                            round,
+                            data.epoch,
+                            data.last_voted_round
+                        ));
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    /// Safety data that went backwards since `earlier`, or a different vote in the same round,
+    /// i.e. a validator that could vote twice in a round.
+    pub fn violations_since(&self, earlier: &ConsensusSafetySnapshot) -> Vec<String> {
+        let mut violations = vec![];
+        for (name, record) in &self.validators {
+            let (before, after) = match (
+                earlier
+                    .validators
+                    .get(name)
+                    .and_then(|r| r.safety_data.as_ref()),
+                &record.safety_data,
+            ) {
+                (Some(before), Some(after)) => (before, after),
+                (Some(before), None) => {
+                    violations.push(format!(
+                        "{} lost its safety data, it was at epoch {} round {}",
+                        name, before.epoch, before.last_voted_round
+                    ));
+                    continue;
+                }
+                _ => continue,
+            };
+            if (after.epoch, after.last_voted_round) < (before.epoch, before.last_voted_round) {
+                violations.push(format!(
+                    "{} went back from epoch {} round {} to epoch {} round {}",
+                    name,
+                    before.epoch,
+                    before.last_voted_round,
+                    after.epoch,
+                    after.last_voted_round
+                ));
+            }
+            if let (Some(vote_before), Some(vote_after)) = (&before.last_vote, &after.last_vote) {
+                let round = vote_after.vote_data().proposed().round();
+                if vote_before.epoch() == vote_after.epoch()
+                    && vote_before.vote_data().proposed().round() == round
+                    && vote_before.vote_data() != vote_after.vote_data()
+                {
+                    violations.push(format!(
+                        "{} voted for two different blocks at epoch {} round {}",
+                        name,
+                        vote_after.epoch(),
+                        round
+                    ));
+                }
+            }
+        }
+        violations
+    }
+}
+
+impl LocalSwarm {
+    /// Reads the safety record of every validator, stopping each in turn as its safety rules
+    /// storage can't be read while it runs, and restarting the ones that were running.
+    pub async fn consensus_safety_snapshot(&mut self) -> Result<ConsensusSafetySnapshot> {
+        let mut snapshot = ConsensusSafetySnapshot::default();
+        for validator in self.validators_mut() {
+            let was_running = validator.is_running();
+            validator.stop();
+            let record = ValidatorSafetyRecord::read(validator);
+            if was_running {
+                validator.start()?;
+                validator
+                    .wait_until_healthy(Instant::now() + SAFETY_READ_RESTART_TIMEOUT)
+                    .await?;
+            }
+            snapshot
+                .validators
+                .insert(validator.name().to_string(), record?);
+        }
+        Ok(snapshot)
+    }
+
+    /// Takes a safety snapshot, failing if it shows conflicting commits, inconsistent safety
+    /// data, or safety data that went backwards since `earlier`.
+    pub async fn check_consensus_safety(
+        &mut self,
+        earlier: Option<&ConsensusSafetySnapshot>,
+    ) -> Result<ConsensusSafetySnapshot> {
+        let snapshot = self.consensus_safety_snapshot().await?;
+        let mut violations = snapshot.violations();
+        if let Some(earlier) = earlier {
+            violations.extend(snapshot.violations_since(earlier));
+        }
+        if !violations.is_empty() {
+            bail!(
+                "Consensus safety violated:\n    {}",
+                violations.join("\n    ")
+            );
+        }
+        info!(
+            "Consensus safety check passed for {} validators",
+            snapshot.validators.len()
+        );
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_sdk::types::{
+        aggregate_signature::AggregateSignature, block_info::BlockInfo, ledger_info::LedgerInfo,
+    };
+
+    fn ledger_info(round: u64, id: HashValue) -> LedgerInfoWithSignatures {
+        let commit = BlockInfo::new(1, round, id, HashValue::zero(), round, 0, None);
+        LedgerInfoWithSignatures::new(
+            LedgerInfo::new(commit, HashValue::zero()),
+            AggregateSignature::empty(),
+        )
+    }
+
+    #[test]
+    fn test_conflicting_commits() {
+        let record = |ledger_infos| ValidatorSafetyRecord {
+            ledger_infos,
+            safety_data: Some(SafetyData::new(1, 12, 10, 11, None)),
+        };
+        let mut snapshot = ConsensusSafetySnapshot::default();
+        snapshot.validators.insert(
+            "0".to_string(),
+            record(vec![ledger_info(5, HashValue::zero())]),
+        );
+        snapshot.validators.insert(
+            "1".to_string(),
+            record(vec![ledger_info(5, HashValue::zero())]),
+        );
+        assert!(snapshot.violations().is_empty());
+
+        snapshot.validators.insert(
+            "2".to_string(),
+            record(vec![ledger_info(5, HashValue::random())]),
+        );
+        let violations = snapshot.violations();
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("at epoch 1 round 5"));
+
+        let mut later = snapshot.clone();
+        later.validators.get_mut("0").unwrap().safety_data =
+            Some(SafetyData::new(1, 8, 6, 7, None));
+        let violations = later.violations_since(&snapshot);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].starts_with("0 went back"));
+    }
+}
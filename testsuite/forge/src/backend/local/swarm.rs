@@ -1,38 +1,109 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
+use super::{
+    cargo,
+    chaos::{apply_network_chaos, PauseTimer, LOCAL_CHAOS},
+    framework_upgrade,
+    metrics_archive::export_openmetrics,
+    metrics_poller::{record_snapshot, MetricsPoller},
+    netns::{NetworkBridge, NetworkNamespace},
+    observability::{ObservabilityStack, ScrapeTarget},
+    ports::NodePorts,
+    promql::{evaluate_promql, to_promql_result},
+    resource_usage::{system_metrics, ResourceBudgetTracker},
+    validator_set,
+};
 use crate::interface::system_metrics::SystemMetricsThreshold;
 use crate::{
-    ChainInfo, FullNode, HealthCheckError, LocalNode, LocalVersion, Node, Swarm, SwarmChaos,
-    SwarmExt, Validator, Version,
+    record_event, scrape_metrics_snapshot, trace_span, ChainInfo, FullNode, HealthCheckConfig,
+    HealthCheckError, HealthCheckFailure, LocalNode, LocalVersion, LogRotation, MetricsSnapshot,
+    Node, NodeExt, ResourceBudget, ResourceLimits, Swarm, SwarmChaos, SwarmChaosKind, SwarmExt,
+    SwarmNodePause, TestReport, TimelineEventKind, Validator, Version,
 };
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use aptos_config::config::NetworkConfig;
 use aptos_config::network_id::NetworkId;
 use aptos_config::{config::NodeConfig, keys::ConfigKey};
-use aptos_genesis::builder::{FullnodeNodeConfig, InitConfigFn, InitGenesisConfigFn};
+use aptos_genesis::{
+    builder::{FullnodeNodeConfig, InitConfigFn, InitGenesisConfigFn, ValidatorNodeConfig},
+    config::ValidatorConfiguration,
+    keys::PrivateIdentity,
+    Validator as OnChainValidator,
+};
 use aptos_infallible::Mutex;
 use aptos_logger::{info, warn};
+use aptos_rest_client::Client as RestClient;
 use aptos_sdk::{
     crypto::ed25519::Ed25519PrivateKey,
+    transaction_builder::TransactionFactory,
     types::{
         chain_id::ChainId, transaction::Transaction, waypoint::Waypoint, AccountKey, LocalAccount,
         PeerId,
     },
 };
 use framework::ReleaseBundle;
+use futures::{
+    future::try_join_all,
+    stream::{self, Stream, StreamExt},
+};
 use prometheus_http_query::response::PromqlResult;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    fs, mem,
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    fmt, fs,
+    io::{BufWriter, Write},
+    mem,
+    net::SocketAddr,
     num::NonZeroUsize,
     ops,
     path::{Path, PathBuf},
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 use tempfile::TempDir;
 
+/// How long the scrapes of `query_metrics` are kept for range functions
+const METRICS_HISTORY_RETENTION: Duration = Duration::from_secs(60 * 60);
+/// Resources are sampled this often to enforce budgets when they weren't sampled already
+const DEFAULT_RESOURCE_SAMPLING_INTERVAL: Duration = Duration::from_secs(1);
+/// How long nodes added to a running swarm get to become healthy
+const ADDED_NODE_HEALTH_TIMEOUT: Duration = Duration::from_secs(60);
+/// Stake of the validators added to a running swarm, the default stake of genesis validators
+const ADDED_VALIDATOR_STAKE: u64 = 1;
+/// Genesis transaction of the swarm, in its directory
+const GENESIS_BLOB: &str = "genesis.blob";
+/// `SwarmManifest` of the swarm, in its directory
+const SWARM_MANIFEST: &str = "swarm.yaml";
+
+/// What `LocalSwarm::attach` needs to rebuild a swarm besides genesis and the node directories.
+#[derive(Debug, Deserialize, Serialize)]
+struct SwarmManifest {
+    chain_id: ChainId,
+    root_key: ConfigKey<Ed25519PrivateKey>,
+    genesis_waypoint: Waypoint,
+    /// Public networks of the validators, as the ones of validators with a fullnode are only
+    /// in the config of the fullnode
+    public_networks: Vec<(PeerId, NetworkConfig)>,
+    /// Base port of the nodes, if they listen on deterministic ports, see the `ports` module
+    #[serde(default)]
+    base_port: Option<u16>,
+}
+
+impl SwarmManifest {
+    fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(SWARM_MANIFEST);
+        let manifest = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(serde_yaml::from_slice(&manifest)?)
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(SWARM_MANIFEST), serde_yaml::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub enum SwarmDirectory {
     Persistent(PathBuf),
@@ -79,6 +150,46 @@ impl AsRef<Path> for SwarmDirectory {
     }
 }
 
+/// Returns the extra command line arguments of a node, given its name and config.
+pub type ExtraArgsFn = Arc<dyn Fn(&str, &NodeConfig) -> Vec<String> + Send + Sync>;
+
+struct ExtraArgs(ExtraArgsFn);
+
+impl fmt::Debug for ExtraArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtraArgs").finish_non_exhaustive()
+    }
+}
+
+/// The network namespaces of the nodes of a swarm, see the `netns` module.
+#[derive(Debug)]
+struct NetworkIsolation {
+    /// Namespace of every node, with the ports the node listens on
+    namespaces: Vec<(Arc<NetworkNamespace>, Vec<u16>)>,
+    bridge: NetworkBridge,
+}
+
+impl NetworkIsolation {
+    /// Moves `node` into a new namespace, forwarding the connections between it and the other
+    /// nodes.
+    fn add_node(&mut self, node: &mut LocalNode) -> Result<()> {
+        let name = format!("{}-{}", self.bridge.name(), node.name());
+        let namespace = Arc::new(self.bridge.add_namespace(&name)?);
+        let ports = node.listen_ports();
+        for (other, other_ports) in &self.namespaces {
+            for port in other_ports {
+                namespace.forward_port(*port, other.address())?;
+            }
+            for port in &ports {
+                other.forward_port(*port, namespace.address())?;
+            }
+        }
+        node.set_network_namespace(Some(namespace.clone()))?;
+        self.namespaces.push((namespace, ports));
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct LocalSwarm {
     node_name_counter: u64,
@@ -87,11 +198,53 @@ pub struct LocalSwarm {
     versions: Arc<HashMap<Version, LocalVersion>>,
     validators: HashMap<PeerId, LocalNode>,
     fullnodes: HashMap<PeerId, LocalNode>,
+    /// Prometheus and Grafana scraping every node, including the ones added later. Dropped
+    /// before the directory holding their data.
+    observability: Option<ObservabilityStack>,
     public_networks: HashMap<PeerId, NetworkConfig>,
     dir: SwarmDirectory,
     root_account: LocalAccount,
     chain_id: ChainId,
     root_key: ConfigKey<Ed25519PrivateKey>,
+    /// Log rotation of every node, including the ones added later
+    log_rotation: Option<LogRotation>,
+    /// Whether nodes, including the ones added later, dump core when they crash
+    core_dumps: bool,
+    /// Grace period of every node when stopped, including the ones added later
+    stop_grace_period: Option<Duration>,
+    /// If set, validator upgrades are rolled back unless the upgraded validator is healthy
+    /// within this timeout
+    upgrade_rollback_timeout: Option<Duration>,
+    /// Extra command line arguments of every node, including the ones added later
+    extra_args: Option<ExtraArgs>,
+    /// Resource sampling interval of every node, including the ones added later
+    resource_sampling_interval: Option<Duration>,
+    /// Budget the resources sampled on every node, including the ones added later, count
+    /// against
+    resource_budget: Option<Arc<ResourceBudgetTracker>>,
+    /// Metrics recording interval of every node, including the ones added later
+    metrics_recording_interval: Option<Duration>,
+    /// If set, nodes, including the ones added later, listen on deterministic ports from this
+    /// one, see `NodePorts`
+    base_port: Option<u16>,
+    /// Health check of every node, including the ones added later
+    health_check_config: HealthCheckConfig,
+    /// Whether `wait_all_alive` also waits for validators to take part in consensus
+    consensus_participation_check: bool,
+    /// Network namespaces of every node, including the ones added later. Dropped after the
+    /// nodes.
+    network_isolation: Option<NetworkIsolation>,
+    /// Network chaos applied to the validators, and the paused nodes
+    chaoses: HashSet<SwarmChaos>,
+    /// Resumes the paused nodes once their pause is over
+    pause_timer: Option<PauseTimer>,
+    /// Failed scrapes of `metrics_snapshot`, by node name
+    scrape_failures: Mutex<BTreeMap<String, u64>>,
+    /// Snapshots taken to answer `query_metrics` or by the poller, oldest first, for range
+    /// functions
+    metrics_history: Arc<Mutex<Vec<MetricsSnapshot>>>,
+    /// Scrapes every node, including the ones added later, into `metrics_history`
+    metrics_poller: Option<MetricsPoller>,
 
     launched: bool,
     #[allow(dead_code)]
@@ -99,21 +252,69 @@ pub struct LocalSwarm {
 }
 
 impl LocalSwarm {
+    /// Builds the genesis of a swarm of `number_of_validators`, which start at `initial_version`,
+    /// or the latest version, except the validators of `node_versions`, by index, which start at
+    /// their own version, e.g. to check that mixed versions agree from the first block. The
+    /// validators of `resource_limits`, by index, run with these limits, e.g. to have a weak
+    /// validator, see `LocalNode::set_resource_limits`. If `base_port` is set, nodes listen on
+    /// deterministic ports from it by index instead of random ones, and building fails if one
+    /// of them is in use.
     pub fn build<R>(
         rng: R,
         number_of_validators: NonZeroUsize,
         versions: Arc<HashMap<Version, LocalVersion>>,
         initial_version: Option<Version>,
+        node_versions: HashMap<usize, Version>,
+        resource_limits: HashMap<usize, ResourceLimits>,
+        base_port: Option<u16>,
         init_config: Option<InitConfigFn>,
         init_genesis_config: Option<InitGenesisConfigFn>,
         dir: Option<PathBuf>,
         genesis_framework: Option<ReleaseBundle>,
+        chain_id: ChainId,
         guard: ActiveNodesGuard,
     ) -> Result<LocalSwarm>
     where
         R: ::rand::RngCore + ::rand::CryptoRng,
     {
         info!("Building a new swarm");
+        for (index, version) in &node_versions {
+            ensure!(
+                *index < number_of_validators.get(),
+                "Version {} is set for validator {}, but the swarm has {} validators",
+                version,
+                index,
+                number_of_validators
+            );
+            ensure!(
+                versions.contains_key(version),
+                "Version {} of validator {} is not available",
+                version,
+                index
+            );
+        }
+        if let Some(index) = resource_limits
+            .keys()
+            .find(|index| **index >= number_of_validators.get())
+        {
+            bail!(
+                "Resource limits are set for validator {}, but the swarm has {} validators",
+                index,
+                number_of_validators
+            );
+        }
+        let validator_ports = base_port
+            .map(|base_port| {
+                (0..number_of_validators.get())
+                    .map(|index| {
+                        let ports = NodePorts::new(base_port, index as u64)?;
+                        ports.check_available(&index.to_string())?;
+                        Ok(ports)
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?;
+        let _span = trace_span("build swarm").with_attribute("validators", number_of_validators);
         let dir_actual = if let Some(dir_) = dir {
             if dir_.exists() {
                 fs::remove_dir_all(&dir_)?;
@@ -124,12 +325,14 @@ impl LocalSwarm {
             SwarmDirectory::Temporary(TempDir::new()?)
         };
 
+        let init_ports = validator_ports.clone();
         let (root_key, genesis, genesis_waypoint, validators) =
             aptos_genesis::builder::Builder::new(
                 &dir_actual,
                 genesis_framework.unwrap_or_else(|| cached_packages::head_release_bundle().clone()),
             )?
             .with_num_validators(number_of_validators)
+            .with_randomize_ports(validator_ports.is_none())
             .with_init_config(Some(Arc::new(
                 move |index, config, genesis_stake_amount| {
                     // for local tests, turn off parallel execution:
@@ -149,13 +352,26 @@ impl LocalSwarm {
                     if let Some(init_config) = &init_config {
                         (init_config)(index, config, genesis_stake_amount);
                     }
+
+                    if let Some(ports) = init_ports.as_ref().map(|ports| ports[index]) {
+                        // The listen address of the first full node network is the public one
+                        config.full_node_networks =
+                            vec![NetworkConfig::network_with_id(NetworkId::Public)];
+                        ports.apply(config, false);
+                        config.logger.disable_console();
+                    }
                 },
             )))
             .with_init_genesis_config(init_genesis_config)
+            .with_chain_id(chain_id)
             .build(rng)?;
+        fs::write(
+            dir_actual.join(GENESIS_BLOB),
+            aptos_sdk::bcs::to_bytes(&genesis)?,
+        )?;
 
         // Get the initial version to start the nodes with, either the one provided or fallback to
-        // using the the latest version
+        // using the the latest version, unless a validator has a version of its own
         let initial_version_actual = initial_version.unwrap_or_else(|| {
             versions
                 .iter()
@@ -164,13 +380,22 @@ impl LocalSwarm {
                 .0
                 .clone()
         });
-        let version = versions.get(&initial_version_actual).unwrap();
 
         let mut validators = validators
             .into_iter()
-            .map(|v| {
-                let node =
+            .enumerate()
+            .map(|(index, v)| {
+                let version = node_versions.get(&index).unwrap_or(&initial_version_actual);
+                let version = versions
+                    .get(version)
+                    .ok_or_else(|| anyhow!("Version {} is not available", version))?;
+                let mut node =
                     LocalNode::new(version.to_owned(), v.name, v.dir, v.account_private_key)?;
+                node.set_resource_limits(resource_limits.get(&index).copied());
+                // The VFN network is only set up by genesis
+                if let Some(ports) = &validator_ports {
+                    node.modify_config(|config| ports[index].apply(config, false))?;
+                }
                 Ok((node.peer_id(), node))
             })
             .collect::<Result<HashMap<_, _>>>()?;
@@ -178,28 +403,7 @@ impl LocalSwarm {
         // After genesis, remove public network from validator and add to public_networks
         let public_networks = validators
             .values_mut()
-            .map(|validator| {
-                let mut validator_config = validator.config().clone();
-
-                // Grab the public network config from the validator and insert it into the VFN's config
-                // The validator's public network identity is the same as the VFN's public network identity
-                // We remove it from the validator so the VFN can hold it
-                let public_network = {
-                    let (i, _) = validator_config
-                        .full_node_networks
-                        .iter()
-                        .enumerate()
-                        .find(|(_i, config)| config.network_id == NetworkId::Public)
-                        .expect("Validator should have a public network");
-                    validator_config.full_node_networks.remove(i)
-                };
-
-                // Since the validator's config has changed we need to save it
-                validator_config.save(validator.config_path())?;
-                *validator.config_mut() = validator_config;
-
-                Ok((validator.peer_id(), public_network))
-            })
+            .map(|validator| Ok((validator.peer_id(), take_public_network(validator)?)))
             .collect::<Result<HashMap<_, _>>>()?;
 
         // We print out the root key to make it easy for users to deploy a local faucet
@@ -209,28 +413,178 @@ impl LocalSwarm {
             encoded_root_key
         );
 
-        let root_key = ConfigKey::new(root_key);
+        let mut swarm = Self::from_parts(
+            validators.len() as u64,
+            genesis,
+            genesis_waypoint,
+            versions,
+            validators,
+            HashMap::new(),
+            public_networks,
+            dir_actual,
+            ConfigKey::new(root_key),
+            chain_id,
+            guard,
+        );
+        swarm.base_port = base_port;
+        swarm.save_manifest()?;
+        Ok(swarm)
+    }
+
+    /// Saves the `SwarmManifest` of the swarm, for `attach` to rebuild it.
+    fn save_manifest(&self) -> Result<()> {
+        SwarmManifest {
+            chain_id: self.chain_id,
+            root_key: self.root_key.clone(),
+            genesis_waypoint: self.genesis_waypoint,
+            public_networks: self
+                .public_networks
+                .iter()
+                .map(|(peer_id, network)| (*peer_id, network.clone()))
+                .collect(),
+            base_port: self.base_port,
+        }
+        .save(self.dir.as_ref())
+    }
+
+    /// Rebuilds the swarm built in `dir`, e.g. by an earlier run, and starts its nodes at
+    /// `version`, or the latest version. The nodes keep their databases, so the swarm picks up
+    /// where it stopped, e.g. after a crash. External fullnodes are left out.
+    pub async fn attach(
+        dir: PathBuf,
+        versions: Arc<HashMap<Version, LocalVersion>>,
+        version: Option<Version>,
+        guard: ActiveNodesGuard,
+    ) -> Result<LocalSwarm> {
+        info!("Attaching to the swarm in {}", dir.display());
+        let manifest = SwarmManifest::load(&dir)?;
+        let genesis_path = dir.join(GENESIS_BLOB);
+        let genesis = fs::read(&genesis_path)
+            .with_context(|| format!("Failed to read {:?}", genesis_path))?;
+        let genesis: Transaction = aptos_sdk::bcs::from_bytes(&genesis)?;
+        let version = version.unwrap_or_else(|| versions.keys().max().unwrap().clone());
+        let local_version = versions
+            .get(&version)
+            .ok_or_else(|| anyhow!("Version {} is not available", version))?;
+
+        let mut node_name_counter = 0;
+        let mut validators = HashMap::new();
+        let mut fullnodes = HashMap::new();
+        for entry in fs::read_dir(&dir)? {
+            let node_dir = entry?.path();
+            // The nodes of the swarm are named by a counter, external fullnodes aren't
+            let index = match node_dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.parse::<u64>().ok())
+            {
+                Some(index) if node_dir.join("node.yaml").exists() => index,
+                _ => continue,
+            };
+            node_name_counter = node_name_counter.max(index + 1);
+            let identity_path = node_dir.join("private-identity.yaml");
+            let account_private_key = if identity_path.exists() {
+                let identity = fs::read(&identity_path)?;
+                let identity: PrivateIdentity = serde_yaml::from_slice(&identity)?;
+                Some(ConfigKey::new(identity.account_private_key))
+            } else {
+                None
+            };
+            let node = LocalNode::new(
+                local_version.to_owned(),
+                index.to_string(),
+                node_dir,
+                account_private_key,
+            )?;
+            if node.config().base.role.is_validator() {
+                validators.insert(node.peer_id(), node);
+            } else {
+                fullnodes.insert(node.peer_id(), node);
+            }
+        }
+        ensure!(
+            !validators.is_empty(),
+            "No validator found in {}",
+            dir.display()
+        );
+
+        let mut swarm = Self::from_parts(
+            node_name_counter,
+            genesis,
+            manifest.genesis_waypoint,
+            versions,
+            validators,
+            fullnodes,
+            manifest.public_networks.into_iter().collect(),
+            SwarmDirectory::Persistent(dir),
+            manifest.root_key,
+            manifest.chain_id,
+            guard,
+        );
+        swarm.base_port = manifest.base_port;
+        // Fullnodes first, as launching waits for every node to be alive
+        for fullnode in swarm.fullnodes.values_mut() {
+            fullnode.start()?;
+        }
+        swarm.launch().await?;
+        let mut chain_info = swarm.chain_info();
+        let client = chain_info.rest_client();
+        chain_info.resync_root_account_seq_num(&client).await?;
+        Ok(swarm)
+    }
+
+    fn from_parts(
+        node_name_counter: u64,
+        genesis: Transaction,
+        genesis_waypoint: Waypoint,
+        versions: Arc<HashMap<Version, LocalVersion>>,
+        validators: HashMap<PeerId, LocalNode>,
+        fullnodes: HashMap<PeerId, LocalNode>,
+        public_networks: HashMap<PeerId, NetworkConfig>,
+        dir: SwarmDirectory,
+        root_key: ConfigKey<Ed25519PrivateKey>,
+        chain_id: ChainId,
+        guard: ActiveNodesGuard,
+    ) -> Self {
         let root_account = LocalAccount::new(
             aptos_sdk::types::account_config::aptos_test_root_address(),
             AccountKey::from_private_key(root_key.private_key()),
             0,
         );
 
-        Ok(LocalSwarm {
-            node_name_counter: validators.len() as u64,
+        LocalSwarm {
+            node_name_counter,
             genesis,
             genesis_waypoint,
             versions,
             validators,
-            fullnodes: HashMap::new(),
+            fullnodes,
             public_networks,
-            dir: dir_actual,
+            dir,
             root_account,
-            chain_id: ChainId::test(),
+            chain_id,
             root_key,
+            log_rotation: None,
+            core_dumps: false,
+            stop_grace_period: Some(LocalNode::DEFAULT_STOP_GRACE_PERIOD),
+            upgrade_rollback_timeout: None,
+            extra_args: None,
+            resource_sampling_interval: None,
+            resource_budget: None,
+            metrics_recording_interval: None,
+            base_port: None,
+            health_check_config: HealthCheckConfig::default(),
+            consensus_participation_check: false,
+            network_isolation: None,
+            chaoses: HashSet::new(),
+            pause_timer: None,
+            scrape_failures: Mutex::new(BTreeMap::new()),
+            metrics_history: Arc::new(Mutex::new(Vec::new())),
+            metrics_poller: None,
+            observability: None,
             launched: false,
             guard,
-        })
+        }
     }
 
     pub async fn launch(&mut self) -> Result<()> {
@@ -238,6 +592,7 @@ impl LocalSwarm {
             return Err(anyhow!("Swarm already launched"));
         }
         self.launched = true;
+        let _span = trace_span("start validators");
 
         // Start all the validators
         for validator in self.validators.values_mut() {
@@ -255,16 +610,36 @@ impl LocalSwarm {
         self.wait_for_startup().await?;
         self.wait_for_connectivity(deadline).await?;
         self.liveness_check(deadline).await?;
+        if self.consensus_participation_check {
+            self.wait_for_consensus_participation(deadline).await?;
+        }
         info!("Swarm alive.");
         Ok(())
     }
 
+    async fn wait_for_consensus_participation(&self, deadline: Instant) -> Result<()> {
+        try_join_all(
+            self.validators
+                .values()
+                .map(|validator| validator.wait_for_consensus_participation(deadline)),
+        )
+        .await?;
+        Ok(())
+    }
+
     async fn wait_for_startup(&mut self) -> Result<()> {
-        let num_attempts = 30;
+        let num_attempts = self.health_check_config.attempts;
         let mut done = vec![false; self.validators.len()];
+        let mut last_failures: Vec<Option<HealthCheckFailure>> =
+            self.validators.values().map(|_| None).collect();
         for i in 0..num_attempts {
             info!("Wait for startup attempt: {} of {}", i, num_attempts);
-            for (node, done) in self.validators.values_mut().zip(done.iter_mut()) {
+            for ((node, done), last_failure) in self
+                .validators
+                .values_mut()
+                .zip(done.iter_mut())
+                .zip(last_failures.iter_mut())
+            {
                 if *done {
                     continue;
                 }
@@ -273,20 +648,21 @@ impl LocalSwarm {
 
                     Err(HealthCheckError::Unknown(e)) => {
                         return Err(anyhow!(
-                            "Node '{}' is not running! Error: {}",
+                            "Node '{}' is not running! Error: {:#}",
                             node.name(),
                             e
                         ));
                     }
-                    Err(HealthCheckError::NotRunning(error)) => {
+                    Err(HealthCheckError::NotRunning(reason)) => {
                         return Err(anyhow!(
-                            "Node '{}' is not running! Error: {:?}",
+                            "Node '{}' is not running! Reason: {}",
                             node.name(),
-                            error
+                            reason
                         ));
                     }
-                    Err(HealthCheckError::Failure(e)) => {
-                        warn!("health check failure: {}", e);
+                    Err(HealthCheckError::Failure(failure)) => {
+                        warn!("Node '{}' health check failure: {}", node.name(), failure);
+                        *last_failure = Some(failure);
                         break;
                     }
                 }
@@ -297,10 +673,24 @@ impl LocalSwarm {
                 return Ok(());
             }
 
-            tokio::time::sleep(::std::time::Duration::from_millis(1000)).await;
+            tokio::time::sleep(self.health_check_config.interval).await;
         }
 
-        Err(anyhow!("Launching Swarm timed out"))
+        let unhealthy = self
+            .validators
+            .values()
+            .zip(done)
+            .zip(last_failures)
+            .filter(|((_, done), _)| !done)
+            .map(|((node, _), failure)| match failure {
+                Some(failure) => format!("'{}': {}", node.name(), failure),
+                None => format!("'{}': not checked yet", node.name()),
+            })
+            .collect::<Vec<_>>();
+        Err(anyhow!(
+            "Launching Swarm timed out, unhealthy nodes: {}",
+            unhealthy.join("; ")
+        ))
     }
 
     pub fn add_validator_fullnode(
@@ -342,6 +732,21 @@ impl LocalSwarm {
             fullnode_config.dir,
             None,
         )?;
+        self.assign_ports(&mut fullnode, true)?;
+        self.apply_node_settings(&mut fullnode);
+        if let Some(network_isolation) = &mut self.network_isolation {
+            network_isolation.add_node(&mut fullnode)?;
+        }
+
+        if let Some(observability) = &mut self.observability {
+            observability.add_target(
+                fullnode.name(),
+                node_scrape_target(&fullnode, "fullnode", self.chain_id),
+            )?;
+        }
+        if let Some(poller) = &self.metrics_poller {
+            poller.set_target(fullnode.name(), fullnode.metrics_url());
+        }
 
         let peer_id = fullnode.peer_id();
         assert_eq!(peer_id, validator_peer_id);
@@ -352,7 +757,184 @@ impl LocalSwarm {
         Ok(peer_id)
     }
 
+    /// Moves the added `node`, named by the counter, to its deterministic ports if the swarm
+    /// has a base port. A VFN keeps the public network of its validator.
+    fn assign_ports(&self, node: &mut LocalNode, is_vfn: bool) -> Result<()> {
+        if let Some(base_port) = self.base_port {
+            let ports = NodePorts::new(base_port, node.name().parse()?)?;
+            ports.check_available(node.name())?;
+            node.modify_config(|config| ports.apply(config, is_vfn))?;
+        }
+        Ok(())
+    }
+
+    /// Applies the swarm wide node settings to a node added after they were set.
+    fn apply_node_settings(&self, node: &mut LocalNode) {
+        node.set_stop_grace_period(self.stop_grace_period);
+        node.set_health_check_config(self.health_check_config.clone());
+        if let Some(ExtraArgs(extra_args)) = &self.extra_args {
+            node.set_extra_args(extra_args(node.name(), node.config()));
+        }
+        if let Some(interval) = self.resource_sampling_interval {
+            node.start_resource_sampling(interval);
+            node.set_resource_budget(self.resource_budget.clone());
+        }
+        if let Some(interval) = self.metrics_recording_interval {
+            let path = metrics_archive_path(&self.artifacts_dir(), node.name());
+            if let Err(e) = node.start_metrics_recording(path, interval) {
+                warn!("Failed to record the metrics of {}: {}", node.name(), e);
+            }
+        }
+        node.set_log_rotation(self.log_rotation.clone());
+        if self.core_dumps {
+            node.set_core_dumps_dir(Some(self.artifacts_dir().join("cores").join(node.name())));
+        }
+    }
+
     fn add_fullnode(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId> {
+        let mut fullnode = self.create_fullnode(version, template)?;
+        let peer_id = fullnode.peer_id();
+        fullnode.start()?;
+
+        self.fullnodes.insert(peer_id, fullnode);
+
+        Ok(peer_id)
+    }
+
+    /// Waits for the fullnode `peer_id` that was just added to become healthy.
+    async fn wait_fullnode_healthy(&mut self, peer_id: PeerId) -> Result<()> {
+        self.fullnodes
+            .get_mut(&peer_id)
+            .ok_or_else(|| anyhow!("no fullnode with peer_id: {}", peer_id))?
+            .wait_until_healthy(Instant::now() + ADDED_NODE_HEALTH_TIMEOUT)
+            .await
+    }
+
+    /// Adds a public fullnode starting from a copy of the database of `source`, instead of
+    /// syncing from genesis. `source` is stopped while its database is copied.
+    pub fn add_fullnode_from_snapshot(
+        &mut self,
+        version: &Version,
+        template: NodeConfig,
+        source: PeerId,
+    ) -> Result<PeerId> {
+        let mut fullnode = self.create_fullnode(version, template)?;
+        let db_dir = fullnode.config().storage.dir();
+        self.node_mut(source)?.checkpoint_db(&db_dir)?;
+
+        let peer_id = fullnode.peer_id();
+        fullnode.start()?;
+
+        self.fullnodes.insert(peer_id, fullnode);
+
+        Ok(peer_id)
+    }
+
+    /// Makes the node `to` start over from a copy of the database of the node `from`, e.g. to
+    /// get two nodes with identical state. Both nodes are stopped meanwhile, and restarted if
+    /// they were running. `to` keeps its keys and consensus safety data, but loses its own
+    /// consensus and state sync progress.
+    pub fn clone_db(&mut self, from: PeerId, to: PeerId) -> Result<()> {
+        if from == to {
+            bail!("Can't clone the db of node {} into itself", from);
+        }
+        self.node_mut(from)?;
+        let to_node = self.node_mut(to)?;
+        let was_running = to_node.is_running();
+        to_node.remove_db()?;
+        let db_dir = to_node.config().storage.dir();
+
+        self.node_mut(from)?.checkpoint_db(&db_dir)?;
+
+        if was_running {
+            self.node_mut(to)?.start()?;
+        }
+        Ok(())
+    }
+
+    fn node_mut(&mut self, peer_id: PeerId) -> Result<&mut LocalNode> {
+        self.validators
+            .get_mut(&peer_id)
+            .or_else(|| self.fullnodes.get_mut(&peer_id))
+            .ok_or_else(|| anyhow!("no node with peer_id: {}", peer_id))
+    }
+
+    /// Creates a validator joining the network after genesis, to be started once it joined the
+    /// validator set on chain with the returned stake pool config.
+    fn create_validator(
+        &mut self,
+        version: &Version,
+        template: NodeConfig,
+    ) -> Result<(LocalNode, OnChainValidator)> {
+        let version = self
+            .versions
+            .get(version)
+            .ok_or_else(|| anyhow!("Version {} is not available", version))?
+            .to_owned();
+        let name = self.node_name_counter.to_string();
+        self.node_name_counter += 1;
+        let mut validator_config = ValidatorNodeConfig::join_network(
+            name,
+            self.dir.as_ref(),
+            template,
+            ADDED_VALIDATOR_STAKE,
+            &self.genesis_waypoint,
+            &self.genesis,
+        )?;
+
+        let mut validator = LocalNode::new(
+            version,
+            validator_config.name.clone(),
+            validator_config.dir.clone(),
+            validator_config.account_private_key.clone(),
+        )?;
+        self.assign_ports(&mut validator, false)?;
+        // The addresses registered on chain are the final ones, public network included
+        validator_config.config = validator.config().clone();
+        let on_chain_config =
+            OnChainValidator::try_from(ValidatorConfiguration::try_from(&validator_config)?)?;
+
+        let public_network = take_public_network(&mut validator)?;
+        self.public_networks
+            .insert(validator.peer_id(), public_network);
+        self.save_manifest()?;
+
+        self.apply_node_settings(&mut validator);
+        if let Some(network_isolation) = &mut self.network_isolation {
+            network_isolation.add_node(&mut validator)?;
+        }
+        if let Some(observability) = &mut self.observability {
+            observability.add_target(
+                validator.name(),
+                node_scrape_target(&validator, "validator", self.chain_id),
+            )?;
+        }
+        if let Some(poller) = &self.metrics_poller {
+            poller.set_target(validator.name(), validator.metrics_url());
+        }
+
+        Ok((validator, on_chain_config))
+    }
+
+    /// Account owning the stake pool of `validator`, and REST client of another validator to
+    /// submit its transactions through.
+    fn stake_pool_owner(&self, validator: &LocalNode) -> Result<(LocalAccount, RestClient)> {
+        let key = validator
+            .account_private_key()
+            .as_ref()
+            .ok_or_else(|| anyhow!("Validator {} has no account key", validator.name()))?;
+        let key = AccountKey::from_private_key(key.private_key());
+        let account = LocalAccount::new(key.authentication_key().derived_address(), key, 0);
+        let client = self
+            .validators
+            .values()
+            .find(|other| other.peer_id() != validator.peer_id())
+            .ok_or_else(|| anyhow!("No other validator to submit transactions to"))?
+            .rest_client();
+        Ok((account, client))
+    }
+
+    fn create_fullnode(&mut self, version: &Version, template: NodeConfig) -> Result<LocalNode> {
         let name = self.node_name_counter.to_string();
         self.node_name_counter += 1;
         let fullnode_config = FullnodeNodeConfig::public_fullnode(
@@ -370,13 +952,404 @@ impl LocalSwarm {
             fullnode_config.dir,
             None,
         )?;
+        self.assign_ports(&mut fullnode, false)?;
+        self.apply_node_settings(&mut fullnode);
+        if let Some(network_isolation) = &mut self.network_isolation {
+            network_isolation.add_node(&mut fullnode)?;
+        }
+        if let Some(observability) = &mut self.observability {
+            observability.add_target(
+                fullnode.name(),
+                node_scrape_target(&fullnode, "fullnode", self.chain_id),
+            )?;
+        }
+        if let Some(poller) = &self.metrics_poller {
+            poller.set_target(fullnode.name(), fullnode.metrics_url());
+        }
 
-        let peer_id = fullnode.peer_id();
-        fullnode.start()?;
+        Ok(fullnode)
+    }
 
-        self.fullnodes.insert(peer_id, fullnode);
+    /// Scrapes every node, including the ones added later, every `interval` in the background,
+    /// for range functions of `query_metrics` to have earlier scrapes to compare to. Network
+    /// isolation has to be set up first, as nodes are scraped on their current address.
+    pub fn start_metrics_polling(&mut self, interval: Duration) -> Result<()> {
+        ensure!(
+            self.metrics_poller.is_none(),
+            "Metrics polling already started"
+        );
+        let poller = MetricsPoller::start(
+            self.metrics_history.clone(),
+            METRICS_HISTORY_RETENTION,
+            interval,
+        )?;
+        for node in self.validators.values().chain(self.fullnodes.values()) {
+            poller.set_target(node.name(), node.metrics_url());
+        }
+        self.metrics_poller = Some(poller);
+        Ok(())
+    }
 
-        Ok(peer_id)
+    /// Runs every node, including the ones added later, in its own Linux network namespace, so
+    /// that network chaos can target a single node. The namespaces are bridged on the subnet
+    /// `<subnet[0]>.<subnet[1]>.0.0/16`, which must not be used by another swarm. Needs root,
+    /// and has to be done before the swarm is launched. See the `netns` module.
+    pub fn isolate_network(&mut self, subnet: [u8; 2]) -> Result<()> {
+        ensure!(!self.launched, "Swarm already launched");
+        ensure!(
+            self.network_isolation.is_none(),
+            "Swarm network already isolated"
+        );
+        let mut network_isolation = NetworkIsolation {
+            namespaces: Vec::new(),
+            bridge: NetworkBridge::create(subnet)?,
+        };
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            network_isolation.add_node(node)?;
+        }
+        self.network_isolation = Some(network_isolation);
+        Ok(())
+    }
+
+    /// Applies `chaoses` to every validator, replacing the chaos applied so far.
+    fn apply_network_chaos(&self, chaoses: &HashSet<SwarmChaos>) -> Result<()> {
+        let network_chaoses: Vec<_> = chaoses
+            .iter()
+            .filter(|chaos| LOCAL_CHAOS.contains(&chaos.kind()))
+            .collect();
+        for validator in self.validators.values() {
+            let namespace = validator.network_namespace().ok_or_else(|| {
+                anyhow!("Validator {} has no network namespace", validator.name())
+            })?;
+            apply_network_chaos(namespace, network_chaoses.iter().copied())?;
+        }
+        Ok(())
+    }
+
+    /// Pauses the nodes of `pause`, and resumes them once it's over.
+    fn pause_nodes(&mut self, pause: &SwarmNodePause) -> Result<()> {
+        let mut targets = vec![];
+        for peer_id in &pause.peers {
+            targets.push(self.node_mut(*peer_id)?.pause_target()?);
+        }
+        for peer_id in &pause.peers {
+            self.node_mut(*peer_id)?.pause()?;
+        }
+        self.pause_timer = Some(PauseTimer::start(
+            targets,
+            Duration::from_secs(pause.duration_secs),
+        ));
+        Ok(())
+    }
+
+    /// Resumes the nodes of `pause` that are still paused.
+    fn resume_nodes(&mut self, pause: &SwarmNodePause) -> Result<()> {
+        self.pause_timer = None;
+        for peer_id in &pause.peers {
+            let node = self.node_mut(*peer_id)?;
+            if node.is_paused() {
+                node.resume()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts a Prometheus scraping every node, including the ones added later, and a Grafana
+    /// with the dashboards of the workspace. Needs Docker. Network isolation has to be set up
+    /// first, as nodes are scraped on their current address. See the `observability` module.
+    pub fn start_observability(&mut self) -> Result<&ObservabilityStack> {
+        ensure!(
+            self.observability.is_none(),
+            "Observability stack already started"
+        );
+        let dashboards_dir = cargo::metadata()
+            .map(|metadata| metadata.workspace_root.join("dashboards"))
+            .ok()
+            .filter(|dir| dir.is_dir());
+        let mut observability =
+            ObservabilityStack::start(&self.dir.join("observability"), dashboards_dir.as_deref())?;
+        for node in self.validators.values() {
+            observability.add_target(
+                node.name(),
+                node_scrape_target(node, "validator", self.chain_id),
+            )?;
+        }
+        for node in self.fullnodes.values() {
+            observability.add_target(
+                node.name(),
+                node_scrape_target(node, "fullnode", self.chain_id),
+            )?;
+        }
+        info!(
+            "Prometheus: {}, Grafana: {}",
+            observability.prometheus_url(),
+            observability.grafana_url()
+        );
+        Ok(self.observability.insert(observability))
+    }
+
+    pub fn observability(&self) -> Option<&ObservabilityStack> {
+        self.observability.as_ref()
+    }
+
+    /// Has the observability stack also scrape `address`, e.g. the metrics endpoint of a
+    /// transaction emitter, with `job` as its job label.
+    pub fn add_scrape_target(&mut self, name: &str, job: &str, address: SocketAddr) -> Result<()> {
+        let observability = self
+            .observability
+            .as_mut()
+            .ok_or_else(|| anyhow!("Observability stack not started"))?;
+        let mut labels = BTreeMap::new();
+        labels.insert("job".to_string(), job.to_string());
+        observability.add_target(name, ScrapeTarget { address, labels })
+    }
+
+    /// Upgrades every validator to `version`, then the on-chain framework to the one of
+    /// `version`, if any. The framework is upgraded through governance, see `upgrade_framework`.
+    pub async fn upgrade_to(&mut self, version: &Version, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let framework = self
+            .versions
+            .get(version)
+            .ok_or_else(|| anyhow!("Invalid version: {:?}", version))?
+            .framework()
+            .cloned();
+        let validators = self.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
+        for id in validators {
+            self.upgrade_validator(id, version).await?;
+        }
+        self.wait_all_alive(deadline.saturating_duration_since(Instant::now()))
+            .await?;
+        if let Some(framework) = framework {
+            self.upgrade_framework(
+                &framework,
+                deadline.saturating_duration_since(Instant::now()),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Upgrades the on-chain framework to `bundle`: every package is proposed and voted for by
+    /// the validators, then published by resolving the proposal. As the validators usually
+    /// can't resolve a proposal early, the genesis needs a voting duration shorter than
+    /// `timeout`, e.g. set with `InitGenesisConfigFn`.
+    pub async fn upgrade_framework(
+        &mut self,
+        bundle: &ReleaseBundle,
+        timeout: Duration,
+    ) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut voters = self
+            .validators()
+            .filter_map(|v| v.account_private_key().as_ref())
+            .map(|key| {
+                let key = AccountKey::from_private_key(key.private_key());
+                LocalAccount::new(key.authentication_key().derived_address(), key, 0)
+            })
+            .collect::<Vec<_>>();
+        let client = self
+            .validators()
+            .next()
+            .ok_or_else(|| anyhow!("No validator"))?
+            .rest_client();
+        let transaction_factory = TransactionFactory::new(self.chain_id).with_gas_unit_price(1);
+        framework_upgrade::upgrade_framework(
+            &client,
+            &transaction_factory,
+            &mut self.root_account,
+            &mut voters,
+            bundle,
+            deadline,
+        )
+        .await
+    }
+
+    /// Runs the validators, from their next start, inside this process instead of spawning
+    /// their binaries. See `LocalNode::set_in_process`.
+    pub fn set_validators_in_process(&mut self, in_process: bool) {
+        for validator in self.validators.values_mut() {
+            validator.set_in_process(in_process);
+        }
+    }
+
+    /// Sets how long every node is given to shut down cleanly after SIGTERM when stopped,
+    /// before being killed. See `LocalNode::set_stop_grace_period`.
+    pub fn set_stop_grace_period(&mut self, stop_grace_period: Option<Duration>) {
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.set_stop_grace_period(stop_grace_period);
+        }
+        self.stop_grace_period = stop_grace_period;
+    }
+
+    /// Stops every node at once: each is sent SIGTERM, or the signal its profiler needs, and
+    /// killed if it didn't exit within its grace period, see `set_stop_grace_period`.
+    pub fn stop_all(&mut self) {
+        info!("Stopping all the nodes of the swarm");
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.signal_stop();
+        }
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.stop();
+        }
+    }
+
+    /// Starts every node that isn't running, e.g. after `stop_all`, and waits for the swarm to
+    /// be alive.
+    pub async fn start_all(&mut self) -> Result<()> {
+        info!("Starting all the nodes of the swarm");
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            if !node.is_running() {
+                node.start()?;
+            }
+        }
+        self.wait_all_alive(Duration::from_secs(60)).await
+    }
+
+    /// Makes `upgrade_validator` snapshot the validator's databases, and roll it back to them
+    /// and its previous version if it isn't healthy within `timeout` after the upgrade. See
+    /// `LocalNode::upgrade_with_rollback`.
+    pub fn set_upgrade_rollback_timeout(&mut self, timeout: Option<Duration>) {
+        self.upgrade_rollback_timeout = timeout;
+    }
+
+    /// Sets the command line arguments passed to every node binary, see
+    /// `LocalNode::set_extra_args`.
+    pub fn set_extra_args(&mut self, extra_args: ExtraArgsFn) {
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.set_extra_args(extra_args(node.name(), node.config()));
+        }
+        self.extra_args = Some(ExtraArgs(extra_args));
+    }
+
+    /// Samples the resources used by every node every `interval`, see
+    /// `LocalNode::start_resource_sampling`.
+    pub fn start_resource_sampling(&mut self, interval: Duration) {
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.start_resource_sampling(interval);
+            node.set_resource_budget(self.resource_budget.clone());
+        }
+        self.resource_sampling_interval = Some(interval);
+    }
+
+    /// Records the metrics of every node every `interval` to
+    /// `<artifacts>/metrics/<node name>.prom.gz`, to be analyzed offline after the run, see
+    /// `read_metrics_archive` and `export_metrics_archives`.
+    pub fn start_metrics_recording(&mut self, interval: Duration) -> Result<()> {
+        let artifacts_dir = self.artifacts_dir();
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            let path = metrics_archive_path(&artifacts_dir, node.name());
+            node.start_metrics_recording(path, interval)?;
+        }
+        self.metrics_recording_interval = Some(interval);
+        Ok(())
+    }
+
+    /// Converts the metrics archives of every node recorded so far to a single OpenMetrics
+    /// file at `<artifacts>/metrics/metrics.om`, which `promtool tsdb create-blocks-from
+    /// openmetrics` imports into a Prometheus.
+    pub fn export_metrics_archives(&self) -> Result<PathBuf> {
+        let archives = self
+            .validators
+            .values()
+            .chain(self.fullnodes.values())
+            .map(|node| {
+                (
+                    node.name().to_string(),
+                    metrics_archive_path(&self.artifacts_dir(), node.name()),
+                )
+            })
+            .filter(|(_, path)| path.exists())
+            .collect::<Vec<_>>();
+        let dir = self.artifacts_dir().join("metrics");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("metrics.om");
+        let mut file = BufWriter::new(fs::File::create(&path)?);
+        export_openmetrics(&archives, &mut file)?;
+        file.flush()?;
+        Ok(path)
+    }
+
+    /// Writes the metrics of every running node to `<dir>/<node name>.prom`, as served by the
+    /// node.
+    async fn dump_metrics(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        for node in self.validators.values().chain(self.fullnodes.values()) {
+            if !node.is_running() {
+                continue;
+            }
+            let mut url = node.inspection_service_endpoint();
+            url.set_path("metrics");
+            let scrape = async { reqwest::get(url).await?.error_for_status()?.text().await };
+            // The node may have crashed, which is when the metrics of the others matter most.
+            match scrape.await {
+                Ok(text) => fs::write(dir.join(format!("{}.prom", node.name())), text)?,
+                Err(e) => warn!("Failed to scrape the metrics of {}: {}", node.name(), e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets what the health check of every node probes, and how long `wait_all_alive` waits for
+    /// the validators to pass it.
+    pub fn set_health_check_config(&mut self, health_check_config: HealthCheckConfig) {
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.set_health_check_config(health_check_config.clone());
+        }
+        self.health_check_config = health_check_config;
+    }
+
+    /// Makes `wait_all_alive` also check that every validator is voting, not just serving REST.
+    pub fn set_consensus_participation_check(&mut self, enabled: bool) {
+        self.consensus_participation_check = enabled;
+    }
+
+    /// Rotates the logs of every node, applies to running nodes from their next restart.
+    pub fn set_log_rotation(&mut self, log_rotation: Option<LogRotation>) {
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.set_log_rotation(log_rotation.clone());
+        }
+        self.log_rotation = log_rotation;
     }
 
     pub fn root_key(&self) -> Ed25519PrivateKey {
@@ -387,6 +1360,38 @@ impl LocalSwarm {
         self.chain_id
     }
 
+    /// The serialized genesis transaction of the swarm, as expected by the
+    /// `execution.genesis_file_location` of a node config.
+    pub fn genesis_blob_path(&self) -> PathBuf {
+        self.dir.join(GENESIS_BLOB)
+    }
+
+    pub fn waypoint(&self) -> Waypoint {
+        self.genesis_waypoint
+    }
+
+    /// Writes the config of a public fullnode able to join the swarm without being managed by
+    /// it, e.g. to run a node by hand under a debugger with `aptos-node -f <config>`. Returns
+    /// the path of the config.
+    pub fn write_external_fullnode_config(&mut self, template: NodeConfig) -> Result<PathBuf> {
+        let name = format!("external-{}", self.node_name_counter);
+        self.node_name_counter += 1;
+        let fullnode_config = FullnodeNodeConfig::public_fullnode(
+            name,
+            self.dir.as_ref(),
+            template,
+            &self.genesis_waypoint,
+            &self.genesis,
+        )?;
+        let config_path = fullnode_config.config_path();
+        info!(
+            "Wrote the config of external fullnode {} to {}",
+            fullnode_config.name,
+            config_path.display()
+        );
+        Ok(config_path)
+    }
+
     pub fn validator(&self, peer_id: PeerId) -> Option<&LocalNode> {
         self.validators.get(&peer_id)
     }
@@ -420,6 +1425,129 @@ impl LocalSwarm {
     pub fn dir(&self) -> &Path {
         self.dir.as_ref()
     }
+
+    /// Lines written to the log files of every current node from now on, as they come,
+    /// prefixed with the name of their node, see `LocalNode::log_stream`.
+    pub fn merged_log_stream(&self) -> impl Stream<Item = String> + Send + Unpin {
+        stream::select_all(
+            self.validators()
+                .chain(self.fullnodes.values())
+                .map(|node| {
+                    let name = node.name().to_string();
+                    node.log_stream()
+                        .map(move |line| format!("[{}] {}", name, line))
+                }),
+        )
+    }
+
+    /// Makes every node dump core when it crashes, from its next start. Cores are moved to
+    /// `<artifacts>/cores/<node name>`.
+    pub fn enable_core_dumps(&mut self) {
+        self.core_dumps = true;
+        let artifacts_dir = self.artifacts_dir();
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.set_core_dumps_dir(Some(artifacts_dir.join("cores").join(node.name())));
+        }
+    }
+
+    /// Where artifacts worth keeping after a run (e.g. profiles) are collected.
+    pub fn artifacts_dir(&self) -> PathBuf {
+        self.dir.join("artifacts")
+    }
+
+    /// Copies the profiles of every node to `<artifacts>/profiles/<node name>`, returning the
+    /// copied files. Profiles are only complete for nodes that have been stopped.
+    pub fn collect_profiles(&self) -> Result<Vec<PathBuf>> {
+        let mut collected = Vec::new();
+        for node in self.validators.values().chain(self.fullnodes.values()) {
+            let profiles_dir = node.profiles_dir();
+            if !profiles_dir.exists() {
+                continue;
+            }
+            let destination = self.artifacts_dir().join("profiles").join(node.name());
+            fs::create_dir_all(&destination)?;
+            for entry in fs::read_dir(&profiles_dir)? {
+                let path = entry?.path();
+                if let Some(file_name) = path.file_name() {
+                    let to = destination.join(file_name);
+                    fs::copy(&path, &to)?;
+                    collected.push(to);
+                }
+            }
+        }
+        Ok(collected)
+    }
+}
+
+fn metrics_archive_path(artifacts_dir: &Path, node_name: &str) -> PathBuf {
+    artifacts_dir
+        .join("metrics")
+        .join(format!("{}.prom.gz", node_name))
+}
+
+/// Fails listing the processes of `nodes` that exited without being stopped.
+fn ensure_no_restart<'a>(role: &str, nodes: impl Iterator<Item = &'a LocalNode>) -> Result<()> {
+    let mut restarts: Vec<_> = nodes
+        .flat_map(|node| {
+            node.unexpected_exits().into_iter().map(move |exit| {
+                format!(
+                    "{}: process {} started {}s ago exited without being stopped, noticed {}s ago",
+                    node.name(),
+                    exit.pid,
+                    exit.started_at.elapsed().unwrap_or_default().as_secs(),
+                    exit.noticed_at.elapsed().unwrap_or_default().as_secs()
+                )
+            })
+        })
+        .collect();
+    if restarts.is_empty() {
+        return Ok(());
+    }
+    restarts.sort();
+    bail!("Some {} restarted:\n    {}", role, restarts.join("\n    "))
+}
+
+/// Scrape target of the metrics of `node`, labeled like the nodes of the Kubernetes
+/// deployments, so that the dashboards can filter them.
+/// Removes the public network from the config of `validator` and returns it.
+fn take_public_network(validator: &mut LocalNode) -> Result<NetworkConfig> {
+    let mut validator_config = validator.config().clone();
+
+    // Grab the public network config from the validator and insert it into the VFN's config
+    // The validator's public network identity is the same as the VFN's public network identity
+    // We remove it from the validator so the VFN can hold it
+    let public_network = {
+        let (i, _) = validator_config
+            .full_node_networks
+            .iter()
+            .enumerate()
+            .find(|(_i, config)| config.network_id == NetworkId::Public)
+            .expect("Validator should have a public network");
+        validator_config.full_node_networks.remove(i)
+    };
+
+    // Since the validator's config has changed we need to save it
+    validator_config.save(validator.config_path())?;
+    *validator.config_mut() = validator_config;
+
+    Ok(public_network)
+}
+
+fn node_scrape_target(node: &LocalNode, role: &str, chain_id: ChainId) -> ScrapeTarget {
+    let mut labels = BTreeMap::new();
+    labels.insert("job".to_string(), "aptos-node".to_string());
+    labels.insert("role".to_string(), role.to_string());
+    labels.insert("namespace".to_string(), "forge-local".to_string());
+    labels.insert("chain_name".to_string(), chain_id.to_string());
+    labels.insert("kubernetes_pod_name".to_string(), node.name().to_string());
+    ScrapeTarget {
+        address: SocketAddr::new(node.ip(), node.config().inspection_service.port),
+        labels,
+    }
 }
 
 impl Drop for LocalSwarm {
@@ -469,7 +1597,17 @@ impl Swarm for LocalSwarm {
             .validators
             .get_mut(&id)
             .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
-        validator.upgrade(version)
+        let _span = trace_span("upgrade validator")
+            .with_attribute("node", validator.name())
+            .with_attribute("version", version.version());
+        match self.upgrade_rollback_timeout {
+            Some(timeout) => {
+                validator
+                    .upgrade_with_rollback(version, Instant::now() + timeout)
+                    .await
+            }
+            None => validator.upgrade(version),
+        }
     }
 
     fn full_nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a> {
@@ -492,30 +1630,91 @@ impl Swarm for LocalSwarm {
         self.fullnodes.get_mut(&id).map(|v| v as &mut dyn FullNode)
     }
 
-    fn add_validator(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
-        todo!()
+    /// The validator joins the validator set on chain, staking as much as a default genesis
+    /// validator, which needs a genesis allowing validator set changes, e.g. with
+    /// `allow_new_validators` set by an `InitGenesisConfigFn`.
+    async fn add_validator(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId> {
+        let (mut validator, on_chain_config) = self.create_validator(version, template)?;
+        let (mut account, client) = self.stake_pool_owner(&validator)?;
+        let transaction_factory = TransactionFactory::new(self.chain_id).with_gas_unit_price(1);
+        validator_set::join_validator_set(
+            &client,
+            &transaction_factory,
+            &mut self.root_account,
+            &mut account,
+            on_chain_config,
+        )
+        .await?;
+
+        let peer_id = validator.peer_id();
+        validator.start()?;
+        validator
+            .wait_until_healthy(Instant::now() + ADDED_NODE_HEALTH_TIMEOUT)
+            .await?;
+        self.validators.insert(peer_id, validator);
+        Ok(peer_id)
     }
 
-    fn remove_validator(&mut self, _id: PeerId) -> Result<()> {
-        todo!()
+    /// The validator leaves the validator set on chain before being stopped. Its directory is
+    /// kept, without its config so that `attach` doesn't start it again.
+    async fn remove_validator(&mut self, id: PeerId) -> Result<()> {
+        let validator = self
+            .validators
+            .get(&id)
+            .ok_or_else(|| anyhow!("no validator with peer_id: {}", id))?;
+        let (mut account, client) = self.stake_pool_owner(validator)?;
+        let transaction_factory = TransactionFactory::new(self.chain_id).with_gas_unit_price(1);
+        *account.sequence_number_mut() = client
+            .get_account(account.address())
+            .await?
+            .into_inner()
+            .sequence_number;
+        validator_set::leave_validator_set(
+            &client,
+            &transaction_factory,
+            &mut self.root_account,
+            &mut account,
+        )
+        .await?;
+
+        let mut validator = self.validators.remove(&id).unwrap();
+        validator.stop();
+        fs::remove_file(validator.config_path())?;
+        if let Some(observability) = &mut self.observability {
+            observability.remove_target(validator.name())?;
+        }
+        if let Some(poller) = &self.metrics_poller {
+            poller.remove_target(validator.name());
+        }
+        Ok(())
     }
 
-    fn add_validator_full_node(
+    async fn add_validator_full_node(
         &mut self,
         version: &Version,
         template: NodeConfig,
         id: PeerId,
     ) -> Result<PeerId> {
-        self.add_validator_fullnode(version, template, id)
+        let peer_id = self.add_validator_fullnode(version, template, id)?;
+        self.wait_fullnode_healthy(peer_id).await?;
+        Ok(peer_id)
     }
 
-    fn add_full_node(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId> {
-        self.add_fullnode(version, template)
+    async fn add_full_node(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId> {
+        let peer_id = self.add_fullnode(version, template)?;
+        self.wait_fullnode_healthy(peer_id).await?;
+        Ok(peer_id)
     }
 
-    fn remove_full_node(&mut self, id: PeerId) -> Result<()> {
+    async fn remove_full_node(&mut self, id: PeerId) -> Result<()> {
         if let Some(mut fullnode) = self.fullnodes.remove(&id) {
             fullnode.stop();
+            if let Some(observability) = &mut self.observability {
+                observability.remove_target(fullnode.name())?;
+            }
+            if let Some(poller) = &self.metrics_poller {
+                poller.remove_target(fullnode.name());
+            }
         }
 
         Ok(())
@@ -542,38 +1741,211 @@ impl Swarm for LocalSwarm {
         self.dir.display().to_string()
     }
 
-    fn inject_chaos(&mut self, _chaos: SwarmChaos) -> Result<()> {
-        todo!()
+    fn inject_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        let _span = trace_span("inject chaos").with_attribute("chaos", format!("{:?}", chaos));
+        self.check_chaos_supported(&chaos)?;
+        if self.chaoses.iter().any(|c| c.kind() == chaos.kind()) {
+            bail!("{} chaos already injected", chaos.kind());
+        }
+        let mut chaoses = self.chaoses.clone();
+        chaoses.insert(chaos.clone());
+        match &chaos {
+            SwarmChaos::Pause(pause) => self.pause_nodes(pause)?,
+            _ => self.apply_network_chaos(&chaoses)?,
+        }
+        record_event(TimelineEventKind::ChaosInjected {
+            chaos: format!("{:?}", chaos),
+        });
+        self.chaoses = chaoses;
+        Ok(())
+    }
+
+    fn remove_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        let _span = trace_span("remove chaos").with_attribute("chaos", format!("{:?}", chaos));
+        if !self.chaoses.contains(&chaos) {
+            bail!("Chaos {:?} not found", chaos);
+        }
+        let mut chaoses = self.chaoses.clone();
+        chaoses.remove(&chaos);
+        match &chaos {
+            SwarmChaos::Pause(pause) => self.resume_nodes(pause)?,
+            _ => self.apply_network_chaos(&chaoses)?,
+        }
+        record_event(TimelineEventKind::ChaosRemoved {
+            chaos: format!("{:?}", chaos),
+        });
+        self.chaoses = chaoses;
+        Ok(())
+    }
+
+    /// Network chaos is applied with tc inside the namespaces of the validators, so it needs
+    /// `isolate_network`. Nodes can be paused either way.
+    fn supported_chaos(&self) -> Vec<SwarmChaosKind> {
+        let mut supported = vec![SwarmChaosKind::Pause];
+        if self.network_isolation.is_some() {
+            supported.extend(LOCAL_CHAOS);
+        }
+        supported
+    }
+
+    fn report(&self, report: &mut TestReport) {
+        let mut nodes: Vec<_> = self
+            .validators
+            .values()
+            .chain(self.fullnodes.values())
+            .collect();
+        nodes.sort_by(|a, b| a.name().cmp(b.name()));
+        for node in nodes {
+            report.report_metric(node.name(), "restarts", node.restart_count() as f64);
+            report.report_metric(
+                node.name(),
+                "unexpected_exits",
+                node.unexpected_exits().len() as f64,
+            );
+            if let Some(uptime) = node.uptime() {
+                report.report_metric(node.name(), "uptime_secs", uptime.as_secs_f64());
+            }
+            let scrape_failures = self
+                .scrape_failures
+                .lock()
+                .get(node.name())
+                .copied()
+                .unwrap_or(0)
+                + node.metrics_recording_failures();
+            report.report_metric(node.name(), "scrape_failures", scrape_failures as f64);
+        }
     }
 
-    fn remove_chaos(&mut self, _chaos: SwarmChaos) -> Result<()> {
-        todo!()
+    /// Writes the metrics of every running node to `<artifacts>/metrics/final/<node name>.prom`,
+    /// as served by the node.
+    async fn dump_final_metrics(&self) -> Result<()> {
+        self.dump_metrics(&self.artifacts_dir().join("metrics").join("final"))
+            .await
     }
 
+    /// Writes the metrics of every running node to
+    /// `<artifacts>/checkpoints/<label>/<node name>.prom`.
+    async fn checkpoint_artifacts(&self, label: &str) -> Result<()> {
+        self.dump_metrics(&self.artifacts_dir().join("checkpoints").join(label))
+            .await
+    }
+
+    /// Enforced with the resource samples of the nodes, starting sampling if needed. Nodes
+    /// running as their own process are killed once the budget is exceeded.
+    fn set_resource_budget(&mut self, budget: Option<ResourceBudget>) -> Result<()> {
+        if budget.is_some() && self.resource_sampling_interval.is_none() {
+            self.start_resource_sampling(DEFAULT_RESOURCE_SAMPLING_INTERVAL);
+        }
+        self.resource_budget = budget.map(|budget| Arc::new(ResourceBudgetTracker::new(budget)));
+        for node in self.validators.values().chain(self.fullnodes.values()) {
+            node.set_resource_budget(self.resource_budget.clone());
+        }
+        Ok(())
+    }
+
+    fn check_resource_budget(&self) -> Result<()> {
+        match self
+            .resource_budget
+            .as_ref()
+            .and_then(|budget| budget.exceeded())
+        {
+            Some(exceeded) => bail!(exceeded),
+            None => Ok(()),
+        }
+    }
+
+    async fn metrics_snapshot(&self) -> Result<MetricsSnapshot> {
+        let snapshot = scrape_metrics_snapshot(self).await;
+        let mut scrape_failures = self.scrape_failures.lock();
+        for (node, error) in &snapshot.failures {
+            warn!("Failed to scrape the metrics of {}: {}", node, error);
+            *scrape_failures.entry(node.clone()).or_default() += 1;
+        }
+        Ok(snapshot)
+    }
+
+    /// Fails if a validator process exited without being stopped, see
+    /// `LocalNode::unexpected_exits`. Validators stopped and started by the test don't count.
     async fn ensure_no_validator_restart(&self) -> Result<()> {
-        todo!()
+        ensure_no_restart("validators", self.validators.values())
     }
 
+    /// Fails if a fullnode process exited without being stopped, see
+    /// `LocalNode::unexpected_exits`. Fullnodes stopped and started by the test don't count.
     async fn ensure_no_fullnode_restart(&self) -> Result<()> {
-        todo!()
+        ensure_no_restart("fullnodes", self.fullnodes.values())
     }
 
+    /// Evaluates the common shapes of queries over the metrics of the nodes, see
+    /// `evaluate_promql`. Every query scrapes the nodes, range functions compare to the scrapes
+    /// of earlier queries and of the poller, see `start_metrics_polling`.
     async fn query_metrics(
         &self,
-        _query: &str,
-        _time: Option<i64>,
+        query: &str,
+        time: Option<i64>,
         _timeout: Option<i64>,
     ) -> Result<PromqlResult> {
-        todo!()
+        let snapshot = self.metrics_snapshot().await?;
+        let history = record_snapshot(&self.metrics_history, snapshot, METRICS_HISTORY_RETENTION);
+        let history: Vec<_> = match time {
+            Some(time) => history
+                .into_iter()
+                .filter(|snapshot| {
+                    snapshot
+                        .time
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(false, |since| since.as_secs() as i64 <= time)
+                })
+                .collect(),
+            None => history,
+        };
+        let latest = history
+            .last()
+            .ok_or_else(|| anyhow!("No metrics scraped at or before {:?}", time))?;
+        to_promql_result(latest, &evaluate_promql(query, &history)?)
+    }
+
+    async fn query_range_metrics(
+        &self,
+        query: &str,
+        start: i64,
+        end: i64,
+        step: f64,
+    ) -> Result<PromqlResult> {
+        let client = self
+            .observability
+            .as_ref()
+            .ok_or_else(|| {
+                anyhow!("Range queries need the observability stack, see start_observability")
+            })?
+            .prometheus_client()?;
+        Ok(client.query_range(query, start, end, step, None).await?)
     }
 
+    /// Checked against the resource samples of the validators, see `start_resource_sampling`
+    /// and `resource_usage::system_metrics`. Network throughput is only sampled per node with
+    /// `isolate_network`.
     async fn ensure_healthy_system_metrics(
         &mut self,
-        _start_time: i64,
-        _end_time: i64,
-        _threshold: SystemMetricsThreshold,
+        start_time: i64,
+        end_time: i64,
+        threshold: SystemMetricsThreshold,
     ) -> Result<()> {
-        todo!()
+        let step = self.resource_sampling_interval.ok_or_else(|| {
+            anyhow!("System metrics need resource sampling, see start_resource_sampling")
+        })?;
+        ensure!(
+            !threshold.checks_network() || self.network_isolation.is_some(),
+            "Network metrics need the nodes in their own network namespace, see isolate_network"
+        );
+        let samples: Vec<_> = self
+            .validators
+            .values()
+            .map(LocalNode::resource_samples)
+            .collect();
+        let at = |secs: i64| UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64);
+        let metrics = system_metrics(&samples, at(start_time), at(end_time), step)?;
+        threshold.ensure_threshold(&metrics)
     }
 }
 
@@ -584,6 +1956,15 @@ pub struct ActiveNodesGuard {
 }
 
 impl ActiveNodesGuard {
+    /// A guard holding no slot, for swarms that are never launched in parallel with others,
+    /// e.g. the ones of the forge runner or of tools launching them one after the other.
+    pub fn unguarded() -> Self {
+        Self {
+            counter: Arc::new(Mutex::new(0)),
+            slots: 0,
+        }
+    }
+
     pub async fn grab(slots: usize, counter: Arc<Mutex<usize>>) -> Self {
         let max = num_cpus::get();
         let mut idx = 0;
@@ -0,0 +1,98 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validators joining and leaving the validator set of a running chain. As at genesis, the
+//! account of a validator owns its stake pool and is also its operator and voter.
+
+use super::framework_upgrade::submit;
+use crate::{reconfig, Result};
+use anyhow::Context;
+use aptos_genesis::Validator;
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::{
+    transaction_builder::{aptos_stdlib, TransactionFactory},
+    types::LocalAccount,
+};
+
+/// Coins minted to a joining validator on top of its stake, so it can pay for its transactions.
+const VALIDATOR_FUNDS: u64 = 100_000_000;
+
+/// Creates the account of `validator`, funded by the root account, stakes `stake_amount` into
+/// its new pool and joins the validator set, which takes effect at the epoch change triggered
+/// at the end. Fails if the genesis doesn't allow validator set changes.
+pub async fn join_validator_set(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    root_account: &mut LocalAccount,
+    account: &mut LocalAccount,
+    validator: Validator,
+) -> Result<()> {
+    let address = account.address();
+    submit(
+        client,
+        transaction_factory,
+        root_account,
+        aptos_stdlib::aptos_account_create_account(address),
+    )
+    .await?;
+    submit(
+        client,
+        transaction_factory,
+        root_account,
+        aptos_stdlib::aptos_coin_mint(address, validator.stake_amount + VALIDATOR_FUNDS),
+    )
+    .await?;
+
+    submit(
+        client,
+        transaction_factory,
+        account,
+        aptos_stdlib::stake_initialize_validator(
+            validator.consensus_pubkey,
+            validator.proof_of_possession,
+            validator.network_addresses,
+            validator.full_node_network_addresses,
+        ),
+    )
+    .await?;
+    submit(
+        client,
+        transaction_factory,
+        account,
+        aptos_stdlib::stake_add_stake(validator.stake_amount),
+    )
+    .await?;
+    submit(
+        client,
+        transaction_factory,
+        account,
+        aptos_stdlib::stake_join_validator_set(address),
+    )
+    .await
+    .with_context(|| format!("Validator {} failed to join the validator set", address))?;
+
+    reconfig(client, transaction_factory, root_account).await?;
+    Ok(())
+}
+
+/// Makes the validator of `account` leave the validator set, at the epoch change triggered at
+/// the end. Fails if the genesis doesn't allow validator set changes.
+pub async fn leave_validator_set(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    root_account: &mut LocalAccount,
+    account: &mut LocalAccount,
+) -> Result<()> {
+    let address = account.address();
+    submit(
+        client,
+        transaction_factory,
+        account,
+        aptos_stdlib::stake_leave_validator_set(address),
+    )
+    .await
+    .with_context(|| format!("Validator {} failed to leave the validator set", address))?;
+
+    reconfig(client, transaction_factory, root_account).await?;
+    Ok(())
+}
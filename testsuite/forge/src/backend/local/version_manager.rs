@@ -0,0 +1,181 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{cargo, sha256_file, LocalVersion};
+use crate::{Result, Version};
+use anyhow::{bail, Context};
+use aptos_logger::info;
+use sha2::{Digest, Sha256};
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Placeholder for the release tag in download URL templates.
+const TAG_PLACEHOLDER: &str = "{tag}";
+
+/// Provides aptos-node binaries for release tags (or any other git revision), so that
+/// compatibility tests don't require every binary to be built and passed by hand.
+///
+/// Binaries are cached under `<cache_dir>/aptos-node--<tag>`, with their SHA-256 next to them
+/// in `aptos-node--<tag>.sha256`. Missing binaries are downloaded if a download URL is
+/// configured, and built from the tagged sources otherwise. Cached binaries not matching their
+/// checksum are fetched again.
+#[derive(Clone, Debug)]
+pub struct VersionManager {
+    cache_dir: PathBuf,
+    /// e.g. `https://example.com/releases/{tag}/aptos-node`
+    download_url_template: Option<String>,
+}
+
+impl VersionManager {
+    pub fn new(cache_dir: PathBuf) -> Self {
+        Self {
+            cache_dir,
+            download_url_template: None,
+        }
+    }
+
+    /// Caches binaries in `target/forge/releases` of the current workspace.
+    pub fn from_workspace() -> Result<Self> {
+        let metadata = cargo::metadata()?;
+        Ok(Self::new(
+            metadata.target_directory.join("forge").join("releases"),
+        ))
+    }
+
+    /// Downloads binaries from `template`, with `{tag}` replaced by the requested tag, instead
+    /// of building them. Falls back to building if the download fails.
+    pub fn with_download_url(mut self, template: impl Into<String>) -> Self {
+        self.download_url_template = Some(template.into());
+        self
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Returns the path of the aptos-node binary for `tag`, fetching it if it isn't cached yet.
+    pub fn get(&self, tag: &str) -> Result<PathBuf> {
+        let bin = self.cached_bin(tag);
+        if bin.exists() {
+            match verify_cached(&bin) {
+                Ok(()) => return Ok(bin),
+                Err(e) => {
+                    info!("Fetching aptos-node {} again: {}", tag, e);
+                    fs::remove_file(&bin)?;
+                }
+            }
+        }
+        fs::create_dir_all(&self.cache_dir)?;
+
+        if let Some(template) = &self.download_url_template {
+            let url = template.replace(TAG_PLACEHOLDER, tag);
+            match download(&url, &bin) {
+                Ok(()) => {
+                    write_checksum(&bin)?;
+                    return Ok(bin);
+                }
+                Err(e) => info!(
+                    "Failed to download aptos-node {} from {}, building it instead: {:?}",
+                    tag, url, e
+                ),
+            }
+        }
+
+        cargo::git_fetch_tag(tag)?;
+        let (_, built) = cargo::get_aptos_node_binary_at_revision(tag)?;
+        fs::copy(&built, &bin)
+            .with_context(|| format!("Failed to cache aptos-node {} at {:?}", tag, bin))?;
+        write_checksum(&bin)?;
+        Ok(bin)
+    }
+
+    /// Fetches the binary for `tag` and registers it as a `LocalVersion`. Versions are ordered
+    /// by `index`, older releases should get lower indices.
+    pub fn local_version(&self, tag: &str, index: usize) -> Result<LocalVersion> {
+        Ok(LocalVersion::new(
+            self.get(tag)?,
+            Version::new(index, tag.to_string()),
+        ))
+    }
+
+    fn cached_bin(&self, tag: &str) -> PathBuf {
+        self.cache_dir.join(format!(
+            "aptos-node--{}{}",
+            tag.replace('/', "-"),
+            env::consts::EXE_SUFFIX
+        ))
+    }
+}
+
+fn checksum_path(bin: &Path) -> PathBuf {
+    let mut file_name = bin.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".sha256");
+    bin.with_file_name(file_name)
+}
+
+fn write_checksum(bin: &Path) -> Result<()> {
+    fs::write(checksum_path(bin), sha256_file(bin)?)?;
+    Ok(())
+}
+
+/// Checks a cached binary against its recorded checksum. Binaries cached without one get it
+/// recorded now.
+fn verify_cached(bin: &Path) -> Result<()> {
+    let expected = match fs::read_to_string(checksum_path(bin)) {
+        Ok(expected) => expected,
+        Err(_) => return write_checksum(bin),
+    };
+    let actual = sha256_file(bin)?;
+    if actual != expected.trim() {
+        bail!(
+            "{:?} has sha256 {}, cached with {}",
+            bin,
+            actual,
+            expected.trim()
+        );
+    }
+    Ok(())
+}
+
+/// Downloads `url` to `to`, going through a temporary file so that interrupted downloads don't
+/// leave a broken binary in the cache. If `<url>.sha256` exists, the download is checked
+/// against it.
+fn download(url: &str, to: &Path) -> Result<()> {
+    info!("Downloading {} to {:?}", url, to);
+    let response = reqwest::blocking::get(url)?.error_for_status()?;
+    let bytes = response.bytes()?;
+    if bytes.is_empty() {
+        bail!("Downloaded an empty binary from {}", url);
+    }
+    let checksum_url = format!("{}.sha256", url);
+    if let Ok(response) =
+        reqwest::blocking::get(&checksum_url).and_then(|response| response.error_for_status())
+    {
+        // Formatted as sha256sum's output, i.e. the checksum followed by the file name.
+        let published = response.text()?;
+        let expected = published.split_whitespace().next().unwrap_or_default();
+        let actual = hex::encode(Sha256::digest(&bytes));
+        if actual != expected {
+            bail!(
+                "Downloaded {} has sha256 {}, {} says {}",
+                url,
+                actual,
+                checksum_url,
+                expected
+            );
+        }
+    }
+
+    let mut file = tempfile::NamedTempFile::new_in(to.parent().unwrap_or_else(|| Path::new(".")))?;
+    file.write_all(&bytes)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(0o755))?;
+    }
+    file.persist(to)?;
+    Ok(())
+}
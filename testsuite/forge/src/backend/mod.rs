@@ -6,3 +6,6 @@ pub use local::{LocalNode, *};
 
 mod k8s;
 pub use k8s::{K8sNode, *};
+
+mod ssh;
+pub use ssh::*;
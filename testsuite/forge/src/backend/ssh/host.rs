@@ -0,0 +1,232 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::Result;
+use anyhow::{bail, format_err, Context};
+use std::{
+    fmt,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+/// Fails fast on hosts that don't answer rather than hanging the run
+const CONNECT_TIMEOUT_SECS: u32 = 10;
+
+/// A host nodes are run on, driven through the `ssh` and `scp` binaries so that the usual SSH
+/// configuration, agent and known hosts apply.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SshHost {
+    /// Address of the host, which the nodes also advertise to each other
+    pub ip: IpAddr,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Private key to authenticate with, instead of the SSH agent or configuration
+    pub identity_file: Option<PathBuf>,
+}
+
+impl SshHost {
+    pub fn new(ip: IpAddr) -> Self {
+        Self {
+            ip,
+            user: None,
+            port: None,
+            identity_file: None,
+        }
+    }
+
+    pub fn with_identity_file(mut self, identity_file: PathBuf) -> Self {
+        self.identity_file = Some(identity_file);
+        self
+    }
+
+    /// Runs `command` with the shell of the host, returning its stdout.
+    pub fn run(&self, command: &str) -> Result<String> {
+        let mut ssh = Command::new("ssh");
+        ssh.args(self.options());
+        if let Some(port) = self.port {
+            ssh.arg("-p").arg(port.to_string());
+        }
+        ssh.arg(self.destination()).arg(command);
+        let output = ssh
+            .output()
+            .with_context(|| format!("Failed to run ssh to {}", self))?;
+        if !output.status.success() {
+            bail!(
+                "`{}` failed on {} with {}: {}",
+                command,
+                self,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|e| format_err!("Invalid output of `{}` on {}: {}", command, self, e))
+    }
+
+    /// Deletes `paths` on the host, recursively. Empty paths and the root are refused, rather
+    /// than deleting whatever they would expand to.
+    pub fn remove_all(&self, paths: &[&Path]) -> Result<()> {
+        let mut command = "rm -rf".to_string();
+        for path in paths {
+            if path.as_os_str().is_empty() || path.parent().is_none() {
+                bail!("Refusing to delete {:?} on {}", path, self);
+            }
+            command.push(' ');
+            command.push_str(&quote_path(path));
+        }
+        self.run(&command)?;
+        Ok(())
+    }
+
+    /// Copies the local file or directory `local` to `remote` on the host.
+    pub fn upload(&self, local: &Path, remote: &Path) -> Result<()> {
+        self.scp(local.display().to_string(), self.remote_path(remote))
+    }
+
+    /// Copies the file or directory `remote` on the host to `local`.
+    pub fn download(&self, remote: &Path, local: &Path) -> Result<()> {
+        self.scp(self.remote_path(remote), local.display().to_string())
+    }
+
+    fn scp(&self, from: String, to: String) -> Result<()> {
+        let mut scp = Command::new("scp");
+        scp.args(self.options()).arg("-r").arg("-q");
+        if let Some(port) = self.port {
+            scp.arg("-P").arg(port.to_string());
+        }
+        let output = scp
+            .arg(&from)
+            .arg(&to)
+            .output()
+            .with_context(|| format!("Failed to run scp to {}", self))?;
+        if !output.status.success() {
+            bail!(
+                "Copying {} to {} failed with {}: {}",
+                from,
+                to,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    fn options(&self) -> Vec<String> {
+        let mut options = vec![
+            "-o".to_string(),
+            "BatchMode=yes".to_string(),
+            "-o".to_string(),
+            format!("ConnectTimeout={}", CONNECT_TIMEOUT_SECS),
+        ];
+        if let Some(identity_file) = &self.identity_file {
+            options.push("-i".to_string());
+            options.push(identity_file.display().to_string());
+        }
+        options
+    }
+
+    fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", user, self.ip),
+            None => self.ip.to_string(),
+        }
+    }
+
+    fn remote_path(&self, path: &Path) -> String {
+        match self.ip {
+            IpAddr::V4(_) => format!("{}:{}", self.destination(), path.display()),
+            IpAddr::V6(ip) => {
+                let user = self.user.as_ref().map(|user| format!("{}@", user));
+                format!("{}[{}]:{}", user.unwrap_or_default(), ip, path.display())
+            }
+        }
+    }
+}
+
+/// Quotes `value` for the shell of a host, so that it's passed as a single word, as is.
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+pub(crate) fn quote_path(path: &Path) -> String {
+    shell_quote(&path.display().to_string())
+}
+
+impl fmt::Display for SshHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.destination())
+    }
+}
+
+/// Parses `[user@]ip[:port]`, IPv6 addresses being bracketed when followed by a port.
+impl FromStr for SshHost {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (user, address) = match s.split_once('@') {
+            Some((user, address)) => (Some(user.to_string()), address),
+            None => (None, s),
+        };
+        let (ip, port) = if let Some(bracketed) = address.strip_prefix('[') {
+            let (ip, rest) = bracketed
+                .split_once(']')
+                .ok_or_else(|| format_err!("Unclosed bracket in host {}", s))?;
+            let port = match rest.strip_prefix(':') {
+                Some(port) => Some(port.parse()?),
+                None if rest.is_empty() => None,
+                None => bail!("Invalid host {}", s),
+            };
+            (ip.parse()?, port)
+        } else if let Ok(ip) = address.parse() {
+            (ip, None)
+        } else {
+            let (ip, port) = address
+                .rsplit_once(':')
+                .ok_or_else(|| format_err!("Invalid host {}, expected an IP address", s))?;
+            (ip.parse()?, Some(port.parse()?))
+        };
+        Ok(Self {
+            ip,
+            user,
+            port,
+            identity_file: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_host() {
+        let host: SshHost = "aptos@10.0.0.1:2222".parse().unwrap();
+        assert_eq!(host.user.as_deref(), Some("aptos"));
+        assert_eq!(host.ip, "10.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(host.port, Some(2222));
+        assert_eq!(
+            host.remote_path(Path::new("/opt/forge")),
+            "aptos@10.0.0.1:/opt/forge"
+        );
+
+        let host: SshHost = "[::1]:22".parse().unwrap();
+        assert_eq!((host.user, host.port), (None, Some(22)));
+        let host: SshHost = "::1".parse().unwrap();
+        assert_eq!(host.remote_path(Path::new("/opt")), "[::1]:/opt");
+
+        assert!("node.mysite.com".parse::<SshHost>().is_err());
+        assert!("[::1".parse::<SshHost>().is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/opt/forge"), "'/opt/forge'");
+        assert_eq!(shell_quote("a b; rm -rf /"), "'a b; rm -rf /'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert!(SshHost::new("10.0.0.1".parse().unwrap())
+            .remove_all(&[Path::new("")])
+            .is_err());
+    }
+}
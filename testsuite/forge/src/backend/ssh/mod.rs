@@ -0,0 +1,82 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Factory, GenesisConfig, GenesisConfigFn, NodeConfigFn, Result, Swarm, Version};
+use anyhow::bail;
+use rand::rngs::StdRng;
+use std::{collections::HashMap, num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration};
+
+mod host;
+mod node;
+mod swarm;
+pub use host::SshHost;
+pub use node::SshNode;
+pub use swarm::SshSwarm;
+
+/// Launches swarms over SSH on dedicated hosts, e.g. performance hardware, see `SshSwarm`.
+pub struct SshFactory {
+    hosts: Vec<SshHost>,
+    /// Local aptos-node binaries by version
+    versions: Arc<HashMap<Version, PathBuf>>,
+    /// Directory of the swarms, both locally and on the hosts
+    dir: PathBuf,
+    keep: bool,
+}
+
+impl SshFactory {
+    pub fn new(hosts: Vec<SshHost>, versions: HashMap<Version, PathBuf>, dir: PathBuf) -> Self {
+        Self {
+            hosts,
+            versions: Arc::new(versions),
+            dir,
+            keep: false,
+        }
+    }
+
+    /// Leaves the nodes of the swarms running once the tests are done.
+    pub fn with_keep(mut self) -> Self {
+        self.keep = true;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Factory for SshFactory {
+    fn versions<'a>(&'a self) -> Box<dyn Iterator<Item = Version> + 'a> {
+        Box::new(self.versions.keys().cloned())
+    }
+
+    async fn launch_swarm(
+        &self,
+        rng: &mut StdRng,
+        num_validators: NonZeroUsize,
+        num_fullnodes: usize,
+        version: &Version,
+        _genesis_version: &Version,
+        genesis_config: Option<&GenesisConfig>,
+        _cleanup_duration: Duration,
+        _genesis_config_fn: Option<GenesisConfigFn>,
+        _node_config_fn: Option<NodeConfigFn>,
+    ) -> Result<Box<dyn Swarm>> {
+        let framework = match genesis_config {
+            Some(GenesisConfig::Bundle(bundle)) => Some(bundle.clone()),
+            Some(GenesisConfig::Path(_)) => {
+                bail!("ssh forge backend does not support flattened dir for genesis")
+            }
+            None => None,
+        };
+        let mut swarm = SshSwarm::build(
+            rng,
+            &self.hosts,
+            num_validators,
+            num_fullnodes,
+            self.versions.clone(),
+            version,
+            self.dir.clone(),
+            framework,
+        )?;
+        swarm.set_keep(self.keep);
+        swarm.launch().await?;
+        Ok(Box::new(swarm))
+    }
+}
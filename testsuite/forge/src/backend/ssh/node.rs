@@ -0,0 +1,250 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{host::quote_path, SshHost};
+use crate::{
+    record_event, FullNode, HealthCheckError, HealthCheckFailure, Node, NodeExt, NotRunningReason,
+    Result, TimelineEventKind, Validator, Version,
+};
+use anyhow::{anyhow, format_err};
+use aptos_config::config::NodeConfig;
+use aptos_logger::info;
+use aptos_sdk::types::PeerId;
+use aptos_secure_storage::SECURE_STORAGE_DB_NAME;
+use aptosdb::{LEDGER_DB_NAME, STATE_MERKLE_DB_NAME};
+use serde_json::Value;
+use state_sync_driver::metadata_storage::STATE_SYNC_DB_NAME;
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use url::Url;
+
+const PID_FILE: &str = "node.pid";
+const LOG_FILE: &str = "node.log";
+/// How long a stopped node gets to exit before it's killed
+const STOP_GRACE_PERIOD_SECS: u64 = 10;
+
+/// A node run over SSH on a host, from `dir` which holds its config, data and logs.
+pub struct SshNode {
+    name: String,
+    peer_id: PeerId,
+    host: SshHost,
+    version: Version,
+    /// Path of aptos-node on the host
+    bin: PathBuf,
+    dir: PathBuf,
+    config: NodeConfig,
+}
+
+impl SshNode {
+    pub(crate) fn new(
+        name: String,
+        host: SshHost,
+        version: Version,
+        bin: PathBuf,
+        dir: PathBuf,
+        config: NodeConfig,
+    ) -> Result<Self> {
+        let peer_id = config
+            .peer_id()
+            .ok_or_else(|| anyhow!("unable to retrieve PeerId from config"))?;
+        Ok(Self {
+            name,
+            peer_id,
+            host,
+            version,
+            bin,
+            dir,
+            config,
+        })
+    }
+
+    pub fn host(&self) -> &SshHost {
+        &self.host
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the logs of the node on its host.
+    pub fn log_path(&self) -> PathBuf {
+        self.dir.join(LOG_FILE)
+    }
+
+    /// Runs the aptos-node at `bin` on the host from the next start on.
+    pub(crate) fn set_version(&mut self, version: Version, bin: PathBuf) {
+        self.version = version;
+        self.bin = bin;
+    }
+
+    /// Copies the logs of the node to `local`.
+    pub fn fetch_logs(&self, local: &Path) -> Result<()> {
+        self.host.download(&self.log_path(), local)
+    }
+
+    /// Stops the process of the node, killing it if it doesn't exit within the grace period.
+    pub(crate) fn stop_process(&self) -> Result<()> {
+        let pid = self.pid_path();
+        self.host.run(&format!(
+            "if [ -f {pid} ]; then p=$(cat {pid}); kill $p 2>/dev/null; \
+             for i in $(seq 1 {grace}); do kill -0 $p 2>/dev/null || break; sleep 1; done; \
+             kill -9 $p 2>/dev/null; rm -f {pid}; fi",
+            pid = pid,
+            grace = STOP_GRACE_PERIOD_SECS,
+        ))?;
+        Ok(())
+    }
+
+    /// Path of the pid file, quoted for the shell of the host
+    fn pid_path(&self) -> String {
+        quote_path(&self.dir.join(PID_FILE))
+    }
+
+    /// Whether the node was started and not stopped, and whether its process is still alive.
+    fn status(&self) -> Result<(bool, bool)> {
+        let pid = self.pid_path();
+        let status = self.host.run(&format!(
+            "if [ ! -f {pid} ]; then echo stopped; \
+             elif kill -0 $(cat {pid}) 2>/dev/null; then echo running; \
+             else echo exited; fi",
+            pid = pid
+        ))?;
+        match status.trim() {
+            "stopped" => Ok((false, false)),
+            "running" => Ok((true, true)),
+            "exited" => Ok((true, false)),
+            other => Err(format_err!("Unexpected status of {}: {}", self.name, other)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Node for SshNode {
+    fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> Version {
+        self.version.clone()
+    }
+
+    fn rest_api_endpoint(&self) -> Url {
+        let address = SocketAddr::new(self.host.ip, self.config.api.address.port());
+        Url::parse(&format!("http://{}/v1", address)).expect("Invalid URL.")
+    }
+
+    fn inspection_service_endpoint(&self) -> Url {
+        let address = SocketAddr::new(self.host.ip, self.config.inspection_service.port);
+        Url::parse(&format!("http://{}", address)).expect("Invalid URL.")
+    }
+
+    fn config(&self) -> &NodeConfig {
+        &self.config
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        let pid = self.pid_path();
+        self.host.run(&format!(
+            "if [ -f {pid} ] && kill -0 $(cat {pid}) 2>/dev/null; then exit 0; fi; \
+             nohup {bin} -f {config} >> {log} 2>&1 < /dev/null & echo $! > {pid}",
+            pid = pid,
+            bin = quote_path(&self.bin),
+            config = quote_path(&self.dir.join("node.yaml")),
+            log = quote_path(&self.log_path()),
+        ))?;
+        record_event(TimelineEventKind::NodeStarted {
+            node: self.name.clone(),
+        });
+        self.wait_until_healthy(Instant::now() + Duration::from_secs(60))
+            .await
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        info!("going to stop node {} on {}", self.name, self.host);
+        self.stop_process()?;
+        record_event(TimelineEventKind::NodeStopped {
+            node: self.name.clone(),
+        });
+        Ok(())
+    }
+
+    async fn clear_storage(&mut self) -> Result<()> {
+        self.stop().await?;
+        let storage_dir = self.config.storage.dir();
+        let paths = [
+            storage_dir.join(LEDGER_DB_NAME),
+            storage_dir.join(STATE_MERKLE_DB_NAME),
+            storage_dir.join(STATE_SYNC_DB_NAME),
+            self.config.base.data_dir.join(SECURE_STORAGE_DB_NAME),
+        ];
+        info!(
+            "Deleting {:?} of node {} on {}",
+            paths, self.name, self.host
+        );
+        self.host
+            .remove_all(&paths.iter().map(PathBuf::as_path).collect::<Vec<_>>())
+    }
+
+    async fn health_check(&mut self) -> Result<(), HealthCheckError> {
+        match self.status().map_err(HealthCheckError::Unknown)? {
+            (false, _) => {
+                return Err(HealthCheckError::NotRunning(NotRunningReason::Stopped));
+            }
+            (true, false) => {
+                return Err(HealthCheckError::NotRunning(NotRunningReason::Exited {
+                    code: None,
+                    signal: None,
+                    core_dump: None,
+                }));
+            }
+            (true, true) => {}
+        }
+        self.rest_client()
+            .get_ledger_information()
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                HealthCheckError::Failure(HealthCheckFailure::Other(format_err!(
+                    "SSH node health_check failed: {}",
+                    e
+                )))
+            })
+    }
+
+    fn counter(&self, counter: &str, port: u64) -> Result<f64> {
+        let url = format!(
+            "http://{}/counters",
+            SocketAddr::new(self.host.ip, port as u16)
+        );
+        let response: Value = reqwest::blocking::get(url)?.json()?;
+        response[counter].as_f64().ok_or_else(|| {
+            format_err!(
+                "Counter({}) was not a number: {:?}",
+                counter,
+                response[counter]
+            )
+        })
+    }
+
+    fn expose_metric(&self) -> Result<u64> {
+        Ok(self.config.inspection_service.port as u64)
+    }
+}
+
+impl Validator for SshNode {}
+
+impl FullNode for SshNode {}
+
+impl fmt::Debug for SshNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} @ {}", self.name, self.host)
+    }
+}
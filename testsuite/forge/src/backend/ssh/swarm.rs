@@ -0,0 +1,518 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{host::quote_path, SshHost, SshNode};
+use crate::interface::system_metrics::SystemMetricsThreshold;
+use crate::{
+    ChainInfo, FullNode, Node, Result, Swarm, SwarmChaos, UnsupportedChaos, Validator, Version,
+};
+use anyhow::{anyhow, bail, ensure};
+use aptos_config::{
+    config::{NetworkConfig, NodeConfig},
+    keys::ConfigKey,
+    network_id::NetworkId,
+};
+use aptos_genesis::builder::FullnodeNodeConfig;
+use aptos_logger::{info, warn};
+use aptos_sdk::types::{
+    chain_id::ChainId, network_address::NetworkAddress, AccountKey, LocalAccount, PeerId,
+};
+use framework::ReleaseBundle;
+use prometheus_http_query::response::PromqlResult;
+use std::{
+    collections::HashMap,
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Validators listen on the same network ports on every host, as they're advertised on chain
+const VALIDATOR_NETWORK_PORT: u16 = 6180;
+const PUBLIC_NETWORK_PORT: u16 = 6182;
+
+/// Ports of the services of a node. A VFN runs on the host of its validator, so it needs
+/// others.
+struct NodePorts {
+    api: u16,
+    inspection: u16,
+    backup: u16,
+    vfn_network: u16,
+}
+
+const VALIDATOR_PORTS: NodePorts = NodePorts {
+    api: 8080,
+    inspection: 9101,
+    backup: 6186,
+    vfn_network: 6181,
+};
+
+const VFN_PORTS: NodePorts = NodePorts {
+    api: 8081,
+    inspection: 9102,
+    backup: 6187,
+    vfn_network: 6183,
+};
+
+/// A swarm of nodes run over SSH on dedicated hosts, one validator per host, along with its
+/// VFN if it has one.
+///
+/// Configs are generated locally under `dir` and copied to the same path on the hosts, so that
+/// the paths within them hold on both sides.
+pub struct SshSwarm {
+    validators: HashMap<PeerId, SshNode>,
+    fullnodes: HashMap<PeerId, SshNode>,
+    /// aptos-node binaries by version, copied to every host
+    versions: Arc<HashMap<Version, PathBuf>>,
+    dir: PathBuf,
+    root_account: LocalAccount,
+    chain_id: ChainId,
+    /// Whether the nodes are left running when the swarm is dropped
+    keep: bool,
+}
+
+impl SshSwarm {
+    /// Builds the genesis and the configs of `number_of_validators` validators on as many
+    /// `hosts`, and of VFNs for the first `number_of_fullnodes` of them. Nothing runs until
+    /// `launch`.
+    pub fn build<R>(
+        rng: R,
+        hosts: &[SshHost],
+        number_of_validators: NonZeroUsize,
+        number_of_fullnodes: usize,
+        versions: Arc<HashMap<Version, PathBuf>>,
+        version: &Version,
+        dir: PathBuf,
+        genesis_framework: Option<ReleaseBundle>,
+    ) -> Result<Self>
+    where
+        R: ::rand::RngCore + ::rand::CryptoRng,
+    {
+        ensure!(
+            hosts.len() >= number_of_validators.get(),
+            "{} hosts for {} validators, one host per validator is needed",
+            hosts.len(),
+            number_of_validators
+        );
+        ensure!(
+            number_of_fullnodes <= number_of_validators.get(),
+            "Only VFNs are supported, at most one per validator"
+        );
+        ensure!(
+            versions.contains_key(version),
+            "No aptos-node binary for version {}",
+            version
+        );
+        info!("Building a new SSH swarm in {:?}", dir);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+
+        let ips = hosts.iter().map(|host| host.ip).collect::<Vec<_>>();
+        let (root_key, genesis, genesis_waypoint, validators) =
+            aptos_genesis::builder::Builder::new(
+                &dir,
+                genesis_framework.unwrap_or_else(|| cached_packages::head_release_bundle().clone()),
+            )?
+            .with_num_validators(number_of_validators)
+            .with_randomize_ports(false)
+            .with_init_config(Some(Arc::new(move |index, config, _| {
+                let ip = ips[index];
+                set_ports(config, ip, &VALIDATOR_PORTS);
+                // The listen address of the first full node network is the public one
+                config.full_node_networks = vec![NetworkConfig {
+                    listen_address: network_address(ip, PUBLIC_NETWORK_PORT),
+                    ..NetworkConfig::network_with_id(NetworkId::Public)
+                }];
+            })))
+            .build(rng)?;
+
+        let bin = remote_bin(&dir, version);
+        let mut swarm_validators = HashMap::new();
+        let mut fullnodes = HashMap::new();
+        for (index, validator) in validators.into_iter().enumerate() {
+            let host = hosts[index].clone();
+            let mut config = validator.config;
+            set_ports(&mut config, host.ip, &VALIDATOR_PORTS);
+            if index < number_of_fullnodes {
+                // The VFN takes over the public network of its validator, as in a deployment
+                let position = config
+                    .full_node_networks
+                    .iter()
+                    .position(|network| network.network_id == NetworkId::Public)
+                    .ok_or_else(|| anyhow!("Validator should have a public network"))?;
+                let public_network = config.full_node_networks.remove(position);
+                let vfn = FullnodeNodeConfig::validator_fullnode(
+                    format!("{}-vfn", validator.name),
+                    &dir,
+                    NodeConfig::default_for_validator_full_node(),
+                    &config,
+                    &genesis_waypoint,
+                    &genesis,
+                    &public_network,
+                )?;
+                let mut vfn_config = vfn.config;
+                set_ports(&mut vfn_config, host.ip, &VFN_PORTS);
+                vfn_config.save(vfn.dir.join("node.yaml"))?;
+                let vfn = SshNode::new(
+                    vfn.name,
+                    host.clone(),
+                    version.clone(),
+                    bin.clone(),
+                    vfn.dir,
+                    vfn_config,
+                )?;
+                fullnodes.insert(vfn.peer_id(), vfn);
+            }
+            config.save(validator.dir.join("node.yaml"))?;
+            let node = SshNode::new(
+                validator.name,
+                host,
+                version.clone(),
+                bin.clone(),
+                validator.dir,
+                config,
+            )?;
+            swarm_validators.insert(node.peer_id(), node);
+        }
+
+        info!(
+            "The root (or mint) key for the swarm is: 0x{}",
+            hex::encode(&root_key.to_bytes())
+        );
+        let root_key = ConfigKey::new(root_key);
+        let root_account = LocalAccount::new(
+            aptos_sdk::types::account_config::aptos_test_root_address(),
+            AccountKey::from_private_key(root_key.private_key()),
+            0,
+        );
+
+        Ok(Self {
+            validators: swarm_validators,
+            fullnodes,
+            versions,
+            dir,
+            root_account,
+            chain_id: ChainId::test(),
+            keep: false,
+        })
+    }
+
+    /// Leaves the nodes running when the swarm is dropped.
+    pub fn set_keep(&mut self, keep: bool) {
+        self.keep = keep;
+    }
+
+    /// Copies the binaries and the configs to the hosts and starts the nodes, validators
+    /// first. Previous data of the nodes on the hosts is deleted.
+    pub async fn launch(&mut self) -> Result<()> {
+        let mut hosts: Vec<SshHost> = Vec::new();
+        for node in self.validators.values() {
+            if !hosts.contains(node.host()) {
+                hosts.push(node.host().clone());
+            }
+        }
+        for host in &hosts {
+            self.deploy_binaries(host)?;
+        }
+        for node in self.validators.values().chain(self.fullnodes.values()) {
+            deploy_config(node)?;
+        }
+
+        for node in self.validators.values_mut() {
+            node.start().await?;
+        }
+        for node in self.fullnodes.values_mut() {
+            node.start().await?;
+        }
+        info!("SSH swarm launched on {} hosts", hosts.len());
+        Ok(())
+    }
+
+    fn deploy_binaries(&self, host: &SshHost) -> Result<()> {
+        for (version, bin) in self.versions.iter() {
+            let remote = remote_bin(&self.dir, version);
+            info!("Copying {:?} to {} as {}", bin, host, version);
+            host.run(&format!(
+                "mkdir -p {}",
+                quote_path(remote.parent().unwrap())
+            ))?;
+            host.upload(bin, &remote)?;
+            host.run(&format!("chmod +x {}", quote_path(&remote)))?;
+        }
+        Ok(())
+    }
+
+    /// Copies the logs of every node to `dir`, as `<node name>.log`, returning how many
+    /// couldn't be copied.
+    pub fn fetch_logs(&self, dir: &Path) -> Result<usize> {
+        fs::create_dir_all(dir)?;
+        let mut failures = 0;
+        for node in self.validators.values().chain(self.fullnodes.values()) {
+            let local = dir.join(format!("{}.log", node.name()));
+            if let Err(e) = node.fetch_logs(&local) {
+                warn!("Failed to fetch the logs of {}: {:#}", node.name(), e);
+                failures += 1;
+            }
+        }
+        Ok(failures)
+    }
+}
+
+impl Drop for SshSwarm {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        for node in self.fullnodes.values().chain(self.validators.values()) {
+            if let Err(e) = node.stop_process() {
+                warn!("Failed to stop {}: {:#}", node.name(), e);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Swarm for SshSwarm {
+    async fn health_check(&mut self) -> Result<()> {
+        let mut unhealthy = Vec::new();
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            if let Err(e) = node.health_check().await {
+                unhealthy.push(format!("{}: {}", node.name(), e));
+            }
+        }
+        if !unhealthy.is_empty() {
+            bail!("Unhealthy nodes: {:?}", unhealthy);
+        }
+        Ok(())
+    }
+
+    fn validators<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn Validator> + 'a> {
+        Box::new(self.validators.values().map(|v| v as &'a dyn Validator))
+    }
+
+    fn validators_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut dyn Validator> + 'a> {
+        Box::new(
+            self.validators
+                .values_mut()
+                .map(|v| v as &'a mut dyn Validator),
+        )
+    }
+
+    fn validator(&self, id: PeerId) -> Option<&dyn Validator> {
+        self.validators.get(&id).map(|v| v as &dyn Validator)
+    }
+
+    fn validator_mut(&mut self, id: PeerId) -> Option<&mut dyn Validator> {
+        self.validators
+            .get_mut(&id)
+            .map(|v| v as &mut dyn Validator)
+    }
+
+    async fn upgrade_validator(&mut self, id: PeerId, version: &Version) -> Result<()> {
+        ensure!(
+            self.versions.contains_key(version),
+            "No aptos-node binary for version {}",
+            version
+        );
+        let bin = remote_bin(&self.dir, version);
+        let validator = self
+            .validators
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        validator.stop().await?;
+        validator.set_version(version.clone(), bin);
+        validator.start().await
+    }
+
+    fn full_nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a> {
+        Box::new(self.fullnodes.values().map(|v| v as &'a dyn FullNode))
+    }
+
+    fn full_nodes_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut dyn FullNode> + 'a> {
+        Box::new(
+            self.fullnodes
+                .values_mut()
+                .map(|v| v as &'a mut dyn FullNode),
+        )
+    }
+
+    fn full_node(&self, id: PeerId) -> Option<&dyn FullNode> {
+        self.fullnodes.get(&id).map(|v| v as &dyn FullNode)
+    }
+
+    fn full_node_mut(&mut self, id: PeerId) -> Option<&mut dyn FullNode> {
+        self.fullnodes.get_mut(&id).map(|v| v as &mut dyn FullNode)
+    }
+
+    async fn add_validator(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
+        bail!("Adding validators is not supported by the SSH backend")
+    }
+
+    async fn remove_validator(&mut self, _id: PeerId) -> Result<()> {
+        bail!("Removing validators is not supported by the SSH backend")
+    }
+
+    async fn add_validator_full_node(
+        &mut self,
+        _version: &Version,
+        _template: NodeConfig,
+        _id: PeerId,
+    ) -> Result<PeerId> {
+        bail!("Adding full nodes is not supported by the SSH backend")
+    }
+
+    async fn add_full_node(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
+        bail!("Adding full nodes is not supported by the SSH backend")
+    }
+
+    async fn remove_full_node(&mut self, _id: PeerId) -> Result<()> {
+        bail!("Removing full nodes is not supported by the SSH backend")
+    }
+
+    fn versions<'a>(&'a self) -> Box<dyn Iterator<Item = Version> + 'a> {
+        Box::new(self.versions.keys().cloned())
+    }
+
+    fn chain_info(&mut self) -> ChainInfo<'_> {
+        let rest_api_url = self
+            .validators
+            .values()
+            .next()
+            .unwrap()
+            .rest_api_endpoint()
+            .to_string();
+        ChainInfo::new(&mut self.root_account, rest_api_url, self.chain_id)
+    }
+
+    /// Fetches the logs of the nodes to `logs` in the swarm directory.
+    fn logs_location(&mut self) -> String {
+        let dir = self.dir.join("logs");
+        if let Err(e) = self.fetch_logs(&dir) {
+            warn!("Failed to fetch the logs of the swarm: {:#}", e);
+        }
+        dir.display().to_string()
+    }
+
+    fn inject_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        Err(UnsupportedChaos(chaos.kind()).into())
+    }
+
+    fn remove_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        Err(UnsupportedChaos(chaos.kind()).into())
+    }
+
+    async fn ensure_no_validator_restart(&self) -> Result<()> {
+        bail!("Restart detection is not supported by the SSH backend")
+    }
+
+    async fn ensure_no_fullnode_restart(&self) -> Result<()> {
+        bail!("Restart detection is not supported by the SSH backend")
+    }
+
+    async fn ensure_healthy_system_metrics(
+        &mut self,
+        _start_time: i64,
+        _end_time: i64,
+        _threshold: SystemMetricsThreshold,
+    ) -> Result<()> {
+        bail!("System metrics are not supported by the SSH backend")
+    }
+
+    async fn query_metrics(
+        &self,
+        _query: &str,
+        _time: Option<i64>,
+        _timeout: Option<i64>,
+    ) -> Result<PromqlResult> {
+        bail!("Prometheus queries are not supported by the SSH backend")
+    }
+
+    async fn query_range_metrics(
+        &self,
+        _query: &str,
+        _start: i64,
+        _end: i64,
+        _step: f64,
+    ) -> Result<PromqlResult> {
+        bail!("Prometheus queries are not supported by the SSH backend")
+    }
+}
+
+/// Makes `config` listen on the ports of `ports`, and on `ip` for the networks other nodes dial.
+fn set_ports(config: &mut NodeConfig, ip: IpAddr, ports: &NodePorts) {
+    config.api.address = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), ports.api);
+    config.inspection_service.address = Ipv4Addr::UNSPECIFIED.to_string();
+    config.inspection_service.port = ports.inspection;
+    config.storage.backup_service_address =
+        SocketAddr::new(Ipv4Addr::LOCALHOST.into(), ports.backup);
+    if let Some(network) = config.validator_network.as_mut() {
+        network.listen_address = network_address(ip, VALIDATOR_NETWORK_PORT);
+    }
+    for network in config.full_node_networks.iter_mut() {
+        if network.network_id == NetworkId::Vfn {
+            network.listen_address = network_address(ip, ports.vfn_network);
+        }
+    }
+}
+
+fn network_address(ip: IpAddr, port: u16) -> NetworkAddress {
+    NetworkAddress::from(SocketAddr::new(ip, port))
+}
+
+/// Path of the aptos-node of `version` on the hosts.
+fn remote_bin(dir: &Path, version: &Version) -> PathBuf {
+    let name = version
+        .to_string()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    dir.join("bin").join(name).join("aptos-node")
+}
+
+/// Replaces the directory of `node` on its host with the local one.
+fn deploy_config(node: &SshNode) -> Result<()> {
+    let dir = node.dir();
+    let parent = dir
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid node directory {:?}", dir))?;
+    node.host().remove_all(&[dir])?;
+    node.host()
+        .run(&format!("mkdir -p {}", quote_path(parent)))?;
+    node.host().upload(dir, parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_ports() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let mut config = NodeConfig::default_for_validator_full_node();
+        set_ports(&mut config, ip, &VFN_PORTS);
+        assert_eq!(config.api.address.port(), 8081);
+        let vfn_network = config
+            .full_node_networks
+            .iter()
+            .find(|network| network.network_id == NetworkId::Vfn)
+            .unwrap();
+        assert_eq!(
+            vfn_network.listen_address,
+            "/ip4/10.0.0.1/tcp/6183".parse().unwrap()
+        );
+        assert_eq!(
+            remote_bin(Path::new("/opt/forge"), &Version::new(0, "v1.2/rc".into())),
+            Path::new("/opt/forge/bin/v1.2_rc/aptos-node")
+        );
+    }
+}
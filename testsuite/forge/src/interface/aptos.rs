@@ -3,8 +3,8 @@
 
 use super::Test;
 use crate::{CoreContext, Result, TestReport};
-use aptos_logger::info;
-use aptos_rest_client::{Client as RestClient, PendingTransaction, State, Transaction};
+use anyhow::bail;
+use aptos_rest_client::{Client as RestClient, FaucetClient, PendingTransaction, State};
 use aptos_sdk::{
     crypto::ed25519::Ed25519PublicKey,
     move_types::identifier::Identifier,
@@ -12,13 +12,25 @@ use aptos_sdk::{
     types::{
         account_address::AccountAddress,
         chain_id::ChainId,
-        transaction::authenticator::{AuthenticationKey, AuthenticationKeyPreimage},
+        transaction::{
+            authenticator::{AuthenticationKey, AuthenticationKeyPreimage},
+            TransactionPayload,
+        },
         LocalAccount,
     },
 };
 use cached_packages::aptos_stdlib;
+use futures::future::join_all;
 use rand::{rngs::OsRng, Rng, SeedableRng};
 use reqwest::Url;
+use std::{
+    sync::atomic::AtomicUsize,
+    time::{Duration, Instant},
+};
+use transaction_emitter_lib::{
+    emitter::account_minter::{create_and_fund_account_request, execute_and_wait_transactions},
+    governance::{self, EpochChange, GovernanceError},
+};
 
 #[async_trait::async_trait]
 pub trait AptosTest: Test {
@@ -105,11 +117,37 @@ impl<'t> AptosContext<'t> {
     }
 }
 
+/// Max gas a transaction from `AptosPublicInfo::transaction_factory` can cost at its gas price
+const MAX_GAS_PER_TXN: u64 = 1_000;
+
+/// How `AptosPublicInfo::fund_accounts` spreads its transfers.
+#[derive(Clone, Debug)]
+pub struct FundingConfig {
+    /// Accounts sending the transfers in parallel, each with its own sequence numbers
+    pub num_funding_accounts: usize,
+    /// Transfers submitted at once by a funding account
+    pub batch_size: usize,
+    /// Transfers submitted per second across all funding accounts
+    pub max_txns_per_sec: usize,
+}
+
+impl Default for FundingConfig {
+    fn default() -> Self {
+        Self {
+            num_funding_accounts: 10,
+            batch_size: 20,
+            max_txns_per_sec: 200,
+        }
+    }
+}
+
 pub struct AptosPublicInfo<'t> {
     chain_id: ChainId,
     rest_api_url: Url,
     rest_client: RestClient,
     root_account: &'t mut LocalAccount,
+    /// Funds test accounts instead of the root account when set
+    faucet: Option<FaucetClient>,
     rng: ::rand::rngs::StdRng,
 }
 
@@ -125,10 +163,17 @@ impl<'t> AptosPublicInfo<'t> {
             rest_api_url,
             chain_id,
             root_account,
+            faucet: None,
             rng: ::rand::rngs::StdRng::from_seed(OsRng.gen()),
         }
     }
 
+    /// Creates and funds test accounts through the faucet at `faucet_url`.
+    pub fn with_faucet(mut self, faucet_url: Url) -> Self {
+        self.faucet = Some(FaucetClient::new(faucet_url, self.rest_api_url.clone()));
+        self
+    }
+
     pub fn client(&self) -> &RestClient {
         &self.rest_client
     }
@@ -144,6 +189,9 @@ impl<'t> AptosPublicInfo<'t> {
     pub async fn create_user_account(&mut self, pubkey: &Ed25519PublicKey) -> Result<()> {
         let preimage = AuthenticationKeyPreimage::ed25519(pubkey);
         let auth_key = AuthenticationKey::from_preimage(&preimage);
+        if let Some(faucet) = &self.faucet {
+            return faucet.create_account(auth_key.derived_address()).await;
+        }
         let create_account_txn =
             self.root_account
                 .sign_with_transaction_builder(self.transaction_factory().payload(
@@ -156,6 +204,9 @@ impl<'t> AptosPublicInfo<'t> {
     }
 
     pub async fn mint(&mut self, addr: AccountAddress, amount: u64) -> Result<()> {
+        if let Some(faucet) = &self.faucet {
+            return faucet.fund(addr, amount).await;
+        }
         let mint_txn = self.root_account.sign_with_transaction_builder(
             self.transaction_factory()
                 .payload(aptos_stdlib::aptos_coin_mint(addr, amount)),
@@ -229,7 +280,119 @@ impl<'t> AptosPublicInfo<'t> {
         Ok(account)
     }
 
-    pub async fn reconfig(&mut self) -> State {
+    /// Funds every address with `amount` coins, creating the accounts that don't exist yet, and
+    /// checks all of them hold at least `amount` at the end.
+    pub async fn fund_accounts(&mut self, addresses: &[AccountAddress], amount: u64) -> Result<()> {
+        self.fund_accounts_with_config(addresses, amount, &FundingConfig::default())
+            .await
+    }
+
+    /// Same as `fund_accounts`, but the transfers are spread as described by `config`: the root
+    /// account funds `num_funding_accounts` intermediate accounts, which then transfer to their
+    /// share of `addresses` in parallel batches. With a faucet, the faucet is called instead.
+    pub async fn fund_accounts_with_config(
+        &mut self,
+        addresses: &[AccountAddress],
+        amount: u64,
+        config: &FundingConfig,
+    ) -> Result<()> {
+        if addresses.is_empty() {
+            return Ok(());
+        }
+        let num_funding_accounts = config.num_funding_accounts.clamp(1, addresses.len());
+        let batch_size = config.batch_size.max(1);
+        // Every funding account gets an equal share of the submission rate
+        let max_txns_per_sec = (config.max_txns_per_sec / num_funding_accounts).max(1);
+
+        if let Some(faucet) = &self.faucet {
+            for batch in addresses.chunks(batch_size * num_funding_accounts) {
+                let start = Instant::now();
+                for result in
+                    join_all(batch.iter().map(|address| faucet.fund(*address, amount))).await
+                {
+                    result?;
+                }
+                throttle(start, batch.len(), config.max_txns_per_sec).await;
+            }
+        } else {
+            let shares: Vec<_> = addresses
+                .chunks((addresses.len() + num_funding_accounts - 1) / num_funding_accounts)
+                .collect();
+            let transaction_factory = self.transaction_factory();
+            let mut funding_accounts = Vec::with_capacity(shares.len());
+            let mut create_requests = Vec::with_capacity(shares.len());
+            for share in &shares {
+                let funding_account = self.random_account();
+                create_requests.push(create_and_fund_account_request(
+                    self.root_account,
+                    (amount + MAX_GAS_PER_TXN) * share.len() as u64,
+                    funding_account.public_key(),
+                    &transaction_factory,
+                ));
+                funding_accounts.push(funding_account);
+            }
+            execute_and_wait_transactions(
+                &self.rest_client,
+                self.root_account,
+                create_requests,
+                &AtomicUsize::new(0),
+            )
+            .await?;
+
+            let client = &self.rest_client;
+            let transaction_factory = &transaction_factory;
+            let results = join_all(shares.into_iter().zip(funding_accounts).map(
+                |(share, mut funding_account)| async move {
+                    for batch in share.chunks(batch_size) {
+                        let start = Instant::now();
+                        let transfers = batch
+                            .iter()
+                            .map(|address| {
+                                funding_account.sign_with_transaction_builder(
+                                    transaction_factory.payload(
+                                        aptos_stdlib::aptos_account_transfer(*address, amount),
+                                    ),
+                                )
+                            })
+                            .collect();
+                        execute_and_wait_transactions(
+                            client,
+                            &mut funding_account,
+                            transfers,
+                            &AtomicUsize::new(0),
+                        )
+                        .await?;
+                        throttle(start, batch.len(), max_txns_per_sec).await;
+                    }
+                    Ok::<_, anyhow::Error>(())
+                },
+            ))
+            .await;
+            for result in results {
+                result?;
+            }
+        }
+
+        let balances = join_all(addresses.iter().map(|address| self.get_balance(*address))).await;
+        let underfunded: Vec<_> = addresses
+            .iter()
+            .zip(balances)
+            .filter(|(_, balance)| balance.unwrap_or(0) < amount)
+            .map(|(address, _)| *address)
+            .collect();
+        if !underfunded.is_empty() {
+            bail!(
+                "{} of {} accounts hold less than {} after funding: {:?}",
+                underfunded.len(),
+                addresses.len(),
+                amount,
+                underfunded
+            );
+        }
+        Ok(())
+    }
+
+    pub async fn reconfig(&mut self) -> std::result::Result<State, GovernanceError> {
         // dedupe with smoke-test::test_utils::reconfig
         reconfig(
             &self.rest_client,
@@ -238,70 +401,37 @@ impl<'t> AptosPublicInfo<'t> {
         )
         .await
     }
+
+    /// Executes an admin payload (feature flags, consensus config, gas schedule, ...) with the
+    /// root account and waits for the epoch change it triggers.
+    pub async fn execute_and_reconfigure(
+        &mut self,
+        payload: TransactionPayload,
+    ) -> std::result::Result<EpochChange, GovernanceError> {
+        governance::execute_and_reconfigure(
+            &self.rest_client,
+            &self.transaction_factory(),
+            self.root_account,
+            payload,
+        )
+        .await
+    }
+}
+
+/// Sleeps long enough after submitting `num_txns` since `start` to stay under `max_txns_per_sec`.
+async fn throttle(start: Instant, num_txns: usize, max_txns_per_sec: usize) {
+    let min_duration = Duration::from_secs_f64(num_txns as f64 / max_txns_per_sec.max(1) as f64);
+    if let Some(remaining) = min_duration.checked_sub(start.elapsed()) {
+        tokio::time::sleep(remaining).await;
+    }
 }
 
 pub async fn reconfig(
     client: &RestClient,
     transaction_factory: &TransactionFactory,
     root_account: &mut LocalAccount,
-) -> State {
-    let aptos_version = client.get_aptos_version().await.unwrap();
-    let (current, state) = aptos_version.into_parts();
-    let current_version = *current.major.inner();
-    let txn = root_account.sign_with_transaction_builder(
-        transaction_factory
-            .clone()
-            .with_max_gas_amount(100000)
-            .payload(aptos_stdlib::version_set_version(current_version + 1)),
-    );
-    let result = client.submit_and_wait(&txn).await;
-    if let Err(e) = result {
-        let last_transactions = client
-            .get_account_transactions(root_account.address(), None, None)
-            .await
-            .map(|result| {
-                result
-                    .into_inner()
-                    .iter()
-                    .map(|t| {
-                        if let Transaction::UserTransaction(ut) = t {
-                            format!(
-                                "user seq={}, payload={:?}",
-                                ut.request.sequence_number, ut.request.payload
-                            )
-                        } else {
-                            t.type_str().to_string()
-                        }
-                    })
-                    .collect::<Vec<_>>()
-            });
-
-        panic!(
-            "Couldn't execute {:?}, for account {:?}, error {:?}, last account transactions: {:?}",
-            txn,
-            root_account,
-            e,
-            last_transactions.unwrap_or_default()
-        )
-    }
-
-    let transaction = result.unwrap();
-    // Next transaction after reconfig should be a new epoch.
-    let new_state = client
-        .wait_for_version(transaction.inner().version().unwrap() + 1)
+) -> std::result::Result<State, GovernanceError> {
+    governance::bump_version(client, transaction_factory, root_account)
         .await
-        .unwrap();
-
-    info!(
-        "Changed aptos version from {} (epoch={}, ledger_v={}), to {}, (epoch={}, ledger_v={})",
-        current_version,
-        state.epoch,
-        state.version,
-        current_version + 1,
-        new_state.epoch,
-        new_state.version
-    );
-    assert_ne!(state.epoch, new_state.epoch);
-
-    new_state
+        .map(|change| change.new_state)
 }
@@ -1,12 +1,12 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::AptosPublicInfo;
+use crate::{AptosPublicInfo, FundingConfig};
 use anyhow::Result;
 use aptos_rest_client::Client as RestClient;
 use aptos_sdk::{
     transaction_builder::TransactionFactory,
-    types::{chain_id::ChainId, LocalAccount},
+    types::{account_address::AccountAddress, chain_id::ChainId, LocalAccount},
 };
 use reqwest::Url;
 
@@ -15,6 +15,9 @@ pub struct ChainInfo<'t> {
     pub root_account: &'t mut LocalAccount,
     pub rest_api_url: String,
     pub chain_id: ChainId,
+    /// Faucet test accounts are funded through instead of the root account, e.g. on public
+    /// networks where the root key isn't available
+    pub faucet_url: Option<Url>,
 }
 
 impl<'t> ChainInfo<'t> {
@@ -27,9 +30,15 @@ impl<'t> ChainInfo<'t> {
             root_account,
             rest_api_url,
             chain_id,
+            faucet_url: None,
         }
     }
 
+    pub fn with_faucet_url(mut self, faucet_url: Url) -> Self {
+        self.faucet_url = Some(faucet_url);
+        self
+    }
+
     pub fn root_account(&mut self) -> &mut LocalAccount {
         self.root_account
     }
@@ -59,7 +68,34 @@ impl<'t> ChainInfo<'t> {
         TransactionFactory::new(self.chain_id())
     }
 
+    /// Funds every address with `amount` coins, see `AptosPublicInfo::fund_accounts`.
+    pub async fn fund_accounts(&mut self, addresses: &[AccountAddress], amount: u64) -> Result<()> {
+        self.fund_accounts_with_config(addresses, amount, &FundingConfig::default())
+            .await
+    }
+
+    pub async fn fund_accounts_with_config(
+        &mut self,
+        addresses: &[AccountAddress],
+        amount: u64,
+        config: &FundingConfig,
+    ) -> Result<()> {
+        let mut public_info =
+            AptosPublicInfo::new(self.chain_id, self.rest_api_url.clone(), self.root_account);
+        if let Some(faucet_url) = &self.faucet_url {
+            public_info = public_info.with_faucet(faucet_url.clone());
+        }
+        public_info
+            .fund_accounts_with_config(addresses, amount, config)
+            .await
+    }
+
     pub fn into_aptos_public_info(self) -> AptosPublicInfo<'t> {
-        AptosPublicInfo::new(self.chain_id, self.rest_api_url.clone(), self.root_account)
+        let public_info =
+            AptosPublicInfo::new(self.chain_id, self.rest_api_url.clone(), self.root_account);
+        match self.faucet_url {
+            Some(faucet_url) => public_info.with_faucet(faucet_url),
+            None => public_info,
+        }
     }
 }
@@ -1,44 +1,157 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+use aptos_sdk::types::PeerId;
+use serde::Deserialize;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SwarmChaos {
     Delay(SwarmNetworkDelay),
     Partition(SwarmNetworkPartition),
     Bandwidth(SwarmNetworkBandwidth),
     Loss(SwarmNetworkLoss),
+    Cpu(SwarmCpuStress),
+    Memory(SwarmMemoryStress),
+    Disk(SwarmDiskDelay),
+    Clock(SwarmClockSkew),
+    Pause(SwarmNodePause),
+}
+
+impl SwarmChaos {
+    pub fn kind(&self) -> SwarmChaosKind {
+        match self {
+            SwarmChaos::Delay(_) => SwarmChaosKind::Delay,
+            SwarmChaos::Partition(_) => SwarmChaosKind::Partition,
+            SwarmChaos::Bandwidth(_) => SwarmChaosKind::Bandwidth,
+            SwarmChaos::Loss(_) => SwarmChaosKind::Loss,
+            SwarmChaos::Cpu(_) => SwarmChaosKind::Cpu,
+            SwarmChaos::Memory(_) => SwarmChaosKind::Memory,
+            SwarmChaos::Disk(_) => SwarmChaosKind::Disk,
+            SwarmChaos::Clock(_) => SwarmChaosKind::Clock,
+            SwarmChaos::Pause(_) => SwarmChaosKind::Pause,
+        }
+    }
 }
 
+/// The variants of `SwarmChaos`, which backends report the support of.
+#[derive(Eq, Hash, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub enum SwarmChaosKind {
+    Delay,
+    Partition,
+    Bandwidth,
+    Loss,
+    Cpu,
+    Memory,
+    Disk,
+    Clock,
+    Pause,
+}
+
+impl SwarmChaosKind {
+    pub const ALL: [SwarmChaosKind; 9] = [
+        SwarmChaosKind::Delay,
+        SwarmChaosKind::Partition,
+        SwarmChaosKind::Bandwidth,
+        SwarmChaosKind::Loss,
+        SwarmChaosKind::Cpu,
+        SwarmChaosKind::Memory,
+        SwarmChaosKind::Disk,
+        SwarmChaosKind::Clock,
+        SwarmChaosKind::Pause,
+    ];
+}
+
+impl fmt::Display for SwarmChaosKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SwarmChaosKind::Delay => "network delay",
+            SwarmChaosKind::Partition => "network partition",
+            SwarmChaosKind::Bandwidth => "network bandwidth",
+            SwarmChaosKind::Loss => "network loss",
+            SwarmChaosKind::Cpu => "CPU stress",
+            SwarmChaosKind::Memory => "memory stress",
+            SwarmChaosKind::Disk => "disk delay",
+            SwarmChaosKind::Clock => "clock skew",
+            SwarmChaosKind::Pause => "node pause",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Returned when injecting chaos the swarm can't inject, so that tests relying on it can be
+/// told apart from tests that actually failed.
+#[derive(Debug, Error)]
+#[error("{0} chaos is not supported by this swarm")]
+pub struct UnsupportedChaos(pub SwarmChaosKind);
+
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub enum NodeChaos {
     NodeNetworkDelayChaos(NodeNetworkDelay),
 }
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
 pub struct SwarmNetworkDelay {
     pub latency_ms: u64,
     pub jitter_ms: u64,
     pub correlation_percentage: u64,
 }
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
 pub struct SwarmNetworkPartition {
     pub partition_percentage: u64,
 }
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
 pub struct SwarmNetworkBandwidth {
     pub rate: u64,
     pub limit: u64,
     pub buffer: u64,
 }
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
 pub struct SwarmNetworkLoss {
     pub loss_percentage: u64,
     pub correlation_percentage: u64,
 }
 
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
+pub struct SwarmCpuStress {
+    pub num_workers: u64,
+    /// Load of every worker on its CPU
+    pub load_percentage: u64,
+}
+
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
+pub struct SwarmMemoryStress {
+    pub num_workers: u64,
+    /// Memory allocated by every worker
+    pub size_mb: u64,
+}
+
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
+pub struct SwarmDiskDelay {
+    pub latency_ms: u64,
+    /// Share of the storage operations delayed
+    pub percentage: u64,
+}
+
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
+pub struct SwarmClockSkew {
+    /// How far the clocks are moved, backwards if negative
+    pub offset_ms: i64,
+}
+
+/// Freezes nodes, which keep their connections open but stop responding, unlike stopped nodes.
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Deserialize)]
+pub struct SwarmNodePause {
+    pub peers: Vec<PeerId>,
+    /// How long the nodes stay paused, unless the chaos is removed before
+    pub duration_secs: u64,
+}
+
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub struct NodeNetworkDelay {
     pub latency_ms: u64,
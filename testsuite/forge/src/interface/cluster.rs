@@ -0,0 +1,44 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Node, Swarm};
+use anyhow::{format_err, Result};
+use aptos_sdk::crypto::ed25519::Ed25519PrivateKey;
+use std::convert::TryFrom;
+use transaction_emitter_lib::{Cluster, Instance};
+
+/// Builds emitter clusters out of forge swarms, so that tests can use the emitter's API without
+/// re-deriving REST endpoints and the root key themselves.
+#[async_trait::async_trait(?Send)]
+pub trait ClusterExt: Sized {
+    /// Targets every validator and fullnode of the swarm, on the swarm's chain, minting with
+    /// the swarm's root account. The swarm is only borrowed mutably to read its root account.
+    async fn from_swarm(swarm: &mut dyn Swarm) -> Result<Self>;
+}
+
+#[async_trait::async_trait(?Send)]
+impl ClusterExt for Cluster {
+    async fn from_swarm(swarm: &mut dyn Swarm) -> Result<Self> {
+        let instances = swarm
+            .validators()
+            .map(swarm_instance)
+            .chain(swarm.full_nodes().map(swarm_instance))
+            .collect();
+        let chain_info = swarm.chain_info();
+        let chain_id = chain_info.chain_id();
+        let mint_key =
+            Ed25519PrivateKey::try_from(chain_info.root_account.private_key().to_bytes().as_ref())
+                .map_err(|e| format_err!("Failed to copy the swarm root key: {}", e))?;
+        Cluster::from_instances(instances, mint_key, chain_id, false).await
+    }
+}
+
+fn swarm_instance<N: Node + ?Sized>(node: &N) -> Instance {
+    Instance::new(
+        node.name().to_string(),
+        node.rest_api_endpoint(),
+        node.inspection_service_endpoint()
+            .port()
+            .map(|port| port as u32),
+    )
+}
@@ -0,0 +1,224 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checks that all nodes agree on the ledger once a run is over: the root hashes at a common
+//! version and the state of a sample of accounts. A fork that doesn't stall the network would
+//! otherwise go unnoticed.
+
+use crate::{
+    interface::swarm::{get_highest_synced_version, wait_for_all_nodes_to_catchup_to_version},
+    Result,
+};
+use anyhow::{bail, Context};
+use aptos_rest_client::{error::RestError, Client as RestClient};
+use aptos_sdk::{crypto::HashValue, types::account_address::AccountAddress};
+use futures::future::try_join_all;
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// What a node reports at the compared version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeLedgerState {
+    /// Latest version of the node, at or past the compared version
+    pub ledger_version: u64,
+    pub accumulator_root_hash: HashValue,
+    pub state_change_hash: HashValue,
+    /// Hash of the resources of every sampled account, None if the account doesn't exist
+    pub accounts: BTreeMap<AccountAddress, Option<HashValue>>,
+}
+
+/// Nodes grouped by the value they reported for some part of the ledger.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub what: String,
+    pub groups: Vec<(String, Vec<String>)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} diverges:", self.what)?;
+        for (value, nodes) in &self.groups {
+            writeln!(f, "    {}: {}", value, nodes.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The ledger of every node at a common version, by node name.
+#[derive(Debug)]
+pub struct ConsistencyReport {
+    pub version: u64,
+    pub nodes: BTreeMap<String, NodeLedgerState>,
+}
+
+impl ConsistencyReport {
+    pub fn divergences(&self) -> Vec<Divergence> {
+        let mut divergences = vec![];
+        let mut compare = |what: String, value: &dyn Fn(&NodeLedgerState) -> String| {
+            let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for (node, state) in &self.nodes {
+                groups.entry(value(state)).or_default().push(node.clone());
+            }
+            if groups.len() > 1 {
+                divergences.push(Divergence {
+                    what,
+                    groups: groups.into_iter().collect(),
+                });
+            }
+        };
+        compare(
+            format!("Accumulator root hash at version {}", self.version),
+            &|state| state.accumulator_root_hash.to_hex(),
+        );
+        compare(
+            format!("State change hash at version {}", self.version),
+            &|state| state.state_change_hash.to_hex(),
+        );
+        let accounts = self
+            .nodes
+            .values()
+            .next()
+            .map(|state| state.accounts.keys().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for account in accounts {
+            compare(
+                format!("Resources of {} at version {}", account, self.version),
+                &|state| match state.accounts.get(&account) {
+                    Some(Some(hash)) => hash.to_hex(),
+                    Some(None) => "missing".to_string(),
+                    None => "not sampled".to_string(),
+                },
+            );
+        }
+        divergences
+    }
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Ledger of {} nodes at version {}",
+            self.nodes.len(),
+            self.version
+        )?;
+        for (node, state) in &self.nodes {
+            writeln!(
+                f,
+                "    {}: at version {}, accumulator root hash {}",
+                node, state.ledger_version, state.accumulator_root_hash
+            )?;
+        }
+        for divergence in self.divergences() {
+            write!(f, "{}", divergence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Waits for all nodes to catch up, then compares their root hashes and the resources of
+/// `accounts` at the highest version any of them had, failing with the report if they differ.
+pub async fn check_state_consistency(
+    clients: &[(String, RestClient)],
+    accounts: &[AccountAddress],
+    timeout: Duration,
+) -> Result<ConsistencyReport> {
+    if clients.is_empty() {
+        bail!("no nodes available")
+    }
+    let version = get_highest_synced_version(clients).await?;
+    wait_for_all_nodes_to_catchup_to_version(clients, version, timeout).await?;
+
+    let states = try_join_all(clients.iter().map(|(name, client)| async move {
+        node_ledger_state(client, version, accounts)
+            .await
+            .with_context(|| format!("Failed to read the ledger of {}", name))
+            .map(|state| (name.clone(), state))
+    }))
+    .await?;
+    let report = ConsistencyReport {
+        version,
+        nodes: states.into_iter().collect(),
+    };
+    if !report.divergences().is_empty() {
+        bail!("Nodes disagree on the ledger\n{}", report);
+    }
+    Ok(report)
+}
+
+async fn node_ledger_state(
+    client: &RestClient,
+    version: u64,
+    accounts: &[AccountAddress],
+) -> Result<NodeLedgerState> {
+    let ledger_version = client.get_ledger_information().await?.into_inner().version;
+    let transaction = client
+        .get_transaction_by_version(version)
+        .await?
+        .into_inner();
+    let info = transaction.transaction_info()?;
+    let mut account_hashes = BTreeMap::new();
+    for account in accounts {
+        let hash = match client
+            .get_account_resources_at_version(*account, version)
+            .await
+        {
+            Ok(resources) => Some(HashValue::sha3_256_of(&serde_json::to_vec(
+                &resources.into_inner(),
+            )?)),
+            Err(RestError::Api(e)) if e.status_code.as_u16() == 404 => None,
+            Err(e) => return Err(e.into()),
+        };
+        account_hashes.insert(*account, hash);
+    }
+    Ok(NodeLedgerState {
+        ledger_version,
+        accumulator_root_hash: info.accumulator_root_hash,
+        state_change_hash: info.state_change_hash,
+        accounts: account_hashes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergences() {
+        let account = AccountAddress::random();
+        let state = NodeLedgerState {
+            ledger_version: 10,
+            accumulator_root_hash: HashValue::zero(),
+            state_change_hash: HashValue::zero(),
+            accounts: vec![(account, Some(HashValue::zero()))]
+                .into_iter()
+                .collect(),
+        };
+        let mut forked = state.clone();
+        forked.ledger_version = 12;
+        forked.accounts.insert(account, None);
+        let report = ConsistencyReport {
+            version: 10,
+            nodes: vec![
+                ("validator-0".to_string(), state.clone()),
+                ("validator-1".to_string(), state),
+                ("full-node-0".to_string(), forked),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        assert_eq!(
+            report.divergences(),
+            vec![Divergence {
+                what: format!("Resources of {} at version 10", account),
+                groups: vec![
+                    (
+                        HashValue::zero().to_hex(),
+                        vec!["validator-0".to_string(), "validator-1".to_string()]
+                    ),
+                    ("missing".to_string(), vec!["full-node-0".to_string()]),
+                ],
+            }]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Watches the epoch changes of a network, to assert on reconfigurations and annotate the
+//! timeline of a run with them.
+
+use crate::{record_event_at, Result, TimelineEventKind};
+use aptos_infallible::Mutex;
+use aptos_logger::info;
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::types::{account_address::AccountAddress, on_chain_config::ValidatorSet};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+const VALIDATOR_SET: &str = "0x1::stake::ValidatorSet";
+
+/// An epoch change seen by an `EpochWatcher`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpochChange {
+    /// When the change was seen, up to the polling interval after it happened
+    pub time: SystemTime,
+    /// The new epoch
+    pub epoch: u64,
+    /// Ledger version the change was seen at
+    pub version: u64,
+    /// Validators that joined the validator set
+    pub added: Vec<AccountAddress>,
+    /// Validators that left the validator set
+    pub removed: Vec<AccountAddress>,
+}
+
+impl fmt::Display for EpochChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "epoch {} at version {}", self.epoch, self.version)?;
+        if !self.added.is_empty() {
+            write!(f, ", {} validators joined", self.added.len())?;
+        }
+        if !self.removed.is_empty() {
+            write!(f, ", {} validators left", self.removed.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct WatcherState {
+    /// Latest epoch and validator set seen
+    current: Option<(u64, BTreeSet<AccountAddress>)>,
+    changes: Vec<EpochChange>,
+    stopped: bool,
+}
+
+/// Polls the ledger info of a node on a background thread to record the epoch changes, until
+/// dropped. Every change is also recorded on the timeline of the run.
+#[derive(Debug)]
+pub struct EpochWatcher {
+    state: Arc<Mutex<WatcherState>>,
+}
+
+impl EpochWatcher {
+    /// Starts polling `client` every `interval`.
+    pub fn start(client: RestClient, interval: Duration) -> Self {
+        let state = Arc::new(Mutex::new(WatcherState::default()));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to create runtime");
+            while !thread_state.lock().stopped {
+                // The node may be down for a while, changes are then seen once it's back.
+                if let Ok((epoch, version, validators)) = runtime.block_on(poll(&client)) {
+                    observe(
+                        &mut thread_state.lock(),
+                        SystemTime::now(),
+                        epoch,
+                        version,
+                        validators,
+                    );
+                }
+                thread::sleep(interval);
+            }
+        });
+        Self { state }
+    }
+
+    /// The epoch changes seen so far, oldest first.
+    pub fn changes(&self) -> Vec<EpochChange> {
+        self.state.lock().changes.clone()
+    }
+
+    /// The latest epoch seen, None until the node was polled successfully.
+    pub fn current_epoch(&self) -> Option<u64> {
+        self.state.lock().current.as_ref().map(|(epoch, _)| *epoch)
+    }
+}
+
+impl Drop for EpochWatcher {
+    fn drop(&mut self) {
+        self.state.lock().stopped = true;
+    }
+}
+
+async fn poll(client: &RestClient) -> Result<(u64, u64, BTreeSet<AccountAddress>)> {
+    let (validator_set, state) = client
+        .get_account_resource_bcs::<ValidatorSet>(AccountAddress::ONE, VALIDATOR_SET)
+        .await?
+        .into_parts();
+    let validators = validator_set
+        .payload()
+        .map(|validator| validator.account_address)
+        .collect();
+    Ok((state.epoch, state.version, validators))
+}
+
+fn observe(
+    state: &mut WatcherState,
+    time: SystemTime,
+    epoch: u64,
+    version: u64,
+    validators: BTreeSet<AccountAddress>,
+) {
+    if let Some((current_epoch, current_validators)) = &state.current {
+        if epoch <= *current_epoch {
+            return;
+        }
+        let change = EpochChange {
+            time,
+            epoch,
+            version,
+            added: validators.difference(current_validators).copied().collect(),
+            removed: current_validators
+                .difference(&validators)
+                .copied()
+                .collect(),
+        };
+        info!("Observed {}", change);
+        record_event_at(time, TimelineEventKind::EpochChanged { epoch });
+        state.changes.push(change);
+    }
+    state.current = Some((epoch, validators));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observe_epoch_changes() {
+        let address = |byte: u8| AccountAddress::new([byte; AccountAddress::LENGTH]);
+        let set = |bytes: &[u8]| bytes.iter().copied().map(address).collect();
+        let mut state = WatcherState::default();
+        let time = SystemTime::UNIX_EPOCH;
+
+        observe(&mut state, time, 2, 100, set(&[1, 2]));
+        observe(&mut state, time, 2, 150, set(&[1, 2]));
+        assert!(state.changes.is_empty());
+
+        observe(&mut state, time, 3, 200, set(&[2, 3]));
+        // A lagging node may serve an older epoch.
+        observe(&mut state, time, 2, 120, set(&[1, 2]));
+        assert_eq!(
+            state.changes,
+            vec![EpochChange {
+                time,
+                epoch: 3,
+                version: 200,
+                added: vec![address(3)],
+                removed: vec![address(1)],
+            }]
+        );
+        assert_eq!(state.current.map(|(epoch, _)| epoch), Some(3));
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runs an emission job and a schedule of faults against the same swarm, to see how the
+//! throughput and latency react to every fault.
+
+use crate::{
+    interface::swarm::{emit_clients, emitter_for},
+    record_event,
+    timeline::unix_millis,
+    timeline_since, EmitJobRequest, FullNode, Result, Swarm, SwarmChaos, TimelineEvent,
+    TimelineEventKind, TxnStats, TxnStatsRate,
+};
+use anyhow::{bail, format_err};
+use aptos_logger::{info, warn};
+use aptos_sdk::types::PeerId;
+use std::{
+    fmt,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A fault applied to the swarm at some point of an experiment.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FaultAction {
+    InjectChaos(SwarmChaos),
+    RemoveChaos(SwarmChaos),
+    StopNode(PeerId),
+    StartNode(PeerId),
+}
+
+impl FaultAction {
+    pub(crate) async fn apply(&self, swarm: &mut dyn Swarm) -> Result<()> {
+        match self {
+            FaultAction::InjectChaos(chaos) => swarm.inject_chaos(chaos.clone()),
+            FaultAction::RemoveChaos(chaos) => swarm.remove_chaos(chaos.clone()),
+            FaultAction::StopNode(id) => {
+                if let Some(validator) = swarm.validator_mut(*id) {
+                    return validator.stop().await;
+                }
+                full_node_mut(swarm, *id)?.stop().await
+            }
+            FaultAction::StartNode(id) => {
+                if let Some(validator) = swarm.validator_mut(*id) {
+                    return validator.start().await;
+                }
+                full_node_mut(swarm, *id)?.start().await
+            }
+        }
+    }
+
+    /// The action undoing this one, if any.
+    fn undo(&self) -> Option<FaultAction> {
+        match self {
+            FaultAction::InjectChaos(chaos) => Some(FaultAction::RemoveChaos(chaos.clone())),
+            FaultAction::StopNode(id) => Some(FaultAction::StartNode(*id)),
+            FaultAction::RemoveChaos(_) | FaultAction::StartNode(_) => None,
+        }
+    }
+}
+
+/// Tracks the faults in effect, to undo them when done.
+#[derive(Debug, Default)]
+pub(crate) struct ActiveFaults {
+    /// Undos of the faults in effect, in the order the faults were applied
+    undos: Vec<FaultAction>,
+}
+
+impl ActiveFaults {
+    pub(crate) async fn apply(
+        &mut self,
+        swarm: &mut dyn Swarm,
+        action: &FaultAction,
+    ) -> Result<()> {
+        action.apply(swarm).await?;
+        self.undos.retain(|undo| undo != action);
+        self.undos.extend(action.undo());
+        Ok(())
+    }
+
+    /// Undoes the faults in effect, most recent first.
+    pub(crate) async fn undo_all(&mut self, swarm: &mut dyn Swarm) {
+        while let Some(action) = self.undos.pop() {
+            if let Err(e) = action.apply(swarm).await {
+                warn!("Failed to undo a fault with {:?}: {}", action, e);
+            }
+        }
+    }
+}
+
+fn full_node_mut(swarm: &mut dyn Swarm, id: PeerId) -> Result<&mut dyn FullNode> {
+    swarm
+        .full_node_mut(id)
+        .ok_or_else(|| format_err!("No node {} in the swarm", id))
+}
+
+/// Faults to apply at given offsets from the start of an experiment.
+#[derive(Clone, Debug, Default)]
+pub struct FaultSchedule {
+    steps: Vec<(Duration, FaultAction)>,
+}
+
+impl FaultSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn at(mut self, offset: Duration, action: FaultAction) -> Self {
+        self.steps.push((offset, action));
+        self
+    }
+
+    /// Applies `action` at `start` and undoes it at `end`.
+    pub fn during(self, start: Duration, end: Duration, action: FaultAction) -> Self {
+        let undo = action.undo();
+        let schedule = self.at(start, action);
+        match undo {
+            Some(undo) => schedule.at(end, undo),
+            None => schedule,
+        }
+    }
+
+    /// The steps ordered by offset, steps at the same offset keeping the order they were added in.
+    pub(crate) fn sorted_steps(&self) -> Vec<(Duration, FaultAction)> {
+        let mut steps = self.steps.clone();
+        steps.sort_by_key(|(offset, _)| *offset);
+        steps
+    }
+}
+
+/// Stats of a window of an experiment, with the events of the timeline that happened in it.
+#[derive(Debug)]
+pub struct ExperimentWindow {
+    /// Offset of the window from the start of the experiment
+    pub offset: Duration,
+    pub length: Duration,
+    pub rate: TxnStatsRate,
+    pub events: Vec<TimelineEvent>,
+}
+
+#[derive(Debug)]
+pub struct FaultExperimentReport {
+    pub windows: Vec<ExperimentWindow>,
+    pub total: TxnStats,
+    /// Faults that failed to apply, with their offset
+    pub failed_faults: Vec<(Duration, String)>,
+}
+
+impl fmt::Display for FaultExperimentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Total: {}", self.total)?;
+        for window in &self.windows {
+            writeln!(f, "+{}s: {}", window.offset.as_secs(), window.rate)?;
+            for event in &window.events {
+                writeln!(f, "    {}", event)?;
+            }
+        }
+        for (offset, error) in &self.failed_faults {
+            writeln!(f, "+{}s: fault failed: {}", offset.as_secs(), error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Emits `request` to `nodes` for `duration` while applying `schedule`, sampling the stats every
+/// `window`. Faults still in effect at the end are undone.
+pub async fn run_fault_experiment(
+    swarm: &mut dyn Swarm,
+    nodes: &[PeerId],
+    request: EmitJobRequest,
+    schedule: &FaultSchedule,
+    duration: Duration,
+    window: Duration,
+) -> Result<FaultExperimentReport> {
+    if window.is_zero() {
+        bail!("The sampling window of an experiment can't be empty");
+    }
+    let clients = emit_clients(swarm, nodes)?;
+    let chain_info = swarm.chain_info();
+    let mut emitter = emitter_for(&chain_info)?;
+    let job = emitter
+        .start_job(chain_info.root_account, request.rest_clients(clients))
+        .await?;
+    record_event(TimelineEventKind::EmissionStarted { nodes: nodes.len() });
+
+    let start = Instant::now();
+    let start_time = SystemTime::now();
+    let mut steps = schedule.sorted_steps().into_iter().peekable();
+    let mut active_faults = ActiveFaults::default();
+    let mut failed_faults = vec![];
+    let mut windows = vec![];
+    let mut prev_stats = TxnStats::default();
+    let mut window_start = Duration::ZERO;
+    while window_start < duration {
+        let window_end = (window_start + window).min(duration);
+        while let Some((offset, _)) = steps.peek() {
+            if *offset >= window_end {
+                break;
+            }
+            let (offset, action) = steps.next().unwrap();
+            tokio::time::sleep_until((start + offset).into()).await;
+            info!("Applying {:?} at +{}s", action, offset.as_secs());
+            if let Err(e) = active_faults.apply(swarm, &action).await {
+                warn!("Failed to apply {:?}: {}", action, e);
+                failed_faults.push((offset, format!("{:?}: {}", action, e)));
+            }
+        }
+        tokio::time::sleep_until((start + window_end).into()).await;
+
+        let stats = emitter.peek_job_stats(&job);
+        let delta = &stats - &prev_stats;
+        prev_stats = stats;
+        let from_ms = unix_millis(start_time + window_start);
+        let to_ms = unix_millis(start_time + window_end);
+        windows.push(ExperimentWindow {
+            offset: window_start,
+            length: window_end - window_start,
+            rate: delta.rate(window_end - window_start),
+            events: timeline_since(start_time + window_start)
+                .into_iter()
+                .filter(|event| event.time_ms >= from_ms && event.time_ms < to_ms)
+                .collect(),
+        });
+        window_start = window_end;
+    }
+
+    let total = emitter.stop_job(job).await;
+    record_event(TimelineEventKind::EmissionStopped { nodes: nodes.len() });
+    active_faults.undo_all(swarm).await;
+
+    Ok(FaultExperimentReport {
+        windows,
+        total,
+        failed_faults,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_schedule_order() {
+        let validator = PeerId::random();
+        let full_node = PeerId::random();
+        let schedule = FaultSchedule::new()
+            .during(
+                Duration::from_secs(10),
+                Duration::from_secs(40),
+                FaultAction::StopNode(validator),
+            )
+            .at(Duration::from_secs(20), FaultAction::StopNode(full_node))
+            .at(Duration::from_secs(10), FaultAction::StartNode(full_node));
+
+        assert_eq!(
+            schedule.sorted_steps(),
+            vec![
+                (Duration::from_secs(10), FaultAction::StopNode(validator)),
+                (Duration::from_secs(10), FaultAction::StartNode(full_node)),
+                (Duration::from_secs(20), FaultAction::StopNode(full_node)),
+                (Duration::from_secs(40), FaultAction::StartNode(validator)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+/// Action taken when a failpoint is hit, rendered in the `fail` crate's syntax expected by the
+/// node's `set_failpoint` endpoint. Nodes need `api.failpoints_enabled` and a binary built with
+/// the failpoints feature.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FailpointAction {
+    /// Disable the failpoint
+    Off,
+    /// Return early from the failpoint's function, with an optional argument
+    Return(Option<String>),
+    /// Panic, with an optional message
+    Panic(Option<String>),
+    /// Log a message and continue
+    Print(Option<String>),
+    /// Sleep for the given number of milliseconds
+    Sleep(u64),
+    /// Busy wait for the given number of milliseconds
+    Delay(u64),
+    /// Block until the failpoint is changed
+    Pause,
+    /// Yield the thread
+    Yield,
+    /// Take `action` with the given percent probability, `Off` otherwise
+    Probability(f64, Box<FailpointAction>),
+    /// Take `action` the given number of times, then move on
+    Times(usize, Box<FailpointAction>),
+    /// Take the actions in turn, each one moving on to the next once exhausted
+    Sequence(Vec<FailpointAction>),
+}
+
+impl FailpointAction {
+    pub fn with_probability(self, percent: f64) -> Self {
+        FailpointAction::Probability(percent, Box::new(self))
+    }
+
+    pub fn times(self, count: usize) -> Self {
+        FailpointAction::Times(count, Box::new(self))
+    }
+}
+
+impl fmt::Display for FailpointAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailpointAction::Sequence(actions) => {
+                for (i, action) in actions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "->")?;
+                    }
+                    write!(f, "{}", action)?;
+                }
+                Ok(())
+            }
+            action => write_task(f, action, None, None),
+        }
+    }
+}
+
+/// Writes a single task as `[<percent>%][<count>*]<task>[(<arg>)]`, the only order the fail
+/// crate accepts, however the modifiers were nested.
+fn write_task(
+    f: &mut fmt::Formatter<'_>,
+    action: &FailpointAction,
+    percent: Option<f64>,
+    count: Option<usize>,
+) -> fmt::Result {
+    match action {
+        FailpointAction::Probability(percent, action) => {
+            return write_task(f, action, Some(*percent), count)
+        }
+        FailpointAction::Times(count, action) => {
+            return write_task(f, action, percent, Some(*count))
+        }
+        _ => {}
+    }
+    if let Some(percent) = percent {
+        write!(f, "{}%", percent)?;
+    }
+    if let Some(count) = count {
+        write!(f, "{}*", count)?;
+    }
+    let with_arg = |f: &mut fmt::Formatter<'_>, task: &str, arg: &Option<String>| match arg {
+        Some(arg) => write!(f, "{}({})", task, arg),
+        None => write!(f, "{}", task),
+    };
+    match action {
+        FailpointAction::Off => write!(f, "off"),
+        FailpointAction::Return(arg) => with_arg(f, "return", arg),
+        FailpointAction::Panic(arg) => with_arg(f, "panic", arg),
+        FailpointAction::Print(arg) => with_arg(f, "print", arg),
+        FailpointAction::Sleep(millis) => write!(f, "sleep({})", millis),
+        FailpointAction::Delay(millis) => write!(f, "delay({})", millis),
+        FailpointAction::Pause => write!(f, "pause"),
+        FailpointAction::Yield => write!(f, "yield"),
+        // Modifiers don't apply to sequences, only to their tasks.
+        FailpointAction::Sequence(_) => write!(f, "{}", action),
+        FailpointAction::Probability(..) | FailpointAction::Times(..) => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failpoint_action_display() {
+        assert_eq!(FailpointAction::Return(None).to_string(), "return");
+        assert_eq!(
+            FailpointAction::Panic(Some("boom".to_string())).to_string(),
+            "panic(boom)"
+        );
+        assert_eq!(
+            FailpointAction::Sequence(vec![
+                FailpointAction::Sleep(100).with_probability(50.0).times(3),
+                FailpointAction::Off,
+            ])
+            .to_string(),
+            "50%3*sleep(100)->off"
+        );
+    }
+}
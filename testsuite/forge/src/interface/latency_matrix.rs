@@ -0,0 +1,180 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{MetricsSnapshot, TestReport};
+use aptos_sdk::types::PeerId;
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// Nodes ping every peer they're connected to with their health checker, every
+/// `ping_interval_ms` of their network config, and observe the round-trip time by remote peer,
+/// except on the public network
+const PING_LATENCY_SUM: &str = "aptos_network_health_checker_ping_latency_seconds_sum";
+const PING_LATENCY_COUNT: &str = "aptos_network_health_checker_ping_latency_seconds_count";
+
+/// Average round-trip time of the pings between every pair of nodes connected on the validator
+/// or VFN network, see `SwarmExt::measure_latency_matrix`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyMatrix {
+    /// Names of the nodes, in the order of the rows and columns
+    nodes: Vec<String>,
+    /// Round-trip time by (pinging node, pinged node)
+    latencies: BTreeMap<(String, String), Duration>,
+}
+
+impl LatencyMatrix {
+    /// The latencies of the pings sent between the `earlier` and `later` snapshots, among the
+    /// given nodes. Pairs of nodes that didn't ping each other are left out.
+    pub fn from_snapshots(
+        earlier: &MetricsSnapshot,
+        later: &MetricsSnapshot,
+        nodes: &[(String, PeerId)],
+    ) -> Self {
+        let mut latencies = BTreeMap::new();
+        for (from, _) in nodes {
+            let (earlier, later) = match (earlier.nodes.get(from), later.nodes.get(from)) {
+                (Some(earlier), Some(later)) => (earlier, later),
+                _ => continue,
+            };
+            for (to, peer_id) in nodes.iter().filter(|(to, _)| to != from) {
+                // Peers are labeled with the short form of their id.
+                let peer_id = peer_id.to_hex();
+                let labels = [("remote_peer_id", &peer_id[..8])];
+                let pings = later.counter_delta(earlier, PING_LATENCY_COUNT, &labels);
+                if pings > 0.0 {
+                    let total = later.counter_delta(earlier, PING_LATENCY_SUM, &labels);
+                    latencies.insert(
+                        (from.clone(), to.clone()),
+                        Duration::from_secs_f64(total / pings),
+                    );
+                }
+            }
+        }
+        Self {
+            nodes: nodes.iter().map(|(name, _)| name.clone()).collect(),
+            latencies,
+        }
+    }
+
+    /// Round-trip time of the pings `from` sent to `to`.
+    pub fn get(&self, from: &str, to: &str) -> Option<Duration> {
+        self.latencies
+            .get(&(from.to_string(), to.to_string()))
+            .copied()
+    }
+
+    /// Pairs of nodes whose pings to each other differ by more than `tolerance`, a ratio of the
+    /// faster direction, as (from, to, latency, latency back).
+    pub fn asymmetries(&self, tolerance: f64) -> Vec<(String, String, Duration, Duration)> {
+        self.latencies
+            .iter()
+            .filter(|((from, to), _)| from < to)
+            .filter_map(|((from, to), latency)| {
+                let back = self.get(to, from)?;
+                let (fast, slow) = if *latency < back {
+                    (*latency, back)
+                } else {
+                    (back, *latency)
+                };
+                if slow.as_secs_f64() > fast.as_secs_f64() * (1.0 + tolerance) {
+                    Some((from.clone(), to.clone(), *latency, back))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Adds the matrix to the text of `report`, under `title`. Rows are the pinging nodes,
+    /// columns the pinged ones.
+    pub fn report(&self, report: &mut TestReport, title: &str) {
+        report.report_text(format!("{}:\n{}", title, self));
+    }
+}
+
+impl fmt::Display for LatencyMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.nodes.iter().map(String::len).max().unwrap_or(0).max(6);
+        write!(f, "{:width$}", "ms", width = width)?;
+        for to in &self.nodes {
+            write!(f, " {:>width$}", to, width = width)?;
+        }
+        for from in &self.nodes {
+            write!(f, "\n{:width$}", from, width = width)?;
+            for to in &self.nodes {
+                match self.get(from, to) {
+                    Some(latency) => write!(
+                        f,
+                        " {:>width$.1}",
+                        latency.as_secs_f64() * 1000.0,
+                        width = width
+                    )?,
+                    None => write!(f, " {:>width$}", "-", width = width)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Metrics;
+
+    #[test]
+    fn test_latency_matrix() {
+        let peer = |byte: u8| PeerId::new([byte; PeerId::LENGTH]);
+        let nodes = vec![
+            ("0".to_string(), peer(0xaa)),
+            ("1".to_string(), peer(0xbb)),
+            ("2".to_string(), peer(0xcc)),
+        ];
+        // As labeled by `network::counters::health_checker_ping_latency`
+        let pings = |from: &str, to: &str, sum: f64, count: f64| {
+            let labels = format!(
+                "role_type=\"validator\",network_id=\"Validator\",peer_id=\"{}\",\
+                 remote_peer_id=\"{}\"",
+                from, to
+            );
+            format!(
+                "aptos_network_health_checker_ping_latency_seconds_sum{{{0}}} {1}\n\
+                 aptos_network_health_checker_ping_latency_seconds_count{{{0}}} {2}\n",
+                labels, sum, count
+            )
+        };
+        let snapshot = |nodes: Vec<(&str, String)>| {
+            MetricsSnapshot::new(
+                nodes
+                    .into_iter()
+                    .map(|(node, text)| (node.to_string(), text.parse::<Metrics>().unwrap()))
+                    .collect(),
+            )
+        };
+        let earlier = snapshot(vec![
+            ("0", pings("aaaaaaaa", "bbbbbbbb", 1.0, 10.0)),
+            ("1", pings("bbbbbbbb", "aaaaaaaa", 1.0, 10.0)),
+        ]);
+        let later = snapshot(vec![
+            ("0", pings("aaaaaaaa", "bbbbbbbb", 1.5, 12.0)),
+            ("1", pings("bbbbbbbb", "aaaaaaaa", 3.0, 12.0)),
+            ("2", pings("cccccccc", "aaaaaaaa", 1.0, 1.0)),
+        ]);
+
+        let matrix = LatencyMatrix::from_snapshots(&earlier, &later, &nodes);
+        assert_eq!(matrix.get("0", "1"), Some(Duration::from_millis(250)));
+        assert_eq!(matrix.get("1", "0"), Some(Duration::from_secs(1)));
+        assert_eq!(matrix.get("0", "2"), None);
+        // Not in the earlier snapshot
+        assert_eq!(matrix.get("2", "0"), None);
+        assert_eq!(
+            matrix.asymmetries(0.5),
+            vec![(
+                "0".to_string(),
+                "1".to_string(),
+                Duration::from_millis(250),
+                Duration::from_secs(1)
+            )]
+        );
+        assert!(matrix.asymmetries(20.0).is_empty());
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Samples the mempool of the nodes of a swarm during a load run, to tell whether latency
+//! spikes come from transactions piling up in mempool. Saturations are also recorded on the
+//! timeline of the run as they happen.
+
+use crate::{record_event_at, Metrics, NodeExt, Swarm, TestReport, TimelineEventKind};
+use aptos_infallible::Mutex;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+use url::Url;
+
+const INDEX_SIZE: &str = "aptos_core_mempool_index_size";
+const COMMIT_LATENCY_SUM: &str = "aptos_core_mempool_txn_commit_latency_sum";
+const COMMIT_LATENCY_COUNT: &str = "aptos_core_mempool_txn_commit_latency_count";
+
+/// When a mempool counts as saturated.
+#[derive(Clone, Debug)]
+pub struct MempoolMonitorConfig {
+    pub interval: Duration,
+    /// Part of its capacity a mempool holds when saturated
+    pub saturation_ratio: f64,
+    /// Average time transactions committed since the previous sample spent in a saturated
+    /// mempool, if set
+    pub max_commit_age: Option<Duration>,
+    /// A mempool saturated for less than this long isn't flagged
+    pub sustained_for: Duration,
+}
+
+impl Default for MempoolMonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            saturation_ratio: 0.8,
+            max_commit_age: None,
+            sustained_for: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The mempool of a node at some point of the run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MempoolSample {
+    pub time: SystemTime,
+    /// Transactions in mempool, parked ones included
+    pub size: u64,
+    /// Transactions waiting for an earlier sequence number of their sender
+    pub parked: u64,
+    pub size_bytes: u64,
+    /// Average time between entering mempool and being committed of the transactions committed
+    /// since the previous sample, None if none was
+    pub commit_age: Option<Duration>,
+}
+
+impl MempoolSample {
+    fn new(time: SystemTime, metrics: &Metrics, previous: Option<&Metrics>) -> Self {
+        let index_size =
+            |index: &str| metrics.get(INDEX_SIZE, &[("index", index)]).unwrap_or(0.0) as u64;
+        let accepted = [("stage", "commit_accepted")];
+        let committed = match previous {
+            Some(previous) => metrics.counter_delta(previous, COMMIT_LATENCY_COUNT, &accepted),
+            None => 0.0,
+        };
+        let commit_age = previous.filter(|_| committed > 0.0).map(|previous| {
+            Duration::from_secs_f64(
+                metrics.counter_delta(previous, COMMIT_LATENCY_SUM, &accepted) / committed,
+            )
+        });
+        Self {
+            time,
+            size: index_size("system_ttl"),
+            parked: index_size("parking_lot"),
+            size_bytes: index_size("size_bytes"),
+            commit_age,
+        }
+    }
+}
+
+/// A stretch of the run during which the mempool of a node stayed saturated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MempoolSaturation {
+    pub node: String,
+    pub start: SystemTime,
+    pub duration: Duration,
+    /// Most transactions in mempool while saturated
+    pub peak_size: u64,
+}
+
+impl fmt::Display for MempoolSaturation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let start = self
+            .start
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "{}: mempool saturated for {:.0}s from unix time {}, peaking at {} txns",
+            self.node,
+            self.duration.as_secs_f64(),
+            start.as_secs(),
+            self.peak_size
+        )
+    }
+}
+
+#[derive(Debug)]
+struct MonitoredNode {
+    name: String,
+    metrics_url: Url,
+    capacity: u64,
+    samples: Vec<MempoolSample>,
+    last_metrics: Option<Metrics>,
+    /// Start of the current saturation, and whether it was recorded on the timeline yet
+    saturated_since: Option<(SystemTime, bool)>,
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    nodes: Vec<MonitoredNode>,
+    stopped: bool,
+}
+
+/// Samples the mempool of every node of a swarm on a background thread, until stopped.
+#[derive(Debug)]
+pub struct MempoolMonitor {
+    config: MempoolMonitorConfig,
+    state: Arc<Mutex<MonitorState>>,
+}
+
+impl MempoolMonitor {
+    /// Starts sampling the mempool of every node of `swarm`, validators and fullnodes.
+    pub fn start(swarm: &dyn Swarm, config: MempoolMonitorConfig) -> Self {
+        let validators = swarm.validators().map(monitored_node);
+        let full_nodes = swarm.full_nodes().map(monitored_node);
+        let state = Arc::new(Mutex::new(MonitorState {
+            nodes: validators.chain(full_nodes).collect(),
+            stopped: false,
+        }));
+
+        let thread_state = state.clone();
+        let thread_config = config.clone();
+        let interval = config.interval;
+        thread::spawn(move || {
+            let client = reqwest::blocking::Client::builder()
+                .timeout(interval)
+                .build()
+                .expect("Failed to create client");
+            loop {
+                let urls = {
+                    let state = thread_state.lock();
+                    if state.stopped {
+                        break;
+                    }
+                    state
+                        .nodes
+                        .iter()
+                        .map(|node| node.metrics_url.clone())
+                        .collect::<Vec<_>>()
+                };
+                // Nodes may be down for a while, their samples are only missing meanwhile.
+                let scrapes = urls
+                    .into_iter()
+                    .map(|url| {
+                        client
+                            .get(url)
+                            .send()
+                            .and_then(|response| response.error_for_status())
+                            .and_then(|response| response.text())
+                            .ok()
+                            .and_then(|text| text.parse::<Metrics>().ok())
+                    })
+                    .collect::<Vec<_>>();
+                let time = SystemTime::now();
+                for (node, metrics) in thread_state.lock().nodes.iter_mut().zip(scrapes) {
+                    if let Some(metrics) = metrics {
+                        let previous = node.last_metrics.as_ref();
+                        let sample = MempoolSample::new(time, &metrics, previous);
+                        track_saturation(node, &sample, &thread_config);
+                        node.samples.push(sample);
+                        node.last_metrics = Some(metrics);
+                    }
+                }
+                thread::sleep(interval);
+            }
+        });
+        Self { config, state }
+    }
+
+    /// The samples taken so far, by node name, oldest first.
+    pub fn samples(&self) -> BTreeMap<String, Vec<MempoolSample>> {
+        self.state
+            .lock()
+            .nodes
+            .iter()
+            .map(|node| (node.name.clone(), node.samples.clone()))
+            .collect()
+    }
+
+    /// The stretches of the run so far during which a mempool stayed saturated for at least
+    /// `sustained_for`.
+    pub fn saturations(&self) -> Vec<MempoolSaturation> {
+        self.state
+            .lock()
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                find_saturations(&node.name, &node.samples, node.capacity, &self.config)
+            })
+            .collect()
+    }
+
+    /// Adds the peak mempool size of every node, and the saturations, to `report`.
+    pub fn report(&self, report: &mut TestReport, test_name: &str) {
+        let mut text = "Mempool:".to_string();
+        for (node, samples) in self.samples() {
+            let peak = samples.iter().map(|sample| sample.size).max().unwrap_or(0);
+            let peak_age = samples
+                .iter()
+                .filter_map(|sample| sample.commit_age)
+                .max()
+                .unwrap_or_default();
+            report.report_metric(
+                test_name,
+                format!("{}_mempool_peak_size", node),
+                peak as f64,
+            );
+            text.push_str(&format!(
+                "\n  {}: {} samples, peak {} txns, peak commit age {:.1}s",
+                node,
+                samples.len(),
+                peak,
+                peak_age.as_secs_f64()
+            ));
+        }
+        for saturation in self.saturations() {
+            text.push_str(&format!("\n  (!) {}", saturation));
+        }
+        report.report_text(text);
+    }
+}
+
+impl Drop for MempoolMonitor {
+    fn drop(&mut self) {
+        self.state.lock().stopped = true;
+    }
+}
+
+fn monitored_node<N: NodeExt + ?Sized>(node: &N) -> MonitoredNode {
+    let mut url = node.inspection_service_endpoint();
+    url.set_path("metrics");
+    MonitoredNode {
+        name: node.name().to_string(),
+        metrics_url: url,
+        capacity: node.config().mempool.capacity as u64,
+        samples: Vec::new(),
+        last_metrics: None,
+        saturated_since: None,
+    }
+}
+
+fn is_saturated(sample: &MempoolSample, capacity: u64, config: &MempoolMonitorConfig) -> bool {
+    sample.size as f64 >= capacity as f64 * config.saturation_ratio
+        || matches!(
+            (sample.commit_age, config.max_commit_age),
+            (Some(age), Some(max_age)) if age >= max_age
+        )
+}
+
+/// Records the saturation of the mempool of `node` on the timeline once it lasted
+/// `sustained_for`, at its start, and its end, given its latest `sample`.
+fn track_saturation(
+    node: &mut MonitoredNode,
+    sample: &MempoolSample,
+    config: &MempoolMonitorConfig,
+) {
+    if is_saturated(sample, node.capacity, config) {
+        let (start, recorded) = node.saturated_since.get_or_insert((sample.time, false));
+        let duration = sample.time.duration_since(*start).unwrap_or_default();
+        if !*recorded && duration >= config.sustained_for {
+            record_event_at(
+                *start,
+                TimelineEventKind::MempoolSaturated {
+                    node: node.name.clone(),
+                },
+            );
+            *recorded = true;
+        }
+    } else if let Some((_, true)) = node.saturated_since.take() {
+        record_event_at(
+            sample.time,
+            TimelineEventKind::MempoolDrained {
+                node: node.name.clone(),
+            },
+        );
+    }
+}
+
+fn find_saturations(
+    node: &str,
+    samples: &[MempoolSample],
+    capacity: u64,
+    config: &MempoolMonitorConfig,
+) -> Vec<MempoolSaturation> {
+    let mut saturations = Vec::new();
+    let mut current: Option<MempoolSaturation> = None;
+    for sample in samples {
+        if is_saturated(sample, capacity, config) {
+            let saturation = current.get_or_insert_with(|| MempoolSaturation {
+                node: node.to_string(),
+                start: sample.time,
+                duration: Duration::ZERO,
+                peak_size: 0,
+            });
+            saturation.duration = sample
+                .time
+                .duration_since(saturation.start)
+                .unwrap_or_default();
+            saturation.peak_size = saturation.peak_size.max(sample.size);
+        } else if let Some(saturation) = current.take() {
+            saturations.push(saturation);
+        }
+    }
+    saturations.extend(current);
+    saturations.retain(|saturation| saturation.duration >= config.sustained_for);
+    saturations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mempool_sample() {
+        let metrics = |text: &str| text.parse::<Metrics>().unwrap();
+        let previous = metrics(
+            "aptos_core_mempool_txn_commit_latency_sum{stage=\"commit_accepted\"} 10\n\
+             aptos_core_mempool_txn_commit_latency_count{stage=\"commit_accepted\"} 20",
+        );
+        let current = metrics(
+            "aptos_core_mempool_index_size{index=\"system_ttl\"} 300\n\
+             aptos_core_mempool_index_size{index=\"parking_lot\"} 5\n\
+             aptos_core_mempool_txn_commit_latency_sum{stage=\"commit_accepted\"} 25\n\
+             aptos_core_mempool_txn_commit_latency_count{stage=\"commit_accepted\"} 30",
+        );
+        let time = SystemTime::UNIX_EPOCH;
+        let sample = MempoolSample::new(time, &current, Some(&previous));
+        assert_eq!(sample.size, 300);
+        assert_eq!(sample.parked, 5);
+        assert_eq!(sample.commit_age, Some(Duration::from_millis(1500)));
+        assert_eq!(MempoolSample::new(time, &current, None).commit_age, None);
+    }
+
+    fn samples(sizes: &[u64]) -> Vec<MempoolSample> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| MempoolSample {
+                time: SystemTime::UNIX_EPOCH + Duration::from_secs(i as u64),
+                size: *size,
+                parked: 0,
+                size_bytes: 0,
+                commit_age: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_saturations() {
+        let config = MempoolMonitorConfig {
+            sustained_for: Duration::from_secs(2),
+            ..MempoolMonitorConfig::default()
+        };
+        let samples = samples(&[10, 90, 95, 80, 20, 85, 90, 100, 100]);
+        let saturation = |start: u64, seconds: u64, peak_size: u64| MempoolSaturation {
+            node: "0".to_string(),
+            start: SystemTime::UNIX_EPOCH + Duration::from_secs(start),
+            duration: Duration::from_secs(seconds),
+            peak_size,
+        };
+        assert_eq!(
+            find_saturations("0", &samples, 100, &config),
+            vec![saturation(1, 2, 95), saturation(5, 3, 100)]
+        );
+    }
+
+    #[test]
+    fn test_track_saturation() {
+        let config = MempoolMonitorConfig {
+            sustained_for: Duration::from_secs(2),
+            ..MempoolMonitorConfig::default()
+        };
+        let mut node = MonitoredNode {
+            name: "mempool-monitor-test".to_string(),
+            metrics_url: "http://127.0.0.1:9101/metrics".parse().unwrap(),
+            capacity: 100,
+            samples: Vec::new(),
+            last_metrics: None,
+            saturated_since: None,
+        };
+        // Only the second saturation lasts long enough to be recorded
+        for sample in samples(&[10, 90, 20, 85, 90, 100, 20]) {
+            track_saturation(&mut node, &sample, &config);
+        }
+        let events: Vec<_> = crate::timeline_since(SystemTime::UNIX_EPOCH)
+            .into_iter()
+            .filter(|event| match &event.kind {
+                TimelineEventKind::MempoolSaturated { node }
+                | TimelineEventKind::MempoolDrained { node } => node == "mempool-monitor-test",
+                _ => false,
+            })
+            .map(|event| event.time_ms)
+            .collect();
+        assert_eq!(events, vec![3000, 6000]);
+    }
+}
@@ -0,0 +1,465 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::TestReport;
+use anyhow::{bail, format_err, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+    Untyped,
+}
+
+impl FromStr for MetricType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "counter" => MetricType::Counter,
+            "gauge" => MetricType::Gauge,
+            "histogram" => MetricType::Histogram,
+            "summary" => MetricType::Summary,
+            "untyped" => MetricType::Untyped,
+            _ => bail!("Unknown metric type: {}", s),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub value: f64,
+}
+
+impl Sample {
+    fn matches(&self, name: &str, labels: &[(&str, &str)]) -> bool {
+        self.name == name
+            && labels
+                .iter()
+                .all(|(key, value)| self.labels.get(*key).map(String::as_str) == Some(*value))
+    }
+}
+
+/// Metrics scraped from a node, in the Prometheus text format.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    types: HashMap<String, MetricType>,
+    samples: Vec<Sample>,
+}
+
+impl Metrics {
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// The type declared for the metric `name`, if any.
+    pub fn metric_type(&self, name: &str) -> Option<MetricType> {
+        self.types.get(name).copied()
+    }
+
+    /// Sum of the samples of the metric `name` having all the given `labels`, or None if there
+    /// is no such sample.
+    pub fn get(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        self.samples
+            .iter()
+            .filter(|sample| sample.matches(name, labels))
+            .map(|sample| sample.value)
+            .fold(None, |sum, value| Some(sum.unwrap_or(0.0) + value))
+    }
+
+    /// Like `get`, but fails if `name` is declared with another type than `expected`.
+    pub fn get_typed(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+        expected: MetricType,
+    ) -> Result<Option<f64>> {
+        match self.metric_type(name) {
+            Some(metric_type) if metric_type != expected => {
+                bail!("{} is a {:?}, not a {:?}", name, metric_type, expected)
+            }
+            _ => Ok(self.get(name, labels)),
+        }
+    }
+
+    /// How much the counter `name`, summed over the samples having all the given `labels`,
+    /// increased since `earlier`. A counter lower than in `earlier` was reset by a restart, and
+    /// is counted from 0. Missing counters count as 0.
+    pub fn counter_delta(&self, earlier: &Metrics, name: &str, labels: &[(&str, &str)]) -> f64 {
+        let before = earlier.get(name, labels).unwrap_or(0.0);
+        let after = self.get(name, labels).unwrap_or(0.0);
+        if after < before {
+            after
+        } else {
+            after - before
+        }
+    }
+}
+
+impl FromStr for Metrics {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut metrics = Metrics::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some(comment) = line.strip_prefix('#') {
+                let mut parts = comment.split_whitespace();
+                if parts.next() == Some("TYPE") {
+                    if let (Some(name), Some(metric_type)) = (parts.next(), parts.next()) {
+                        metrics.types.insert(name.to_string(), metric_type.parse()?);
+                    }
+                }
+                continue;
+            }
+            metrics.samples.push(
+                parse_sample(line)
+                    .map_err(|e| format_err!("Failed to parse metric '{}': {}", line, e))?,
+            );
+        }
+        Ok(metrics)
+    }
+}
+
+/// Parses `name{label="value",...} value [timestamp]`.
+fn parse_sample(line: &str) -> Result<Sample> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .ok_or_else(|| format_err!("missing value"))?;
+    let name = line[..name_end].to_string();
+    let mut rest = &line[name_end..];
+    let mut labels = HashMap::new();
+    if let Some(label_text) = rest.strip_prefix('{') {
+        let mut chars = label_text.char_indices();
+        let mut key = String::new();
+        let end = loop {
+            let (i, c) = chars
+                .next()
+                .ok_or_else(|| format_err!("unterminated labels"))?;
+            match c {
+                '}' => break i,
+                ',' | ' ' => {}
+                '=' => {
+                    if chars.next().map(|(_, c)| c) != Some('"') {
+                        bail!("unquoted value of label {}", key);
+                    }
+                    let mut value = String::new();
+                    loop {
+                        match chars.next().map(|(_, c)| c) {
+                            Some('"') => break,
+                            Some('\\') => match chars.next().map(|(_, c)| c) {
+                                Some('n') => value.push('\n'),
+                                Some(c) => value.push(c),
+                                None => bail!("unterminated value of label {}", key),
+                            },
+                            Some(c) => value.push(c),
+                            None => bail!("unterminated value of label {}", key),
+                        }
+                    }
+                    labels.insert(std::mem::take(&mut key), value);
+                }
+                c => key.push(c),
+            }
+        };
+        rest = &label_text[end + 1..];
+    }
+    let value = rest
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| format_err!("missing value"))?;
+    let value = match value {
+        "+Inf" => f64::INFINITY,
+        "-Inf" => f64::NEG_INFINITY,
+        value => value.parse()?,
+    };
+    Ok(Sample {
+        name,
+        labels,
+        value,
+    })
+}
+
+/// Metrics of every node of a swarm at some point in time, see `Swarm::metrics_snapshot`.
+#[derive(Clone, Debug)]
+pub struct MetricsSnapshot {
+    pub time: SystemTime,
+    /// Metrics of every node, by node name
+    pub nodes: BTreeMap<String, Metrics>,
+    /// Nodes that couldn't be scraped, e.g. as they were down, with the error. They're gaps
+    /// in the snapshot rather than failing it.
+    pub failures: BTreeMap<String, String>,
+}
+
+impl MetricsSnapshot {
+    pub fn new(nodes: BTreeMap<String, Metrics>) -> Self {
+        Self {
+            time: SystemTime::now(),
+            nodes,
+            failures: BTreeMap::new(),
+        }
+    }
+
+    /// A snapshot of the scrapes of every node, by node name, failed scrapes being left as
+    /// gaps.
+    pub fn from_scrapes<I>(scrapes: I) -> Self
+    where
+        I: IntoIterator<Item = (String, Result<Metrics>)>,
+    {
+        let mut snapshot = Self::new(BTreeMap::new());
+        for (node, scrape) in scrapes {
+            match scrape {
+                Ok(metrics) => {
+                    snapshot.nodes.insert(node, metrics);
+                }
+                Err(e) => {
+                    snapshot.failures.insert(node, format!("{:#}", e));
+                }
+            }
+        }
+        snapshot
+    }
+
+    /// The counters that moved on every node since the `earlier` snapshot. Nodes missing from
+    /// either snapshot are left out.
+    pub fn diff(&self, earlier: &MetricsSnapshot) -> MetricsDiff {
+        let nodes = self
+            .nodes
+            .iter()
+            .filter_map(|(node, metrics)| {
+                let earlier = earlier.nodes.get(node)?;
+                Some((node.clone(), counter_changes(earlier, metrics)))
+            })
+            .collect();
+        MetricsDiff {
+            window: self.time.duration_since(earlier.time).unwrap_or_default(),
+            nodes,
+        }
+    }
+
+    /// How much the counter `name` increased on every node since the `earlier` snapshot, by
+    /// node name, see `Metrics::counter_delta`. Nodes missing from either snapshot are left out.
+    pub fn counter_deltas(
+        &self,
+        earlier: &MetricsSnapshot,
+        name: &str,
+        labels: &[(&str, &str)],
+    ) -> BTreeMap<String, f64> {
+        self.nodes
+            .iter()
+            .filter_map(|(node, metrics)| {
+                let earlier = earlier.nodes.get(node)?;
+                Some((node.clone(), metrics.counter_delta(earlier, name, labels)))
+            })
+            .collect()
+    }
+}
+
+/// How much a counter moved between two snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CounterChange {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub delta: f64,
+}
+
+impl fmt::Display for CounterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.labels.is_empty() {
+            let labels = self
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, value))
+                .collect::<Vec<_>>();
+            write!(f, "{{{}}}", labels.join(","))?;
+        }
+        write!(f, " +{}", self.delta)
+    }
+}
+
+/// The counters that moved on every node between two snapshots, see `MetricsSnapshot::diff`.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsDiff {
+    pub window: Duration,
+    /// Counters that moved on every node, by node name, sorted by name and labels
+    pub nodes: BTreeMap<String, Vec<CounterChange>>,
+}
+
+impl MetricsDiff {
+    /// Counters that moved on the node `name`.
+    pub fn node(&self, name: &str) -> &[CounterChange] {
+        self.nodes.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Adds the diff to the text of `report`, under `title`.
+    pub fn report(&self, report: &mut TestReport, title: &str) {
+        report.report_text(format!("{}:\n{}", title, self));
+    }
+}
+
+impl fmt::Display for MetricsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Counters moved over {:.0}s", self.window.as_secs_f64())?;
+        for (node, changes) in &self.nodes {
+            write!(f, "\n  {}: {} counters moved", node, changes.len())?;
+            for change in changes {
+                write!(f, "\n    {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The counter samples of `later` that moved since `earlier`. A counter lower than in
+/// `earlier` was reset by a restart, and is counted from 0.
+fn counter_changes(earlier: &Metrics, later: &Metrics) -> Vec<CounterChange> {
+    let mut changes = later
+        .samples()
+        .iter()
+        .filter(|sample| later.metric_type(&sample.name) == Some(MetricType::Counter))
+        .filter_map(|sample| {
+            let before = earlier
+                .samples()
+                .iter()
+                .find(|before| before.name == sample.name && before.labels == sample.labels)
+                .map_or(0.0, |before| before.value);
+            let delta = if sample.value < before {
+                sample.value
+            } else {
+                sample.value - before
+            };
+            if delta == 0.0 {
+                return None;
+            }
+            Some(CounterChange {
+                name: sample.name.clone(),
+                labels: sample
+                    .labels
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+                delta,
+            })
+        })
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| (&a.name, &a.labels).cmp(&(&b.name, &b.labels)));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metrics() {
+        let metrics: Metrics = r#"
+# HELP aptos_connections Number of current connections
+# TYPE aptos_connections gauge
+aptos_connections{direction="inbound",network_id="Validator"} 2
+aptos_connections{direction="outbound",network_id="Validator"} 3
+# TYPE aptos_consensus_proposals_count counter
+aptos_consensus_proposals_count 17 1660000000000
+aptos_weird{label="a \"quoted\", value"} +Inf
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            metrics.get("aptos_connections", &[("network_id", "Validator")]),
+            Some(5.0)
+        );
+        assert_eq!(
+            metrics.get("aptos_connections", &[("direction", "inbound")]),
+            Some(2.0)
+        );
+        assert_eq!(
+            metrics.get("aptos_connections", &[("direction", "sideways")]),
+            None
+        );
+        assert_eq!(
+            metrics
+                .get_typed("aptos_consensus_proposals_count", &[], MetricType::Counter)
+                .unwrap(),
+            Some(17.0)
+        );
+        assert!(metrics
+            .get_typed("aptos_connections", &[], MetricType::Counter)
+            .is_err());
+        assert_eq!(
+            metrics.get("aptos_weird", &[("label", "a \"quoted\", value")]),
+            Some(f64::INFINITY)
+        );
+    }
+
+    #[test]
+    fn test_metrics_diff() {
+        let metrics = |text: &str| text.parse::<Metrics>().unwrap();
+        let snapshot = |nodes: Vec<(&str, Metrics)>| {
+            MetricsSnapshot::new(
+                nodes
+                    .into_iter()
+                    .map(|(node, metrics)| (node.to_string(), metrics))
+                    .collect(),
+            )
+        };
+        let earlier = snapshot(vec![
+            (
+                "0",
+                metrics("# TYPE a counter\na{kind=\"x\"} 1\na{kind=\"y\"} 5\n# TYPE g gauge\ng 1"),
+            ),
+            ("1", metrics("# TYPE a counter\na{kind=\"x\"} 10")),
+        ]);
+        let later = snapshot(vec![
+            (
+                "0",
+                metrics(
+                    "# TYPE a counter\na{kind=\"x\"} 4\na{kind=\"y\"} 5\na{kind=\"z\"} 2\n\
+                     # TYPE g gauge\ng 7",
+                ),
+            ),
+            // Restarted
+            ("1", metrics("# TYPE a counter\na{kind=\"x\"} 3")),
+            ("2", metrics("# TYPE a counter\na 1")),
+        ]);
+
+        let diff = later.diff(&earlier);
+        let change = |kind: &str, delta: f64| CounterChange {
+            name: "a".to_string(),
+            labels: vec![("kind".to_string(), kind.to_string())]
+                .into_iter()
+                .collect(),
+            delta,
+        };
+        assert_eq!(diff.node("0"), &[change("x", 3.0), change("z", 2.0)]);
+        assert_eq!(diff.node("1"), &[change("x", 3.0)]);
+        assert!(diff.nodes.get("2").is_none());
+
+        let deltas = later.counter_deltas(&earlier, "a", &[]);
+        assert_eq!(
+            deltas.into_iter().collect::<Vec<_>>(),
+            vec![("0".to_string(), 5.0), ("1".to_string(), 3.0)]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_from_scrapes() {
+        let snapshot = MetricsSnapshot::from_scrapes(vec![
+            ("0".to_string(), "a 1".parse()),
+            ("1".to_string(), Err(format_err!("connection refused"))),
+        ]);
+        assert_eq!(snapshot.nodes.keys().collect::<Vec<_>>(), vec!["0"]);
+        assert_eq!(
+            snapshot.failures.get("1").map(String::as_str),
+            Some("connection refused")
+        );
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Metrics, MetricsSnapshot, Result, Swarm};
+use anyhow::bail;
+use std::fmt;
+
+/// Assertions on the metrics of every node of a swarm, evaluated between two snapshots, e.g.
+///
+/// ```ignore
+/// let metrics_assert = MetricsAssert::new(swarm)
+///     .await?
+///     .counter_delta("aptos_consensus_timeout_count", &[])
+///     .less_than(5.0);
+/// // run the test
+/// metrics_assert.check(swarm).await?;
+/// ```
+///
+/// Every assertion holds on every node scraped in both snapshots. A node missing a metric
+/// counts it as 0, as metrics with labels only appear once they are first set.
+#[derive(Debug)]
+pub struct MetricsAssert {
+    before: MetricsSnapshot,
+    assertions: Vec<MetricAssertion>,
+}
+
+impl MetricsAssert {
+    /// Snapshots the metrics of every node of `swarm`, to evaluate the assertions against.
+    pub async fn new(swarm: &dyn Swarm) -> Result<Self> {
+        Ok(Self {
+            before: swarm.metrics_snapshot().await?,
+            assertions: Vec::new(),
+        })
+    }
+
+    /// Asserts on how much the counter `name`, summed over the samples having all the given
+    /// `labels`, increased between the snapshots. A counter reset by a restart is counted from
+    /// 0.
+    pub fn counter_delta(self, name: &str, labels: &[(&str, &str)]) -> PendingAssertion {
+        PendingAssertion::new(self, MetricValue::CounterDelta, name, labels)
+    }
+
+    /// Asserts on the value of the gauge `name`, summed over the samples having all the given
+    /// `labels`, in the second snapshot.
+    pub fn gauge(self, name: &str, labels: &[(&str, &str)]) -> PendingAssertion {
+        PendingAssertion::new(self, MetricValue::Gauge, name, labels)
+    }
+
+    /// Snapshots the metrics of `swarm` again and evaluates every assertion, failing with all
+    /// the assertions that don't hold.
+    pub async fn check(&self, swarm: &dyn Swarm) -> Result<()> {
+        let after = swarm.metrics_snapshot().await?;
+        let failures = self.evaluate(&after);
+        if !failures.is_empty() {
+            bail!("Metrics assertions failed:\n{}", failures.join("\n"));
+        }
+        Ok(())
+    }
+
+    /// Evaluates every assertion between the first snapshot and `after`, returning a
+    /// description of each one that doesn't hold on a node.
+    fn evaluate(&self, after: &MetricsSnapshot) -> Vec<String> {
+        let mut failures = Vec::new();
+        for (node, after) in &after.nodes {
+            let before = match self.before.nodes.get(node) {
+                Some(before) => before,
+                None => continue,
+            };
+            for assertion in &self.assertions {
+                let value = assertion.value(before, after);
+                if !assertion.bound.holds(value) {
+                    failures.push(format!(
+                        "{}: {} is {}, expected {}",
+                        node, assertion, value, assertion.bound
+                    ));
+                }
+            }
+        }
+        failures
+    }
+}
+
+/// An assertion waiting for its bound, see `MetricsAssert`.
+#[must_use = "the assertion is only added once given a bound"]
+pub struct PendingAssertion {
+    metrics_assert: MetricsAssert,
+    value: MetricValue,
+    name: String,
+    labels: Vec<(String, String)>,
+}
+
+impl PendingAssertion {
+    fn new(
+        metrics_assert: MetricsAssert,
+        value: MetricValue,
+        name: &str,
+        labels: &[(&str, &str)],
+    ) -> Self {
+        Self {
+            metrics_assert,
+            value,
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn less_than(self, bound: f64) -> MetricsAssert {
+        self.with_bound(Bound::LessThan(bound))
+    }
+
+    pub fn at_most(self, bound: f64) -> MetricsAssert {
+        self.with_bound(Bound::AtMost(bound))
+    }
+
+    pub fn greater_than(self, bound: f64) -> MetricsAssert {
+        self.with_bound(Bound::GreaterThan(bound))
+    }
+
+    pub fn at_least(self, bound: f64) -> MetricsAssert {
+        self.with_bound(Bound::AtLeast(bound))
+    }
+
+    fn with_bound(self, bound: Bound) -> MetricsAssert {
+        let mut metrics_assert = self.metrics_assert;
+        metrics_assert.assertions.push(MetricAssertion {
+            value: self.value,
+            name: self.name,
+            labels: self.labels,
+            bound,
+        });
+        metrics_assert
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum MetricValue {
+    CounterDelta,
+    Gauge,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Bound {
+    LessThan(f64),
+    AtMost(f64),
+    GreaterThan(f64),
+    AtLeast(f64),
+}
+
+impl Bound {
+    fn holds(&self, value: f64) -> bool {
+        match *self {
+            Bound::LessThan(bound) => value < bound,
+            Bound::AtMost(bound) => value <= bound,
+            Bound::GreaterThan(bound) => value > bound,
+            Bound::AtLeast(bound) => value >= bound,
+        }
+    }
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::LessThan(bound) => write!(f, "< {}", bound),
+            Bound::AtMost(bound) => write!(f, "<= {}", bound),
+            Bound::GreaterThan(bound) => write!(f, "> {}", bound),
+            Bound::AtLeast(bound) => write!(f, ">= {}", bound),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MetricAssertion {
+    value: MetricValue,
+    name: String,
+    labels: Vec<(String, String)>,
+    bound: Bound,
+}
+
+impl MetricAssertion {
+    fn value(&self, before: &Metrics, after: &Metrics) -> f64 {
+        let labels = self
+            .labels
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        match self.value {
+            MetricValue::Gauge => after.get(&self.name, &labels).unwrap_or(0.0),
+            MetricValue::CounterDelta => after.counter_delta(before, &self.name, &labels),
+        }
+    }
+}
+
+impl fmt::Display for MetricAssertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let MetricValue::CounterDelta = self.value {
+            write!(f, "increase of ")?;
+        }
+        write!(f, "{}", self.name)?;
+        if !self.labels.is_empty() {
+            let labels = self
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, value))
+                .collect::<Vec<_>>();
+            write!(f, "{{{}}}", labels.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_evaluate_assertions() {
+        let metrics = |text: &str| text.parse::<Metrics>().unwrap();
+        let mut before = BTreeMap::new();
+        before.insert(
+            "0".to_string(),
+            metrics(
+                "aptos_consensus_timeout_count 3\naptos_connections{network_id=\"Validator\"} 3",
+            ),
+        );
+        before.insert("1".to_string(), metrics("aptos_consensus_timeout_count 10"));
+        let metrics_assert = MetricsAssert {
+            before: MetricsSnapshot::new(before),
+            assertions: Vec::new(),
+        }
+        .counter_delta("aptos_consensus_timeout_count", &[])
+        .less_than(5.0)
+        .gauge("aptos_connections", &[("network_id", "Validator")])
+        .at_least(2.0);
+
+        let mut after = BTreeMap::new();
+        after.insert(
+            "0".to_string(),
+            metrics(
+                "aptos_consensus_timeout_count 7\naptos_connections{network_id=\"Validator\"} 3",
+            ),
+        );
+        // Restarted, so its counter was reset.
+        after.insert(
+            "1".to_string(),
+            metrics(
+                "aptos_consensus_timeout_count 6\naptos_connections{network_id=\"Validator\"} 1",
+            ),
+        );
+        // Not in the first snapshot.
+        after.insert(
+            "2".to_string(),
+            metrics("aptos_consensus_timeout_count 100"),
+        );
+
+        assert_eq!(
+            metrics_assert.evaluate(&MetricsSnapshot::new(after)),
+            vec![
+                "1: increase of aptos_consensus_timeout_count is 6, expected < 5".to_string(),
+                "1: aptos_connections{network_id=\"Validator\"} is 1, expected >= 2".to_string(),
+            ]
+        );
+    }
+}
@@ -15,12 +15,40 @@ mod swarm;
 pub use swarm::*;
 mod chaos;
 pub use chaos::*;
+mod failpoint;
+pub use failpoint::*;
 mod node;
 pub use node::*;
+mod rest_client;
+pub use rest_client::*;
+mod metrics;
+pub use metrics::*;
+mod latency_matrix;
+pub use latency_matrix::*;
+mod mempool_monitor;
+pub use mempool_monitor::*;
+mod metrics_assert;
+pub use metrics_assert::*;
+mod state_sync;
+pub use state_sync::*;
+mod storage_stats;
+pub use storage_stats::*;
+mod epoch_watcher;
+pub use epoch_watcher::*;
+mod stall_detector;
+pub use stall_detector::*;
+mod experiment;
+pub use experiment::*;
+mod soak;
+pub use soak::*;
+mod consistency;
+pub use consistency::*;
 mod chain_info;
+mod cluster;
 pub mod system_metrics;
 
 pub use chain_info::*;
+pub use cluster::*;
 use framework::ReleaseBundle;
 
 /// A wrapper around a usize in order to represent an opaque version of a Node.
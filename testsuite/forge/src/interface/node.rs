@@ -1,7 +1,7 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{Result, Version};
+use crate::{FailpointAction, Metrics, RestRetryPolicy, Result, RetryingRestClient, Version};
 use anyhow::anyhow;
 use aptos_config::{config::NodeConfig, network_id::NetworkId};
 use aptos_rest_client::Client as RestClient;
@@ -9,25 +9,114 @@ use aptos_sdk::types::PeerId;
 use inspection_service::inspection_client::InspectionClient;
 use std::{
     collections::HashMap,
+    path::PathBuf,
     time::{Duration, Instant},
 };
 use url::Url;
 
 #[derive(Debug)]
 pub enum HealthCheckError {
-    NotRunning(String),
-    Failure(anyhow::Error),
+    NotRunning(NotRunningReason),
+    Failure(HealthCheckFailure),
     Unknown(anyhow::Error),
 }
 
 impl std::fmt::Display for HealthCheckError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            HealthCheckError::NotRunning(reason) => write!(f, "not running: {}", reason),
+            HealthCheckError::Failure(failure) => write!(f, "unhealthy: {}", failure),
+            HealthCheckError::Unknown(error) => write!(f, "unknown error: {:#}", error),
+        }
     }
 }
 
 impl std::error::Error for HealthCheckError {}
 
+/// Why a node isn't running.
+#[derive(Debug)]
+pub enum NotRunningReason {
+    /// The node was stopped
+    Stopped,
+    /// The process of the node exited by itself
+    Exited {
+        /// Exit code, if the process exited rather than being killed by a signal
+        code: Option<i32>,
+        /// Signal which killed the process
+        signal: Option<i32>,
+        /// Where the core dump of the process was collected to, if it dumped core
+        core_dump: Option<PathBuf>,
+    },
+}
+
+impl std::fmt::Display for NotRunningReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NotRunningReason::Stopped => write!(f, "stopped"),
+            NotRunningReason::Exited {
+                code,
+                signal,
+                core_dump,
+            } => {
+                match (code, signal) {
+                    (Some(code), _) => write!(f, "exited with code {}", code)?,
+                    (None, Some(signal)) => write!(f, "killed by signal {}", signal)?,
+                    (None, None) => write!(f, "exited")?,
+                }
+                if let Some(core_dump) = core_dump {
+                    write!(f, ", core dump at {}", core_dump.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Why a running node failed its health check.
+#[derive(Debug)]
+pub enum HealthCheckFailure {
+    /// Nothing accepts connections on the port of `service`
+    PortClosed {
+        service: &'static str,
+        port: u16,
+    },
+    /// The REST API answered with an error status
+    RestStatus {
+        status: u16,
+        message: String,
+    },
+    /// `service` accepts connections but didn't answer properly, e.g. it timed out
+    ServiceError {
+        service: &'static str,
+        error: anyhow::Error,
+    },
+    /// The ledger version didn't advance since the previous health check
+    LedgerNotProgressing {
+        version: u64,
+    },
+    Other(anyhow::Error),
+}
+
+impl std::fmt::Display for HealthCheckFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HealthCheckFailure::PortClosed { service, port } => {
+                write!(f, "{} port {} is closed", service, port)
+            }
+            HealthCheckFailure::RestStatus { status, message } => {
+                write!(f, "REST API answered {}: {}", status, message)
+            }
+            HealthCheckFailure::ServiceError { service, error } => {
+                write!(f, "{} failed: {:#}", service, error)
+            }
+            HealthCheckFailure::LedgerNotProgressing { version } => {
+                write!(f, "ledger is not progressing, at version {}", version)
+            }
+            HealthCheckFailure::Other(error) => write!(f, "{:#}", error),
+        }
+    }
+}
+
 /// Trait used to represent a running Validator or FullNode
 #[async_trait::async_trait]
 pub trait Node: Send + Sync {
@@ -141,11 +230,45 @@ pub trait NodeExt: Node {
         RestClient::new_with_timeout(self.rest_api_endpoint(), timeout)
     }
 
+    /// Return REST API client of this Node, retrying the requests failing with transient errors
+    /// according to `policy`
+    fn rest_client_with_retries(&self, policy: RestRetryPolicy) -> RetryingRestClient {
+        RetryingRestClient::new(
+            self.rest_client_with_timeout(policy.request_timeout),
+            policy,
+        )
+    }
+
     /// Return an InspectionClient for this Node
     fn inspection_client(&self) -> InspectionClient {
         InspectionClient::from_url(self.inspection_service_endpoint())
     }
 
+    /// Scrapes the metrics endpoint of this Node
+    async fn metrics(&self) -> Result<Metrics> {
+        let mut url = self.inspection_service_endpoint();
+        url.set_path("metrics");
+        reqwest::get(url)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?
+            .parse()
+    }
+
+    /// Sets the failpoint `name` on this Node
+    async fn set_failpoint(&self, name: &str, action: FailpointAction) -> Result<()> {
+        self.rest_client()
+            .set_failpoint(name.to_string(), action.to_string())
+            .await?;
+        Ok(())
+    }
+
+    /// Turns the failpoint `name` off on this Node
+    async fn clear_failpoint(&self, name: &str) -> Result<()> {
+        self.set_failpoint(name, FailpointAction::Off).await
+    }
+
     /// Restarts this Node by calling Node::Stop followed by Node::Start
     async fn restart(&mut self) -> Result<()> {
         self.stop().await?;
@@ -204,13 +327,46 @@ pub trait NodeExt: Node {
         Ok(self.rest_client().health_check(seconds).await?)
     }
 
+    /// Waits until this Node is taking part in consensus, i.e. both its current round and the
+    /// last round it voted in advance. A validator can serve REST, and so pass the liveness
+    /// check, while being stuck outside consensus.
+    async fn wait_for_consensus_participation(&self, deadline: Instant) -> Result<()> {
+        let mut fields = HashMap::new();
+        fields.insert("field".to_string(), "last_voted_round".to_string());
+        let start_round = self.get_metric("aptos_consensus_current_round").await?;
+        let start_voted_round = self
+            .get_metric_with_fields("aptos_safety_rules_state", fields.clone())
+            .await?;
+        loop {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let round = self.get_metric("aptos_consensus_current_round").await?;
+            let voted_round = self
+                .get_metric_with_fields("aptos_safety_rules_state", fields.clone())
+                .await?;
+            if round > start_round && voted_round > start_voted_round {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                return Err(anyhow!(
+                    "Node {}:{} is not participating in consensus: round {:?} -> {:?}, last voted round {:?} -> {:?}",
+                    self.name(),
+                    self.peer_id(),
+                    start_round,
+                    round,
+                    start_voted_round,
+                    voted_round,
+                ));
+            }
+        }
+    }
+
     async fn wait_until_healthy(&mut self, deadline: Instant) -> Result<()> {
         while Instant::now() < deadline {
             match self.health_check().await {
                 Ok(()) => return Ok(()),
                 Err(HealthCheckError::NotRunning(error)) => {
                     return Err(anyhow::anyhow!(
-                        "Node {}:{} not running! Error: {:?}",
+                        "Node {}:{} not running! Error: {}",
                         self.name(),
                         self.peer_id(),
                         error,
@@ -0,0 +1,99 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_rest_client::{
+    error::RestError, Account, Client as RestClient, Response, State, Transaction,
+};
+use aptos_sdk::types::account_address::AccountAddress;
+use std::{future::Future, time::Duration};
+
+/// How a RetryingRestClient retries failed requests.
+#[derive(Clone, Debug)]
+pub struct RestRetryPolicy {
+    /// Timeout of a single request
+    pub request_timeout: Duration,
+    /// Wait before the first retry, doubled after every retry
+    pub initial_interval: Duration,
+    /// No retry is started after this long
+    pub total_wait: Duration,
+    /// Also retry requests failing with 404, e.g. for data the node hasn't synced yet
+    pub retry_not_found: bool,
+}
+
+impl Default for RestRetryPolicy {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(10),
+            initial_interval: Duration::from_millis(500),
+            total_wait: Duration::from_secs(60),
+            retry_not_found: false,
+        }
+    }
+}
+
+/// A REST client retrying with exponential backoff the requests failing with a transient error
+/// (timeouts, connection errors, 429 and 5xx). Requests can also be made without retries
+/// through `inner`.
+#[derive(Clone, Debug)]
+pub struct RetryingRestClient {
+    inner: RestClient,
+    policy: RestRetryPolicy,
+}
+
+impl RetryingRestClient {
+    pub fn new(inner: RestClient, policy: RestRetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub fn inner(&self) -> &RestClient {
+        &self.inner
+    }
+
+    pub fn policy(&self) -> &RestRetryPolicy {
+        &self.policy
+    }
+
+    /// Runs `request` against the client until it succeeds, fails with an error that isn't
+    /// transient, or the policy's total wait is over.
+    pub async fn retry<F, Fut, T>(&self, request: F) -> Result<T, RestError>
+    where
+        F: Fn(RestClient) -> Fut,
+        Fut: Future<Output = Result<T, RestError>>,
+    {
+        let retry_not_found = self.policy.retry_not_found;
+        RestClient::try_until_ok(
+            Some(self.policy.total_wait),
+            Some(self.policy.initial_interval),
+            |status, error| {
+                if retry_not_found {
+                    aptos_rest_client::retriable_with_404(status, error)
+                } else {
+                    aptos_rest_client::retriable(status, error)
+                }
+            },
+            || request(self.inner.clone()),
+        )
+        .await
+    }
+
+    pub async fn get_ledger_information(&self) -> Result<Response<State>, RestError> {
+        self.retry(|client| async move { client.get_ledger_information().await })
+            .await
+    }
+
+    pub async fn get_account(
+        &self,
+        address: AccountAddress,
+    ) -> Result<Response<Account>, RestError> {
+        self.retry(|client| async move { client.get_account(address).await })
+            .await
+    }
+
+    pub async fn get_transaction_by_version(
+        &self,
+        version: u64,
+    ) -> Result<Response<Transaction>, RestError> {
+        self.retry(|client| async move { client.get_transaction_by_version(version).await })
+            .await
+    }
+}
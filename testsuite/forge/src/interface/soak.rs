@@ -0,0 +1,338 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Long-haul soak runs: open-ended emission with a repeating fault schedule, periodic metrics
+//! assertions and artifact checkpoints, reporting incrementally so that a failure late in the
+//! run keeps everything learned before it.
+
+use crate::{
+    interface::{
+        experiment::ActiveFaults,
+        swarm::{emit_clients, emitter_for},
+    },
+    record_event,
+    timeline::unix_millis,
+    timeline_since, EmitJobRequest, FaultAction, FaultSchedule, MetricsAssert, Result, Swarm,
+    TimelineEvent, TimelineEventKind, TxnStats,
+};
+use anyhow::{bail, format_err, Context};
+use aptos_logger::{info, warn};
+use aptos_sdk::types::PeerId;
+use serde::Serialize;
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Builds the metrics assertions of a soak period from a fresh snapshot.
+pub type SoakAssertions = Box<dyn Fn(MetricsAssert) -> MetricsAssert + Send + Sync>;
+
+pub struct SoakConfig {
+    /// How long to run for, until a failure if None
+    pub duration: Option<Duration>,
+    /// Faults applied every `chaos_period`, at their offset from the start of the period
+    pub chaos_schedule: FaultSchedule,
+    pub chaos_period: Duration,
+    /// How often the metrics assertions are checked and a report is appended to `report_path`
+    pub report_interval: Duration,
+    /// How often the swarm checkpoints its artifacts, see `Swarm::checkpoint_artifacts`
+    pub checkpoint_interval: Duration,
+    /// JSON lines file every report is appended to
+    pub report_path: PathBuf,
+    pub assertions: Option<SoakAssertions>,
+}
+
+impl SoakConfig {
+    pub fn new(report_path: PathBuf) -> Self {
+        Self {
+            duration: None,
+            chaos_schedule: FaultSchedule::new(),
+            chaos_period: Duration::from_secs(3600),
+            report_interval: Duration::from_secs(600),
+            checkpoint_interval: Duration::from_secs(3 * 3600),
+            report_path,
+            assertions: None,
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn with_chaos_schedule(mut self, schedule: FaultSchedule, period: Duration) -> Self {
+        self.chaos_schedule = schedule;
+        self.chaos_period = period;
+        self
+    }
+
+    pub fn with_report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+
+    pub fn with_checkpoint_interval(mut self, checkpoint_interval: Duration) -> Self {
+        self.checkpoint_interval = checkpoint_interval;
+        self
+    }
+
+    pub fn with_assertions(
+        mut self,
+        assertions: impl Fn(MetricsAssert) -> MetricsAssert + Send + Sync + 'static,
+    ) -> Self {
+        self.assertions = Some(Box::new(assertions));
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.report_interval.is_zero() || self.checkpoint_interval.is_zero() {
+            bail!("The report and checkpoint intervals of a soak run can't be empty");
+        }
+        if self.chaos_period.is_zero() {
+            bail!("The chaos period of a soak run can't be empty");
+        }
+        if let Some((offset, _)) = self.chaos_schedule.sorted_steps().last() {
+            if *offset >= self.chaos_period {
+                bail!(
+                    "Faults at +{}s don't fit a chaos period of {}s",
+                    offset.as_secs(),
+                    self.chaos_period.as_secs()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A report of a soak period, appended to the report file as soon as the period ends.
+#[derive(Debug, Serialize)]
+pub struct SoakReport {
+    /// Seconds since the start of the run
+    pub elapsed_secs: u64,
+    pub period_secs: u64,
+    pub submitted: u64,
+    pub committed: u64,
+    pub expired: u64,
+    pub failed_submission: u64,
+    /// Average latency in millis
+    pub latency: u64,
+    pub p99_latency: u64,
+    pub failed_faults: Vec<String>,
+    /// Why the metrics assertions failed, None if they held
+    pub assertion_failure: Option<String>,
+    pub events: Vec<TimelineEvent>,
+}
+
+impl fmt::Display for SoakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "+{}s: committed {} of {} txns, {} expired, {} ms latency, {} ms p99 latency",
+            self.elapsed_secs,
+            self.committed,
+            self.submitted,
+            self.expired,
+            self.latency,
+            self.p99_latency
+        )?;
+        if !self.failed_faults.is_empty() {
+            write!(f, ", failed faults: {:?}", self.failed_faults)?;
+        }
+        if let Some(failure) = &self.assertion_failure {
+            write!(f, ", {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// The next fault of a schedule repeated every period.
+struct RepeatingSchedule {
+    steps: Vec<(Duration, FaultAction)>,
+    period: Duration,
+    next: usize,
+    period_start: Duration,
+}
+
+impl RepeatingSchedule {
+    fn new(schedule: &FaultSchedule, period: Duration) -> Self {
+        Self {
+            steps: schedule.sorted_steps(),
+            period,
+            next: 0,
+            period_start: Duration::ZERO,
+        }
+    }
+
+    /// Offset from the start of the run of the next fault.
+    fn next_offset(&self) -> Option<Duration> {
+        self.steps
+            .get(self.next)
+            .map(|(offset, _)| self.period_start + *offset)
+    }
+
+    fn advance(&mut self) -> FaultAction {
+        let action = self.steps[self.next].1.clone();
+        self.next += 1;
+        if self.next == self.steps.len() {
+            self.next = 0;
+            self.period_start += self.period;
+        }
+        action
+    }
+}
+
+/// Emits `request` to `nodes` until `config.duration` elapses or the metrics assertions fail,
+/// applying the chaos schedule every period and appending a `SoakReport` to the report file
+/// every report interval. Faults in effect at the end are undone.
+pub async fn run_soak(
+    swarm: &mut dyn Swarm,
+    nodes: &[PeerId],
+    request: EmitJobRequest,
+    config: &SoakConfig,
+) -> Result<TxnStats> {
+    config.validate()?;
+    let mut report_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.report_path)
+        .with_context(|| format!("Failed to open {}", config.report_path.display()))?;
+    let mut metrics_assert = new_metrics_assert(swarm, config).await?;
+
+    let clients = emit_clients(swarm, nodes)?;
+    let chain_info = swarm.chain_info();
+    let mut emitter = emitter_for(&chain_info)?;
+    let job = emitter
+        .start_job(chain_info.root_account, request.rest_clients(clients))
+        .await?;
+    record_event(TimelineEventKind::EmissionStarted { nodes: nodes.len() });
+
+    let start = Instant::now();
+    let start_time = SystemTime::now();
+    let mut chaos = RepeatingSchedule::new(&config.chaos_schedule, config.chaos_period);
+    let mut active_faults = ActiveFaults::default();
+    let mut failed_faults = vec![];
+    let mut prev_stats = TxnStats::default();
+    let mut next_report = config.report_interval;
+    let mut next_checkpoint = config.checkpoint_interval;
+    let mut prev_report = Duration::ZERO;
+    let result = loop {
+        let end = config.duration.unwrap_or(Duration::MAX);
+        let mut next = next_report.min(next_checkpoint).min(end);
+        if let Some(offset) = chaos.next_offset() {
+            next = next.min(offset);
+        }
+        tokio::time::sleep_until((start + next).into()).await;
+
+        while chaos.next_offset().map_or(false, |offset| offset <= next) {
+            let action = chaos.advance();
+            info!("Applying {:?} at +{}s", action, next.as_secs());
+            if let Err(e) = active_faults.apply(swarm, &action).await {
+                warn!("Failed to apply {:?}: {}", action, e);
+                failed_faults.push(format!("{:?}: {}", action, e));
+            }
+        }
+        if next >= next_checkpoint {
+            let label = format!("{}s", next.as_secs());
+            if let Err(e) = swarm.checkpoint_artifacts(&label).await {
+                warn!("Failed to checkpoint the artifacts at {}: {}", label, e);
+            }
+            next_checkpoint += config.checkpoint_interval;
+        }
+        if next >= next_report || next >= end {
+            let assertion_failure = match &metrics_assert {
+                Some(metrics_assert) => metrics_assert
+                    .check(swarm)
+                    .await
+                    .err()
+                    .map(|e| format!("{:#}", e)),
+                None => None,
+            };
+            let stats = emitter.peek_job_stats(&job);
+            let delta = &stats - &prev_stats;
+            prev_stats = stats;
+            let period = next - prev_report;
+            let rate = delta.rate(period);
+            let report = SoakReport {
+                elapsed_secs: next.as_secs(),
+                period_secs: period.as_secs(),
+                submitted: delta.submitted,
+                committed: delta.committed,
+                expired: delta.expired,
+                failed_submission: delta.failed_submission,
+                latency: rate.latency,
+                p99_latency: rate.p99_latency,
+                failed_faults: std::mem::take(&mut failed_faults),
+                assertion_failure,
+                events: timeline_since(start_time + prev_report)
+                    .into_iter()
+                    .filter(|event| event.time_ms < unix_millis(start_time + next))
+                    .collect(),
+            };
+            info!("Soak report: {}", report);
+            if let Err(e) = append_report(&mut report_file, &report) {
+                break Err(e);
+            }
+            if let Some(failure) = report.assertion_failure {
+                break Err(format_err!(failure));
+            }
+            prev_report = next;
+            next_report += config.report_interval;
+            metrics_assert = match new_metrics_assert(swarm, config).await {
+                Ok(metrics_assert) => metrics_assert,
+                Err(e) => break Err(e),
+            };
+        }
+        if next >= end {
+            break Ok(());
+        }
+    };
+
+    let stats = emitter.stop_job(job).await;
+    record_event(TimelineEventKind::EmissionStopped { nodes: nodes.len() });
+    active_faults.undo_all(swarm).await;
+    result.map(|()| stats)
+}
+
+fn append_report(file: &mut File, report: &SoakReport) -> Result<()> {
+    writeln!(file, "{}", serde_json::to_string(report)?)?;
+    // Flushed right away, the run may not end gracefully
+    file.flush()?;
+    Ok(())
+}
+
+async fn new_metrics_assert(
+    swarm: &dyn Swarm,
+    config: &SoakConfig,
+) -> Result<Option<MetricsAssert>> {
+    match &config.assertions {
+        Some(assertions) => Ok(Some(assertions(MetricsAssert::new(swarm).await?))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeating_schedule() {
+        let node = PeerId::random();
+        let schedule = FaultSchedule::new().during(
+            Duration::from_secs(10),
+            Duration::from_secs(20),
+            FaultAction::StopNode(node),
+        );
+        let mut chaos = RepeatingSchedule::new(&schedule, Duration::from_secs(60));
+
+        let mut offsets = vec![];
+        for _ in 0..4 {
+            offsets.push(chaos.next_offset().unwrap().as_secs());
+            chaos.advance();
+        }
+        assert_eq!(offsets, vec![10, 20, 70, 80]);
+        assert_eq!(chaos.advance(), FaultAction::StopNode(node));
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Detects when the validators stop producing blocks for longer than some gap, e.g. to fail a
+//! test that stalled for a while even if its average throughput looks fine.
+
+use crate::{record_event_at, send_alert, Result, TimelineEventKind};
+use anyhow::bail;
+use aptos_infallible::Mutex;
+use aptos_logger::{info, warn};
+use aptos_rest_client::Client as RestClient;
+use futures::future::join_all;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// A period the highest block height across the validators didn't move.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stall {
+    /// When the last block before the stall was seen
+    pub start: SystemTime,
+    /// When blocks were seen again, None while still stalled
+    pub end: Option<SystemTime>,
+    /// Highest block height when the stall was detected
+    pub block_height: u64,
+    /// Block height of every validator that could be polled when the stall was detected
+    pub heights: BTreeMap<String, u64>,
+}
+
+impl Stall {
+    /// How long the stall lasted, up to `now` if it's still ongoing.
+    pub fn duration(&self, now: SystemTime) -> Duration {
+        self.end
+            .unwrap_or(now)
+            .duration_since(self.start)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Stall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no block after height {}", self.block_height)?;
+        let secs = self.duration(SystemTime::now()).as_secs();
+        match self.end {
+            Some(_) => write!(f, " for {}s", secs),
+            None => write!(f, " for {}s so far", secs),
+        }
+    }
+}
+
+/// Tells stalls apart from the progress of a counter, e.g. the block height of a network.
+#[derive(Debug)]
+pub struct StallTracker {
+    max_gap: Duration,
+    /// Highest value seen and when it was first seen
+    last_progress: Option<(SystemTime, u64)>,
+    stalls: Vec<Stall>,
+}
+
+impl StallTracker {
+    /// Progress stalls when the counter doesn't move for more than `max_gap`.
+    pub fn new(max_gap: Duration) -> Self {
+        Self {
+            max_gap,
+            last_progress: None,
+            stalls: Vec::new(),
+        }
+    }
+
+    /// Records the heights seen at `time`, returning the stall that started or ended, if any.
+    pub fn observe(&mut self, time: SystemTime, heights: &BTreeMap<String, u64>) -> Option<Stall> {
+        let height = heights.values().copied().max()?;
+        let (last_time, last_height) = match self.last_progress {
+            Some(last_progress) => last_progress,
+            None => {
+                self.last_progress = Some((time, height));
+                return None;
+            }
+        };
+        if height > last_height {
+            self.last_progress = Some((time, height));
+            return match self.stalls.last_mut() {
+                Some(stall) if stall.end.is_none() => {
+                    stall.end = Some(time);
+                    Some(stall.clone())
+                }
+                _ => None,
+            };
+        }
+        let ongoing = matches!(self.stalls.last(), Some(stall) if stall.end.is_none());
+        if !ongoing && time.duration_since(last_time).unwrap_or_default() > self.max_gap {
+            let stall = Stall {
+                start: last_time,
+                end: None,
+                block_height: last_height,
+                heights: heights.clone(),
+            };
+            self.stalls.push(stall.clone());
+            return Some(stall);
+        }
+        None
+    }
+
+    /// The stalls detected so far, oldest first.
+    pub fn stalls(&self) -> &[Stall] {
+        &self.stalls
+    }
+}
+
+#[derive(Debug)]
+struct DetectorState {
+    tracker: StallTracker,
+    stopped: bool,
+}
+
+/// Polls the block height of the validators on a background thread until dropped, logging,
+/// alerting and recording on the timeline of the run every stall longer than the max gap.
+#[derive(Debug)]
+pub struct StallDetector {
+    max_gap: Duration,
+    state: Arc<Mutex<DetectorState>>,
+}
+
+impl StallDetector {
+    /// Starts polling `clients`, by node name, every `interval`.
+    pub fn start(
+        clients: Vec<(String, RestClient)>,
+        max_gap: Duration,
+        interval: Duration,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(DetectorState {
+            tracker: StallTracker::new(max_gap),
+            stopped: false,
+        }));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to create runtime");
+            while !thread_state.lock().stopped {
+                let heights = runtime.block_on(poll(&clients));
+                let time = SystemTime::now();
+                let change = thread_state.lock().tracker.observe(time, &heights);
+                match change {
+                    Some(stall) if stall.end.is_none() => {
+                        warn!("Block production stalled: {}", stall);
+                        record_event_at(
+                            time,
+                            TimelineEventKind::ProductionStalled {
+                                height: stall.block_height,
+                            },
+                        );
+                        send_alert(&format!(":warning: Block production stalled: {}", stall));
+                    }
+                    Some(stall) => {
+                        info!("Block production resumed after {}", stall);
+                        record_event_at(
+                            time,
+                            TimelineEventKind::ProductionResumed {
+                                height: stall.block_height,
+                            },
+                        );
+                        send_alert(&format!(
+                            ":white_check_mark: Block production resumed after {}",
+                            stall
+                        ));
+                    }
+                    None => {}
+                }
+                thread::sleep(interval);
+            }
+        });
+        Self { max_gap, state }
+    }
+
+    /// The stalls detected so far, oldest first.
+    pub fn stalls(&self) -> Vec<Stall> {
+        self.state.lock().tracker.stalls().to_vec()
+    }
+
+    /// Fails if blocks stopped being produced for longer than the max gap at some point.
+    pub fn check_no_stall(&self) -> Result<()> {
+        let stalls = self.stalls();
+        if let Some(longest) = stalls
+            .iter()
+            .max_by_key(|stall| stall.duration(SystemTime::now()))
+        {
+            bail!(
+                "Block production stalled {} times for more than {}s, longest: {}",
+                stalls.len(),
+                self.max_gap.as_secs(),
+                longest
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StallDetector {
+    fn drop(&mut self) {
+        self.state.lock().stopped = true;
+    }
+}
+
+/// Block height of every node that responds.
+async fn poll(clients: &[(String, RestClient)]) -> BTreeMap<String, u64> {
+    let states = join_all(
+        clients
+            .iter()
+            .map(|(_, client)| client.get_ledger_information()),
+    )
+    .await;
+    clients
+        .iter()
+        .zip(states)
+        .filter_map(|((name, _), state)| {
+            Some((name.clone(), state.ok()?.into_inner().block_height))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_tracker() {
+        let at = |secs: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let heights = |height: u64| -> BTreeMap<String, u64> {
+            vec![("0".to_string(), height), ("1".to_string(), height / 2)]
+                .into_iter()
+                .collect()
+        };
+        let mut tracker = StallTracker::new(Duration::from_secs(10));
+
+        assert_eq!(tracker.observe(at(0), &BTreeMap::new()), None);
+        assert_eq!(tracker.observe(at(0), &heights(10)), None);
+        assert_eq!(tracker.observe(at(5), &heights(12)), None);
+        assert_eq!(tracker.observe(at(15), &heights(12)), None);
+
+        let stall = tracker.observe(at(16), &heights(12)).unwrap();
+        assert_eq!(
+            (stall.start, stall.end, stall.block_height),
+            (at(5), None, 12)
+        );
+        assert_eq!(tracker.observe(at(20), &heights(12)), None);
+
+        let stall = tracker.observe(at(25), &heights(13)).unwrap();
+        assert_eq!(stall.duration(at(100)), Duration::from_secs(20));
+        assert_eq!(tracker.observe(at(30), &heights(14)), None);
+        assert_eq!(tracker.stalls().len(), 1);
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Metrics, NodeExt, Result};
+use aptos_config::config::{BootstrappingMode, ContinuousSyncingMode, NodeConfig};
+use std::fmt;
+
+const EXECUTING_COMPONENT: &str = "aptos_state_sync_executing_component_counters";
+
+/// How a node keeps its state in sync with the chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Catching up to the chain after starting, with empty storage or a stale one
+    Bootstrapping(BootstrappingMode),
+    /// Bootstrapped, keeping up with the chain
+    ContinuousSyncing(ContinuousSyncingMode),
+    /// A validator whose state is updated by consensus. It falls back to continuous syncing
+    /// while it lags behind, which can't be told apart from a single scrape.
+    Consensus,
+}
+
+impl SyncMode {
+    /// The mode of a node from its config and metrics. The state sync driver counts every
+    /// iteration by the component it ran, and only starts continuous syncing, or letting
+    /// consensus execute, once bootstrapped.
+    pub fn from_metrics(config: &NodeConfig, metrics: &Metrics) -> Self {
+        let driver = &config.state_sync.state_sync_driver;
+        let iterations = |component: &str| {
+            metrics
+                .get(EXECUTING_COMPONENT, &[("label", component)])
+                .unwrap_or(0.0)
+        };
+        if iterations("consensus") > 0.0 {
+            SyncMode::Consensus
+        } else if iterations("continuous_syncer") > 0.0 {
+            SyncMode::ContinuousSyncing(driver.continuous_syncing_mode)
+        } else {
+            SyncMode::Bootstrapping(driver.bootstrapping_mode)
+        }
+    }
+}
+
+impl fmt::Display for SyncMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncMode::Bootstrapping(mode) => write!(f, "bootstrapping ({})", mode.to_label()),
+            SyncMode::ContinuousSyncing(mode) => {
+                write!(f, "continuous syncing ({})", mode.to_label())
+            }
+            SyncMode::Consensus => write!(f, "consensus"),
+        }
+    }
+}
+
+/// Where a node is in syncing the chain, see `Swarm::node_sync_status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeSyncStatus {
+    /// Latest ledger version the node serves
+    pub synced_version: u64,
+    /// Highest ledger version synced by a node of the swarm
+    pub target_version: u64,
+    pub mode: SyncMode,
+}
+
+impl NodeSyncStatus {
+    /// How many versions the node is behind its target.
+    pub fn lag(&self) -> u64 {
+        self.target_version.saturating_sub(self.synced_version)
+    }
+
+    pub fn is_caught_up(&self) -> bool {
+        self.lag() == 0
+    }
+}
+
+impl fmt::Display for NodeSyncStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at version {} of {}",
+            self.mode, self.synced_version, self.target_version
+        )
+    }
+}
+
+/// The sync status of `node`, from its REST API and metrics.
+pub async fn node_sync_status<N: NodeExt + ?Sized>(
+    node: &N,
+    target_version: u64,
+) -> Result<NodeSyncStatus> {
+    let synced_version = node
+        .rest_client()
+        .get_ledger_information()
+        .await?
+        .into_inner()
+        .version;
+    let metrics = node.metrics().await?;
+    Ok(NodeSyncStatus {
+        synced_version,
+        // The node may have synced past the target since it was computed.
+        target_version: target_version.max(synced_version),
+        mode: SyncMode::from_metrics(node.config(), &metrics),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_mode_from_metrics() {
+        let config = NodeConfig::default();
+        let driver = config.state_sync.state_sync_driver;
+        let mode = |text: &str| SyncMode::from_metrics(&config, &text.parse().unwrap());
+
+        assert_eq!(mode(""), SyncMode::Bootstrapping(driver.bootstrapping_mode));
+        assert_eq!(
+            mode("aptos_state_sync_executing_component_counters{label=\"bootstrapper\"} 12"),
+            SyncMode::Bootstrapping(driver.bootstrapping_mode)
+        );
+        assert_eq!(
+            mode(
+                "aptos_state_sync_executing_component_counters{label=\"bootstrapper\"} 12\n\
+                 aptos_state_sync_executing_component_counters{label=\"continuous_syncer\"} 3"
+            ),
+            SyncMode::ContinuousSyncing(driver.continuous_syncing_mode)
+        );
+        assert_eq!(
+            mode(
+                "aptos_state_sync_executing_component_counters{label=\"continuous_syncer\"} 3\n\
+                 aptos_state_sync_executing_component_counters{label=\"consensus\"} 40"
+            ),
+            SyncMode::Consensus
+        );
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage pruning and RocksDB compaction progress of the nodes, from their metrics, to tell
+//! whether pruning and compaction keep up with the write load of a test.
+
+use crate::{Metrics, MetricsSnapshot, TestReport};
+use anyhow::{bail, Result};
+use std::{collections::BTreeMap, fmt};
+
+const LEDGER_VERSION: &str = "aptos_storage_ledger_version";
+const PRUNER_MIN_READABLE_VERSION: &str = "aptos_pruner_min_readable_version";
+const PRUNE_WINDOW: &str = "aptos_storage_prune_window";
+/// RocksDB properties of every column family, labeled with their `property_name`
+const ROCKSDB_PROPERTIES: &str = "aptos_rocksdb_properties";
+const PENDING_COMPACTION_BYTES: &str = "aptos_rocksdb_estimate-pending-compaction-bytes";
+const RUNNING_COMPACTIONS: &str = "aptos_rocksdb_num-running-compactions";
+const LIVE_SST_FILES_SIZE: &str = "aptos_rocksdb_live-sst-files-size";
+
+/// Where a pruner is at.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrunerProgress {
+    /// Versions below this one were pruned
+    pub min_readable_version: u64,
+    /// Versions the pruner keeps behind the latest one
+    pub prune_window: u64,
+}
+
+impl PrunerProgress {
+    /// Versions that should have been pruned at `ledger_version` and weren't yet.
+    pub fn lag(&self, ledger_version: u64) -> u64 {
+        ledger_version
+            .saturating_sub(self.prune_window)
+            .saturating_sub(self.min_readable_version)
+    }
+}
+
+/// The storage of a node at some point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorageStats {
+    pub ledger_version: u64,
+    /// Progress of every pruner, by pruner name, e.g. `ledger_pruner`
+    pub pruners: BTreeMap<String, PrunerProgress>,
+    /// Bytes RocksDB estimates compactions have to rewrite, summed over column families
+    pub pending_compaction_bytes: u64,
+    pub running_compactions: u64,
+    pub live_sst_bytes: u64,
+}
+
+impl StorageStats {
+    pub fn from_metrics(metrics: &Metrics) -> Self {
+        let rocksdb_property = |property: &str| {
+            metrics
+                .get(ROCKSDB_PROPERTIES, &[("property_name", property)])
+                .unwrap_or(0.0) as u64
+        };
+        let mut pruners: BTreeMap<String, PrunerProgress> = BTreeMap::new();
+        for sample in metrics.samples() {
+            let pruner = match sample.labels.get("pruner_name") {
+                Some(pruner) => pruners.entry(pruner.clone()).or_default(),
+                None => continue,
+            };
+            match sample.name.as_str() {
+                PRUNER_MIN_READABLE_VERSION => pruner.min_readable_version = sample.value as u64,
+                PRUNE_WINDOW => pruner.prune_window = sample.value as u64,
+                _ => {}
+            }
+        }
+        Self {
+            ledger_version: metrics.get(LEDGER_VERSION, &[]).unwrap_or(0.0) as u64,
+            pruners,
+            pending_compaction_bytes: rocksdb_property(PENDING_COMPACTION_BYTES),
+            running_compactions: rocksdb_property(RUNNING_COMPACTIONS),
+            live_sst_bytes: rocksdb_property(LIVE_SST_FILES_SIZE),
+        }
+    }
+
+    /// Versions the pruner `name` is behind, 0 if there is no such pruner.
+    pub fn pruner_lag(&self, name: &str) -> u64 {
+        self.pruners
+            .get(name)
+            .map_or(0, |pruner| pruner.lag(self.ledger_version))
+    }
+}
+
+/// How pruning and compaction went on a node over several snapshots.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeStorageSummary {
+    /// Versions committed between the first and last snapshots
+    pub versions_written: u64,
+    /// Versions pruned between the first and last snapshots, by pruner name
+    pub versions_pruned: BTreeMap<String, u64>,
+    /// Largest lag of every pruner, by pruner name
+    pub max_pruner_lag: BTreeMap<String, u64>,
+    /// Lag of every pruner at the last snapshot, by pruner name
+    pub final_pruner_lag: BTreeMap<String, u64>,
+    pub peak_pending_compaction_bytes: u64,
+    pub final_live_sst_bytes: u64,
+}
+
+/// Pruning and compaction of every node over a run, see `StorageSummary::from_snapshots`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorageSummary {
+    /// Summary of every node, by node name
+    pub nodes: BTreeMap<String, NodeStorageSummary>,
+}
+
+impl StorageSummary {
+    /// Summarizes `snapshots`, oldest first. Nodes are summarized over the snapshots they're
+    /// in.
+    pub fn from_snapshots(snapshots: &[MetricsSnapshot]) -> Self {
+        let mut stats: BTreeMap<&str, Vec<StorageStats>> = BTreeMap::new();
+        for snapshot in snapshots {
+            for (node, metrics) in &snapshot.nodes {
+                stats
+                    .entry(node)
+                    .or_default()
+                    .push(StorageStats::from_metrics(metrics));
+            }
+        }
+        let nodes = stats
+            .into_iter()
+            .map(|(node, stats)| (node.to_string(), summarize(&stats)))
+            .collect();
+        Self { nodes }
+    }
+
+    /// Fails if a pruner of some node ended more than `max_lag` versions behind.
+    pub fn check_pruning_keeps_up(&self, max_lag: u64) -> Result<()> {
+        for (node, summary) in &self.nodes {
+            for (pruner, lag) in &summary.final_pruner_lag {
+                if *lag > max_lag {
+                    bail!(
+                        "{} of {} is {} versions behind, at most {} allowed",
+                        pruner,
+                        node,
+                        lag,
+                        max_lag
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the largest lag of every pruner and the peak pending compaction bytes of every
+    /// node to `report`, and the summary to its text.
+    pub fn report(&self, report: &mut TestReport, test_name: &str) {
+        for (node, summary) in &self.nodes {
+            for (pruner, lag) in &summary.max_pruner_lag {
+                report.report_metric(
+                    test_name,
+                    format!("{}_{}_max_lag", node, pruner),
+                    *lag as f64,
+                );
+            }
+            report.report_metric(
+                test_name,
+                format!("{}_peak_pending_compaction_bytes", node),
+                summary.peak_pending_compaction_bytes as f64,
+            );
+        }
+        report.report_text(format!("{}", self));
+    }
+}
+
+impl fmt::Display for StorageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Storage:")?;
+        for (node, summary) in &self.nodes {
+            write!(
+                f,
+                "\n  {}: {} versions written, peak {} MiB pending compaction, {} MiB live SSTs",
+                node,
+                summary.versions_written,
+                summary.peak_pending_compaction_bytes >> 20,
+                summary.final_live_sst_bytes >> 20
+            )?;
+            for (pruner, pruned) in &summary.versions_pruned {
+                write!(
+                    f,
+                    "\n    {}: {} versions pruned, max lag {}, final lag {}",
+                    pruner,
+                    pruned,
+                    summary.max_pruner_lag.get(pruner).unwrap_or(&0),
+                    summary.final_pruner_lag.get(pruner).unwrap_or(&0)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn summarize(stats: &[StorageStats]) -> NodeStorageSummary {
+    let (first, last) = match (stats.first(), stats.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return NodeStorageSummary::default(),
+    };
+    let mut summary = NodeStorageSummary {
+        versions_written: last.ledger_version.saturating_sub(first.ledger_version),
+        final_live_sst_bytes: last.live_sst_bytes,
+        ..NodeStorageSummary::default()
+    };
+    for (name, pruner) in &last.pruners {
+        let first_min_readable = first
+            .pruners
+            .get(name)
+            .map_or(0, |pruner| pruner.min_readable_version);
+        summary.versions_pruned.insert(
+            name.clone(),
+            pruner
+                .min_readable_version
+                .saturating_sub(first_min_readable),
+        );
+        summary
+            .final_pruner_lag
+            .insert(name.clone(), pruner.lag(last.ledger_version));
+    }
+    for stats in stats {
+        for name in stats.pruners.keys() {
+            let max_lag = summary.max_pruner_lag.entry(name.clone()).or_default();
+            *max_lag = (*max_lag).max(stats.pruner_lag(name));
+        }
+        summary.peak_pending_compaction_bytes = summary
+            .peak_pending_compaction_bytes
+            .max(stats.pending_compaction_bytes);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(ledger_version: u64, min_readable_version: u64, pending: u64) -> MetricsSnapshot {
+        let text = format!(
+            "aptos_storage_ledger_version {}\n\
+             aptos_pruner_min_readable_version{{pruner_name=\"ledger_pruner\"}} {}\n\
+             aptos_storage_prune_window{{pruner_name=\"ledger_pruner\"}} 100\n\
+             aptos_rocksdb_properties{{cf_name=\"a\",\
+             property_name=\"aptos_rocksdb_estimate-pending-compaction-bytes\"}} {}\n\
+             aptos_rocksdb_properties{{cf_name=\"b\",\
+             property_name=\"aptos_rocksdb_estimate-pending-compaction-bytes\"}} 1\n",
+            ledger_version, min_readable_version, pending
+        );
+        MetricsSnapshot::new(
+            vec![("0".to_string(), text.parse().unwrap())]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_storage_summary() {
+        let stats = StorageStats::from_metrics(&snapshot(1000, 850, 9).nodes["0"]);
+        assert_eq!(stats.pending_compaction_bytes, 10);
+        assert_eq!(stats.pruner_lag("ledger_pruner"), 50);
+        assert_eq!(stats.pruner_lag("state_merkle_pruner"), 0);
+
+        let summary = StorageSummary::from_snapshots(&[
+            snapshot(1000, 900, 9),
+            snapshot(2000, 1000, 99),
+            snapshot(3000, 2850, 0),
+        ]);
+        let node = &summary.nodes["0"];
+        assert_eq!(node.versions_written, 2000);
+        assert_eq!(node.versions_pruned["ledger_pruner"], 1950);
+        assert_eq!(node.max_pruner_lag["ledger_pruner"], 900);
+        assert_eq!(node.final_pruner_lag["ledger_pruner"], 50);
+        assert_eq!(node.peak_pending_compaction_bytes, 100);
+        assert!(summary.check_pruning_keeps_up(50).is_ok());
+        assert!(summary.check_pruning_keeps_up(49).is_err());
+    }
+}
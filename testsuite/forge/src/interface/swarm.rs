@@ -3,18 +3,35 @@
 
 use crate::interface::system_metrics::SystemMetricsThreshold;
 use crate::{
-    AptosPublicInfo, ChainInfo, FullNode, NodeExt, Result, SwarmChaos, Validator, Version,
+    check_state_consistency, node_sync_status, AptosPublicInfo, ChainInfo, ConsistencyReport,
+    EmitJobRequest, EpochWatcher, FailpointAction, FullNode, LatencyMatrix, MetricType, Metrics,
+    MetricsSnapshot, NodeExt, NodeSyncStatus, ResourceBudget, Result, StallDetector, StorageStats,
+    SwarmChaos, SwarmChaosKind, TestReport, TxnEmitter, TxnStats, UnsupportedChaos, Validator,
+    Version,
 };
-use anyhow::{anyhow, bail};
+use anyhow::{anyhow, bail, ensure};
 use aptos_config::config::NodeConfig;
 use aptos_logger::info;
 use aptos_rest_client::Client as RestClient;
-use aptos_sdk::types::PeerId;
-use futures::future::try_join_all;
+use aptos_sdk::{
+    transaction_builder::TransactionFactory,
+    types::{account_address::AccountAddress, PeerId},
+};
+use futures::future::{join_all, try_join_all};
 use prometheus_http_query::response::PromqlResult;
-use std::time::{Duration, Instant};
+use rand::{rngs::OsRng, SeedableRng};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
 use tokio::runtime::Runtime;
 
+const CONSENSUS_CURRENT_ROUND: &str = "aptos_consensus_current_round";
+const CONSENSUS_TIMEOUT_COUNT: &str = "aptos_consensus_timeout_count";
+const CONSENSUS_PROPOSALS_COUNT: &str = "aptos_consensus_proposals_count";
+/// Clients of nodes under load get a longer timeout than the default one
+const EMIT_CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Trait used to represent a running network comprised of Validators and FullNodes
 #[async_trait::async_trait]
 pub trait Swarm: Sync {
@@ -49,24 +66,26 @@ pub trait Swarm: Sync {
     /// Returns a mutable reference to the FullNode with the provided PeerId
     fn full_node_mut(&mut self, id: PeerId) -> Option<&mut dyn FullNode>;
 
-    /// Adds a Validator to the swarm and returns the PeerId
-    fn add_validator(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId>;
+    /// Adds a Validator to the swarm and returns the PeerId once it's healthy
+    async fn add_validator(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId>;
 
     /// Removes the Validator with the provided PeerId
-    fn remove_validator(&mut self, id: PeerId) -> Result<()>;
+    async fn remove_validator(&mut self, id: PeerId) -> Result<()>;
 
-    fn add_validator_full_node(
+    /// Adds a FullNode to the Validator with the provided PeerId and returns the PeerId once
+    /// it's healthy
+    async fn add_validator_full_node(
         &mut self,
         version: &Version,
         template: NodeConfig,
         id: PeerId,
     ) -> Result<PeerId>;
 
-    /// Adds a FullNode to the swarm and returns the PeerId
-    fn add_full_node(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId>;
+    /// Adds a FullNode to the swarm and returns the PeerId once it's healthy
+    async fn add_full_node(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId>;
 
     /// Removes the FullNode with the provided PeerId
-    fn remove_full_node(&mut self, id: PeerId) -> Result<()>;
+    async fn remove_full_node(&mut self, id: PeerId) -> Result<()>;
 
     /// Return a list of supported Versions
     fn versions<'a>(&'a self) -> Box<dyn Iterator<Item = Version> + 'a>;
@@ -76,10 +95,16 @@ pub trait Swarm: Sync {
 
     fn logs_location(&mut self) -> String;
 
-    /// Injects all types of chaos
+    /// Injects all types of chaos, failing with `UnsupportedChaos` for the kinds missing from
+    /// `supported_chaos`
     fn inject_chaos(&mut self, chaos: SwarmChaos) -> Result<()>;
     fn remove_chaos(&mut self, chaos: SwarmChaos) -> Result<()>;
 
+    /// The kinds of chaos this swarm can inject.
+    fn supported_chaos(&self) -> Vec<SwarmChaosKind> {
+        Vec::new()
+    }
+
     async fn ensure_no_validator_restart(&self) -> Result<()>;
     async fn ensure_no_fullnode_restart(&self) -> Result<()>;
 
@@ -98,15 +123,91 @@ pub trait Swarm: Sync {
         timeout: Option<i64>,
     ) -> Result<PromqlResult>;
 
+    // Get prometheus metrics from the swarm, evaluated every `step` seconds from `start` to
+    // `end` (unix timestamps in seconds)
+    async fn query_range_metrics(
+        &self,
+        query: &str,
+        start: i64,
+        end: i64,
+        step: f64,
+    ) -> Result<PromqlResult>;
+
+    /// Scrapes the metrics of every node. Nodes that can't be scraped are gaps in the
+    /// snapshot, see `MetricsSnapshot::failures`.
+    async fn metrics_snapshot(&self) -> Result<MetricsSnapshot> {
+        Ok(scrape_metrics_snapshot(self).await)
+    }
+
+    /// Where the node `id` is in syncing the chain, its target being the highest version
+    /// synced by any node of the swarm
+    async fn node_sync_status(&self, id: PeerId) -> Result<NodeSyncStatus> {
+        let clients = self
+            .validators()
+            .map(|node| (node.name().to_string(), node.rest_client()))
+            .chain(
+                self.full_nodes()
+                    .map(|node| (node.name().to_string(), node.rest_client())),
+            )
+            .collect::<Vec<_>>();
+        let target_version = get_highest_synced_version(&clients).await?;
+        if let Some(validator) = self.validator(id) {
+            node_sync_status(validator, target_version).await
+        } else if let Some(full_node) = self.full_node(id) {
+            node_sync_status(full_node, target_version).await
+        } else {
+            bail!("No node {} in the swarm", id)
+        }
+    }
+
     fn aptos_public_info(&mut self) -> AptosPublicInfo<'_> {
         self.chain_info().into_aptos_public_info()
     }
+
+    /// Adds what the backend knows about how the run went, e.g. node restarts, to the final
+    /// report
+    fn report(&self, _report: &mut TestReport) {}
+
+    /// Scrapes the metrics of every node one last time into the artifacts of the run, for
+    /// backends that collect artifacts, so that post-mortems have the final counters even
+    /// without a Prometheus. Called at teardown.
+    async fn dump_final_metrics(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Saves the artifacts collected so far under `label`, e.g. the current metrics, so that
+    /// long runs keep them even if they fail much later. A noop for backends that don't collect
+    /// artifacts.
+    async fn checkpoint_artifacts(&self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Enforces `budget` on the resources the nodes use from now on, replacing the previous
+    /// one, or stops enforcing any if None.
+    fn set_resource_budget(&mut self, _budget: Option<ResourceBudget>) -> Result<()> {
+        bail!("Resource budgets are not supported by this backend")
+    }
+
+    /// Fails if the budget set with `set_resource_budget` was exceeded.
+    fn check_resource_budget(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl<T: ?Sized> SwarmExt for T where T: Swarm {}
 
 #[async_trait::async_trait]
 pub trait SwarmExt: Swarm {
+    /// Fails with `UnsupportedChaos` if `chaos` can't be injected into this swarm, e.g. for
+    /// tests to check before starting any load.
+    fn check_chaos_supported(&self, chaos: &SwarmChaos) -> Result<()> {
+        let kind = chaos.kind();
+        if !self.supported_chaos().contains(&kind) {
+            return Err(UnsupportedChaos(kind).into());
+        }
+        Ok(())
+    }
+
     async fn liveness_check(&self, deadline: Instant) -> Result<()> {
         let liveness_check_seconds = 10;
         let validators = self.validators().collect::<Vec<_>>();
@@ -248,6 +349,147 @@ pub trait SwarmExt: Swarm {
         wait_for_all_nodes_to_catchup(&self.get_clients_with_names(), timeout).await
     }
 
+    /// Waits for all nodes to catch up, then checks that they agree on the root hashes and on
+    /// the resources of `accounts`, see `check_state_consistency`.
+    async fn check_state_consistency(
+        &self,
+        accounts: &[AccountAddress],
+        timeout: Duration,
+    ) -> Result<ConsistencyReport> {
+        check_state_consistency(&self.get_clients_with_names(), accounts, timeout).await
+    }
+
+    /// Measures the round-trip time between every pair of nodes connected on the validator or
+    /// VFN network, from the pings they send each other over `window`. The window should span a few ping intervals, 10s
+    /// by default, for every pair to be measured.
+    async fn measure_latency_matrix(&self, window: Duration) -> Result<LatencyMatrix> {
+        let nodes = self
+            .validators()
+            .map(|node| (node.name().to_string(), node.peer_id()))
+            .chain(
+                self.full_nodes()
+                    .map(|node| (node.name().to_string(), node.peer_id())),
+            )
+            .collect::<Vec<_>>();
+        let earlier = self.metrics_snapshot().await?;
+        tokio::time::sleep(window).await;
+        let later = self.metrics_snapshot().await?;
+        Ok(LatencyMatrix::from_snapshots(&earlier, &later, &nodes))
+    }
+
+    /// Storage pruning and compaction progress of every node, by node name. Snapshots taken
+    /// through a run are summarized by `StorageSummary::from_snapshots`.
+    async fn storage_stats(&self) -> Result<BTreeMap<String, StorageStats>> {
+        let snapshot = self.metrics_snapshot().await?;
+        Ok(snapshot
+            .nodes
+            .iter()
+            .map(|(node, metrics)| (node.clone(), StorageStats::from_metrics(metrics)))
+            .collect())
+    }
+
+    /// Starts recording the epoch changes of the swarm, polling the first validator every
+    /// `interval`.
+    fn watch_epochs(&self, interval: Duration) -> Result<EpochWatcher> {
+        let validator = self
+            .validators()
+            .next()
+            .ok_or_else(|| anyhow!("No validator to watch epochs from"))?;
+        Ok(EpochWatcher::start(validator.rest_client(), interval))
+    }
+
+    /// Starts detecting block production stalls longer than `max_gap`, polling the validators
+    /// every `interval`.
+    fn detect_stalls(&self, max_gap: Duration, interval: Duration) -> StallDetector {
+        let clients = self
+            .validators()
+            .map(|validator| (validator.name().to_string(), validator.rest_client()))
+            .collect();
+        StallDetector::start(clients, max_gap, interval)
+    }
+
+    /// Waits for the node `id` to catch up to the rest of the swarm, returning how long it took.
+    async fn wait_for_node_to_sync(&self, id: PeerId, timeout: Duration) -> Result<Duration> {
+        let start_time = Instant::now();
+        loop {
+            let status = self.node_sync_status(id).await;
+            match &status {
+                Ok(status) if status.is_caught_up() => return Ok(start_time.elapsed()),
+                _ if start_time.elapsed() > timeout => {
+                    bail!(
+                        "Waiting for node {} to sync timed out, current status: {}",
+                        id,
+                        status
+                            .as_ref()
+                            .map_or_else(ToString::to_string, ToString::to_string)
+                    );
+                }
+                _ => tokio::time::sleep(Duration::from_millis(500)).await,
+            }
+        }
+    }
+
+    /// Sets the failpoint `name` on every validator
+    async fn set_validators_failpoint(&self, name: &str, action: FailpointAction) -> Result<()> {
+        try_join_all(
+            self.validators()
+                .map(|node| node.set_failpoint(name, action.clone())),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Turns the failpoint `name` off on every validator
+    async fn clear_validators_failpoint(&self, name: &str) -> Result<()> {
+        self.set_validators_failpoint(name, FailpointAction::Off)
+            .await
+    }
+
+    /// Scrapes the metrics of every validator, to compute the consensus helpers below from.
+    /// Validators that can't be scraped are gaps in the snapshot.
+    async fn validators_metrics_snapshot(&self) -> Result<MetricsSnapshot> {
+        let validators: Vec<_> = self.validators().collect();
+        let scrapes = join_all(validators.iter().map(|v| scrape(*v))).await;
+        Ok(MetricsSnapshot::from_scrapes(scrapes))
+    }
+
+    /// Highest consensus round any validator is in.
+    async fn current_round(&self) -> Result<u64> {
+        let snapshot = self.validators_metrics_snapshot().await?;
+        let mut current_round = None;
+        for (name, metrics) in &snapshot.nodes {
+            let round = metrics
+                .get_typed(CONSENSUS_CURRENT_ROUND, &[], MetricType::Gauge)?
+                .ok_or_else(|| anyhow!("{} has no {}", name, CONSENSUS_CURRENT_ROUND))?;
+            current_round = current_round.max(Some(round as u64));
+        }
+        current_round.ok_or_else(|| anyhow!("No validator in the swarm"))
+    }
+
+    /// Round timeouts the validators went through since the `since` snapshot, summed over
+    /// validators. Validators missing from `since` are left out.
+    async fn timeout_count_delta(&self, since: &MetricsSnapshot) -> Result<u64> {
+        let snapshot = self.validators_metrics_snapshot().await?;
+        Ok(snapshot
+            .counter_deltas(since, CONSENSUS_TIMEOUT_COUNT, &[])
+            .values()
+            .sum::<f64>() as u64)
+    }
+
+    /// Blocks every validator proposed since the `since` snapshot, by validator name.
+    /// Validators missing from `since` are left out.
+    async fn proposer_distribution(
+        &self,
+        since: &MetricsSnapshot,
+    ) -> Result<BTreeMap<String, u64>> {
+        let snapshot = self.validators_metrics_snapshot().await?;
+        Ok(snapshot
+            .counter_deltas(since, CONSENSUS_PROPOSALS_COUNT, &[])
+            .into_iter()
+            .map(|(name, proposals)| (name, proposals as u64))
+            .collect())
+    }
+
     fn get_clients_with_names(&self) -> Vec<(String, RestClient)> {
         self.validators()
             .map(|node| (node.name().to_string(), node.rest_client()))
@@ -259,6 +501,48 @@ pub trait SwarmExt: Swarm {
     }
 }
 
+/// Runs `request` against the validators and full nodes of `swarm` in `nodes` for `duration`,
+/// funding the emitter's accounts from the swarm's root account.
+pub async fn emit_transactions_with_swarm(
+    swarm: &mut dyn Swarm,
+    nodes: &[PeerId],
+    request: EmitJobRequest,
+    duration: Duration,
+) -> Result<TxnStats> {
+    let clients = emit_clients(swarm, nodes)?;
+    let chain_info = swarm.chain_info();
+    emitter_for(&chain_info)?
+        .emit_txn_for(
+            chain_info.root_account,
+            request.rest_clients(clients),
+            duration,
+        )
+        .await
+}
+
+/// Clients of the full nodes and validators of `swarm` in `nodes`, to emit transactions to.
+pub(crate) fn emit_clients(swarm: &dyn Swarm, nodes: &[PeerId]) -> Result<Vec<RestClient>> {
+    let fullnode_clients = swarm
+        .full_nodes()
+        .filter(|n| nodes.contains(&n.peer_id()))
+        .map(|n| n.rest_client_with_timeout(EMIT_CLIENT_TIMEOUT));
+    let validator_clients = swarm
+        .validators()
+        .filter(|v| nodes.contains(&v.peer_id()))
+        .map(|v| v.rest_client_with_timeout(EMIT_CLIENT_TIMEOUT));
+    let clients: Vec<_> = fullnode_clients.chain(validator_clients).collect();
+    ensure!(!clients.is_empty(), "None of {:?} are in the swarm", nodes);
+    Ok(clients)
+}
+
+pub(crate) fn emitter_for(chain_info: &ChainInfo<'_>) -> Result<TxnEmitter> {
+    let transaction_factory = TransactionFactory::new(chain_info.chain_id).with_gas_unit_price(1);
+    Ok(TxnEmitter::new(
+        transaction_factory,
+        SeedableRng::from_rng(OsRng)?,
+    ))
+}
+
 /// Waits for all nodes to have caught up to the specified `verison`.
 pub async fn wait_for_all_nodes_to_catchup_to_version(
     clients: &[(String, RestClient)],
@@ -329,3 +613,17 @@ pub async fn get_highest_synced_version(clients: &[(String, RestClient)]) -> Res
     }
     Ok(latest_version)
 }
+
+/// Scrapes the metrics of every node of `swarm`, leaving the ones that can't be scraped as
+/// gaps, see `Swarm::metrics_snapshot`.
+pub async fn scrape_metrics_snapshot<S: Swarm + ?Sized>(swarm: &S) -> MetricsSnapshot {
+    let validators: Vec<_> = swarm.validators().collect();
+    let full_nodes: Vec<_> = swarm.full_nodes().collect();
+    let validator_scrapes = join_all(validators.iter().map(|v| scrape(*v))).await;
+    let full_node_scrapes = join_all(full_nodes.iter().map(|n| scrape(*n))).await;
+    MetricsSnapshot::from_scrapes(validator_scrapes.into_iter().chain(full_node_scrapes))
+}
+
+async fn scrape<N: NodeExt + ?Sized>(node: &N) -> (String, Result<Metrics>) {
+    (node.name().to_string(), node.metrics().await)
+}
@@ -14,6 +14,11 @@ use std::time::Duration;
 pub struct SystemMetrics {
     cpu_core_metrics: Vec<Sample>,
     memory_bytes_metrics: Vec<Sample>,
+    // Bytes per second
+    disk_read_bytes_metrics: Vec<Sample>,
+    disk_write_bytes_metrics: Vec<Sample>,
+    network_received_bytes_metrics: Vec<Sample>,
+    network_sent_bytes_metrics: Vec<Sample>,
 }
 
 // This retry policy is used for important client calls necessary for setting
@@ -32,8 +37,29 @@ impl SystemMetrics {
         Self {
             cpu_core_metrics: cpu_metrics,
             memory_bytes_metrics: memory_metrics,
+            ..Self::default()
         }
     }
+
+    pub fn with_disk_metrics(
+        mut self,
+        read_bytes_metrics: Vec<Sample>,
+        write_bytes_metrics: Vec<Sample>,
+    ) -> Self {
+        self.disk_read_bytes_metrics = read_bytes_metrics;
+        self.disk_write_bytes_metrics = write_bytes_metrics;
+        self
+    }
+
+    pub fn with_network_metrics(
+        mut self,
+        received_bytes_metrics: Vec<Sample>,
+        sent_bytes_metrics: Vec<Sample>,
+    ) -> Self {
+        self.network_received_bytes_metrics = received_bytes_metrics;
+        self.network_sent_bytes_metrics = sent_bytes_metrics;
+        self
+    }
 }
 
 #[derive(Default, Clone, Debug)]
@@ -56,28 +82,96 @@ impl MetricsThreshold {
 pub struct SystemMetricsThreshold {
     cpu_threshold: MetricsThreshold,
     memory_threshold: MetricsThreshold,
+    // Bytes per second, only checked if set
+    disk_read_threshold: Option<MetricsThreshold>,
+    disk_write_threshold: Option<MetricsThreshold>,
+    network_received_threshold: Option<MetricsThreshold>,
+    network_sent_threshold: Option<MetricsThreshold>,
 }
 
 impl SystemMetricsThreshold {
     pub fn ensure_threshold(&self, metrics: &SystemMetrics) -> anyhow::Result<()> {
-        ensure_metrics_threshold(&self.cpu_threshold, &metrics.cpu_core_metrics)?;
-        ensure_metrics_threshold(&self.memory_threshold, &metrics.memory_bytes_metrics)?;
+        ensure_metrics_threshold("cpu", &self.cpu_threshold, &metrics.cpu_core_metrics)?;
+        ensure_metrics_threshold(
+            "memory",
+            &self.memory_threshold,
+            &metrics.memory_bytes_metrics,
+        )?;
+        let optional_thresholds = [
+            (
+                "disk read",
+                &self.disk_read_threshold,
+                &metrics.disk_read_bytes_metrics,
+            ),
+            (
+                "disk write",
+                &self.disk_write_threshold,
+                &metrics.disk_write_bytes_metrics,
+            ),
+            (
+                "network received",
+                &self.network_received_threshold,
+                &metrics.network_received_bytes_metrics,
+            ),
+            (
+                "network sent",
+                &self.network_sent_threshold,
+                &metrics.network_sent_bytes_metrics,
+            ),
+        ];
+        for (name, threshold, metrics) in optional_thresholds {
+            if let Some(threshold) = threshold {
+                ensure_metrics_threshold(name, threshold, metrics)?;
+            }
+        }
         Ok(())
     }
     pub fn new(cpu_threshold: MetricsThreshold, memory_threshold: MetricsThreshold) -> Self {
         Self {
             cpu_threshold,
             memory_threshold,
+            ..Self::default()
         }
     }
+
+    /// Also checks the disk read and write throughput, in bytes per second.
+    pub fn with_disk_thresholds(
+        mut self,
+        read_threshold: MetricsThreshold,
+        write_threshold: MetricsThreshold,
+    ) -> Self {
+        self.disk_read_threshold = Some(read_threshold);
+        self.disk_write_threshold = Some(write_threshold);
+        self
+    }
+
+    /// Also checks the network receive and send throughput, in bytes per second.
+    pub fn with_network_thresholds(
+        mut self,
+        received_threshold: MetricsThreshold,
+        sent_threshold: MetricsThreshold,
+    ) -> Self {
+        self.network_received_threshold = Some(received_threshold);
+        self.network_sent_threshold = Some(sent_threshold);
+        self
+    }
+
+    pub fn checks_disk(&self) -> bool {
+        self.disk_read_threshold.is_some() || self.disk_write_threshold.is_some()
+    }
+
+    pub fn checks_network(&self) -> bool {
+        self.network_received_threshold.is_some() || self.network_sent_threshold.is_some()
+    }
 }
 
 fn ensure_metrics_threshold(
+    name: &str,
     threshold: &MetricsThreshold,
     metrics: &Vec<Sample>,
 ) -> anyhow::Result<()> {
     if metrics.is_empty() {
-        bail!("Empty metrics provided");
+        bail!("Empty {} metrics provided", name);
     }
     let breach_count = metrics
         .iter()
@@ -86,7 +180,9 @@ fn ensure_metrics_threshold(
     let breach_pct = (breach_count * 100) / metrics.len();
     if breach_pct > threshold.max_breach_pct {
         bail!(
-            "Metrics violated threshold, max_breach_pct {:?}, breach_pct{:?} ",
+            "{} metrics violated threshold, max {:?}, max_breach_pct {:?}, breach_pct{:?} ",
+            name,
+            threshold.max,
             threshold.max_breach_pct,
             breach_pct
         );
@@ -178,6 +274,75 @@ pub async fn query_prometheus_system_metrics(
     Ok(SystemMetrics::new(cpu_samples, memory_samples))
 }
 
+/// Disk read and write throughput of the validators, in bytes per second.
+pub async fn query_prometheus_disk_metrics(
+    client: &PrometheusClient,
+    start_time: i64,
+    end_time: i64,
+    internal_secs: f64,
+    namespace: &str,
+) -> anyhow::Result<(Vec<Sample>, Vec<Sample>)> {
+    let read_query = r#"avg(rate(container_fs_reads_bytes_total{container=~"validator"}[30s]))"#;
+    let write_query = r#"avg(rate(container_fs_writes_bytes_total{container=~"validator"}[30s]))"#;
+
+    let read_samples = query_prometheus_range_metrics(
+        read_query,
+        client,
+        start_time,
+        end_time,
+        internal_secs,
+        namespace,
+    )
+    .await?;
+
+    let write_samples = query_prometheus_range_metrics(
+        write_query,
+        client,
+        start_time,
+        end_time,
+        internal_secs,
+        namespace,
+    )
+    .await?;
+
+    Ok((read_samples, write_samples))
+}
+
+/// Network receive and send throughput of the validator pods, in bytes per second. Network
+/// metrics are only reported per pod, not per container.
+pub async fn query_prometheus_network_metrics(
+    client: &PrometheusClient,
+    start_time: i64,
+    end_time: i64,
+    internal_secs: f64,
+    namespace: &str,
+) -> anyhow::Result<(Vec<Sample>, Vec<Sample>)> {
+    let received_query = r#"avg(sum by (pod) (rate(container_network_receive_bytes_total{pod=~".*validator.*"}[30s])))"#;
+    let sent_query = r#"avg(sum by (pod) (rate(container_network_transmit_bytes_total{pod=~".*validator.*"}[30s])))"#;
+
+    let received_samples = query_prometheus_range_metrics(
+        received_query,
+        client,
+        start_time,
+        end_time,
+        internal_secs,
+        namespace,
+    )
+    .await?;
+
+    let sent_samples = query_prometheus_range_metrics(
+        sent_query,
+        client,
+        start_time,
+        end_time,
+        internal_secs,
+        namespace,
+    )
+    .await?;
+
+    Ok((received_samples, sent_samples))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -190,4 +355,32 @@ mod tests {
         let metrics = SystemMetrics::new(vec![], vec![]);
         threshold.ensure_threshold(&metrics).unwrap_err();
     }
+
+    #[test]
+    fn test_disk_threshold() {
+        let sample = |value: &str| serde_json::from_str::<Sample>(&format!("[0, \"{}\"]", value));
+        let samples = |values: &[&str]| {
+            values
+                .iter()
+                .map(|value| sample(value).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let threshold = SystemMetricsThreshold::new(
+            MetricsThreshold::new(10, 30),
+            MetricsThreshold::new(100, 40),
+        );
+        let metrics = SystemMetrics::new(samples(&["1", "2"]), samples(&["10", "20"]));
+        // Disk metrics aren't checked unless a disk threshold is set.
+        threshold.ensure_threshold(&metrics).unwrap();
+
+        let threshold = threshold.with_disk_thresholds(
+            MetricsThreshold::new(1000, 0),
+            MetricsThreshold::new(1000, 0),
+        );
+        threshold.ensure_threshold(&metrics).unwrap_err();
+        let metrics = metrics.with_disk_metrics(samples(&["10", "20"]), samples(&["10", "2000"]));
+        threshold.ensure_threshold(&metrics).unwrap_err();
+        let metrics = metrics.with_disk_metrics(samples(&["10", "20"]), samples(&["10", "20"]));
+        threshold.ensure_threshold(&metrics).unwrap();
+    }
 }
@@ -1,6 +1,7 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
+use anyhow::{bail, Result};
 use rand::SeedableRng;
 
 /// Whether a test is expected to fail or not
@@ -11,6 +12,37 @@ pub enum ShouldFail {
     YesWithMessage(&'static str),
 }
 
+/// Most resources the nodes of the swarm may use while a test runs, summed over nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResourceBudget {
+    pub max_cpu_seconds: Option<f64>,
+    pub max_rss_bytes: Option<u64>,
+}
+
+impl ResourceBudget {
+    pub fn check(&self, cpu_seconds: f64, rss_bytes: u64) -> Result<()> {
+        if let Some(max_cpu_seconds) = self.max_cpu_seconds {
+            if cpu_seconds > max_cpu_seconds {
+                bail!(
+                    "Resource budget exceeded: nodes used {:.0} CPU seconds, at most {:.0} allowed",
+                    cpu_seconds,
+                    max_cpu_seconds
+                );
+            }
+        }
+        if let Some(max_rss_bytes) = self.max_rss_bytes {
+            if rss_bytes > max_rss_bytes {
+                bail!(
+                    "Resource budget exceeded: nodes used {} MiB of memory, at most {} MiB allowed",
+                    rss_bytes >> 20,
+                    max_rss_bytes >> 20
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Represents a Test in Forge
 ///
 /// This is meant to be a super trait of the other test interfaces.
@@ -27,6 +59,12 @@ pub trait Test: Send + Sync {
     fn should_fail(&self) -> ShouldFail {
         ShouldFail::No
     }
+
+    /// Resources the test may use, enforced on network tests by backends that sample the
+    /// resources of their nodes
+    fn resource_budget(&self) -> Option<ResourceBudget> {
+        None
+    }
 }
 
 impl<T: Test + ?Sized> Test for &T {
@@ -41,6 +79,10 @@ impl<T: Test + ?Sized> Test for &T {
     fn should_fail(&self) -> ShouldFail {
         (**self).should_fail()
     }
+
+    fn resource_budget(&self) -> Option<ResourceBudget> {
+        (**self).resource_budget()
+    }
 }
 
 #[derive(Debug)]
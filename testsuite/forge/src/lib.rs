@@ -25,6 +25,18 @@ pub use github::*;
 mod slack;
 pub use slack::*;
 
+mod trace;
+pub use trace::*;
+
+mod timeline;
+pub use timeline::*;
+
+mod alert;
+pub use alert::*;
+
 pub mod success_criteria;
 
+mod scenario;
+pub use scenario::*;
+
 pub mod test_utils;
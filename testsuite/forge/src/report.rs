@@ -1,6 +1,7 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::{take_timeline, TimelineEvent};
 use serde::Serialize;
 use std::{fmt, time::Duration};
 use transaction_emitter_lib::emitter::stats::TxnStats;
@@ -9,6 +10,8 @@ use transaction_emitter_lib::emitter::stats::TxnStats;
 pub struct TestReport {
     metrics: Vec<ReportedMetric>,
     text: String,
+    /// Significant events of the run, see `record_event`
+    timeline: Vec<TimelineEvent>,
 }
 
 #[derive(Debug, Serialize)]
@@ -64,6 +67,15 @@ impl TestReport {
         ));
     }
 
+    /// Moves the events recorded so far into the report.
+    pub fn collect_timeline(&mut self) {
+        self.timeline.extend(take_timeline());
+    }
+
+    pub fn timeline(&self) -> &[TimelineEvent] {
+        &self.timeline
+    }
+
     pub fn print_report(&self) {
         println!("Test Statistics: ");
         println!("{}", self);
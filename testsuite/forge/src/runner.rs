@@ -14,6 +14,7 @@ use std::{
 use structopt::{clap::arg_enum, StructOpt};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tokio::runtime::Runtime;
+use url::Url;
 // TODO going to remove random seed once cluster deployment supports re-run genesis
 use crate::success_criteria::SuccessCriteria;
 use framework::ReleaseBundle;
@@ -67,6 +68,19 @@ pub struct Options {
     /// NO-OP: unsupported option, exists for compatibility with the default test harness
     /// Show captured stdout of successful tests
     show_output: bool,
+    #[structopt(long)]
+    /// Report, per node, the counters that moved during each network test
+    report_metrics_diff: bool,
+    #[structopt(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    /// Export spans of the steps of the run to this OTLP/HTTP collector, e.g.
+    /// http://localhost:4318
+    otlp_endpoint: Option<Url>,
+    #[structopt(long, env = "FORGE_ALERT_WEBHOOK")]
+    /// Post to this Slack compatible webhook when a test breaches its success criteria mid-run
+    alert_webhook: Option<Url>,
+    #[structopt(long)]
+    /// Fail the tests needing chaos the swarm can't inject, instead of skipping them
+    fail_on_unsupported_chaos: bool,
 }
 
 impl Options {
@@ -302,11 +316,35 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
     }
 
     pub fn run(&self) -> Result<TestReport> {
+        if let Some(endpoint) = &self.options.otlp_endpoint {
+            if let Err(e) = init_tracing(endpoint, "forge") {
+                println!("Failed to initialize tracing: {:#}", e);
+            }
+        }
+        if let Some(url) = &self.options.alert_webhook {
+            if let Err(e) = init_alert_webhook(url.clone()) {
+                println!("Failed to register the alert webhook: {:#}", e);
+            }
+        }
+        let span = trace_span("forge run");
+        let result = self.run_tests();
+        drop(span);
+        if let Err(e) = flush_traces() {
+            println!("Failed to export traces: {:#}", e);
+        }
+        result
+    }
+
+    fn run_tests(&self) -> Result<TestReport> {
         let test_count = self.filter_tests(self.tests.all_tests()).count();
         let filtered_out = test_count.saturating_sub(self.tests.all_tests().count());
 
         let mut report = TestReport::new();
-        let mut summary = TestSummary::new(test_count, filtered_out);
+        let mut summary = TestSummary::new(
+            test_count,
+            filtered_out,
+            self.options.fail_on_unsupported_chaos,
+        );
         summary.write_starting_msg()?;
 
         if test_count > 0 {
@@ -322,6 +360,7 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
             let genesis_version = initial_version.clone();
             let runtime = Runtime::new().unwrap();
             let mut rng = ::rand::rngs::StdRng::from_seed(OsRng.gen());
+            let launch_span = trace_span("launch swarm");
             let mut swarm = runtime.block_on(self.factory.launch_swarm(
                 &mut rng,
                 self.tests.initial_validator_count,
@@ -333,6 +372,7 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
                 self.tests.genesis_helm_config_fn.clone(),
                 self.tests.node_helm_config_fn.clone(),
             ))?;
+            drop(launch_span);
 
             // Run AptosTests
             for test in self.filter_tests(self.tests.aptos_tests.iter()) {
@@ -341,7 +381,8 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
                     swarm.chain_info().into_aptos_public_info(),
                     &mut report,
                 );
-                let result = run_test(|| runtime.block_on(test.run(&mut aptos_ctx)));
+                let result =
+                    run_traced_test(test.name(), || runtime.block_on(test.run(&mut aptos_ctx)));
                 report.report_text(result.to_string());
                 summary.handle_result(test.name().to_owned(), result)?;
             }
@@ -353,12 +394,17 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
                     swarm.chain_info(),
                     &mut report,
                 );
-                let result = run_test(|| test.run(&mut admin_ctx));
+                let result = run_traced_test(test.name(), || test.run(&mut admin_ctx));
                 report.report_text(result.to_string());
                 summary.handle_result(test.name().to_owned(), result)?;
             }
 
             for test in self.filter_tests(self.tests.network_tests.iter()) {
+                let metrics_before = if self.options.report_metrics_diff {
+                    self.metrics_snapshot(&runtime, &*swarm)
+                } else {
+                    None
+                };
                 let mut network_ctx = NetworkContext::new(
                     CoreContext::from_rng(&mut rng),
                     &mut *swarm,
@@ -367,11 +413,36 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
                     self.tests.emit_job_request.clone(),
                     self.tests.success_criteria.clone(),
                 );
-                let result = run_test(|| test.run(&mut network_ctx));
+                let budget = test.resource_budget();
+                if let Err(e) = network_ctx.swarm().set_resource_budget(budget) {
+                    if budget.is_some() {
+                        println!("Failed to enforce the resource budget: {:#}", e);
+                    }
+                }
+                let result = run_traced_test(test.name(), || {
+                    let result = test.run(&mut network_ctx);
+                    // Exceeding the budget is what made the test fail if it did.
+                    network_ctx.swarm().check_resource_budget().and(result)
+                });
+                if budget.is_some() {
+                    network_ctx.swarm().set_resource_budget(None)?;
+                }
                 report.report_text(result.to_string());
+                if let Some(before) = metrics_before {
+                    if let Some(after) = self.metrics_snapshot(&runtime, &*swarm) {
+                        after
+                            .diff(&before)
+                            .report(&mut report, &format!("{} metrics diff", test.name()));
+                    }
+                }
                 summary.handle_result(test.name().to_owned(), result)?;
             }
 
+            if let Err(e) = runtime.block_on(swarm.dump_final_metrics()) {
+                println!("Failed to dump the final metrics: {:#}", e);
+            }
+            swarm.report(&mut report);
+            report.collect_timeline();
             report.print_report();
 
             io::stdout().flush()?;
@@ -391,6 +462,18 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
         }
     }
 
+    /// Snapshots the metrics of `swarm`, only warning on failure as the snapshot is just
+    /// informative.
+    fn metrics_snapshot(&self, runtime: &Runtime, swarm: &dyn Swarm) -> Option<MetricsSnapshot> {
+        match runtime.block_on(swarm.metrics_snapshot()) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                println!("Failed to snapshot the swarm metrics: {:#}", e);
+                None
+            }
+        }
+    }
+
     fn filter_tests<'a, T: Test, I: Iterator<Item = T> + 'a>(
         &'a self,
         tests: I,
@@ -422,6 +505,8 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
 enum TestResult {
     Ok,
     FailedWithMsg(String),
+    /// The test needs chaos the swarm can't inject
+    Skipped(String),
 }
 
 impl Display for TestResult {
@@ -429,6 +514,7 @@ impl Display for TestResult {
         match self {
             TestResult::Ok => write!(f, "Test Ok"),
             TestResult::FailedWithMsg(msg) => write!(f, "Test Failed: {}", msg),
+            TestResult::Skipped(msg) => write!(f, "Test Skipped: {}", msg),
         }
     }
 }
@@ -436,6 +522,9 @@ impl Display for TestResult {
 fn run_test<F: FnOnce() -> Result<()>>(f: F) -> TestResult {
     match f() {
         Ok(()) => TestResult::Ok,
+        Err(e) if e.downcast_ref::<UnsupportedChaos>().is_some() => {
+            TestResult::Skipped(format!("{:#}", e))
+        }
         Err(e) => {
             let is_triggerd_by_github_actions =
                 std::env::var("FORGE_TRIGGERED_BY").unwrap_or_default() == "github-actions";
@@ -448,22 +537,44 @@ fn run_test<F: FnOnce() -> Result<()>>(f: F) -> TestResult {
     }
 }
 
+/// Runs the test `name` in a span of its own, and records when it started and finished on the
+/// timeline.
+fn run_traced_test<F: FnOnce() -> Result<()>>(name: &str, f: F) -> TestResult {
+    let mut span = trace_span("test").with_attribute("test.name", name);
+    record_event(TimelineEventKind::TestStarted {
+        test: name.to_string(),
+    });
+    let result = run_test(f);
+    if let TestResult::FailedWithMsg(message) = &result {
+        span.set_error(message);
+    }
+    record_event(TimelineEventKind::TestFinished {
+        test: name.to_string(),
+        success: matches!(result, TestResult::Ok),
+    });
+    result
+}
+
 struct TestSummary {
     stdout: StandardStream,
     total: usize,
     filtered_out: usize,
     passed: usize,
     failed: Vec<String>,
+    skipped: usize,
+    fail_skipped: bool,
 }
 
 impl TestSummary {
-    fn new(total: usize, filtered_out: usize) -> Self {
+    fn new(total: usize, filtered_out: usize, fail_skipped: bool) -> Self {
         Self {
             stdout: StandardStream::stdout(ColorChoice::Auto),
             total,
             filtered_out,
             passed: 0,
             failed: Vec::new(),
+            skipped: 0,
+            fail_skipped,
         }
     }
 
@@ -481,6 +592,21 @@ impl TestSummary {
 
                 write!(self.stdout, "Error: {}", msg)?;
             }
+            TestResult::Skipped(msg) if self.fail_skipped => {
+                self.failed.push(name);
+                self.write_failed()?;
+                writeln!(self.stdout)?;
+
+                write!(self.stdout, "Error: {}", msg)?;
+            }
+            TestResult::Skipped(msg) => {
+                self.skipped += 1;
+                self.stdout
+                    .set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+                write!(self.stdout, "skipped")?;
+                self.stdout.reset()?;
+                write!(self.stdout, ": {}", msg)?;
+            }
         }
         writeln!(self.stdout)?;
         Ok(())
@@ -531,9 +657,10 @@ impl TestSummary {
         }
         writeln!(
             self.stdout,
-            ". {} passed; {} failed; {} filtered out",
+            ". {} passed; {} failed; {} skipped; {} filtered out",
             self.passed,
             self.failed.len(),
+            self.skipped,
             self.filtered_out
         )?;
         writeln!(self.stdout)?;
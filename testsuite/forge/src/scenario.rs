@@ -0,0 +1,419 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Test scenarios defined in YAML, for experiments to be set up without writing any test code,
+//! e.g.
+//!
+//! ```yaml
+//! name: validator_down_under_load
+//! swarm:
+//!   validators: 4
+//!   validator_fullnodes: 1
+//! genesis:
+//!   chain:
+//!     epoch_duration_secs: 60
+//! load:
+//!   duration_secs: 300
+//!   tps: 100
+//!   mix: [[P2P, 70], [NftMint, 30]]
+//! chaos:
+//!   - at_secs: 60
+//!     until_secs: 120
+//!     fault:
+//!       stop_validator: 0
+//!   - at_secs: 180
+//!     until_secs: 240
+//!     fault:
+//!       chaos:
+//!         delay: {latency_ms: 200, jitter_ms: 50, correlation_percentage: 50}
+//! success_criteria:
+//!   min_tps: 50
+//!   max_block_gap_secs: 30
+//! ```
+
+use crate::{
+    run_fault_experiment, success_criteria::SuccessCriteria, EmitJobMode, EmitJobRequest,
+    FaultAction, FaultSchedule, ForgeConfig, InitialVersion, NetworkContext, NetworkTest, Result,
+    Swarm, SwarmChaos, Test, TransactionType,
+};
+use anyhow::{bail, format_err, Context};
+use aptos_sdk::types::PeerId;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::{fs, num::NonZeroUsize, path::Path, sync::Arc, time::Duration};
+use tokio::runtime::Runtime;
+
+/// How often the stats of the load are sampled for the report
+const SCENARIO_REPORT_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub swarm: SwarmShape,
+    /// Merged over the genesis helm values of the swarm
+    #[serde(default)]
+    pub genesis: Option<Value>,
+    #[serde(default)]
+    pub load: LoadProfile,
+    #[serde(default)]
+    pub chaos: Vec<ScheduledFault>,
+    #[serde(default)]
+    pub success_criteria: ScenarioCriteria,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwarmShape {
+    pub validators: usize,
+    pub validator_fullnodes: usize,
+    /// Version the swarm starts with, the oldest or the newest one
+    pub initial_version: ScenarioVersion,
+}
+
+impl Default for SwarmShape {
+    fn default() -> Self {
+        Self {
+            validators: 4,
+            validator_fullnodes: 0,
+            initial_version: ScenarioVersion::Oldest,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioVersion {
+    Oldest,
+    Newest,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioDestination {
+    AllNodes,
+    Validators,
+    Fullnodes,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoadProfile {
+    /// How long the load runs for, the duration of the run if None
+    pub duration_secs: Option<u64>,
+    /// Constant rate of transactions, exclusive with `mempool_backlog`
+    pub tps: Option<usize>,
+    /// Transactions kept in mempool when loading the swarm as much as possible
+    pub mempool_backlog: Option<usize>,
+    /// Transaction types with their weight
+    pub mix: Vec<(TransactionType, usize)>,
+    pub gas_price: u64,
+    pub destination: ScenarioDestination,
+}
+
+impl Default for LoadProfile {
+    fn default() -> Self {
+        Self {
+            duration_secs: None,
+            tps: None,
+            mempool_backlog: None,
+            mix: Vec::new(),
+            gas_price: 1,
+            destination: ScenarioDestination::AllNodes,
+        }
+    }
+}
+
+/// A fault applied at `at_secs` into the load, and undone at `until_secs` if set.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduledFault {
+    pub at_secs: u64,
+    pub until_secs: Option<u64>,
+    pub fault: ScenarioFault,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioFault {
+    /// Stops the validator at this index, validators being ordered by name
+    StopValidator(usize),
+    /// Stops the fullnode at this index, fullnodes being ordered by name
+    StopFullnode(usize),
+    Chaos(SwarmChaos),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioCriteria {
+    pub min_tps: usize,
+    pub max_latency_ms: usize,
+    pub check_no_restarts: bool,
+    /// How long all nodes may take to catch up after the load, not checked if None
+    pub wait_for_catchup_secs: Option<u64>,
+    pub max_block_gap_secs: Option<u64>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+        let scenario: Scenario = serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse scenario {}", path.display()))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.swarm.validators == 0 {
+            bail!("A scenario needs at least one validator");
+        }
+        if self.load.tps.is_some() && self.load.mempool_backlog.is_some() {
+            bail!("The load of a scenario can't have both a tps and a mempool backlog");
+        }
+        if self.load.gas_price == 0 {
+            bail!("The gas price of the load of a scenario can't be 0");
+        }
+        for fault in &self.chaos {
+            if fault
+                .until_secs
+                .map_or(false, |until| until <= fault.at_secs)
+            {
+                bail!("Fault {:?} ends before it starts", fault);
+            }
+            match fault.fault {
+                ScenarioFault::StopValidator(index) if index >= self.swarm.validators => {
+                    bail!(
+                        "No validator {} in a swarm of {}",
+                        index,
+                        self.swarm.validators
+                    )
+                }
+                ScenarioFault::StopFullnode(index) if index >= self.swarm.validator_fullnodes => {
+                    bail!(
+                        "No fullnode {} in a swarm of {}",
+                        index,
+                        self.swarm.validator_fullnodes
+                    )
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The forge config running the scenario. The scenario is leaked, as it lives as long as
+    /// the process running it anyway.
+    pub fn into_forge_config(self) -> ForgeConfig<'static> {
+        let scenario: &'static Scenario = Box::leak(Box::new(self));
+        let test: &'static ScenarioTest = Box::leak(Box::new(ScenarioTest { scenario }));
+        let tests: &'static [&'static dyn NetworkTest] = Box::leak(Box::new([test as _]));
+
+        let mut config = ForgeConfig::default()
+            .with_network_tests(tests)
+            .with_initial_validator_count(NonZeroUsize::new(scenario.swarm.validators).unwrap())
+            .with_initial_fullnode_count(scenario.swarm.validator_fullnodes)
+            .with_initial_version(match scenario.swarm.initial_version {
+                ScenarioVersion::Oldest => InitialVersion::Oldest,
+                ScenarioVersion::Newest => InitialVersion::Newest,
+            })
+            .with_emit_job(scenario.emit_job())
+            .with_success_criteria(scenario.success_criteria());
+        if let Some(genesis) = &scenario.genesis {
+            config = config.with_genesis_helm_config_fn(Arc::new(move |helm_values| {
+                merge_yaml(helm_values, genesis)
+            }));
+        }
+        config
+    }
+
+    fn emit_job(&self) -> EmitJobRequest {
+        let mut request = EmitJobRequest::default()
+            .mode(EmitJobMode::create(
+                self.load.mempool_backlog,
+                self.load.tps,
+            ))
+            .gas_price(self.load.gas_price);
+        if !self.load.mix.is_empty() {
+            request = request.transaction_mix(self.load.mix.clone());
+        }
+        request
+    }
+
+    fn success_criteria(&self) -> SuccessCriteria {
+        let criteria = &self.success_criteria;
+        let success_criteria = SuccessCriteria::new(
+            criteria.min_tps,
+            criteria.max_latency_ms,
+            criteria.check_no_restarts,
+            criteria.wait_for_catchup_secs.map(Duration::from_secs),
+        );
+        match criteria.max_block_gap_secs {
+            Some(secs) => success_criteria.with_max_block_gap(Duration::from_secs(secs)),
+            None => success_criteria,
+        }
+    }
+
+    /// The chaos timeline of the scenario, with nodes resolved in `swarm`.
+    fn fault_schedule(&self, swarm: &dyn Swarm) -> Result<FaultSchedule> {
+        let mut validators: Vec<_> = swarm
+            .validators()
+            .map(|v| (v.name().to_string(), v.peer_id()))
+            .collect();
+        validators.sort();
+        let mut full_nodes: Vec<_> = swarm
+            .full_nodes()
+            .map(|n| (n.name().to_string(), n.peer_id()))
+            .collect();
+        full_nodes.sort();
+        let node = |nodes: &[(String, PeerId)], index: usize| {
+            nodes
+                .get(index)
+                .map(|(_, peer_id)| *peer_id)
+                .ok_or_else(|| format_err!("No node {} in the swarm", index))
+        };
+
+        let mut schedule = FaultSchedule::new();
+        for fault in &self.chaos {
+            let action = match &fault.fault {
+                ScenarioFault::StopValidator(index) => {
+                    FaultAction::StopNode(node(&validators, *index)?)
+                }
+                ScenarioFault::StopFullnode(index) => {
+                    FaultAction::StopNode(node(&full_nodes, *index)?)
+                }
+                ScenarioFault::Chaos(chaos) => FaultAction::InjectChaos(chaos.clone()),
+            };
+            let start = Duration::from_secs(fault.at_secs);
+            schedule = match fault.until_secs {
+                Some(until) => schedule.during(start, Duration::from_secs(until), action),
+                None => schedule.at(start, action),
+            };
+        }
+        Ok(schedule)
+    }
+}
+
+/// Merges `overrides` into `value`, mappings key by key and anything else replacing what's in
+/// `value`.
+fn merge_yaml(value: &mut Value, overrides: &Value) {
+    match (value, overrides) {
+        (Value::Mapping(value), Value::Mapping(overrides)) => {
+            for (key, override_value) in overrides {
+                match value.get_mut(key) {
+                    Some(existing) => merge_yaml(existing, override_value),
+                    None => {
+                        value.insert(key.clone(), override_value.clone());
+                    }
+                }
+            }
+        }
+        (value, overrides) => *value = overrides.clone(),
+    }
+}
+
+struct ScenarioTest {
+    scenario: &'static Scenario,
+}
+
+impl Test for ScenarioTest {
+    fn name(&self) -> &'static str {
+        &self.scenario.name
+    }
+}
+
+impl NetworkTest for ScenarioTest {
+    fn run<'t>(&self, ctx: &mut NetworkContext<'t>) -> Result<()> {
+        let duration = self
+            .scenario
+            .load
+            .duration_secs
+            .map_or(ctx.global_duration, Duration::from_secs);
+        let request = ctx.emit_job.clone();
+        let swarm = ctx.swarm();
+        let schedule = self.scenario.fault_schedule(swarm)?;
+        let validators = swarm.validators().map(|v| v.peer_id());
+        let full_nodes = swarm.full_nodes().map(|n| n.peer_id());
+        let nodes: Vec<_> = match self.scenario.load.destination {
+            ScenarioDestination::AllNodes => validators.chain(full_nodes).collect(),
+            ScenarioDestination::Validators => validators.collect(),
+            ScenarioDestination::Fullnodes => full_nodes.collect(),
+        };
+
+        let runtime = Runtime::new()?;
+        let report = runtime.block_on(run_fault_experiment(
+            ctx.swarm(),
+            &nodes,
+            request,
+            &schedule,
+            duration,
+            SCENARIO_REPORT_WINDOW,
+        ))?;
+        ctx.report
+            .report_txn_stats(self.name().to_string(), &report.total, duration);
+        ctx.report.report_text(report.to_string());
+        if !report.failed_faults.is_empty() {
+            bail!("Faults of the scenario failed: {:?}", report.failed_faults);
+        }
+        ctx.check_for_success(&report.total, &duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario: Scenario = serde_yaml::from_str(
+            r#"
+name: validator_down
+swarm:
+  validators: 3
+load:
+  tps: 100
+  mix: [[P2P, 70], [NftMint, 30]]
+chaos:
+  - at_secs: 60
+    until_secs: 120
+    fault:
+      stop_validator: 2
+  - at_secs: 90
+    fault:
+      chaos:
+        loss: {loss_percentage: 5, correlation_percentage: 10}
+success_criteria:
+  min_tps: 50
+"#,
+        )
+        .unwrap();
+        scenario.validate().unwrap();
+        assert_eq!(scenario.swarm.validators, 3);
+        assert_eq!(scenario.load.gas_price, 1);
+        assert_eq!(scenario.chaos.len(), 2);
+        assert!(matches!(
+            scenario.chaos[1].fault,
+            ScenarioFault::Chaos(SwarmChaos::Loss(_))
+        ));
+        assert_eq!(scenario.success_criteria.min_tps, 50);
+
+        let out_of_range: Scenario =
+            serde_yaml::from_str("{name: bad, chaos: [{at_secs: 1, fault: {stop_validator: 4}}]}")
+                .unwrap();
+        assert!(out_of_range.validate().is_err());
+    }
+
+    #[test]
+    fn test_merge_yaml() {
+        let mut values: Value =
+            serde_yaml::from_str("{chain: {era: 1, epoch_duration_secs: 7200}, other: 1}").unwrap();
+        let overrides: Value = serde_yaml::from_str("{chain: {epoch_duration_secs: 60}}").unwrap();
+
+        merge_yaml(&mut values, &overrides);
+
+        let expected: Value =
+            serde_yaml::from_str("{chain: {era: 1, epoch_duration_secs: 60}, other: 1}").unwrap();
+        assert_eq!(values, expected);
+    }
+}
@@ -2,11 +2,19 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::bail;
+use aptos_logger::warn;
 use serde::Serialize;
-use std::time::Duration;
-use transaction_emitter_lib::emitter::stats::TxnStats;
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use transaction_emitter_lib::emitter::stats::{StatsAccumulator, TxnStats};
 
-use crate::{Swarm, SwarmExt};
+use crate::{send_alert, Swarm, SwarmExt};
 
 #[derive(Default, Clone, Debug, Serialize)]
 pub struct SuccessCriteria {
@@ -14,6 +22,8 @@ pub struct SuccessCriteria {
     pub max_latency_ms: usize,
     check_no_restarts: bool,
     wait_for_all_nodes_to_catchup: Option<Duration>,
+    /// Longest time allowed without any new block, see `StallDetector`
+    pub max_block_gap: Option<Duration>,
 }
 
 impl SuccessCriteria {
@@ -28,9 +38,16 @@ impl SuccessCriteria {
             max_latency_ms,
             check_no_restarts,
             wait_for_all_nodes_to_catchup,
+            max_block_gap: None,
         }
     }
 
+    /// Fails the test if no block is produced for longer than `max_block_gap` while it runs.
+    pub fn with_max_block_gap(mut self, max_block_gap: Duration) -> Self {
+        self.max_block_gap = Some(max_block_gap);
+        self
+    }
+
     pub async fn check_for_success(
         &self,
         stats: &TxnStats,
@@ -60,4 +77,84 @@ impl SuccessCriteria {
         // latency
         Ok(())
     }
+
+    /// Checks the criteria that can be told from the stats of a job still running for `elapsed`.
+    pub fn check_in_progress(&self, stats: &TxnStats, elapsed: Duration) -> anyhow::Result<()> {
+        let avg_tps = stats.committed as f64 / elapsed.as_secs_f64();
+        if avg_tps < self.avg_tps as f64 {
+            bail!(
+                "Average TPS {:.0} so far, minimum TPS requirement {}",
+                avg_tps,
+                self.avg_tps
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks the criteria against the stats of the job of `test` every `interval` until the
+    /// returned watcher is dropped, and alerts when they start or stop being breached.
+    pub fn watch(
+        &self,
+        test: &str,
+        stats: Arc<StatsAccumulator>,
+        interval: Duration,
+    ) -> SuccessCriteriaWatcher {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let criteria = self.clone();
+        let test = test.to_string();
+        let start = Instant::now();
+        thread::spawn(move || {
+            let mut breached = false;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                match criteria.check_in_progress(&stats.accumulate(), start.elapsed()) {
+                    Err(e) if !breached => {
+                        breached = true;
+                        warn!("{} is breaching its success criteria: {:#}", test, e);
+                        send_alert(&format!(
+                            ":warning: {} is breaching its success criteria after {}s: {:#}",
+                            test,
+                            start.elapsed().as_secs(),
+                            e
+                        ));
+                    }
+                    Ok(()) if breached => {
+                        breached = false;
+                        send_alert(&format!(
+                            ":white_check_mark: {} meets its success criteria again after {}s",
+                            test,
+                            start.elapsed().as_secs()
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        });
+        SuccessCriteriaWatcher { _stop: stop }
+    }
+}
+
+/// Checks the success criteria of a running job in the background, see
+/// `SuccessCriteria::watch`. Stops when dropped.
+pub struct SuccessCriteriaWatcher {
+    _stop: Sender<()>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_in_progress() {
+        let criteria = SuccessCriteria::new(100, 10000, false, None);
+        let stats = TxnStats {
+            committed: 6000,
+            ..TxnStats::default()
+        };
+        assert!(criteria
+            .check_in_progress(&stats, Duration::from_secs(60))
+            .is_ok());
+        assert!(criteria
+            .check_in_progress(&stats, Duration::from_secs(61))
+            .is_err());
+    }
 }
@@ -116,7 +116,7 @@ pub async fn test_consensus_fault_tolerance(
 
         check_cycle(cycle, epochs, rounds, transactions, cur.clone(), previous);
         if new_epoch_on_cycle {
-            swarm.aptos_public_info().reconfig().await;
+            swarm.aptos_public_info().reconfig().await.unwrap();
         }
     }
 
@@ -0,0 +1,191 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Significant events of a run, e.g. nodes restarting or chaos being injected, recorded with
+//! their time wherever they happen and added to the report at the end of the run, to correlate
+//! anomalies of the stats with what the harness was doing at that moment.
+
+use aptos_infallible::Mutex;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    fmt, mem,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static TIMELINE: Lazy<Mutex<Vec<TimelineEvent>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TimelineEventKind {
+    NodeStarted {
+        node: String,
+    },
+    NodeStopped {
+        node: String,
+    },
+    ChaosInjected {
+        chaos: String,
+    },
+    ChaosRemoved {
+        chaos: String,
+    },
+    EpochChanged {
+        epoch: u64,
+    },
+    /// No block was produced after this height for longer than allowed
+    ProductionStalled {
+        height: u64,
+    },
+    ProductionResumed {
+        height: u64,
+    },
+    /// Transactions are emitted to this many nodes
+    EmissionStarted {
+        nodes: usize,
+    },
+    EmissionStopped {
+        nodes: usize,
+    },
+    /// The mempool of the node stayed saturated for long enough to be flagged, from then on
+    MempoolSaturated {
+        node: String,
+    },
+    MempoolDrained {
+        node: String,
+    },
+    TestStarted {
+        test: String,
+    },
+    TestFinished {
+        test: String,
+        success: bool,
+    },
+}
+
+impl fmt::Display for TimelineEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineEventKind::NodeStarted { node } => write!(f, "node {} started", node),
+            TimelineEventKind::NodeStopped { node } => write!(f, "node {} stopped", node),
+            TimelineEventKind::ChaosInjected { chaos } => write!(f, "injected {}", chaos),
+            TimelineEventKind::ChaosRemoved { chaos } => write!(f, "removed {}", chaos),
+            TimelineEventKind::EpochChanged { epoch } => write!(f, "epoch {} started", epoch),
+            TimelineEventKind::ProductionStalled { height } => {
+                write!(f, "block production stalled at height {}", height)
+            }
+            TimelineEventKind::ProductionResumed { height } => {
+                write!(f, "block production resumed after height {}", height)
+            }
+            TimelineEventKind::EmissionStarted { nodes } => {
+                write!(f, "started emitting transactions to {} nodes", nodes)
+            }
+            TimelineEventKind::EmissionStopped { nodes } => {
+                write!(f, "stopped emitting transactions to {} nodes", nodes)
+            }
+            TimelineEventKind::MempoolSaturated { node } => {
+                write!(f, "mempool of node {} saturated", node)
+            }
+            TimelineEventKind::MempoolDrained { node } => {
+                write!(f, "mempool of node {} drained", node)
+            }
+            TimelineEventKind::TestStarted { test } => write!(f, "{} started", test),
+            TimelineEventKind::TestFinished { test, success } => {
+                write!(f, "{} {}", test, if *success { "passed" } else { "failed" })
+            }
+        }
+    }
+}
+
+/// An event of the timeline, see `record_event`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TimelineEvent {
+    /// Unix time in millis
+    pub time_ms: u64,
+    #[serde(flatten)]
+    pub kind: TimelineEventKind,
+}
+
+impl TimelineEvent {
+    pub fn new(time: SystemTime, kind: TimelineEventKind) -> Self {
+        Self {
+            time_ms: unix_millis(time),
+            kind,
+        }
+    }
+}
+
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl fmt::Display for TimelineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:03} {}",
+            self.time_ms / 1000,
+            self.time_ms % 1000,
+            self.kind
+        )
+    }
+}
+
+/// Adds an event that happened now to the timeline of the run.
+pub fn record_event(kind: TimelineEventKind) {
+    record_event_at(SystemTime::now(), kind)
+}
+
+/// Adds an event that happened at `time` to the timeline of the run.
+pub fn record_event_at(time: SystemTime, kind: TimelineEventKind) {
+    TIMELINE.lock().push(TimelineEvent::new(time, kind));
+}
+
+/// The events recorded at or after `time` so far, sorted by time, leaving them on the timeline.
+pub fn timeline_since(time: SystemTime) -> Vec<TimelineEvent> {
+    let since_ms = unix_millis(time);
+    let mut events: Vec<_> = TIMELINE
+        .lock()
+        .iter()
+        .filter(|event| event.time_ms >= since_ms)
+        .cloned()
+        .collect();
+    events.sort_by_key(|event| event.time_ms);
+    events
+}
+
+/// Removes the events recorded so far from the timeline, sorted by time.
+pub fn take_timeline() -> Vec<TimelineEvent> {
+    let mut events = mem::take(&mut *TIMELINE.lock());
+    events.sort_by_key(|event| event.time_ms);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timeline_event_serialization() {
+        let event = TimelineEvent::new(
+            UNIX_EPOCH + Duration::from_millis(1_500),
+            TimelineEventKind::TestFinished {
+                test: "perf".to_string(),
+                success: false,
+            },
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "time_ms": 1500,
+                "event": "test_finished",
+                "test": "perf",
+                "success": false,
+            })
+        );
+        assert_eq!(event.to_string(), "1.500 perf failed");
+    }
+}
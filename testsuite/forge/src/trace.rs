@@ -0,0 +1,255 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! Spans of the steps of a forge run, e.g. launching the swarm or running a test, exported to
+//! an OpenTelemetry collector over OTLP/HTTP with the JSON encoding, to explore the timeline of
+//! a run in a trace viewer.
+//!
+//! Spans are nested by when they're opened: the parent of a span is the latest span opened
+//! and not finished yet, which matches how a run goes through its steps one at a time. Spans
+//! are only recorded once `init_tracing` is called, and are exported by batches.
+
+use anyhow::{anyhow, bail, Result};
+use aptos_infallible::Mutex;
+use aptos_logger::warn;
+use once_cell::sync::OnceCell;
+use rand::Rng;
+use serde_json::{json, Value};
+use std::{
+    fmt, mem, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+/// Finished spans are exported once there are this many.
+const EXPORT_BATCH_SIZE: usize = 256;
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TRACER: OnceCell<Tracer> = OnceCell::new();
+
+struct Tracer {
+    traces_url: Url,
+    service_name: String,
+    /// A run is a single trace
+    trace_id: String,
+    state: Mutex<TracerState>,
+}
+
+#[derive(Default)]
+struct TracerState {
+    /// Ids of the open spans, oldest first
+    open: Vec<String>,
+    /// Finished spans not exported yet, in the OTLP JSON encoding
+    finished: Vec<Value>,
+}
+
+/// Records the spans opened from now on, to export them to the OTLP/HTTP collector at
+/// `endpoint`, e.g. `http://localhost:4318`, under the service `service_name`.
+pub fn init_tracing(endpoint: &Url, service_name: &str) -> Result<()> {
+    let tracer = Tracer {
+        traces_url: endpoint.join("v1/traces")?,
+        service_name: service_name.to_string(),
+        trace_id: random_id(16),
+        state: Mutex::new(TracerState::default()),
+    };
+    TRACER
+        .set(tracer)
+        .map_err(|_| anyhow!("Tracing already initialized"))
+}
+
+/// Opens a span, finished when dropped. Does nothing unless tracing was initialized.
+pub fn trace_span(name: &str) -> Span {
+    let data = TRACER.get().map(|tracer| {
+        let id = random_id(8);
+        let mut state = tracer.state.lock();
+        let parent = state.open.last().cloned();
+        state.open.push(id.clone());
+        SpanData {
+            id,
+            parent,
+            name: name.to_string(),
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: None,
+        }
+    });
+    Span { data }
+}
+
+/// Exports the finished spans not exported yet.
+pub fn flush_traces() -> Result<()> {
+    let tracer = match TRACER.get() {
+        Some(tracer) => tracer,
+        None => return Ok(()),
+    };
+    let spans = mem::take(&mut tracer.state.lock().finished);
+    if spans.is_empty() {
+        return Ok(());
+    }
+    let body = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", &tracer.service_name)],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "forge" },
+                "spans": spans,
+            }],
+        }],
+    });
+    let url = tracer.traces_url.clone();
+    // The blocking client can't be used from within the runtimes the spans are opened in.
+    thread::spawn(move || -> Result<()> {
+        let response = reqwest::blocking::Client::builder()
+            .timeout(EXPORT_TIMEOUT)
+            .build()?
+            .post(url)
+            .json(&body)
+            .send()?;
+        if !response.status().is_success() {
+            bail!(
+                "Collector rejected the spans with {}: {}",
+                response.status(),
+                response.text().unwrap_or_default()
+            );
+        }
+        Ok(())
+    })
+    .join()
+    .map_err(|_| anyhow!("Exporting spans panicked"))?
+}
+
+/// A step of the run, see `trace_span`.
+#[must_use = "the span is finished as soon as it is dropped"]
+pub struct Span {
+    data: Option<SpanData>,
+}
+
+struct SpanData {
+    id: String,
+    parent: Option<String>,
+    name: String,
+    start: SystemTime,
+    attributes: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+impl Span {
+    pub fn with_attribute<V: fmt::Display>(mut self, key: &str, value: V) -> Self {
+        self.set_attribute(key, value);
+        self
+    }
+
+    pub fn set_attribute<V: fmt::Display>(&mut self, key: &str, value: V) {
+        if let Some(data) = &mut self.data {
+            data.attributes.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    /// Marks the span as failed with `error`.
+    pub fn set_error<E: fmt::Display>(&mut self, error: E) {
+        if let Some(data) = &mut self.data {
+            data.error = Some(format!("{:#}", error));
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let (tracer, data) = match (TRACER.get(), self.data.take()) {
+            (Some(tracer), Some(data)) => (tracer, data),
+            _ => return,
+        };
+        let id = data.id.clone();
+        let span = data.into_otlp(&tracer.trace_id, SystemTime::now());
+        let export = {
+            let mut state = tracer.state.lock();
+            state.open.retain(|open| open != &id);
+            state.finished.push(span);
+            state.finished.len() >= EXPORT_BATCH_SIZE
+        };
+        if export {
+            if let Err(e) = flush_traces() {
+                warn!("Failed to export spans: {:#}", e);
+            }
+        }
+    }
+}
+
+impl SpanData {
+    fn into_otlp(self, trace_id: &str, end: SystemTime) -> Value {
+        let mut span = json!({
+            "traceId": trace_id,
+            "spanId": self.id,
+            "name": self.name,
+            // Internal
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(end),
+            "attributes": self
+                .attributes
+                .iter()
+                .map(|(key, value)| attribute(key, value))
+                .collect::<Vec<_>>(),
+            "status": match &self.error {
+                Some(error) => json!({ "code": 2, "message": error }),
+                None => json!({ "code": 1 }),
+            },
+        });
+        if let Some(parent) = self.parent {
+            span["parentSpanId"] = json!(parent);
+        }
+        span
+    }
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// Nanoseconds since the unix epoch, as a string as OTLP JSON encodes 64 bits integers.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// Hex encoded random id of `len` bytes.
+fn random_id(len: usize) -> String {
+    let bytes: Vec<u8> = (0..len).map(|_| rand::thread_rng().gen()).collect();
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_into_otlp() {
+        let start = UNIX_EPOCH + Duration::from_millis(1_500);
+        let span = SpanData {
+            id: "00000000000000aa".to_string(),
+            parent: Some("00000000000000bb".to_string()),
+            name: "upgrade validator".to_string(),
+            start,
+            attributes: vec![("node".to_string(), "0".to_string())],
+            error: Some("timed out".to_string()),
+        }
+        .into_otlp("0123", start + Duration::from_secs(2));
+        assert_eq!(
+            span,
+            json!({
+                "traceId": "0123",
+                "spanId": "00000000000000aa",
+                "parentSpanId": "00000000000000bb",
+                "name": "upgrade validator",
+                "kind": 1,
+                "startTimeUnixNano": "1500000000",
+                "endTimeUnixNano": "3500000000",
+                "attributes": [{ "key": "node", "value": { "stringValue": "0" } }],
+                "status": { "code": 2, "message": "timed out" },
+            })
+        );
+    }
+}
@@ -1,2 +1,25 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
+
+use crate::smoke_test_environment::new_local_swarm_with_aptos;
+use forge::{FundingConfig, Swarm};
+
+#[tokio::test]
+async fn test_fund_accounts() {
+    let mut swarm = new_local_swarm_with_aptos(1).await;
+    let mut info = swarm.aptos_public_info();
+
+    let addresses: Vec<_> = (0..100).map(|_| info.random_account().address()).collect();
+    let config = FundingConfig {
+        num_funding_accounts: 4,
+        batch_size: 10,
+        max_txns_per_sec: 100,
+    };
+    info.fund_accounts_with_config(&addresses, 1_000, &config)
+        .await
+        .unwrap();
+
+    for address in addresses {
+        assert_eq!(info.get_balance(address).await, Some(1_000));
+    }
+}
@@ -40,7 +40,8 @@ async fn test_analyze_validators() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     tokio::time::sleep(Duration::from_secs(3)).await;
 
@@ -49,7 +50,8 @@ async fn test_analyze_validators() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     cli.analyze_validator_performance(None, None).await.unwrap();
 }
@@ -127,7 +129,8 @@ async fn test_large_total_stake() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     assert_eq!(
         get_validator_state(&cli, validator_cli_index).await,
@@ -227,7 +230,8 @@ async fn test_nodes_rewards() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     let (start_2_failures_state, start_2_failures_validator_set) =
         get_state_and_validator_set(&rest_clients[0]).await;
@@ -246,7 +250,8 @@ async fn test_nodes_rewards() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     cli.fund_account(validator_cli_indices[3], Some(30000))
         .await
@@ -268,7 +273,8 @@ async fn test_nodes_rewards() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     let (start_3_left_state, start_3_left_validator_set) =
         get_state_and_validator_set(&rest_clients[0]).await;
@@ -294,7 +300,8 @@ async fn test_nodes_rewards() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     tokio::time::sleep(Duration::from_secs(3)).await;
 
@@ -303,7 +310,8 @@ async fn test_nodes_rewards() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     let (end_state, end_validator_set) = get_state_and_validator_set(&rest_clients[0]).await;
     println!(
@@ -559,7 +567,8 @@ async fn test_register_and_update_validator() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     // because we haven't joined the validator set yet, we shouldn't be there
     let validator_set = cli.show_validator_set().await.unwrap();
@@ -641,7 +650,8 @@ async fn test_join_and_leave_validator() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     assert_validator_set_sizes(&cli, 1, 0, 0).await;
 
@@ -650,7 +660,8 @@ async fn test_join_and_leave_validator() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     assert_validator_set_sizes(&cli, 1, 0, 0).await;
 
@@ -667,7 +678,8 @@ async fn test_join_and_leave_validator() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     assert_validator_set_sizes(&cli, 2, 0, 0).await;
 
@@ -676,7 +688,8 @@ async fn test_join_and_leave_validator() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     gas_used += get_gas(
         cli.leave_validator_set(validator_cli_index, None)
@@ -691,7 +704,8 @@ async fn test_join_and_leave_validator() {
         &transaction_factory,
         swarm.chain_info().root_account(),
     )
-    .await;
+    .await
+    .unwrap();
 
     assert_validator_set_sizes(&cli, 1, 0, 0).await;
 
@@ -888,7 +902,8 @@ async fn test_owner_create_and_delegate_flow() {
             &transaction_factory,
             swarm.chain_info().root_account(),
         )
-        .await;
+        .await
+        .unwrap();
 
         assert_eq!(
             get_validator_state(&cli, owner_cli_index).await,
@@ -935,7 +950,8 @@ async fn test_owner_create_and_delegate_flow() {
             &transaction_factory,
             swarm.chain_info().root_account(),
         )
-        .await;
+        .await
+        .unwrap();
 
         assert_eq!(
             get_validator_state(&cli, owner_cli_index).await,
@@ -2,14 +2,18 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    smoke_test_environment::new_local_swarm_with_aptos,
+    smoke_test_environment::{new_local_swarm_with_aptos, SwarmBuilder},
     test_utils::{
         assert_balance, check_create_mint_transfer, create_and_fund_account, transfer_coins,
     },
 };
+use aptos_types::chain_id::ChainId;
 use cached_packages::aptos_stdlib;
-use forge::{NodeExt, Swarm};
-use std::time::{Duration, Instant};
+use forge::{Factory, LocalFactory, LocalMultiSwarm, LocalNetworkSpec, NodeExt, Swarm};
+use std::{
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
 
 #[tokio::test]
 async fn test_create_mint_transfer_block_metadata() {
@@ -85,6 +89,42 @@ async fn test_basic_restartability() {
     assert_balance(&client, &account_1, 30).await;
 }
 
+#[tokio::test]
+async fn test_stop_start_all() {
+    let mut swarm = SwarmBuilder::new_local(4)
+        .with_num_fullnodes(1)
+        .with_aptos()
+        .build()
+        .await;
+    let validator = swarm.validators().next().unwrap();
+    let client = validator.rest_client();
+    let vfn_peer_id = validator.peer_id();
+    let transaction_factory = swarm.chain_info().transaction_factory();
+
+    let mut account_0 = create_and_fund_account(&mut swarm, 100).await;
+    let account_1 = create_and_fund_account(&mut swarm, 10).await;
+
+    swarm.stop_all();
+    assert!(swarm.validators().all(|validator| !validator.is_running()));
+    assert!(!swarm.fullnode(vfn_peer_id).unwrap().is_running());
+    swarm.start_all().await.unwrap();
+    swarm.ensure_no_validator_restart().await.unwrap();
+    swarm.ensure_no_fullnode_restart().await.unwrap();
+
+    assert_balance(&client, &account_0, 100).await;
+    assert_balance(&client, &account_1, 10).await;
+    transfer_coins(
+        &client,
+        &transaction_factory,
+        &mut account_0,
+        &account_1,
+        10,
+    )
+    .await;
+    assert_balance(&client, &account_0, 90).await;
+    assert_balance(&client, &account_1, 20).await;
+}
+
 #[tokio::test]
 async fn test_concurrent_transfers_single_node() {
     let mut swarm = new_local_swarm_with_aptos(1).await;
@@ -144,3 +184,49 @@ async fn test_latest_events_and_transactions() {
     assert_eq!(cur_transations.len(), 2);
     assert_eq!(cur_transations[1].version().unwrap(), cur_ledger.version);
 }
+
+#[tokio::test]
+async fn test_multi_swarm_mirrored_account() {
+    let factory = LocalFactory::from_workspace().unwrap();
+    let version = factory.versions().max().unwrap();
+    let one = NonZeroUsize::new(1).unwrap();
+    let mut multi_swarm = LocalMultiSwarm::launch(
+        &factory,
+        &version,
+        &[
+            LocalNetworkSpec::new("left", ChainId::new(10), one),
+            LocalNetworkSpec::new("right", ChainId::new(11), one),
+        ],
+    )
+    .await
+    .unwrap();
+
+    for (network, chain_id) in [("left", 10), ("right", 11)] {
+        let ledger_info = multi_swarm
+            .rest_client(network)
+            .unwrap()
+            .get_ledger_information()
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(ledger_info.chain_id, chain_id);
+    }
+
+    let accounts = multi_swarm.create_mirrored_account(1000).await.unwrap();
+    assert_eq!(accounts["left"].address(), accounts["right"].address());
+    for (network, account) in &accounts {
+        let balance = multi_swarm
+            .rest_client(network)
+            .unwrap()
+            .get_account_balance(account.address())
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(balance.get(), 1000);
+    }
+    let bridged = multi_swarm
+        .bridge_account(&accounts["left"], "right")
+        .await
+        .unwrap();
+    assert_eq!(bridged.sequence_number(), 0);
+}
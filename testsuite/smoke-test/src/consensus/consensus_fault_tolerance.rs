@@ -16,8 +16,9 @@ use forge::{
     test_utils::consensus_utils::{
         no_failure_injection, test_consensus_fault_tolerance, FailPointFailureInjection, NodeState,
     },
-    LocalSwarm, Swarm, SwarmExt,
+    LocalSwarm, NodeExt, Swarm, SwarmChaos, SwarmExt, SwarmNodePause,
 };
+use futures::future;
 use rand::{self, Rng};
 use rand::{rngs::SmallRng, SeedableRng};
 
@@ -136,6 +137,7 @@ async fn run_fail_point_test(
     )
     .await
     .unwrap();
+    swarm.check_consensus_safety(None).await.unwrap();
 }
 
 #[tokio::test]
@@ -432,3 +434,59 @@ async fn test_alternating_having_consensus() {
     )
     .await;
 }
+
+#[tokio::test]
+async fn test_crash_reported_as_restart() {
+    let mut swarm = create_swarm(1, 1).await;
+    swarm.ensure_no_validator_restart().await.unwrap();
+
+    let validator = swarm.validators_mut().next().unwrap();
+    let name = validator.name().to_string();
+    let pid = validator.pid().unwrap();
+    assert!(std::process::Command::new("kill")
+        .args(&["-KILL", &pid.to_string()])
+        .status()
+        .unwrap()
+        .success());
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let error = swarm.ensure_no_validator_restart().await.unwrap_err();
+    assert!(error.to_string().contains(&name));
+
+    // Still reported once the crashed validator was started again
+    let validator = swarm.validators_mut().next().unwrap();
+    validator.stop();
+    validator.start().unwrap();
+    assert!(swarm.ensure_no_validator_restart().await.is_err());
+}
+
+#[tokio::test]
+async fn test_pause_validator() {
+    let mut swarm = create_swarm(4, 1).await;
+    let peer_id = swarm.validators().next().unwrap().peer_id();
+    let pause = SwarmChaos::Pause(SwarmNodePause {
+        peers: vec![peer_id],
+        duration_secs: 60,
+    });
+    swarm.inject_chaos(pause.clone()).unwrap();
+    assert!(swarm.validator(peer_id).unwrap().is_paused());
+
+    // The other validators keep making progress without it
+    let others: Vec<_> = swarm
+        .validators()
+        .filter(|v| v.peer_id() != peer_id)
+        .map(|v| v.rest_client())
+        .collect();
+    let heights = future::join_all(others.iter().map(|c| c.get_ledger_information())).await;
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    let later = future::join_all(others.iter().map(|c| c.get_ledger_information())).await;
+    for (before, after) in heights.into_iter().zip(later) {
+        assert!(after.unwrap().into_inner().version > before.unwrap().into_inner().version);
+    }
+
+    swarm.remove_chaos(pause).unwrap();
+    assert!(!swarm.validator(peer_id).unwrap().is_paused());
+    swarm
+        .wait_for_all_nodes_to_catchup(Duration::from_secs(30))
+        .await
+        .unwrap();
+}
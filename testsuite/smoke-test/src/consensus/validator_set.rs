@@ -0,0 +1,54 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::smoke_test_environment::SwarmBuilder;
+use aptos_config::config::NodeConfig;
+use aptos_rest_client::Client as RestClient;
+use aptos_types::{account_address::AccountAddress, on_chain_config::ValidatorSet, PeerId};
+use forge::{NodeExt, Swarm, SwarmExt};
+use std::{sync::Arc, time::Duration};
+
+const MAX_WAIT_SECS: u64 = 60;
+
+async fn active_validators(client: &RestClient) -> Vec<PeerId> {
+    client
+        .get_account_resource_bcs::<ValidatorSet>(AccountAddress::ONE, "0x1::stake::ValidatorSet")
+        .await
+        .unwrap()
+        .into_inner()
+        .active_validators
+        .iter()
+        .map(|validator| *validator.account_address())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_add_and_remove_validator() {
+    let mut swarm = SwarmBuilder::new_local(3)
+        .with_init_genesis_config(Arc::new(|genesis_config| {
+            genesis_config.allow_new_validators = true;
+        }))
+        .build()
+        .await;
+    let client = swarm.validators().next().unwrap().rest_client();
+    let version = swarm.versions().max().unwrap();
+
+    let peer_id = swarm
+        .add_validator(&version, NodeConfig::default_for_validator())
+        .await
+        .unwrap();
+    assert_eq!(swarm.validators().count(), 4);
+    assert!(active_validators(&client).await.contains(&peer_id));
+    swarm
+        .wait_for_all_nodes_to_catchup(Duration::from_secs(MAX_WAIT_SECS))
+        .await
+        .unwrap();
+
+    swarm.remove_validator(peer_id).await.unwrap();
+    assert_eq!(swarm.validators().count(), 3);
+    assert!(!active_validators(&client).await.contains(&peer_id));
+    swarm
+        .wait_for_all_nodes_to_catchup(Duration::from_secs(MAX_WAIT_SECS))
+        .await
+        .unwrap();
+}
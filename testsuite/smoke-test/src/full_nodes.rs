@@ -25,6 +25,7 @@ async fn test_full_node_basic_flow() {
     let version = swarm.versions().max().unwrap();
     let pfn_peer_id = swarm
         .add_full_node(&version, NodeConfig::default_for_public_full_node())
+        .await
         .unwrap();
     for fullnode in swarm.full_nodes_mut() {
         fullnode
@@ -203,7 +204,7 @@ async fn test_private_full_node() {
         NetworkId::Public,
         PeerRole::PreferredUpstream,
     );
-    let private = swarm.add_full_node(&version, private_config).unwrap();
+    let private = swarm.add_full_node(&version, private_config).await.unwrap();
 
     // And connect the user to the private swarm
     add_node_to_seeds(
@@ -212,7 +213,7 @@ async fn test_private_full_node() {
         NetworkId::Public,
         PeerRole::PreferredUpstream,
     );
-    let user = swarm.add_full_node(&version, user_config).unwrap();
+    let user = swarm.add_full_node(&version, user_config).await.unwrap();
 
     swarm
         .wait_for_connectivity(Instant::now() + Duration::from_secs(MAX_WAIT_SECS))
@@ -21,6 +21,7 @@ async fn test_indexer() {
     let version = swarm.versions().max().unwrap();
     let fullnode_peer_id = swarm
         .add_full_node(&version, NodeConfig::default_for_public_full_node())
+        .await
         .unwrap();
     let validator_peer_id = swarm.validators().next().unwrap().peer_id();
     let _vfn_peer_id = swarm
@@ -29,6 +30,7 @@ async fn test_indexer() {
             NodeConfig::default_for_validator_full_node(),
             validator_peer_id,
         )
+        .await
         .unwrap();
 
     let fullnode = swarm.full_node_mut(fullnode_peer_id).unwrap();
@@ -82,3 +84,18 @@ async fn wait_for_account(client: &RestClient, address: AccountAddress) -> Resul
     }
     bail!("wait for account(address={}) timeout", address,)
 }
+
+#[tokio::test]
+async fn test_external_fullnode_config() {
+    let mut swarm = new_local_swarm_with_aptos(1).await;
+
+    let config_path = swarm
+        .write_external_fullnode_config(NodeConfig::default_for_public_full_node())
+        .unwrap();
+    let config = NodeConfig::load(&config_path).unwrap();
+    assert_eq!(config.base.waypoint.waypoint(), swarm.waypoint());
+    assert_eq!(
+        std::fs::read(&config.execution.genesis_file_location).unwrap(),
+        std::fs::read(swarm.genesis_blob_path()).unwrap()
+    );
+}
@@ -0,0 +1,82 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::smoke_test_environment::SwarmBuilder;
+use aptos_gas::{AptosGasParameters, InitialGasSchedule, InternalGas, ToOnChainGasSchedule};
+use aptos_genesis::LockedStakePool;
+use aptos_types::{
+    account_address::AccountAddress,
+    on_chain_config::{ConsensusConfigV1, GasSchedule, OnChainConsensusConfig},
+    stake_pool::StakePool,
+};
+use forge::Swarm;
+
+#[tokio::test]
+async fn test_genesis_on_chain_configs() {
+    let consensus_config = OnChainConsensusConfig::V1(ConsensusConfigV1 {
+        exclude_round: 30,
+        ..ConsensusConfigV1::default()
+    });
+    let mut gas_parameters = AptosGasParameters::initial();
+    gas_parameters.txn.min_transaction_gas_units = InternalGas::new(1234);
+
+    let mut swarm = SwarmBuilder::new_local(1)
+        .with_aptos()
+        .with_consensus_config(consensus_config.clone())
+        .with_gas_parameters(gas_parameters.clone())
+        .build()
+        .await;
+    let info = swarm.aptos_public_info();
+    let client = info.client();
+
+    let config_bytes: Vec<u8> = client
+        .get_account_resource_bcs(
+            AccountAddress::ONE,
+            "0x1::consensus_config::ConsensusConfig",
+        )
+        .await
+        .unwrap()
+        .into_inner();
+    let on_chain: OnChainConsensusConfig = bcs::from_bytes(&config_bytes).unwrap();
+    assert_eq!(on_chain, consensus_config);
+
+    let gas_schedule: GasSchedule = client
+        .get_account_resource_bcs(AccountAddress::ONE, "0x1::gas_schedule::GasSchedule")
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        gas_schedule.entries,
+        gas_parameters.to_on_chain_gas_schedule()
+    );
+}
+
+#[tokio::test]
+async fn test_genesis_locked_stake_pools() {
+    let owner_address = AccountAddress::from_hex_literal("0xa11ce").unwrap();
+    let operator_address = AccountAddress::from_hex_literal("0xb0b").unwrap();
+    let stake_amount = 1_000_000;
+
+    let mut swarm = SwarmBuilder::new_local(1)
+        .with_aptos()
+        .with_locked_stake_pools(vec![LockedStakePool {
+            owner_address,
+            operator_address,
+            voter_address: owner_address,
+            stake_amount,
+        }])
+        .build()
+        .await;
+    let info = swarm.aptos_public_info();
+
+    let stake_pool: StakePool = info
+        .client()
+        .get_account_resource_bcs(owner_address, "0x1::stake::StakePool")
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(stake_pool.active, stake_amount);
+    assert_eq!(stake_pool.operator_address, operator_address);
+    assert_eq!(stake_pool.delegated_voter, owner_address);
+    assert!(stake_pool.locked_until_secs > 0);
+}
@@ -68,6 +68,7 @@ async fn test_connection_limiting() {
                 peer_set,
             ),
         )
+        .await
         .unwrap();
     swarm
         .fullnode_mut(pfn_peer_id)
@@ -107,6 +108,7 @@ async fn test_connection_limiting() {
                 peer_set,
             ),
         )
+        .await
         .unwrap();
 
     // This node should fail to connect
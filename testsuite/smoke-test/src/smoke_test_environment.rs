@@ -5,12 +5,19 @@ use aptos::test::CliTestFramework;
 use aptos_config::{keys::ConfigKey, utils::get_available_port};
 use aptos_crypto::ed25519::Ed25519PrivateKey;
 use aptos_faucet::FaucetArgs;
-use aptos_genesis::builder::{InitConfigFn, InitGenesisConfigFn};
+use aptos_gas::AptosGasParameters;
+use aptos_genesis::{
+    builder::{InitConfigFn, InitGenesisConfigFn},
+    LockedStakePool,
+};
 use aptos_infallible::Mutex;
 use aptos_logger::info;
-use aptos_types::{account_config::aptos_test_root_address, chain_id::ChainId};
+use aptos_types::{
+    account_config::aptos_test_root_address, chain_id::ChainId,
+    on_chain_config::OnChainConsensusConfig,
+};
 use forge::{ActiveNodesGuard, Node};
-use forge::{Factory, LocalFactory, LocalSwarm};
+use forge::{ExtraArgsFn, Factory, LocalFactory, LocalSwarm};
 use framework::ReleaseBundle;
 use once_cell::sync::Lazy;
 use rand::rngs::OsRng;
@@ -24,6 +31,11 @@ pub struct SwarmBuilder {
     genesis_framework: Option<ReleaseBundle>,
     init_config: Option<InitConfigFn>,
     init_genesis_config: Option<InitGenesisConfigFn>,
+    consensus_config: Option<OnChainConsensusConfig>,
+    gas_parameters: Option<AptosGasParameters>,
+    locked_stake_pools: Vec<LockedStakePool>,
+    extra_args: Option<ExtraArgsFn>,
+    chain_id: ChainId,
 }
 
 impl SwarmBuilder {
@@ -35,6 +47,11 @@ impl SwarmBuilder {
             genesis_framework: None,
             init_config: None,
             init_genesis_config: None,
+            consensus_config: None,
+            gas_parameters: None,
+            locked_stake_pools: Vec::new(),
+            extra_args: None,
+            chain_id: ChainId::test(),
         }
     }
 
@@ -57,6 +74,35 @@ impl SwarmBuilder {
         self
     }
 
+    /// Sets the on-chain consensus config at genesis, after `with_init_genesis_config` ran.
+    pub fn with_consensus_config(mut self, consensus_config: OnChainConsensusConfig) -> Self {
+        self.consensus_config = Some(consensus_config);
+        self
+    }
+
+    /// Sets the gas schedule at genesis, after `with_init_genesis_config` ran.
+    pub fn with_gas_parameters(mut self, gas_parameters: AptosGasParameters) -> Self {
+        self.gas_parameters = Some(gas_parameters);
+        self
+    }
+
+    /// Creates stake pools at genesis which are locked up but don't join the validator set.
+    pub fn with_locked_stake_pools(mut self, locked_stake_pools: Vec<LockedStakePool>) -> Self {
+        self.locked_stake_pools = locked_stake_pools;
+        self
+    }
+
+    /// Passes extra command line arguments to the nodes, given their name and config.
+    pub fn with_extra_args(mut self, extra_args: ExtraArgsFn) -> Self {
+        self.extra_args = Some(extra_args);
+        self
+    }
+
+    pub fn with_chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
     pub fn with_num_fullnodes(mut self, num_fullnodes: usize) -> Self {
         self.num_fullnodes = num_fullnodes;
         self
@@ -79,6 +125,9 @@ impl SwarmBuilder {
         let guard = ActiveNodesGuard::grab(slots, ACTIVE_NODES.clone()).await;
 
         let init_genesis_config = self.init_genesis_config;
+        let consensus_config = self.consensus_config;
+        let gas_parameters = self.gas_parameters;
+        let locked_stake_pools = self.locked_stake_pools;
 
         FACTORY
             .new_swarm_with_version(
@@ -87,12 +136,23 @@ impl SwarmBuilder {
                 self.num_fullnodes,
                 &version,
                 self.genesis_framework,
+                self.chain_id,
                 self.init_config,
                 Some(Arc::new(move |genesis_config| {
                     if let Some(init_genesis_config) = &init_genesis_config {
                         (init_genesis_config)(genesis_config);
                     }
+                    if let Some(consensus_config) = &consensus_config {
+                        genesis_config.consensus_config = consensus_config.clone();
+                    }
+                    if let Some(gas_parameters) = &gas_parameters {
+                        genesis_config.gas_parameters = gas_parameters.clone();
+                    }
+                    genesis_config
+                        .locked_stake_pools
+                        .extend(locked_stake_pools.iter().cloned());
                 })),
+                self.extra_args,
                 guard,
             )
             .await
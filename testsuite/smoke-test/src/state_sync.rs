@@ -32,13 +32,14 @@ async fn test_full_node_bootstrap_state_snapshot() {
     swarm.fullnode_mut(vfn_peer_id).unwrap().stop();
 
     // Set at most 2 values per storage request for the validator
-    let validator = swarm.validators_mut().next().unwrap();
-    let mut config = validator.config().clone();
-    config.state_sync.storage_service.max_state_chunk_size = 2;
-    config.save(validator.config_path()).unwrap();
-    validator.restart().await.unwrap();
-    validator
-        .wait_until_healthy(Instant::now() + Duration::from_secs(MAX_CATCH_UP_SECS))
+    swarm
+        .validators_mut()
+        .next()
+        .unwrap()
+        .modify_config_and_restart(
+            |config| config.state_sync.storage_service.max_state_chunk_size = 2,
+            Instant::now() + Duration::from_secs(MAX_CATCH_UP_SECS),
+        )
         .await
         .unwrap();
 
@@ -86,7 +86,8 @@ async fn test_db_restore() {
         &transaction_factory,
         swarm.chain_info().root_account,
     )
-    .await;
+    .await
+    .unwrap();
     transfer_and_reconfig(
         &client_1,
         &transaction_factory,
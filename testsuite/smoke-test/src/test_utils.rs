@@ -56,7 +56,9 @@ pub async fn transfer_and_reconfig(
     for _ in 0..num_transfers {
         // Reconfigurations have a 20% chance of being executed
         if random::<u16>() % 5 == 0 {
-            reconfig(client, transaction_factory, root_account).await;
+            reconfig(client, transaction_factory, root_account)
+                .await
+                .unwrap();
         }
 
         transfer_coins(client, transaction_factory, sender, receiver, 1).await;
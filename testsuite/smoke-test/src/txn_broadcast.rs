@@ -4,7 +4,7 @@
 use crate::smoke_test_environment::SwarmBuilder;
 use crate::test_utils::{assert_balance, create_and_fund_account, transfer_coins};
 use aptos_config::config::NodeConfig;
-use forge::{NodeExt, Swarm, SwarmExt};
+use forge::{FailpointAction, NodeExt, Swarm, SwarmExt};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -60,9 +60,10 @@ async fn test_txn_broadcast() {
     let vfn_client = swarm.full_node(vfn).unwrap().rest_client();
 
     // set up validator_client. proposals not sent from this validator. txn should still go through.
-    let validator_client = swarm.validator(validator).unwrap().rest_client();
-    validator_client
-        .set_failpoint("consensus::send_proposal".to_string(), "return".to_string())
+    let validator_node = swarm.validator(validator).unwrap();
+    let validator_client = validator_node.rest_client();
+    validator_node
+        .set_failpoint("consensus::send_proposal", FailpointAction::Return(None))
         .await
         .unwrap();
 
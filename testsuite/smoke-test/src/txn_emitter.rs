@@ -5,11 +5,11 @@ use crate::smoke_test_environment::new_local_swarm_with_aptos;
 use anyhow::ensure;
 use aptos_sdk::{transaction_builder::TransactionFactory, types::PeerId};
 use forge::{
-    EmitJobMode, EmitJobRequest, NodeExt, Result, Swarm, TransactionType, TxnEmitter, TxnStats,
+    EmitJobMode, EmitJobRequest, NodeExt, Result, Swarm, SwarmExt, TransactionType, TxnEmitter,
+    TxnStats,
 };
 use rand::{rngs::OsRng, SeedableRng};
 use std::time::Duration;
-use tokio::runtime::Builder;
 
 pub async fn generate_traffic(
     swarm: &mut dyn Swarm,
@@ -18,9 +18,6 @@ pub async fn generate_traffic(
     gas_price: u64,
 ) -> Result<TxnStats> {
     ensure!(gas_price > 0, "gas_price is required to be non zero");
-    let mut runtime_builder = Builder::new_multi_thread();
-    runtime_builder.disable_lifo_slot().enable_all();
-    runtime_builder.worker_threads(64);
     let rng = SeedableRng::from_rng(OsRng)?;
     let validator_clients = swarm
         .validators()
@@ -61,3 +58,20 @@ async fn test_txn_emmitter() {
     assert!(txn_stat.submitted > 30);
     assert!(txn_stat.committed > 30);
 }
+
+#[tokio::test]
+async fn test_state_consistency_after_load() {
+    let mut swarm = new_local_swarm_with_aptos(4).await;
+
+    let all_validators = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
+    generate_traffic(&mut swarm, &all_validators, Duration::from_secs(10), 1)
+        .await
+        .unwrap();
+
+    let root = swarm.chain_info().root_account.address();
+    let report = swarm
+        .check_state_consistency(&[root], Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(report.nodes.len(), 4);
+}
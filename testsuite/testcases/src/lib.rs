@@ -19,13 +19,19 @@ use anyhow::{anyhow, ensure};
 use aptos_logger::info;
 use aptos_sdk::{transaction_builder::TransactionFactory, types::PeerId};
 use forge::{
-    EmitJobRequest, NetworkContext, NetworkTest, NodeExt, Result, Swarm, Test, TxnEmitter,
-    TxnStats, Version,
+    emit_transactions_with_swarm, record_event, trace_span, EmitJobRequest, NetworkContext,
+    NetworkTest, NodeExt, Result, Swarm, SwarmExt, Test, TimelineEventKind, TxnEmitter, TxnStats,
+    Version,
 };
 use rand::SeedableRng;
 use std::time::{Duration, Instant};
 use tokio::runtime::Builder;
 
+/// How often the success criteria of a load test are checked while it runs, to alert early
+const CRITERIA_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the validators are polled for stalls when the success criteria have a max block gap
+const STALL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 async fn batch_update(
     ctx: &mut NetworkContext<'_>,
     validators_to_update: &[PeerId],
@@ -89,7 +95,8 @@ pub fn generate_traffic(
     duration: Duration,
     gas_price: u64,
 ) -> Result<TxnStats> {
-    let (mut emitter, emit_job_request) = create_emitter_and_request(ctx, nodes, gas_price)?;
+    ensure!(gas_price > 0, "gas_price is required to be non zero");
+    let emit_job_request = ctx.emit_job.clone().gas_price(gas_price);
 
     let mut runtime_builder = Builder::new_multi_thread();
     runtime_builder.disable_lifo_slot().enable_all();
@@ -97,13 +104,19 @@ pub fn generate_traffic(
     let rt = runtime_builder
         .build()
         .map_err(|err| anyhow!("Failed to start runtime for transaction emitter. {}", err))?;
-    let stats = rt.block_on(emitter.emit_txn_for(
-        ctx.swarm().chain_info().root_account,
+    let _span = trace_span("emit transactions")
+        .with_attribute("duration_secs", duration.as_secs())
+        .with_attribute("nodes", nodes.len());
+    record_event(TimelineEventKind::EmissionStarted { nodes: nodes.len() });
+    let stats = rt.block_on(emit_transactions_with_swarm(
+        ctx.swarm(),
+        nodes,
         emit_job_request,
         duration,
-    ))?;
+    ));
+    record_event(TimelineEventKind::EmissionStopped { nodes: nodes.len() });
 
-    Ok(stats)
+    stats
 }
 
 pub enum LoadDestination {
@@ -167,17 +180,32 @@ impl NetworkTest for dyn NetworkLoadTest {
         let job = rt
             .block_on(emitter.start_job(ctx.swarm().chain_info().root_account, emit_job_request))?;
         info!("Starting emitting txns for {} secs", duration.as_secs());
+        let nodes = nodes_to_send_load_to.len();
+        record_event(TimelineEventKind::EmissionStarted { nodes });
+        let stats = job.stats();
+        let watcher = ctx
+            .success_criteria
+            .watch(self.name(), stats, CRITERIA_CHECK_INTERVAL);
+        let stall_detector = ctx
+            .success_criteria
+            .max_block_gap
+            .map(|max_gap| ctx.swarm().detect_stalls(max_gap, STALL_POLL_INTERVAL));
 
         self.test(ctx.swarm(), duration)?;
 
+        drop(watcher);
         info!("Ran for {} secs, stopping job...", duration.as_secs());
         let txn_stat = rt.block_on(emitter.stop_job(job));
+        record_event(TimelineEventKind::EmissionStopped { nodes });
         info!("Stopped job");
 
         ctx.report
             .report_txn_stats(self.name().to_string(), &txn_stat, duration);
 
         ctx.check_for_success(&txn_stat, &duration)?;
+        if let Some(stall_detector) = stall_detector {
+            stall_detector.check_no_stall()?;
+        }
 
         self.finish(ctx.swarm())?;
 